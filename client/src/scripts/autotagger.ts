@@ -51,7 +51,7 @@ class AutotaggerConfig {
     strictness: number = 0.7;
     mergeGenres: boolean = false;
     albumArtFile: boolean = false;
-    camelot: boolean = false;
+    keyNotation: string = 'original';
     parseFilename: boolean = false;
     filenameTemplate: string = '%artists% - %title%';
     shortTitle: boolean = false;
@@ -64,6 +64,10 @@ class AutotaggerConfig {
     trackNumberLeadingZeroes: number = 0;
     enableShazam: boolean = false;
     forceShazam: boolean = false;
+    identifyBackend: string = 'shazam';
+    acoustidKey?: string;
+    shazam: ShazamConfig = { positions: [0.3, 0.5, 0.7], minAgreement: 0.0, cache: true };
+    httpCache: HttpCacheConfig = { enabled: true, offline: false, defaultTtl: 168, platformTtl: { discogs: 720, musicbrainz: 720 }, maxSize: 512 };
     skipTagged: boolean = false;
     onlyYear: boolean = false;
     includeSubfolders: boolean = true;
@@ -83,6 +87,10 @@ class AutotaggerConfig {
     albumTagging: boolean = false;
     albumTaggingRatio: number = 0.5;
     coverFilename: string | undefined = undefined;
+    dryRun: boolean = false;
+    localBpm: LocalBpmConfig = { enabled: false, min: 70, max: 180, minConfidence: 0.3 };
+    localKey: LocalKeyConfig = { enabled: false, minConfidence: 0.5 };
+    replayGain: ReplayGainConfig = { enabled: false, album: true, reference: -18, overwrite: false };
 
     spotify?: SpotifyConfig;
 
@@ -97,9 +105,52 @@ class AutotaggerConfig {
         Object.assign(this, data);
         this.stylesCustomTag = Object.assign(FrameName.same('STYLE'), data.stylesCustomTag);
         this.separators = Object.assign(new Separators(), data.separators);
+        this.localBpm = Object.assign({ enabled: false, min: 70, max: 180, minConfidence: 0.3 }, data.localBpm);
+        this.localKey = Object.assign({ enabled: false, minConfidence: 0.5 }, data.localKey);
+        this.replayGain = Object.assign({ enabled: false, album: true, reference: -18, overwrite: false }, data.replayGain);
+        this.shazam = Object.assign({ positions: [0.3, 0.5, 0.7], minAgreement: 0.0, cache: true }, data.shazam);
+        this.httpCache = Object.assign({ enabled: true, offline: false, defaultTtl: 168, platformTtl: { discogs: 720, musicbrainz: 720 }, maxSize: 512 }, data.httpCache);
+        // Migrate from old camelot toggle
+        if (data.camelot && !data.keyNotation) {
+            this.keyNotation = 'camelot';
+        }
+        delete (this as any).camelot;
     }
 }
 
+interface LocalBpmConfig {
+    enabled: boolean;
+    min: number;
+    max: number;
+    minConfidence: number;
+}
+
+interface LocalKeyConfig {
+    enabled: boolean;
+    minConfidence: number;
+}
+
+interface ShazamConfig {
+    positions: number[];
+    minAgreement: number;
+    cache: boolean;
+}
+
+interface HttpCacheConfig {
+    enabled: boolean;
+    offline: boolean;
+    defaultTtl: number;
+    platformTtl: Record<string, number>;
+    maxSize: number;
+}
+
+interface ReplayGainConfig {
+    enabled: boolean;
+    album: boolean;
+    reference: number;
+    overwrite: boolean;
+}
+
 interface SpotifyConfig {
     clientId: string;
     clientSecret: string;
@@ -138,7 +189,40 @@ interface TaggingStatus {
     path: 'string',
     message?: string;
     accuracy?: number;
-    usedShazam: boolean
+    usedShazam: boolean;
+    shazam?: ShazamMatch;
+    diff?: TagDiff;
+}
+
+interface ShazamMatch {
+    segment: number;
+    position: number;
+    votes: number;
+    segments: number;
+    agreement: number;
+}
+
+/// Stopped or crashed AutoTagger run
+interface ResumableRun {
+    id: string;
+    created: number;
+    files: number;
+    remaining: number;
+    platforms: string[];
+}
+
+/// Saved undo journal
+interface UndoRun {
+    id: string;
+    files: number;
+    created: number;
+}
+
+interface TagDiff {
+    path: string;
+    platform: string;
+    changes: { tag: string, before?: string[], after?: string[] }[];
+    albumArt: boolean;
 }
 
 enum SupportedTag {
@@ -170,7 +254,20 @@ enum SupportedTag {
     MetaTags = "metaTags",
     BPM = "bpm",
     URL = "url",
-    ISRC = "isrc"
+    ISRC = "isrc",
+    Composer = "composer",
+    Lyricist = "lyricist",
+    Conductor = "conductor",
+    Producer = "producer",
+    Comment = "comment",
+    Grouping = "grouping",
+    Copyright = "copyright",
+    Compilation = "compilation",
+    ArtistSort = "artistSort",
+    TitleSort = "titleSort",
+    AlbumSort = "albumSort",
+    Work = "work",
+    Movement = "movement"
 }
 
 /// All supported tags with labels and tooltips
@@ -204,6 +301,19 @@ const SUPPORTED_TAGS = [
     { tag: SupportedTag.UnsyncedLyrics, label: 'Unsynced Lyrics' },
     { tag: SupportedTag.SyncedLyrics, label: 'Synced Lyrics' },
     { tag: SupportedTag.Explicit, label: 'Explicit' },
+    { tag: SupportedTag.Composer, label: 'Composer' },
+    { tag: SupportedTag.Lyricist, label: 'Lyricist' },
+    { tag: SupportedTag.Conductor, label: 'Conductor' },
+    { tag: SupportedTag.Producer, label: 'Producer' },
+    { tag: SupportedTag.Comment, label: 'Comment' },
+    { tag: SupportedTag.Grouping, label: 'Grouping' },
+    { tag: SupportedTag.Copyright, label: 'Copyright' },
+    { tag: SupportedTag.Compilation, label: 'Compilation', tooltip: 'Various artists release flag' },
+    { tag: SupportedTag.ArtistSort, label: 'Artist Sort' },
+    { tag: SupportedTag.TitleSort, label: 'Title Sort' },
+    { tag: SupportedTag.AlbumSort, label: 'Album Sort' },
+    { tag: SupportedTag.Work, label: 'Work', tooltip: 'Classical work' },
+    { tag: SupportedTag.Movement, label: 'Movement', tooltip: 'Classical movement name' },
 ];
 
 /// Track from 
@@ -233,6 +343,19 @@ interface Track {
     isrc?: string;
     mood?: string;
     explicit?: boolean;
+    composers: string[];
+    lyricists: string[];
+    conductors: string[];
+    producers: string[];
+    comment?: string;
+    grouping?: string;
+    copyright?: string;
+    compilation?: boolean;
+    artist_sort?: string;
+    title_sort?: string;
+    album_sort?: string;
+    work?: string;
+    movement?: string;
     lyrics?: any;
     release_year?: number;
     release_date?: string;
@@ -260,5 +383,5 @@ interface RepoPlatform {
     versions: { [key: string]: number };
 }
 
-export type { AutotaggerPlatform, PlatformInfo, AutotaggerProfile, TaggingStatusWrap, Track, ConfigCallbackResponse, RepoPlatform };
+export type { AutotaggerPlatform, PlatformInfo, AutotaggerProfile, TaggingStatusWrap, TagDiff, ResumableRun, UndoRun, Track, ConfigCallbackResponse, RepoPlatform };
 export { AutotaggerConfig, TaggerStatus, SupportedTag, SUPPORTED_TAGS };
\ No newline at end of file
@@ -1,10 +1,10 @@
 import { Dialog, DialogChainObject, Notify, setCssVar } from 'quasar';
 import { ref, Ref } from 'vue';
-import { AutotaggerConfig, AutotaggerPlatform, ConfigCallbackResponse, TaggerStatus } from './autotagger';
+import { AutotaggerConfig, AutotaggerPlatform, ConfigCallbackResponse, ResumableRun, TaggerStatus, UndoRun } from './autotagger';
 import { Player } from './player';
 import { QTTrack, QuickTag, QuickTagFile } from './quicktag';
 import { Settings } from './settings';
-import { Keybind, Playlist, Spotify, wsUrl } from './utils';
+import { Keybind, Playlist, Spotify, parseTagFilters, wsUrl } from './utils';
 import { ManualTag } from './manualtag';
 import ExitDialog from '../components/ExitDialog.vue';
 import router from './router';
@@ -26,6 +26,10 @@ class OneTagger {
     folderBrowser: Ref<FolderBrowser> = ref(new FolderBrowser());
     taggerStatus: Ref<TaggerStatus> = ref(new TaggerStatus());
     autoTaggerPlaylist: Ref<Playlist> = ref({});
+    resumableRuns: Ref<ResumableRun[]> = ref([]);
+    undoRuns: Ref<UndoRun[]> = ref([]);
+    libraryRoots: Ref<string[]> = ref([]);
+    libraryScanning: Ref<boolean> = ref(false);
     manualTag: Ref<ManualTag> = ref(new ManualTag());
 
     // Websocket
@@ -324,6 +328,40 @@ class OneTagger {
                 });
                 break;
 
+            // Stopped AutoTagger runs
+            case 'resumableRuns':
+                this.resumableRuns.value = json.runs;
+                break;
+
+            // Library index
+            case 'libraryRoots':
+                this.libraryRoots.value = json.roots;
+                break;
+            case 'libraryScan':
+                this.libraryScanning.value = false;
+                Notify.create({
+                    position: 'top-right',
+                    timeout: 5000,
+                    progress: true,
+                    message: `Library scanned: ${json.stats.added} added, ${json.stats.updated} updated, ${json.stats.removed} removed` + (json.stats.failed > 0 ? `, ${json.stats.failed} failed` : '')
+                });
+                break;
+
+            // Saved undo journals
+            case 'listRuns':
+                this.undoRuns.value = json.runs;
+                break;
+
+            // Undo journal reverted
+            case 'revertRun':
+                Notify.create({
+                    position: 'top-right',
+                    timeout: 5000,
+                    progress: true,
+                    message: `Reverted ${json.reverted} files` + (json.failed.length > 0 ? `, ${json.failed.length} failed` : '')
+                });
+                break;
+
             // Show notification
             case 'notify':
                 Notify.create({
@@ -346,6 +384,11 @@ class OneTagger {
                     this.onRenamerEvent(json);
                     break;
                 }
+                // Song downloader
+                if (json.action == 'analyzeSongs' || json.action.startsWith('downloadSongs')) {
+                    this.onSongDownloaderEvent(json);
+                    break;
+                }
 
                 console.log(`Unknown action: ${json.action}`);
                 console.log(json);
@@ -469,6 +512,9 @@ class OneTagger {
         // Renamer
         if (json.context == 'rn' || json.context == 'rnOutput')
             this.onRenamerEvent(json)
+        // Library root
+        if (json.context == 'lib')
+            this.send('libraryAddRoot', { path: json.path });
     }
 
     // Save settings to file
@@ -608,7 +654,8 @@ class OneTagger {
                 path: this.settings.value.path,
                 recursive: this.settings.value.quickTag.recursive,
                 separators: this.settings.value.quickTag.separators,
-                limit: limit
+                limit: limit,
+                filters: parseTagFilters(this.quickTag.value.libraryFilter)
             });
             this.saveSettings(false);
             // Save limit info
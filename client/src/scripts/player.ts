@@ -199,7 +199,7 @@ class Player {
 
         // Server side
         this._playerLoadPromise = new Promise((res) => this._playerLoadResolve = res);
-        $1t.send("playerLoad", { path });
+        $1t.send("playerLoad", { path, replayGain: $1t.settings.value.replayGain });
         this.generateWaveform(path);
 
         this.path = path;
@@ -8,6 +8,8 @@ class QuickTag {
     track: QTMultiTrack = new QTMultiTrack();
     failed: QuickTagFailed[] = [];
     wasLimited = false;
+    /// Library tag filter, `tag=value; tag~value`
+    libraryFilter?: string;
 
     /// Number of tracks to save
     saving: number = 0;
@@ -73,7 +75,7 @@ interface QuickTagCustomValue {
 
 interface QuickTagFile {
     path: string;
-    format: 'flac' | 'aiff' | 'mp3' | 'mp4' | 'wav' | 'ogg';
+    format: 'flac' | 'aiff' | 'mp3' | 'mp4' | 'wav' | 'ogg' | 'ape' | 'dsd';
     title: string;
     artists: string[];
     genres: string[];
@@ -272,7 +274,7 @@ class QTMultiTrack {
 class QTTrack implements QuickTagFile {
     // QuickTagFile
     path!: string;
-    format!: "flac" | "aiff" | "mp3" | "mp4" | "wav" | "ogg";
+    format!: "flac" | "aiff" | "mp3" | "mp4" | "wav" | "ogg" | "ape" | "dsd";
     title!: string;
     artists!: string[];
     genres!: string[];
@@ -17,6 +17,7 @@ class Settings {
     nonNativeBrowser: boolean = false;
     playOnSeek: boolean = false;
     autoPlayNext: boolean = false;
+    replayGain: boolean = false;
     externalAudioPlayer: boolean = false;
     devtools: boolean = false;
 
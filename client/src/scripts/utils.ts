@@ -26,6 +26,27 @@ class Separators {
     }
 }
 
+// Library tag filter
+interface TagFilter {
+    tag: string;
+    value: string;
+    mode: 'equals' | 'contains';
+}
+
+// Parse `tag=value; tag~value` into library filters
+function parseTagFilters(input?: string): TagFilter[] {
+    if (!input) return [];
+    return input.split(';').map((f) => {
+        const i = f.search(/[=~]/);
+        if (i == -1) return undefined;
+        return {
+            tag: f.substring(0, i).trim(),
+            value: f.substring(i + 1).trim(),
+            mode: f[i] == '~' ? 'contains' : 'equals'
+        } as TagFilter;
+    }).filter((f) => f && f.tag) as TagFilter[];
+}
+
 // Frame name in different formats
 class FrameName {
     constructor(public id3: string, public vorbis: string, public mp4: string) {}
@@ -48,9 +69,11 @@ class FrameName {
             case 'aif':
             case 'id3':
             case 'wav':
+            case 'dsd':
                 return this.id3;
             case 'flac':
             case 'ogg':
+            case 'ape':
             case 'vorbis':
                 return this.vorbis;
             case 'mp4':
@@ -115,5 +138,5 @@ interface RustDuration {
     nanos: number;
 }
 
-export type { Playlist, RustDuration };
-export { wsUrl, httpUrl, spotifyUrl, Separators, FrameName, Keybind, Spotify };
\ No newline at end of file
+export type { Playlist, RustDuration, TagFilter };
+export { wsUrl, httpUrl, spotifyUrl, parseTagFilters, Separators, FrameName, Keybind, Spotify };
\ No newline at end of file
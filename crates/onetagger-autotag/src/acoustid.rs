@@ -0,0 +1,409 @@
+use anyhow::Error;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use reqwest::blocking::Client;
+use rustfft::FftPlanner;
+use rustfft::num_complex::Complex;
+use serde::{Serialize, Deserialize};
+use std::path::Path;
+use onetagger_player::AudioSources;
+
+use crate::analysis::load_mono;
+
+/// Chromaprint default algorithm (TEST2) parameters
+const ALGORITHM: u8 = 1;
+const SAMPLE_RATE: u32 = 11025;
+const FRAME: usize = 4096;
+const HOP: usize = FRAME / 3;
+const MIN_FREQ: f64 = 28.0;
+const MAX_FREQ: f64 = 3520.0;
+const FILTER_COEFFICIENTS: [f64; 5] = [0.25, 0.75, 1.0, 0.75, 0.25];
+/// Same as fpcalc
+const MAX_LENGTH: u128 = 120_000;
+
+/// Filter type, chroma offset, chroma height, time width
+type Filter = (u8, usize, usize, usize);
+
+/// Filters with quantizer thresholds
+const CLASSIFIERS: [(Filter, [f64; 3]); 16] = [
+    ((0, 4, 3, 15), [1.98215, 2.35817, 2.63523]),
+    ((4, 4, 6, 15), [-1.03809, -0.651211, -0.282167]),
+    ((1, 0, 4, 16), [-0.298702, 0.119262, 0.558497]),
+    ((3, 8, 2, 12), [-0.105439, 0.0153946, 0.135898]),
+    ((3, 4, 4, 8), [-0.142891, 0.0258736, 0.200632]),
+    ((4, 0, 3, 5), [-0.826319, -0.590612, -0.368214]),
+    ((1, 2, 2, 9), [-0.557409, -0.233035, 0.0534525]),
+    ((2, 7, 3, 4), [-0.0646826, 0.00620476, 0.0784847]),
+    ((2, 6, 2, 16), [-0.192387, -0.029699, 0.215855]),
+    ((2, 1, 3, 2), [-0.0397818, -0.00568076, 0.0292026]),
+    ((5, 10, 1, 15), [-0.53823, -0.369934, -0.190235]),
+    ((3, 6, 2, 10), [-0.124877, 0.0296483, 0.139239]),
+    ((2, 1, 1, 14), [-0.101475, 0.0225617, 0.231971]),
+    ((3, 5, 6, 4), [-0.0799915, -0.00729616, 0.063262]),
+    ((1, 9, 2, 12), [-0.272556, 0.019424, 0.302559]),
+    ((3, 4, 2, 14), [-0.164292, -0.0321188, 0.0846339]),
+];
+
+/// Chromaprint compatible audio fingerprint
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fingerprint {
+    pub data: Vec<u32>,
+    /// Duration of whole file in seconds
+    pub duration: u64,
+}
+
+impl Fingerprint {
+    /// Fingerprint first 2 minutes of audio file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Fingerprint, Error> {
+        let duration = AudioSources::from_path(&path)?.duration();
+        let samples = load_mono(&path, SAMPLE_RATE, 0, MAX_LENGTH)?;
+        let data = Self::calculate(&samples);
+        if data.is_empty() {
+            bail!("Audio is too short for fingerprinting");
+        }
+        Ok(Fingerprint { data, duration: (duration / 1000) as u64 })
+    }
+
+    /// Calculate raw fingerprint from mono samples at 11025Hz
+    pub fn calculate(samples: &[f32]) -> Vec<u32> {
+        let image = chroma_image(samples);
+        let max_width = CLASSIFIERS.iter().map(|c| c.0.3).max().unwrap_or(1);
+        if image.len() < max_width {
+            return vec![];
+        }
+        let integral = IntegralImage::new(&image);
+        (0..=image.len() - max_width).map(|x| {
+            CLASSIFIERS.iter().fold(0u32, |bits, (filter, thresholds)| {
+                let value = integral.filter(x, *filter);
+                let quantized = thresholds.iter().filter(|t| value >= **t).count();
+                // Gray code
+                (bits << 2) | [0, 1, 3, 2][quantized]
+            })
+        }).collect()
+    }
+
+    /// Compress and encode fingerprint for AcoustID
+    pub fn encode(&self) -> String {
+        let (mut normal, mut exceptional) = (vec![], vec![]);
+        let mut previous = 0;
+        for x in &self.data {
+            // Positions of changed bits, as differences
+            let (mut value, mut bit, mut last) = (x ^ previous, 1u8, 0u8);
+            while value != 0 {
+                if value & 1 == 1 {
+                    let diff = bit - last;
+                    if diff >= 7 {
+                        normal.push(7);
+                        exceptional.push(diff - 7);
+                    } else {
+                        normal.push(diff);
+                    }
+                    last = bit;
+                }
+                value >>= 1;
+                bit += 1;
+            }
+            normal.push(0);
+            previous = *x;
+        }
+
+        let length = self.data.len() as u32;
+        let mut out = vec![ALGORITHM, (length >> 16) as u8, (length >> 8) as u8, length as u8];
+        out.extend(pack_bits(&normal, 3));
+        out.extend(pack_bits(&exceptional, 5));
+        URL_SAFE_NO_PAD.encode(out)
+    }
+}
+
+/// Pack values LSB first
+fn pack_bits(values: &[u8], bits: usize) -> Vec<u8> {
+    let mut out = vec![0u8; (values.len() * bits).div_ceil(8)];
+    for (i, v) in values.iter().enumerate() {
+        for b in 0..bits {
+            if v >> b & 1 == 1 {
+                let position = i * bits + b;
+                out[position / 8] |= 1 << (position % 8);
+            }
+        }
+    }
+    out
+}
+
+/// Filtered and normalized chroma features of every frame
+fn chroma_image(samples: &[f32]) -> Vec<[f64; 12]> {
+    let fft = FftPlanner::<f32>::new().plan_fft_forward(FRAME);
+    let window = (0..FRAME)
+        .map(|i| 0.54 - 0.46 * (std::f32::consts::TAU * i as f32 / (FRAME - 1) as f32).cos())
+        .collect::<Vec<_>>();
+    // FFT bin => note
+    let index = |freq: f64| (FRAME as f64 * freq / SAMPLE_RATE as f64).round() as usize;
+    let notes = (index(MIN_FREQ).max(1)..index(MAX_FREQ).min(FRAME / 2)).map(|bin| {
+        let freq = bin as f64 * SAMPLE_RATE as f64 / FRAME as f64;
+        let octave = (freq / (440.0 / 16.0)).log2();
+        (bin, (12.0 * (octave - octave.floor())) as usize)
+    }).collect::<Vec<_>>();
+
+    let mut chroma = vec![];
+    let mut buffer = vec![Complex::default(); FRAME];
+    let mut start = 0;
+    while start + FRAME <= samples.len() {
+        for (i, b) in buffer.iter_mut().enumerate() {
+            *b = Complex::new(samples[start + i] * window[i], 0.0);
+        }
+        fft.process(&mut buffer);
+        let mut features = [0.0; 12];
+        for (bin, note) in &notes {
+            features[*note] += buffer[*bin].norm_sqr() as f64;
+        }
+        chroma.push(features);
+        start += HOP;
+    }
+
+    // Smooth in time and normalize
+    chroma.windows(FILTER_COEFFICIENTS.len()).map(|frames| {
+        let mut features = [0.0; 12];
+        for (frame, c) in frames.iter().zip(FILTER_COEFFICIENTS) {
+            for (f, v) in features.iter_mut().zip(frame) {
+                *f += v * c;
+            }
+        }
+        let norm = features.iter().map(|f| f * f).sum::<f64>().sqrt();
+        match norm < 0.01 {
+            true => [0.0; 12],
+            false => features.map(|f| f / norm)
+        }
+    }).collect()
+}
+
+/// Summed area table of chroma image
+struct IntegralImage {
+    data: Vec<[f64; 13]>,
+}
+
+impl IntegralImage {
+    fn new(image: &[[f64; 12]]) -> IntegralImage {
+        let mut data = vec![[0.0; 13]; image.len() + 1];
+        for (row, features) in image.iter().enumerate() {
+            for col in 0..12 {
+                data[row + 1][col + 1] = features[col] + data[row][col + 1] + data[row + 1][col] - data[row][col];
+            }
+        }
+        IntegralImage { data }
+    }
+
+    /// Sum of rows r1..r2 and columns c1..c2
+    fn area(&self, r1: usize, c1: usize, r2: usize, c2: usize) -> f64 {
+        self.data[r2][c2] - self.data[r1][c2] - self.data[r2][c1] + self.data[r1][c1]
+    }
+
+    /// Haar-like filter at row x, compared as difference of logarithms
+    fn filter(&self, x: usize, (kind, y, h, w): Filter) -> f64 {
+        let (a, b) = match kind {
+            0 => (self.area(x, y, x + w, y + h), 0.0),
+            1 => {
+                let h2 = h / 2;
+                (self.area(x, y + h2, x + w, y + h), self.area(x, y, x + w, y + h2))
+            },
+            2 => {
+                let w2 = w / 2;
+                (self.area(x + w2, y, x + w, y + h), self.area(x, y, x + w2, y + h))
+            },
+            3 => {
+                let (w2, h2) = (w / 2, h / 2);
+                (
+                    self.area(x, y + h2, x + w2, y + h) + self.area(x + w2, y, x + w, y + h2),
+                    self.area(x, y, x + w2, y + h2) + self.area(x + w2, y + h2, x + w, y + h)
+                )
+            },
+            4 => {
+                let h3 = h / 3;
+                (
+                    self.area(x, y + h3, x + w, y + 2 * h3),
+                    self.area(x, y, x + w, y + h3) + self.area(x, y + 2 * h3, x + w, y + h)
+                )
+            },
+            _ => {
+                let w3 = w / 3;
+                (
+                    self.area(x + w3, y, x + 2 * w3, y + h),
+                    self.area(x, y, x + w3, y + h) + self.area(x + 2 * w3, y, x + w, y + h)
+                )
+            }
+        };
+        (1.0 + a).ln() - (1.0 + b).ln()
+    }
+}
+
+/// AcoustID lookup client
+pub struct AcoustId {
+    client: Client,
+    key: String,
+    url: String,
+}
+
+impl AcoustId {
+    /// Create client with application API key
+    pub fn new(key: &str) -> AcoustId {
+        AcoustId {
+            client: Client::builder()
+                .user_agent("OneTagger/1.0")
+                .build()
+                .unwrap(),
+            key: key.to_string(),
+            url: "https://api.acoustid.org/v2/lookup".to_string()
+        }
+    }
+
+    /// Use different lookup endpoint
+    #[cfg(test)]
+    fn with_url(mut self, url: &str) -> AcoustId {
+        self.url = url.to_string();
+        self
+    }
+
+    /// Lookup fingerprint, results are sorted by score
+    pub fn lookup(&self, fingerprint: &Fingerprint) -> Result<Vec<AcoustIdResult>, Error> {
+        let response: AcoustIdResponse = self.client.post(&self.url)
+            .form(&[
+                ("client", self.key.as_str()),
+                ("meta", "recordings"),
+                ("duration", &fingerprint.duration.to_string()),
+                ("fingerprint", &fingerprint.encode()),
+            ])
+            .send()?
+            .json()?;
+        if response.status != "ok" {
+            bail!("AcoustID error: {}", response.error.map(|e| e.message).unwrap_or(response.status));
+        }
+        let mut results = response.results;
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+        Ok(results)
+    }
+
+    /// Fingerprint and identify file, returns best recording, all matched recording IDs and duration (ms)
+    pub fn recognize_from_file(&self, path: impl AsRef<Path>) -> Result<(AcoustIdRecording, Vec<String>, u128), Error> {
+        let fingerprint = Fingerprint::from_file(&path)?;
+        let results = self.lookup(&fingerprint)?;
+        let best = results.first().ok_or(anyhow!("AcoustID returned no matches!"))?;
+        let recordings = best.recordings.clone().unwrap_or_default();
+        let recording = recordings.iter()
+            .find(|r| r.title.is_some())
+            .ok_or(anyhow!("AcoustID match has no linked recordings!"))?
+            .clone();
+        let ids = recordings.into_iter().map(|r| r.id).collect();
+        Ok((recording, ids, fingerprint.duration as u128 * 1000))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcoustIdResponse {
+    pub status: String,
+    #[serde(default)]
+    pub results: Vec<AcoustIdResult>,
+    pub error: Option<AcoustIdError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcoustIdError {
+    pub code: i32,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcoustIdResult {
+    pub id: String,
+    pub score: f64,
+    pub recordings: Option<Vec<AcoustIdRecording>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcoustIdRecording {
+    /// MusicBrainz recording ID
+    pub id: String,
+    pub title: Option<String>,
+    pub duration: Option<f64>,
+    #[serde(default)]
+    pub artists: Vec<AcoustIdArtist>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcoustIdArtist {
+    pub id: String,
+    pub name: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_fingerprint() {
+        // Chord progression, 20s
+        let samples = (0..SAMPLE_RATE * 20).map(|i| {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            let root = [220.0, 174.6, 261.6, 196.0][(t / 2.0) as usize % 4];
+            [1.0, 1.26, 1.5].iter().map(|m| (t * root * m * std::f32::consts::TAU).sin() * 0.2).sum::<f32>()
+        }).collect::<Vec<_>>();
+        let a = Fingerprint::calculate(&samples);
+        assert_eq!(a.len(), (samples.len() - FRAME) / HOP + 1 - 4 - 15);
+        // Same audio with tiny noise gives almost the same fingerprint
+        let noisy = samples.iter().enumerate().map(|(i, s)| s + ((i * 7919) % 13) as f32 * 1e-4).collect::<Vec<_>>();
+        let b = Fingerprint::calculate(&noisy);
+        let errors = a.iter().zip(&b).map(|(a, b)| (a ^ b).count_ones()).sum::<u32>();
+        assert!((errors as f32) < a.len() as f32 * 32.0 * 0.05);
+        assert!(Fingerprint::calculate(&samples[..FRAME * 2]).is_empty());
+    }
+
+    #[test]
+    fn test_encode() {
+        let fingerprint = Fingerprint { data: vec![1, 0b1000_0001, 0b1000_0001], duration: 10 };
+        // Header, bit positions: [1, 0], [8 => 7 + 1, 0], [0], exceptional: [1]
+        assert_eq!(URL_SAFE_NO_PAD.decode(fingerprint.encode()).unwrap(), vec![1, 0, 0, 3, 0b1100_0001, 0b0000_0001, 1]);
+    }
+
+    #[test]
+    fn test_lookup() {
+        // Mock AcoustID server
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/v2/lookup", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(l) = line.to_lowercase().strip_prefix("content-length:") {
+                    length = l.trim().parse().unwrap();
+                }
+                if line.trim().is_empty() {
+                    break;
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            let response = r#"{"status": "ok", "results": [
+                {"id": "a", "score": 0.5, "recordings": [{"id": "rec-low"}]},
+                {"id": "b", "score": 0.97, "recordings": [
+                    {"id": "rec-1", "title": "Title", "duration": 210.0, "artists": [{"id": "x", "name": "Artist"}]},
+                    {"id": "rec-2"}
+                ]}
+            ]}"#;
+            write!(reader.get_mut(), "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{response}", response.len()).unwrap();
+            String::from_utf8(body).unwrap()
+        });
+
+        let fingerprint = Fingerprint { data: vec![1, 2, 3], duration: 210 };
+        let results = AcoustId::new("key").with_url(&url).lookup(&fingerprint).unwrap();
+        assert_eq!(results[0].id, "b");
+        let recording = &results[0].recordings.as_ref().unwrap()[0];
+        assert_eq!((recording.id.as_str(), recording.artists[0].name.as_str()), ("rec-1", "Artist"));
+
+        let body = server.join().unwrap();
+        assert!(body.contains("client=key"));
+        assert!(body.contains("duration=210"));
+        assert!(body.contains(&format!("fingerprint={}", fingerprint.encode())));
+    }
+}
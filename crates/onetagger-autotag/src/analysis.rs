@@ -0,0 +1,48 @@
+use anyhow::Error;
+use crossbeam_channel::{unbounded, Receiver};
+use std::path::{Path, PathBuf};
+use onetagger_player::AudioSources;
+use onetagger_player::rodio::source::UniformSourceIterator;
+
+/// Decode part of audio file into mono samples, offset is ignored for short files (ms)
+pub(crate) fn load_mono(path: impl AsRef<Path>, sample_rate: u32, offset: u128, length: u128) -> Result<Vec<f32>, Error> {
+    let source = AudioSources::from_path(path)?;
+    let offset = match source.duration() > offset + length {
+        true => offset,
+        false => 0
+    };
+    let samples = UniformSourceIterator::<_, i16>::new(source.get_source()?, 1, sample_rate)
+        .skip((offset * sample_rate as u128 / 1000) as usize)
+        .take((length * sample_rate as u128 / 1000) as usize)
+        .map(|s| s as f32 / i16::MAX as f32)
+        .collect::<Vec<_>>();
+    if samples.is_empty() {
+        bail!("No audio samples");
+    }
+    Ok(samples)
+}
+
+/// Run analysis on multiple files in parallel
+pub(crate) fn analyze_files<T, F>(files: Vec<PathBuf>, threads: usize, analyze: F) -> Receiver<(PathBuf, Result<T, Error>)>
+where
+    T: Send + 'static,
+    F: Fn(&Path) -> Result<T, Error> + Send + Clone + 'static
+{
+    let (tx, rx) = unbounded();
+    let (file_tx, file_rx) = unbounded();
+    for file in files {
+        file_tx.send(file).ok();
+    }
+    for _ in 0..threads.max(1) {
+        let (tx, file_rx, analyze) = (tx.clone(), file_rx.clone(), analyze.clone());
+        std::thread::spawn(move || {
+            while let Ok(path) = file_rx.try_recv() {
+                let result = analyze(&path);
+                if tx.send((path, result)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    rx
+}
@@ -0,0 +1,221 @@
+use anyhow::Error;
+use crossbeam_channel::Receiver;
+use serde::{Serialize, Deserialize};
+use std::path::{Path, PathBuf};
+use onetagger_tag::{Field, Tag};
+use onetagger_tagger::LocalBpmConfig;
+
+use crate::analysis::{analyze_files, load_mono};
+
+/// Sample rate used for analysis
+const SAMPLE_RATE: u32 = 11025;
+/// Samples per onset envelope frame (~11.6ms)
+const HOP: usize = 128;
+/// Tempo range searched before folding into the configured range
+const SEARCH_MIN: f32 = 50.0;
+const SEARCH_MAX: f32 = 240.0;
+
+/// Detected tempo
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BpmResult {
+    pub bpm: f32,
+    /// How distinct the beat is, 0.0 - 1.0
+    pub confidence: f32,
+}
+
+/// Offline BPM analyzer
+pub struct BpmDetector;
+
+impl BpmDetector {
+    /// Detect BPM of audio file
+    pub fn detect_file(path: impl AsRef<Path>, config: &LocalBpmConfig) -> Result<BpmResult, Error> {
+        // Skip intro of longer tracks, 2 minutes is enough
+        let samples = load_mono(path, SAMPLE_RATE, 30_000, 120_000)?;
+        Self::detect(&samples, SAMPLE_RATE, config).ok_or(anyhow!("No tempo detected"))
+    }
+
+    /// Detect BPM from mono samples
+    pub fn detect(samples: &[f32], sample_rate: u32, config: &LocalBpmConfig) -> Option<BpmResult> {
+        let envelope = onset_envelope(samples);
+        let rate = sample_rate as f32 / HOP as f32;
+        let min_lag = (rate * 60.0 / SEARCH_MAX).floor() as usize;
+        let max_lag = (rate * 60.0 / SEARCH_MIN).ceil() as usize;
+        // Need at least a few beats
+        if envelope.len() < max_lag * 4 {
+            return None;
+        }
+        let ac = autocorrelation(&envelope, max_lag * 2 + 1);
+        if ac[0] <= 0.0 {
+            return None;
+        }
+
+        // Periodicity at double period supports the beat over random peaks
+        let scores = (min_lag..=max_lag)
+            .map(|lag| (ac[lag] + 0.5 * ac[lag * 2]) / ac[0])
+            .collect::<Vec<_>>();
+        // Multiples of the period score similarly, prefer tempos around the center of the range
+        let center = (config.min.max(1.0) * config.max.max(1.0)).sqrt();
+        let (mut i, _) = scores.iter().enumerate().map(|(i, s)| {
+            let octaves = (rate * 60.0 / (min_lag + i) as f32 / center).log2();
+            (i, s * (-0.5 * octaves * octaves).exp())
+        }).max_by(|a, b| a.1.total_cmp(&b.1))?;
+        // Use double tempo if it's periodic as well and still fits into range
+        loop {
+            let half = ((min_lag + i) as f32 / 2.0).round() as usize;
+            if half <= min_lag || rate * 60.0 / half as f32 > config.max {
+                break;
+            }
+            let j = (half - 1..=half + 1).max_by(|a, b| scores[a - min_lag].total_cmp(&scores[b - min_lag]))? - min_lag;
+            if scores[j] < scores[i] * 0.9 {
+                break;
+            }
+            i = j;
+        }
+        let best = scores[i];
+        // Parabolic interpolation for sub-frame precision
+        let mut offset = 0.0;
+        if i > 0 && i < scores.len() - 1 {
+            let (a, b, c) = (scores[i - 1], scores[i], scores[i + 1]);
+            let d = a - 2.0 * b + c;
+            if d.abs() > f32::EPSILON {
+                offset = (0.5 * (a - c) / d).clamp(-0.5, 0.5);
+            }
+        }
+        let lag = (min_lag + i) as f32 + offset;
+        let bpm = fold_bpm(rate * 60.0 / lag, config.min, config.max);
+
+        let mean = scores.iter().sum::<f32>() / scores.len() as f32;
+        let confidence = ((best - mean) / best.max(f32::EPSILON)).clamp(0.0, 1.0);
+        Some(BpmResult { bpm: (bpm * 10.0).round() / 10.0, confidence })
+    }
+
+    /// Detect BPM of multiple files in parallel
+    pub fn detect_files(files: Vec<PathBuf>, config: &LocalBpmConfig, threads: usize) -> Receiver<(PathBuf, Result<BpmResult, Error>)> {
+        let config = config.clone();
+        analyze_files(files, threads, move |path| Self::detect_file(path, &config))
+    }
+
+    /// Does the file already have BPM tag
+    pub fn has_bpm(path: impl AsRef<Path>) -> bool {
+        Tag::load_file(path, false)
+            .ok()
+            .and_then(|t| t.tag().get_field(Field::BPM))
+            .map(|v| v.iter().any(|b| !b.trim().is_empty() && b.trim() != "0"))
+            .unwrap_or(false)
+    }
+
+    /// Write BPM to file, returns false if file already has BPM and overwrite is disabled
+    pub fn write_bpm(path: impl AsRef<Path>, result: &BpmResult, overwrite: bool) -> Result<bool, Error> {
+        if !overwrite && Self::has_bpm(&path) {
+            return Ok(false);
+        }
+        let mut tag_wrap = Tag::load_file(&path, true)?;
+        let tag = tag_wrap.tag_mut();
+        tag.set_field(Field::BPM, vec![result.bpm.round().to_string()], true);
+        tag.save_file(path.as_ref())?;
+        Ok(true)
+    }
+}
+
+/// Halve / double BPM until it fits into the range
+pub fn fold_bpm(bpm: f32, min: f32, max: f32) -> f32 {
+    if bpm <= 0.0 || min <= 0.0 || max < min {
+        return bpm;
+    }
+    let mut bpm = bpm;
+    while bpm < min {
+        bpm *= 2.0;
+    }
+    while bpm > max {
+        bpm /= 2.0;
+    }
+    // Range narrower than an octave, use whichever is closer
+    if bpm < min && bpm * 2.0 - max < min - bpm {
+        bpm *= 2.0;
+    }
+    bpm
+}
+
+/// Rises of log energy per frame, energy of the first difference emphasizes transients
+fn onset_envelope(samples: &[f32]) -> Vec<f32> {
+    let mut previous = 0.0;
+    let energy = samples.chunks(HOP).map(|chunk| {
+        let mut sum = 0.0;
+        for s in chunk {
+            let d = s - previous;
+            sum += d * d;
+            previous = *s;
+        }
+        (1.0 + 1000.0 * sum / chunk.len() as f32).ln()
+    }).collect::<Vec<f32>>();
+    // Smoothing spreads onsets which fall between frames
+    let flux = energy.windows(2).map(|w| (w[1] - w[0]).max(0.0)).collect::<Vec<_>>();
+    let flux = (0..flux.len()).map(|i| {
+        0.5 * flux[i] + 0.25 * (flux[i.saturating_sub(1)] + flux[(i + 1).min(flux.len() - 1)])
+    }).collect::<Vec<_>>();
+
+    // Remove slow changes using moving average (~0.25s)
+    let half = 10;
+    let mut prefix = vec![0.0; flux.len() + 1];
+    for (i, f) in flux.iter().enumerate() {
+        prefix[i + 1] = prefix[i] + f;
+    }
+    (0..flux.len()).map(|i| {
+        let (lo, hi) = (i.saturating_sub(half), (i + half + 1).min(flux.len()));
+        let mean = (prefix[hi] - prefix[lo]) / (hi - lo) as f32;
+        (flux[i] - mean).max(0.0)
+    }).collect()
+}
+
+/// Normalized autocorrelation for lags 0..n
+fn autocorrelation(x: &[f32], n: usize) -> Vec<f32> {
+    (0..n.min(x.len())).map(|lag| {
+        let sum = x[..x.len() - lag].iter().zip(&x[lag..]).map(|(a, b)| a * b).sum::<f32>();
+        sum / (x.len() - lag) as f32
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Short 2kHz clicks at given tempo
+    fn click_track(bpm: f32, seconds: f32) -> Vec<f32> {
+        let period = SAMPLE_RATE as f32 * 60.0 / bpm;
+        (0..(seconds * SAMPLE_RATE as f32) as usize).map(|i| {
+            let t = i as f32 % period;
+            match t < 110.0 {
+                true => (t / SAMPLE_RATE as f32 * 2000.0 * std::f32::consts::TAU).sin() * 0.8,
+                false => 0.0
+            }
+        }).collect()
+    }
+
+    #[test]
+    fn test_fold_bpm() {
+        assert_eq!(fold_bpm(60.0, 70.0, 180.0), 120.0);
+        assert_eq!(fold_bpm(240.0, 70.0, 180.0), 120.0);
+        assert_eq!(fold_bpm(174.0, 70.0, 180.0), 174.0);
+        assert_eq!(fold_bpm(87.0, 100.0, 180.0), 174.0);
+        // Range narrower than an octave
+        assert_eq!(fold_bpm(110.0, 120.0, 200.0), 110.0);
+        assert_eq!(fold_bpm(65.0, 120.0, 200.0), 130.0);
+    }
+
+    #[test]
+    fn test_detect_clicks() {
+        let config = LocalBpmConfig::default();
+        for bpm in [96.0, 120.0, 128.0, 174.0] {
+            let result = BpmDetector::detect(&click_track(bpm, 30.0), SAMPLE_RATE, &config).unwrap();
+            assert!((result.bpm - bpm).abs() < 1.0, "expected {bpm}, got {}", result.bpm);
+            assert!(result.confidence > 0.5);
+        }
+        // Half tempo folded into range
+        let config = LocalBpmConfig { min: 100.0, max: 200.0, ..Default::default() };
+        let result = BpmDetector::detect(&click_track(80.0, 30.0), SAMPLE_RATE, &config).unwrap();
+        assert!((result.bpm - 160.0).abs() < 1.0);
+        // Silence
+        assert!(BpmDetector::detect(&vec![0.0; SAMPLE_RATE as usize * 10], SAMPLE_RATE, &config).is_none());
+    }
+}
@@ -0,0 +1,120 @@
+use anyhow::Error;
+use std::collections::{BTreeSet, HashMap};
+use std::io::BufWriter;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+
+/// Meta tag which changes on every run, would mark every file as changed
+const TAGGED_DATE: &str = "1T_TAGGEDDATE";
+
+/// Changes the AutoTagger would make to a single file
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TagDiff {
+    pub path: PathBuf,
+    /// Platform which produced the match
+    pub platform: String,
+    pub changes: Vec<TagFieldChange>,
+    /// Album art would be (re)placed
+    pub album_art: bool,
+}
+
+/// Before / after value of a single raw tag, `None` = tag missing
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TagFieldChange {
+    pub tag: String,
+    pub before: Option<Vec<String>>,
+    pub after: Option<Vec<String>>,
+}
+
+impl TagDiff {
+    /// Compare 2 snapshots of `TagImpl::all_tags`
+    pub fn compute(path: impl AsRef<Path>, platform: &str, before: &HashMap<String, Vec<String>>, after: &HashMap<String, Vec<String>>, album_art: bool) -> TagDiff {
+        // Sorted for stable output
+        let keys = before.keys().chain(after.keys()).collect::<BTreeSet<_>>();
+        let changes = keys.into_iter().filter_map(|key| {
+            let (b, a) = (before.get(key), after.get(key));
+            // MP4 prefixes it with freeform namespace
+            if b == a || key.ends_with(TAGGED_DATE) {
+                return None;
+            }
+            Some(TagFieldChange {
+                tag: key.to_string(),
+                before: b.cloned(),
+                after: a.cloned()
+            })
+        }).collect();
+
+        TagDiff {
+            path: path.as_ref().to_owned(),
+            platform: platform.to_string(),
+            changes,
+            album_art
+        }
+    }
+
+    /// Would anything change
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty() && !self.album_art
+    }
+}
+
+/// Output of a dry run
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct DryRunReport {
+    pub files: Vec<TagDiff>,
+    /// Files which didn't match on any platform
+    pub failed: Vec<PathBuf>,
+}
+
+impl DryRunReport {
+    /// Add diff. In multiplatform mode a file can have one diff per platform,
+    /// each computed against the untouched file.
+    pub fn add(&mut self, diff: TagDiff) {
+        if let Some(i) = self.failed.iter().position(|p| p == &diff.path) {
+            self.failed.remove(i);
+        }
+        self.files.push(diff);
+    }
+
+    /// Mark file as failed
+    pub fn add_failed(&mut self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        if !self.files.iter().any(|d| d.path == path) && !self.failed.iter().any(|p| p == path) {
+            self.failed.push(path.to_owned());
+        }
+    }
+
+    /// Save as JSON
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    /// Load saved report
+    pub fn load(path: impl AsRef<Path>) -> Result<DryRunReport, Error> {
+        Ok(serde_json::from_reader(File::open(path)?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_ignores_tagged_date() {
+        let before = HashMap::from([("TIT2".to_string(), vec!["Title".to_string()])]);
+        let mut after = before.clone();
+        after.insert("1T_TAGGEDDATE".to_string(), vec!["2024-01-01 00:00:00_AT".to_string()]);
+        after.insert("----:com.apple.iTunes:1T_TAGGEDDATE".to_string(), vec!["2024-01-01 00:00:00_AT".to_string()]);
+        assert!(TagDiff::compute("a.mp3", "beatport", &before, &after, false).is_empty());
+
+        after.insert("TPE1".to_string(), vec!["Artist".to_string()]);
+        let diff = TagDiff::compute("a.mp3", "beatport", &before, &after, false);
+        assert_eq!(diff.changes, vec![TagFieldChange { tag: "TPE1".to_string(), before: None, after: Some(vec!["Artist".to_string()]) }]);
+    }
+}
@@ -0,0 +1,435 @@
+use anyhow::Error;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Cursor};
+use std::path::{Path, PathBuf};
+use image::ImageReader;
+use lofty::file::AudioFile;
+use serde::{Serialize, Deserialize};
+use onetagger_tag::{AudioFileFormat, Field, Tag};
+use onetagger_tagger::{AudioFileInfo, MatchingUtils};
+
+use crate::AudioFileInfoImpl;
+use crate::acoustid::Fingerprint;
+use crate::analysis::analyze_files;
+use crate::journal::UndoJournal;
+
+/// Fields counted for tag completeness
+const COMPLETENESS_FIELDS: [Field; 9] = [
+    Field::Title, Field::Artist, Field::Album, Field::Genre, Field::BPM,
+    Field::Key, Field::Label, Field::ISRC, Field::TrackNumber
+];
+/// Maximum fingerprint alignment offset (~10s)
+const MAX_OFFSET: usize = 80;
+/// Minimum overlap of aligned fingerprints (~6s)
+const MIN_OVERLAP: usize = 50;
+/// Minimum shared fingerprint values to compare 2 files
+const MIN_SHARED: usize = 5;
+/// Values shared by more files are ignored (silence etc)
+const MAX_POSTINGS: usize = 50;
+
+/// Duplicate finder options
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicatesConfig {
+    pub path: PathBuf,
+    pub subfolders: bool,
+    /// Compare acoustic fingerprints, otherwise only ISRC and artist + title are used
+    pub fingerprint: bool,
+    /// Minimum fingerprint similarity (0.0 - 1.0), unrelated audio is around 0.5
+    pub similarity: f32,
+    /// In seconds, not checked for ISRC matches
+    pub max_duration_difference: u64,
+    pub threads: usize,
+}
+
+impl Default for DuplicatesConfig {
+    fn default() -> Self {
+        Self { path: PathBuf::new(), subfolders: true, fingerprint: true, similarity: 0.8, max_duration_difference: 5, threads: 4 }
+    }
+}
+
+/// Why were the files grouped, ordered from the strongest
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub enum DuplicateReason {
+    Fingerprint,
+    Isrc,
+    Metadata,
+}
+
+/// Quality info of a single file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateFile {
+    pub path: PathBuf,
+    pub format: AudioFileFormat,
+    pub lossless: bool,
+    /// kbps
+    pub bitrate: u32,
+    pub sample_rate: u32,
+    pub bit_depth: Option<u8>,
+    /// ms
+    pub duration: u128,
+    /// How many of the common tags are filled
+    pub tags: usize,
+    /// Largest album art (width, height)
+    pub art: Option<(u32, u32)>,
+}
+
+impl DuplicateFile {
+    /// Load quality info from file
+    pub fn load(path: impl AsRef<Path>) -> Result<(DuplicateFile, Tag), Error> {
+        let path = path.as_ref();
+        let tagged_file = lofty::read_from_path(path)?;
+        let properties = tagged_file.properties();
+        let tag = Tag::load_file(path, false)?;
+        let format = tag.format();
+        let lossless = match format {
+            AudioFileFormat::FLAC | AudioFileFormat::AIFF | AudioFileFormat::WAV | AudioFileFormat::DSD => true,
+            // ALAC
+            AudioFileFormat::MP4 => properties.bit_depth().is_some(),
+            // WavPack / Monkey's Audio, Musepack is lossy
+            AudioFileFormat::APE => properties.bit_depth().is_some(),
+            _ => false
+        };
+        let t = tag.tag();
+        let tags = COMPLETENESS_FIELDS.iter()
+            .filter(|f| t.get_field((*f).clone()).is_some_and(|v| v.iter().any(|v| !v.trim().is_empty())))
+            .count() + t.get_date().is_some() as usize;
+        let art = t.get_art().iter()
+            .filter_map(|p| ImageReader::new(Cursor::new(&p.data)).with_guessed_format().ok()?.into_dimensions().ok())
+            .max_by_key(|(w, h)| *w as u64 * *h as u64);
+
+        Ok((DuplicateFile {
+            path: path.to_owned(),
+            format,
+            lossless,
+            bitrate: properties.audio_bitrate().or(properties.overall_bitrate()).unwrap_or(0),
+            sample_rate: properties.sample_rate().unwrap_or(0),
+            bit_depth: properties.bit_depth(),
+            duration: properties.duration().as_millis(),
+            tags,
+            art,
+        }, tag))
+    }
+
+    /// Sort key, higher is better
+    fn quality(&self) -> (bool, u32, u32, usize, u64) {
+        let art = self.art.map(|(w, h)| w as u64 * h as u64).unwrap_or(0);
+        (self.lossless, self.bitrate, self.sample_rate, self.tags, art)
+    }
+}
+
+/// Group of duplicate files, best quality first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateGroup {
+    pub reason: DuplicateReason,
+    pub files: Vec<DuplicateFile>,
+}
+
+/// Output of the duplicate finder
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateReport {
+    pub groups: Vec<DuplicateGroup>,
+    /// Files which couldn't be loaded
+    pub failed: Vec<PathBuf>,
+}
+
+impl DuplicateReport {
+    /// Save as M3U if the extension is `m3u` / `m3u8`, otherwise as JSON
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let ext = path.as_ref().extension().unwrap_or_default().to_string_lossy().to_lowercase();
+        if ext == "m3u" || ext == "m3u8" {
+            std::fs::write(path, self.to_m3u())?;
+            return Ok(());
+        }
+        let file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    /// Playlist with all groups, best copy first
+    pub fn to_m3u(&self) -> String {
+        let mut out = String::from("#EXTM3U\n");
+        for (i, group) in self.groups.iter().enumerate() {
+            out.push_str(&format!("# Group {} ({:?})\n", i + 1, group.reason));
+            for file in &group.files {
+                out.push_str(&format!("{}\n", file.path.to_string_lossy()));
+            }
+        }
+        out
+    }
+
+    /// Move all but the best file of each group into folder, can be reverted as `duplicates` run.
+    /// Returns (from, to)
+    pub fn quarantine(&self, folder: impl AsRef<Path>) -> Result<Vec<(PathBuf, PathBuf)>, Error> {
+        let journal = UndoJournal::new("duplicates")?;
+        std::fs::create_dir_all(&folder)?;
+        let mut moved = vec![];
+        for file in self.groups.iter().flat_map(|g| g.files.iter().skip(1)) {
+            // Duplicates often share the filename
+            let stem = file.path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+            let ext = file.path.extension().unwrap_or_default().to_string_lossy().to_string();
+            let mut target = folder.as_ref().join(format!("{stem}.{ext}"));
+            let mut n = 1;
+            while target.exists() {
+                target = folder.as_ref().join(format!("{stem} ({n}).{ext}"));
+                n += 1;
+            }
+            match move_file(&file.path, &target) {
+                Ok(target) => {
+                    // Move back, so no file is left without undo record
+                    if let Err(e) = journal.moved(&file.path, &target) {
+                        if let Err(e) = move_file(&target, &file.path) {
+                            error!("Failed moving back {target:?}: {e}");
+                        }
+                        return Err(e);
+                    }
+                    moved.push((file.path.clone(), target));
+                },
+                Err(e) => warn!("Failed moving duplicate {:?}: {e}", file.path),
+            }
+        }
+        Ok(moved)
+    }
+}
+
+/// Rename or copy if on another drive
+fn move_file(from: &Path, to: &Path) -> Result<PathBuf, Error> {
+    if std::fs::rename(from, to).is_err() {
+        std::fs::copy(from, to)?;
+        std::fs::remove_file(from)?;
+    }
+    Ok(to.to_owned())
+}
+
+/// Analyzed file
+struct Entry {
+    file: DuplicateFile,
+    fingerprint: Option<Vec<u32>>,
+    isrc: Option<String>,
+    /// Cleaned artists + title
+    metadata: Option<String>,
+}
+
+pub struct DuplicateFinder;
+
+impl DuplicateFinder {
+    /// Find duplicates in folder
+    pub fn find(config: &DuplicatesConfig) -> DuplicateReport {
+        let files = match config.path.is_file() {
+            true => vec![config.path.clone()],
+            false => AudioFileInfo::get_file_list(&config.path, config.subfolders)
+        };
+        let fingerprint = config.fingerprint;
+        let mut entries = vec![];
+        let mut report = DuplicateReport::default();
+        for (path, result) in analyze_files(files, config.threads, move |path| Self::analyze(path, fingerprint)) {
+            match result {
+                Ok(entry) => entries.push(entry),
+                Err(e) => {
+                    warn!("Failed loading {path:?} for duplicate search: {e}");
+                    report.failed.push(path);
+                }
+            }
+        }
+        info!("Searching duplicates in {} files", entries.len());
+        report.groups = Self::group(entries, config);
+        report
+    }
+
+    /// Load file info and fingerprint
+    fn analyze(path: &Path, fingerprint: bool) -> Result<Entry, Error> {
+        let (file, tag) = DuplicateFile::load(path)?;
+        let t = tag.tag();
+        let first = |field: Field| t.get_field(field).and_then(|v| v.first().cloned()).filter(|v| !v.trim().is_empty());
+        let isrc = first(Field::ISRC).map(|i| i.trim().replace('-', "").to_uppercase());
+        let metadata = match (first(Field::Title), t.get_field(Field::Artist)) {
+            (Some(title), Some(artists)) => {
+                let artists = AudioFileInfo::parse_artist_tag(artists.iter().map(|a| a.as_str()).collect());
+                Some(format!("{} - {}", MatchingUtils::clean_artists(&artists).join(", "), MatchingUtils::clean_title_matching(&title)))
+            },
+            _ => None
+        };
+        let fingerprint = match fingerprint {
+            true => Fingerprint::from_file(path).inspect_err(|e| warn!("Failed fingerprinting {path:?}: {e}")).ok().map(|f| f.data),
+            false => None
+        };
+        Ok(Entry { file, fingerprint, isrc, metadata })
+    }
+
+    /// Group entries, fingerprints are preferred, ISRC and metadata are used when either file has no fingerprint
+    fn group(entries: Vec<Entry>, config: &DuplicatesConfig) -> Vec<DuplicateGroup> {
+        let mut groups = UnionFind::new(entries.len());
+        let duration_ok = |a: &Entry, b: &Entry| a.file.duration.abs_diff(b.file.duration) <= config.max_duration_difference as u128 * 1000;
+
+        // Fingerprints, only compare files sharing some values
+        let mut postings: HashMap<u32, Vec<usize>> = HashMap::new();
+        for (i, entry) in entries.iter().enumerate() {
+            let mut values = entry.fingerprint.clone().unwrap_or_default();
+            values.sort_unstable();
+            values.dedup();
+            for v in values {
+                postings.entry(v).or_default().push(i);
+            }
+        }
+        let mut shared: HashMap<(usize, usize), usize> = HashMap::new();
+        for files in postings.values().filter(|f| f.len() > 1 && f.len() <= MAX_POSTINGS) {
+            for (n, a) in files.iter().enumerate() {
+                for b in &files[n + 1..] {
+                    *shared.entry((*a, *b)).or_default() += 1;
+                }
+            }
+        }
+        for ((a, b), _) in shared.into_iter().filter(|(_, count)| *count >= MIN_SHARED) {
+            let (ea, eb) = (&entries[a], &entries[b]);
+            if duration_ok(ea, eb) && similarity(ea.fingerprint.as_ref().unwrap(), eb.fingerprint.as_ref().unwrap()) >= config.similarity {
+                groups.union(a, b, DuplicateReason::Fingerprint);
+            }
+        }
+
+        // ISRC and metadata fallback
+        let mut isrcs: HashMap<&str, Vec<usize>> = HashMap::new();
+        let mut metadata: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (i, entry) in entries.iter().enumerate() {
+            if let Some(isrc) = &entry.isrc {
+                isrcs.entry(isrc).or_default().push(i);
+            }
+            if let Some(m) = &entry.metadata {
+                metadata.entry(m).or_default().push(i);
+            }
+        }
+        for (map, reason) in [(isrcs, DuplicateReason::Isrc), (metadata, DuplicateReason::Metadata)] {
+            for files in map.values() {
+                for (n, a) in files.iter().enumerate() {
+                    for b in &files[n + 1..] {
+                        let (ea, eb) = (&entries[*a], &entries[*b]);
+                        if ea.fingerprint.is_some() && eb.fingerprint.is_some() {
+                            continue;
+                        }
+                        if reason == DuplicateReason::Isrc || duration_ok(ea, eb) {
+                            groups.union(*a, *b, reason);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Collect and rank
+        let mut out: HashMap<usize, Vec<DuplicateFile>> = HashMap::new();
+        let roots = (0..entries.len()).map(|i| groups.find(i)).collect::<Vec<_>>();
+        for (entry, root) in entries.into_iter().zip(&roots) {
+            out.entry(*root).or_default().push(entry.file);
+        }
+        let mut out = out.into_iter()
+            .filter(|(_, files)| files.len() > 1)
+            .map(|(root, mut files)| {
+                files.sort_by_key(|f| std::cmp::Reverse(f.quality()));
+                DuplicateGroup { reason: groups.reason[root].unwrap(), files }
+            })
+            .collect::<Vec<_>>();
+        out.sort_by(|a, b| a.files[0].path.cmp(&b.files[0].path));
+        out
+    }
+}
+
+/// Ratio of matching bits at the best alignment
+fn similarity(a: &[u32], b: &[u32]) -> f32 {
+    let mut best = 0.0f32;
+    for offset in 0..=MAX_OFFSET {
+        for (a, b) in [(a, b), (b, a)] {
+            if offset >= a.len() {
+                continue;
+            }
+            let a = &a[offset..];
+            let len = a.len().min(b.len());
+            if len < MIN_OVERLAP {
+                continue;
+            }
+            let errors: u32 = a.iter().zip(b).map(|(x, y)| (x ^ y).count_ones()).sum();
+            best = best.max(1.0 - errors as f32 / (len * 32) as f32);
+        }
+    }
+    best
+}
+
+/// Disjoint sets with the strongest reason of each group
+struct UnionFind {
+    parent: Vec<usize>,
+    reason: Vec<Option<DuplicateReason>>,
+}
+
+impl UnionFind {
+    fn new(len: usize) -> UnionFind {
+        UnionFind { parent: (0..len).collect(), reason: vec![None; len] }
+    }
+
+    fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        self.parent[i] = root;
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize, reason: DuplicateReason) {
+        let (a, b) = (self.find(a), self.find(b));
+        let reason = [self.reason[a], self.reason[b], Some(reason)].into_iter().flatten().min();
+        self.parent[b] = a;
+        self.reason[a] = reason;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, lossless: bool, bitrate: u32, fingerprint: Option<Vec<u32>>, isrc: Option<&str>) -> Entry {
+        Entry {
+            file: DuplicateFile {
+                path: path.into(), format: AudioFileFormat::MP3, lossless, bitrate, sample_rate: 44100,
+                bit_depth: None, duration: 200_000, tags: 0, art: None
+            },
+            fingerprint,
+            isrc: isrc.map(String::from),
+            metadata: None
+        }
+    }
+
+    #[test]
+    fn test_similarity() {
+        // Pseudo random fingerprint
+        let a = (0..500u32).map(|i| i.wrapping_mul(2654435761).rotate_left(i % 32)).collect::<Vec<_>>();
+        let b = (0..500u32).map(|i| (i + 7).wrapping_mul(40503).wrapping_mul(2246822519)).collect::<Vec<_>>();
+        assert_eq!(similarity(&a, &a), 1.0);
+        // Shifted + few flipped bits
+        let shifted = a[20..].iter().enumerate().map(|(i, v)| if i % 10 == 0 { v ^ 0b101 } else { *v }).collect::<Vec<_>>();
+        assert!(similarity(&a, &shifted) > 0.99);
+        assert!(similarity(&shifted, &a) > 0.99);
+        assert!(similarity(&a, &b) < 0.6);
+    }
+
+    #[test]
+    fn test_group() {
+        let fp = (0..500u32).map(|i| i.wrapping_mul(2654435761)).collect::<Vec<_>>();
+        let other = (0..500u32).map(|i| i.wrapping_mul(2246822519) ^ 0xdeadbeef).collect::<Vec<_>>();
+        let entries = vec![
+            entry("a.mp3", false, 320, Some(fp.clone()), None),
+            entry("b.flac", true, 900, Some(fp.clone()), None),
+            entry("c.mp3", false, 128, Some(other), Some("USABC1234567")),
+            entry("d.mp3", false, 256, None, Some("USABC1234567")),
+            entry("e.mp3", false, 256, None, None),
+        ];
+        let groups = DuplicateFinder::group(entries, &DuplicatesConfig::default());
+        assert_eq!(groups.len(), 2);
+        // Lossless first
+        assert_eq!(groups[0].reason, DuplicateReason::Fingerprint);
+        assert_eq!(groups[0].files.iter().map(|f| f.path.to_str().unwrap()).collect::<Vec<_>>(), ["b.flac", "a.mp3"]);
+        assert_eq!(groups[1].reason, DuplicateReason::Isrc);
+        assert_eq!(groups[1].files.iter().map(|f| f.path.to_str().unwrap()).collect::<Vec<_>>(), ["d.mp3", "c.mp3"]);
+    }
+}
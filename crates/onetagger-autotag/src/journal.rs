@@ -0,0 +1,368 @@
+use anyhow::Error;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::hash::Hasher;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use serde::{Serialize, Deserialize};
+use fnv::FnvHasher;
+use onetagger_tag::{AudioFileFormat, CoverType, Field, Tag};
+use onetagger_shared::Settings;
+
+/// Records the tag state of every file before it is first written,
+/// so the whole run can be reverted later.
+/// Stored in `runs/undo-<id>/`, one JSON entry per line + deduplicated pictures and raw ID3 tags.
+pub struct UndoJournal {
+    id: String,
+    folder: PathBuf,
+    file: Mutex<File>,
+    touched: Mutex<HashSet<PathBuf>>,
+}
+
+impl UndoJournal {
+    /// Create new journal, kind is used as prefix of the ID (autotagger, quicktag...)
+    pub fn new(kind: &str) -> Result<UndoJournal, Error> {
+        let id = format!("{kind}-{}", timestamp!());
+        let folder = Self::runs_folder()?.join(format!("undo-{id}"));
+        std::fs::create_dir_all(folder.join("pictures"))?;
+        std::fs::create_dir_all(folder.join("id3"))?;
+        let file = File::create(folder.join("journal.jsonl"))?;
+        Ok(UndoJournal {
+            id,
+            folder,
+            file: Mutex::new(file),
+            touched: Mutex::new(HashSet::new())
+        })
+    }
+
+    /// Get ID of this journal
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Save the current state of file, only the first call per file is recorded
+    pub fn snapshot(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref().to_owned();
+        if !self.touched.lock().unwrap().insert(path.clone()) {
+            return Ok(());
+        }
+        let snapshot = TagSnapshot::load(&path, &self.folder)?;
+        self.write_entry(&JournalEntry::Snapshot(snapshot))
+    }
+
+    /// Record file being moved (AutoTagger move success / failed)
+    pub fn moved(&self, from: impl AsRef<Path>, to: impl AsRef<Path>) -> Result<(), Error> {
+        if from.as_ref() == to.as_ref() {
+            return Ok(());
+        }
+        self.write_entry(&JournalEntry::Moved { from: from.as_ref().to_owned(), to: to.as_ref().to_owned() })
+    }
+
+    /// Append entry to journal file
+    fn write_entry(&self, entry: &JournalEntry) -> Result<(), Error> {
+        let mut file = self.file.lock().unwrap();
+        writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Get path to runs folder
+    fn runs_folder() -> Result<PathBuf, Error> {
+        Ok(Settings::get_folder()?.join("runs"))
+    }
+
+    /// List all saved journals, newest first
+    pub fn list() -> Result<Vec<JournalInfo>, Error> {
+        let folder = Self::runs_folder()?;
+        if !folder.exists() {
+            return Ok(vec![]);
+        }
+        let mut out = vec![];
+        for entry in std::fs::read_dir(&folder)?.filter_map(|e| e.ok()) {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !name.starts_with("undo-") || !entry.path().join("journal.jsonl").exists() {
+                continue;
+            }
+            let id = name.replacen("undo-", "", 1);
+            let files = match Self::read_entries(&entry.path()) {
+                Ok(entries) => entries.iter().filter(|e| matches!(e, JournalEntry::Snapshot(_))).count(),
+                Err(e) => {
+                    warn!("Failed reading journal {id}: {e}");
+                    continue;
+                }
+            };
+            let created = id.rsplit('-').next().and_then(|t| t.parse().ok()).unwrap_or(0);
+            out.push(JournalInfo { id, files, created });
+        }
+        out.sort_by(|a, b| b.created.cmp(&a.created));
+        Ok(out)
+    }
+
+    /// Read all entries of journal in folder
+    fn read_entries(folder: &Path) -> Result<Vec<JournalEntry>, Error> {
+        let reader = BufReader::new(File::open(folder.join("journal.jsonl"))?);
+        let mut entries = vec![];
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            // Last line might be incomplete after crash
+            match serde_json::from_str(&line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => warn!("Invalid journal entry: {e}"),
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Restore all files of a journal to their original state
+    /// Returns list of files and the result of reverting them
+    pub fn revert(id: &str) -> Result<Vec<(PathBuf, Result<(), Error>)>, Error> {
+        let folder = Self::runs_folder()?.join(format!("undo-{id}"));
+        if !folder.join("journal.jsonl").exists() {
+            return Err(anyhow!("Run {id} not found!"));
+        }
+        let entries = Self::read_entries(&folder)?;
+
+        // Where did the files end up
+        let mut moves: HashMap<PathBuf, PathBuf> = HashMap::new();
+        for entry in &entries {
+            if let JournalEntry::Moved { from, to } = entry {
+                moves.insert(from.to_owned(), to.to_owned());
+            }
+        }
+
+        let mut out = vec![];
+        for entry in entries {
+            if let JournalEntry::Snapshot(snapshot) = entry {
+                let result = Self::revert_snapshot(&snapshot, moves.remove(&snapshot.path), &folder);
+                if let Err(e) = &result {
+                    warn!("Failed reverting {}: {e}", snapshot.path.display());
+                }
+                out.push((snapshot.path, result));
+            }
+        }
+        // Files which were only moved (failed files)
+        for (original, moved) in moves {
+            let result = Self::move_back(&moved, &original);
+            if let Err(e) = &result {
+                warn!("Failed moving back {}: {e}", original.display());
+            }
+            out.push((original, result));
+        }
+        info!("Reverted run {id}, {} files", out.len());
+        Ok(out)
+    }
+
+    /// Move file back to original location if needed and restore tags
+    fn revert_snapshot(snapshot: &TagSnapshot, moved: Option<PathBuf>, folder: &Path) -> Result<(), Error> {
+        if let Some(moved) = moved {
+            Self::move_back(&moved, &snapshot.path)?;
+        }
+        snapshot.restore(folder)
+    }
+
+    /// Move file to its original location
+    fn move_back(moved: &Path, original: &Path) -> Result<(), Error> {
+        if !moved.exists() || original.exists() {
+            return Ok(());
+        }
+        if let Some(parent) = original.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Try to rename, if fails copy
+        if std::fs::rename(moved, original).is_err() {
+            std::fs::copy(moved, original)?;
+            std::fs::remove_file(moved)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+enum JournalEntry {
+    Snapshot(TagSnapshot),
+    Moved { from: PathBuf, to: PathBuf }
+}
+
+/// Info about saved journal for listing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalInfo {
+    pub id: String,
+    /// Amount of touched files
+    pub files: usize,
+    /// Timestamp in ms
+    pub created: u128,
+}
+
+/// Hash data for filename, stable across Rust versions unlike `DefaultHasher`
+fn stable_hash(data: &[u8]) -> String {
+    let mut hasher = FnvHasher::default();
+    hasher.write(data);
+    format!("{:016x}", hasher.finish())
+}
+
+/// State of a tag before it was written
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagSnapshot {
+    pub path: PathBuf,
+    pub format: AudioFileFormat,
+    pub tags: HashMap<String, Vec<String>>,
+    pub pictures: Vec<PictureSnapshot>,
+    pub rating: Option<u8>,
+    /// Not part of `all_tags` in MP4
+    pub track_number: Option<String>,
+    pub track_total: Option<u16>,
+    /// Whole ID3 tag (filename in the id3 folder), because not all frames are text
+    #[serde(default)]
+    pub id3: Option<String>,
+}
+
+/// Picture metadata, data is saved separately
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PictureSnapshot {
+    pub kind: CoverType,
+    pub mime: String,
+    pub description: String,
+    /// Filename in the pictures folder
+    pub file: String,
+}
+
+impl TagSnapshot {
+    /// Load current state of file, save pictures and raw ID3 into the journal folder
+    pub fn load(path: impl AsRef<Path>, folder: impl AsRef<Path>) -> Result<TagSnapshot, Error> {
+        let tag_wrap = Tag::load_file(&path, false)?;
+        let tag = tag_wrap.tag();
+
+        // Save pictures, deduplicated by hash since usually the whole album has the same art
+        let mut pictures = vec![];
+        for picture in tag.get_art() {
+            let file = stable_hash(&picture.data);
+            let picture_path = folder.as_ref().join("pictures").join(&file);
+            if !picture_path.exists() {
+                std::fs::write(&picture_path, &picture.data)?;
+            }
+            pictures.push(PictureSnapshot { kind: picture.kind, mime: picture.mime, description: picture.description, file });
+        }
+
+        // Raw ID3, so SYLT, PRIV, GEOB etc. are reverted as well
+        let id3 = match &tag_wrap {
+            Tag::ID3(id3) => {
+                let data = id3.to_bytes()?;
+                let file = stable_hash(&data);
+                std::fs::write(folder.as_ref().join("id3").join(&file), &data)?;
+                Some(file)
+            },
+            _ => None
+        };
+
+        Ok(TagSnapshot {
+            path: path.as_ref().to_owned(),
+            format: tag_wrap.format(),
+            tags: tag.all_tags(),
+            rating: tag.get_rating(),
+            track_number: tag.get_field(Field::TrackNumber).and_then(|v| v.first().map(String::from)),
+            track_total: tag.get_field(Field::TrackTotal).and_then(|v| v.first().and_then(|t| t.parse().ok())),
+            pictures,
+            id3,
+        })
+    }
+
+    /// Write the snapshot back to file
+    pub fn restore(&self, folder: impl AsRef<Path>) -> Result<(), Error> {
+        let mut tag_wrap = Tag::load_file(&self.path, true)?;
+        if let Tag::MP4(mp4) = &mut tag_wrap {
+            mp4.remove_all_artworks();
+        }
+        let tag = tag_wrap.tag_mut();
+
+        // Rating first, because it might affect raw tags
+        match self.rating {
+            Some(rating) => tag.set_rating(rating, true),
+            None => {
+                let key = match self.format {
+                    AudioFileFormat::FLAC | AudioFileFormat::OGG => "RATING",
+                    AudioFileFormat::MP4 => "rate",
+                    AudioFileFormat::APE => "Rating",
+                    _ => "POPM"
+                };
+                tag.remove_raw(key);
+                tag.remove_raw("RATING WMP");
+            }
+        }
+
+        // Remove tags which weren't there
+        for key in tag.all_tags().keys() {
+            if !self.tags.contains_key(key) {
+                tag.remove_raw(key);
+            }
+        }
+        if self.format == AudioFileFormat::MP4 {
+            match &self.track_number {
+                Some(track_number) => tag.set_track_number(track_number, self.track_total, true),
+                None => tag.remove_raw("trkn"),
+            }
+        }
+        for (key, value) in &self.tags {
+            // MP4 BPM is stored as integer
+            if self.format == AudioFileFormat::MP4 && key == "tmpo" {
+                tag.set_field(Field::BPM, value.clone(), true);
+                continue;
+            }
+            tag.set_raw(key, value.clone(), true);
+        }
+
+        // Pictures
+        for kind in CoverType::types() {
+            tag.remove_art(kind);
+        }
+        for picture in &self.pictures {
+            let data = std::fs::read(folder.as_ref().join("pictures").join(&picture.file))?;
+            tag.set_art(picture.kind.clone(), &picture.mime, Some(&picture.description), data);
+        }
+
+        // Replace all ID3 frames, text values above are still needed for RIFF INFO
+        if let (Tag::ID3(id3), Some(file)) = (&mut tag_wrap, &self.id3) {
+            id3.replace_from_bytes(&std::fs::read(folder.as_ref().join("id3").join(file))?)?;
+        }
+
+        tag_wrap.tag_mut().save_file(&self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use onetagger_tag::id3::ID3Tag;
+    use onetagger_tag::TagImpl;
+
+    #[test]
+    fn test_restore_without_rating() {
+        let folder = std::env::temp_dir().join(format!("onetagger_journal_test_{}", std::process::id()));
+        std::fs::create_dir_all(folder.join("pictures")).unwrap();
+        std::fs::create_dir_all(folder.join("id3")).unwrap();
+        let path = folder.join("track.mp3");
+        std::fs::write(&path, []).unwrap();
+        let mut tag = ID3Tag::load_or_new(&path);
+        tag.set_field(Field::Title, vec!["Title".to_string()], true);
+        tag.save_file(&path).unwrap();
+
+        let snapshot = TagSnapshot::load(&path, &folder).unwrap();
+        let mut tag = ID3Tag::load_file(&path).unwrap();
+        tag.set_rating(3, true);
+        tag.save_file(&path).unwrap();
+        snapshot.restore(&folder).unwrap();
+
+        let tag = ID3Tag::load_file(&path).unwrap();
+        std::fs::remove_dir_all(&folder).ok();
+        assert_eq!(tag.get_rating(), None);
+        assert_eq!(tag.get_field(Field::Title), Some(vec!["Title".to_string()]));
+    }
+}
@@ -0,0 +1,187 @@
+use anyhow::Error;
+use crossbeam_channel::Receiver;
+use rustfft::FftPlanner;
+use rustfft::num_complex::Complex;
+use serde::{Serialize, Deserialize};
+use std::path::{Path, PathBuf};
+use onetagger_tag::{Field, Tag};
+use onetagger_tagger::Key;
+
+use crate::analysis::{analyze_files, load_mono};
+
+/// Sample rate used for analysis
+const SAMPLE_RATE: u32 = 11025;
+/// FFT size (~2.7Hz per bin) and hop
+const FRAME: usize = 4096;
+const HOP: usize = 2048;
+/// Frequency range used for chroma
+const MIN_FREQ: f32 = 55.0;
+const MAX_FREQ: f32 = 2000.0;
+
+/// Krumhansl-Kessler key profiles, starting from tonic
+const MAJOR_PROFILE: [f32; 12] = [6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88];
+const MINOR_PROFILE: [f32; 12] = [6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17];
+
+/// Detected key
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyResult {
+    pub key: Key,
+    /// Correlation with the key profile, 0.0 - 1.0
+    pub confidence: f32,
+}
+
+/// Offline chromagram based key estimator
+pub struct KeyDetector;
+
+impl KeyDetector {
+    /// Detect key of audio file
+    pub fn detect_file(path: impl AsRef<Path>) -> Result<KeyResult, Error> {
+        let samples = load_mono(path, SAMPLE_RATE, 30_000, 120_000)?;
+        Self::detect(&samples, SAMPLE_RATE).ok_or(anyhow!("No key detected"))
+    }
+
+    /// Detect key from mono samples
+    pub fn detect(samples: &[f32], sample_rate: u32) -> Option<KeyResult> {
+        Self::estimate(&chromagram(samples, sample_rate))
+    }
+
+    /// Find key profile with highest correlation to chroma
+    fn estimate(chroma: &[f32; 12]) -> Option<KeyResult> {
+        if chroma.iter().sum::<f32>() <= 0.0 {
+            return None;
+        }
+        let mut best = (f32::MIN, Key::new(0, false));
+        for tonic in 0..12 {
+            for (profile, minor) in [(&MAJOR_PROFILE, false), (&MINOR_PROFILE, true)] {
+                let rotated: [f32; 12] = std::array::from_fn(|i| profile[(i + 12 - tonic) % 12]);
+                let r = correlation(chroma, &rotated);
+                if r > best.0 {
+                    best = (r, Key::new(tonic as u8, minor));
+                }
+            }
+        }
+        Some(KeyResult { key: best.1, confidence: best.0.clamp(0.0, 1.0) })
+    }
+
+    /// Detect key of multiple files in parallel
+    pub fn detect_files(files: Vec<PathBuf>, threads: usize) -> Receiver<(PathBuf, Result<KeyResult, Error>)> {
+        analyze_files(files, threads, |path| Self::detect_file(path))
+    }
+
+    /// Does the file already have key tag
+    pub fn has_key(path: impl AsRef<Path>) -> bool {
+        Tag::load_file(path, false)
+            .ok()
+            .and_then(|t| t.tag().get_field(Field::Key))
+            .map(|v| v.iter().any(|k| !k.trim().is_empty()))
+            .unwrap_or(false)
+    }
+
+    /// Write key to file, returns false if file already has key and overwrite is disabled
+    pub fn write_key(path: impl AsRef<Path>, key: &str, overwrite: bool) -> Result<bool, Error> {
+        if !overwrite && Self::has_key(&path) {
+            return Ok(false);
+        }
+        let mut tag_wrap = Tag::load_file(&path, true)?;
+        let tag = tag_wrap.tag_mut();
+        tag.set_field(Field::Key, vec![key.to_string()], true);
+        tag.save_file(path.as_ref())?;
+        Ok(true)
+    }
+}
+
+/// Sum of per-frame normalized pitch class energies
+fn chromagram(samples: &[f32], sample_rate: u32) -> [f32; 12] {
+    let fft = FftPlanner::<f32>::new().plan_fft_forward(FRAME);
+    let window = (0..FRAME)
+        .map(|i| 0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / FRAME as f32).cos())
+        .collect::<Vec<_>>();
+    // FFT bin => pitch class
+    let bins = (1..FRAME / 2).filter_map(|bin| {
+        let freq = bin as f32 * sample_rate as f32 / FRAME as f32;
+        if !(MIN_FREQ..=MAX_FREQ).contains(&freq) {
+            return None;
+        }
+        let midi = 12.0 * (freq / 440.0).log2() + 69.0;
+        Some((bin, (midi.round() as i32).rem_euclid(12) as usize))
+    }).collect::<Vec<_>>();
+
+    let mut chroma = [0.0; 12];
+    let mut buffer = vec![Complex::default(); FRAME];
+    let mut start = 0;
+    while start + FRAME <= samples.len() {
+        for (i, b) in buffer.iter_mut().enumerate() {
+            *b = Complex::new(samples[start + i] * window[i], 0.0);
+        }
+        fft.process(&mut buffer);
+
+        let mut frame = [0.0; 12];
+        for (bin, pitch) in &bins {
+            frame[*pitch] += buffer[*bin].norm();
+        }
+        // Every frame has the same weight, skip silence
+        let max = frame.iter().copied().fold(0.0, f32::max);
+        if max > 1e-3 {
+            for (c, f) in chroma.iter_mut().zip(frame) {
+                *c += f / max;
+            }
+        }
+        start += HOP;
+    }
+    chroma
+}
+
+/// Pearson correlation
+fn correlation(a: &[f32; 12], b: &[f32; 12]) -> f32 {
+    let (mean_a, mean_b) = (a.iter().sum::<f32>() / 12.0, b.iter().sum::<f32>() / 12.0);
+    let (mut cov, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        cov += (x - mean_a) * (y - mean_b);
+        var_a += (x - mean_a).powi(2);
+        var_b += (y - mean_b).powi(2);
+    }
+    if var_a <= 0.0 || var_b <= 0.0 {
+        return 0.0;
+    }
+    cov / (var_a * var_b).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One second long chords, notes as MIDI numbers
+    fn chords(chords: &[&[u8]]) -> Vec<f32> {
+        let mut samples = vec![];
+        for _ in 0..3 {
+            for chord in chords {
+                samples.extend((0..SAMPLE_RATE).map(|i| {
+                    let t = i as f32 / SAMPLE_RATE as f32;
+                    chord.iter().map(|n| {
+                        let freq = 440.0 * 2f32.powf((*n as f32 - 69.0) / 12.0);
+                        (t * freq * std::f32::consts::TAU).sin() * 0.2
+                    }).sum::<f32>()
+                }));
+            }
+        }
+        samples
+    }
+
+    #[test]
+    fn test_detect_key() {
+        // C - F - G - C
+        let c_major = chords(&[&[48, 60, 64, 67], &[53, 60, 65, 69], &[55, 59, 62, 67], &[48, 60, 64, 67]]);
+        assert_eq!(KeyDetector::detect(&c_major, SAMPLE_RATE).unwrap().key.to_string(), "C");
+        // Am - Dm - E - Am
+        let a_minor = chords(&[&[45, 57, 60, 64], &[50, 57, 62, 65], &[52, 56, 59, 64], &[45, 57, 60, 64]]);
+        let result = KeyDetector::detect(&a_minor, SAMPLE_RATE).unwrap();
+        assert_eq!(result.key.to_string(), "Am");
+        assert!(result.confidence > 0.5);
+        // F#m - Bm - C# - F#m
+        let fs_minor = chords(&[&[42, 54, 57, 61], &[47, 54, 59, 62], &[49, 53, 56, 61], &[42, 54, 57, 61]]);
+        assert_eq!(KeyDetector::detect(&fs_minor, SAMPLE_RATE).unwrap().key.to_string(), "F#m");
+        // Silence
+        assert!(KeyDetector::detect(&vec![0.0; SAMPLE_RATE as usize * 5], SAMPLE_RATE).is_none());
+    }
+}
@@ -2,7 +2,7 @@
 #[macro_use] extern crate anyhow;
 #[macro_use] extern crate onetagger_shared;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use anyhow::Error;
 use onetagger_renamer::{Renamer, RenamerConfig, TemplateParser};
 use rand::seq::SliceRandom;
@@ -16,6 +16,7 @@ use std::io::prelude::*;
 use chrono::Local;
 use execute::Execute;
 use onetagger_tagger::{FileTaggedStatus, LyricsExt, MatchReason, MatchingUtils, SupportedTag, TrackMatch};
+use onetagger_tagger::ratelimit;
 use regex::Regex;
 use reqwest::StatusCode;
 use walkdir::WalkDir;
@@ -25,22 +26,44 @@ use crossbeam_channel::{unbounded, Sender, Receiver};
 use onetagger_tag::{AudioFileFormat, Tag, Field, TagDate, CoverType, TagImpl, EXTENSIONS};
 use onetagger_shared::Settings;
 use onetagger_player::AudioSources;
-use onetagger_tagger::{Track, AudioFileInfo, TaggerConfig, StylesOptions, AutotaggerSource, AutotaggerSourceBuilder};
+use onetagger_library::{Library, LibraryFile};
+use onetagger_tagger::{Track, AudioFileInfo, TaggerConfig, StylesOptions, AutotaggerSource, AutotaggerSourceBuilder, IdentifyBackend, ShazamConfig};
+use onetagger_platforms::musicbrainz::RECORDING_ID_TAG;
+use onetagger_platforms::http::HTTP_CACHE;
 
 use crate::shazam::Shazam;
+pub use crate::shazam::ShazamMatch;
+use crate::acoustid::AcoustId;
+use crate::bpm::BpmDetector;
+use crate::key::KeyDetector;
+use crate::loudness::LoudnessScanner;
+use crate::dryrun::{TagDiff, DryRunReport};
+use crate::journal::UndoJournal;
+use crate::resume::{RunLog, ResumeState};
 mod shazam;
+mod acoustid;
+mod analysis;
 
 pub mod repo;
+pub mod dryrun;
+pub mod journal;
+pub mod resume;
 pub mod platforms;
 pub mod audiofeatures;
+pub mod bpm;
+pub mod key;
+pub mod loudness;
+pub mod duplicates;
+pub mod lossless;
+pub mod watch;
 
 // Re-exports
 pub use platforms::{AUTOTAGGER_PLATFORMS, AutotaggerPlatforms};
 
 
 lazy_static::lazy_static! {
-    /// Stop tagging global variable
-    pub static ref STOP_TAGGING: AtomicBool = AtomicBool::new(false);
+    /// Stop tagging global variable, used by UI and CLI runs
+    pub static ref STOP_TAGGING: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
 }
 
 pub trait TaggerConfigExt {
@@ -69,242 +92,296 @@ impl TaggerConfigExt for TaggerConfig {
 
 pub trait TrackImpl {
     fn write_to_file(&self, path: impl AsRef<Path>, config: &TaggerConfig) -> Result<(), Error>;
+    /// Apply tags in memory only and return what would change
+    fn diff_file(&self, path: impl AsRef<Path>, config: &TaggerConfig) -> Result<TagDiff, Error>;
     fn download_art(&self, url: &str) -> Result<Option<Vec<u8>>, Error>;
     fn merge_styles(self, option: &StylesOptions) -> Self;
 }
 
-impl TrackImpl for Track {
-    // Write tags to file
-    fn write_to_file(&self, path: impl AsRef<Path>, config: &TaggerConfig) -> Result<(), Error> {        
-        // Get tag
-        let mut tag_wrap = Tag::load_file(&path, true)?;
-        tag_wrap.set_separators(&config.separators);
-        let format = tag_wrap.format();
-
-        // Configure format specific
-        if let Tag::ID3(t) = &mut tag_wrap {
-            t.set_id3v24(config.id3v24);
-            if let Some(lang) = config.id3_comm_lang.as_ref() {
-                if !lang.is_empty() {
-                    t.set_comm_lang(lang.to_string());
-                }
-            }
-        }
-        // MP4 Album art override
-        if let Tag::MP4(mp4) = &mut tag_wrap {
-            // Has art
-            if (config.overwrite_tag(SupportedTag::AlbumArt) || mp4.get_art().is_empty()) && self.art.is_some() && config.tag_enabled(SupportedTag::AlbumArt) {
-                mp4.remove_all_artworks();
-            }
-        }
-        
-        let tag = tag_wrap.tag_mut();
-        // Set tags
-        if config.tag_enabled(SupportedTag::Title) {
-            match config.short_title {
-                true => tag.set_field(Field::Title, vec![self.title.to_string()], config.overwrite_tag(SupportedTag::Title)),
-                false => tag.set_field(Field::Title, vec![self.full_title()], config.overwrite_tag(SupportedTag::Title))
-            }
-        }
-        // Version
-        if config.tag_enabled(SupportedTag::Version) && self.version.is_some() {
-            tag.set_field(Field::Version, vec![self.version.as_ref().unwrap().to_string()], config.overwrite_tag(SupportedTag::Version));
-        }
-        if config.tag_enabled(SupportedTag::Artist) {
-            tag.set_field(Field::Artist, self.artists.clone(), config.overwrite_tag(SupportedTag::Artist));
-        }
-        if config.tag_enabled(SupportedTag::AlbumArtist) && !self.album_artists.is_empty() {
-            tag.set_field(Field::AlbumArtist, self.album_artists.clone(), config.overwrite_tag(SupportedTag::AlbumArtist));
-        }
-        if self.album.is_some() && config.tag_enabled(SupportedTag::Album)  {
-            tag.set_field(Field::Album, vec![self.album.as_ref().unwrap().to_string()], config.overwrite_tag(SupportedTag::Album));
-        }
-        if config.tag_enabled(SupportedTag::Key) && self.key.is_some() {
-            let mut value = self.key.as_ref().unwrap().to_string();
-            // Convert to camelot
-            if config.camelot {
-                value = onetagger_tagger::to_camelot(&value).to_owned();
+/// Load tag and configure it for writing
+fn load_tag_for_writing(path: impl AsRef<Path>, config: &TaggerConfig) -> Result<Tag, Error> {
+    let mut tag_wrap = Tag::load_file(&path, true)?;
+    tag_wrap.set_separators(&config.separators);
+
+    // Configure format specific
+    if let Tag::ID3(t) = &mut tag_wrap {
+        t.set_id3v24(config.id3v24);
+        if let Some(lang) = config.id3_comm_lang.as_ref() {
+            if !lang.is_empty() {
+                t.set_comm_lang(lang.to_string());
             }
-            tag.set_field(Field::Key, vec![value], config.overwrite_tag(SupportedTag::Key));
-        }
-        if config.tag_enabled(SupportedTag::BPM) && self.bpm.is_some() {
-            tag.set_field(Field::BPM, vec![self.bpm.unwrap().to_string()], config.overwrite_tag(SupportedTag::BPM));
         }
-        if config.tag_enabled(SupportedTag::Label) && self.label.is_some() {
-            tag.set_field(Field::Label, vec![self.label.as_ref().unwrap().to_string()], config.overwrite_tag(SupportedTag::Label));
-        }
-        if config.tag_enabled(SupportedTag::Genre) && !self.genres.is_empty() {
-            let mut genres = if config.merge_genres {
-                // Merge with existing ones
-                let mut current: Vec<String> = tag.get_field(Field::Genre).unwrap_or(vec![]).into_iter().filter(|i| !i.trim().is_empty()).collect::<Vec<_>>();
-                let mut genres = self.genres.clone().into_iter().filter(|g| !current.iter().any(|i| i.to_lowercase() == g.to_lowercase())).collect();
-                current.append(&mut genres);
-                current
-            } else {
-                self.genres.clone()
-            };
+    }
+    Ok(tag_wrap)
+}
 
-            // Capitalize genres
-            if config.capitalize_genres {
-                genres = genres.into_iter().map(|g| onetagger_shared::capitalize(&g)).collect();
-            }
+/// Apply track to the tag using the overwrite / merge rules from config
+/// When `dry_run` is set album art isn't downloaded nor removed
+/// Returns (downloaded cover data, whether album art is written)
+fn apply_track(track: &Track, tag_wrap: &mut Tag, config: &TaggerConfig, dry_run: bool) -> (Option<Vec<u8>>, bool) {
+    let format = tag_wrap.format();
 
-            tag.set_field(Field::Genre, genres, config.overwrite_tag(SupportedTag::Genre));
+    // MP4 Album art override
+    if let Tag::MP4(mp4) = tag_wrap {
+        // Has art
+        if !dry_run && (config.overwrite_tag(SupportedTag::AlbumArt) || mp4.get_art().is_empty()) && track.art.is_some() && config.tag_enabled(SupportedTag::AlbumArt) {
+            mp4.remove_all_artworks();
         }
-        if config.tag_enabled(SupportedTag::Style) && !self.styles.is_empty() {
-            if config.styles_options == StylesOptions::CustomTag && config.styles_custom_tag.is_some() {
-                // Custom style tag
-                let ui_tag = config.styles_custom_tag.as_ref().unwrap();
-                tag.set_raw(&ui_tag.by_format(&format), self.styles.clone(), config.overwrite_tag(SupportedTag::Style));
-
-            } else if config.merge_genres {
-                // Merge with existing ones
-                let mut current: Vec<String> = tag.get_field(Field::Style).unwrap_or(vec![]).into_iter().filter(|i| !i.trim().is_empty()).collect::<Vec<_>>();
-                let mut styles = self.styles.clone().into_iter().filter(|s| !current.iter().any(|i| i.to_lowercase() == s.to_lowercase())).collect();
-                current.append(&mut styles);
-                tag.set_field(Field::Style, current, config.overwrite_tag(SupportedTag::Style)); 
+    }
 
-            } else {
-                // Default write to style
-                tag.set_field(Field::Style, self.styles.clone(), config.overwrite_tag(SupportedTag::Style));
-            }
+    let tag = tag_wrap.tag_mut();
+    // Set tags
+    if config.tag_enabled(SupportedTag::Title) {
+        match config.short_title {
+            true => tag.set_field(Field::Title, vec![track.title.to_string()], config.overwrite_tag(SupportedTag::Title)),
+            false => tag.set_field(Field::Title, vec![track.full_title()], config.overwrite_tag(SupportedTag::Title))
         }
-        // Release dates
-        if config.tag_enabled(SupportedTag::ReleaseDate) {
-            if let Some(date) = self.release_date {
-                tag.set_date(&TagDate {
-                    year: date.year() as i32,
-                    month: match config.only_year {
-                        true => None,
-                        false => Some(date.month() as u8)
-                    },
-                    day: match config.only_year {
-                        true => None,
-                        false => Some(date.day() as u8)
-                    }
-                }, config.overwrite_tag(SupportedTag::ReleaseDate));
-            } else if let Some(year) = self.release_year {
-                tag.set_date(&TagDate {
-                    year: year as i32,
-                    month: None,
-                    day: None
-                }, config.overwrite_tag(SupportedTag::ReleaseDate));
-            }
-        }
-        // Publish date
-        if config.tag_enabled(SupportedTag::PublishDate) {
-            if let Some(date) = self.publish_date {
-                tag.set_publish_date(&TagDate {
-                    year: date.year() as i32,
-                    month: match config.only_year {
-                        true => None,
-                        false => Some(date.month() as u8)
-                    },
-                    day: match config.only_year {
-                        true => None,
-                        false => Some(date.day() as u8)
-                    }
-                }, config.overwrite_tag(SupportedTag::PublishDate));
-            } else if let Some(year) = self.publish_year {
-                tag.set_publish_date(&TagDate {
-                    year: year as i32,
-                    month: None,
-                    day: None
-                }, config.overwrite_tag(SupportedTag::PublishDate));
-            }
-        }
-        // URL
-        if config.tag_enabled(SupportedTag::URL) {
-            tag.set_raw("WWWAUDIOFILE", vec![self.url.to_string()], config.overwrite_tag(SupportedTag::URL));
-        }
-        // Other tags
-        if config.tag_enabled(SupportedTag::OtherTags) {
-            for (t, value) in &self.other {
-                tag.set_raw(&t.by_format(&format), value.to_owned(), config.overwrite_tag(SupportedTag::OtherTags));
-            }
-        }
-        // IDs
-        if config.tag_enabled(SupportedTag::TrackId) && self.track_id.is_some() {
-            let t = format!("{}_TRACK_ID", serde_json::to_value(self.platform.clone()).unwrap().as_str().unwrap().to_uppercase());
-            tag.set_raw(&t, vec![self.track_id.as_ref().unwrap().to_string()], config.overwrite_tag(SupportedTag::TrackId));
-        }
-        if config.tag_enabled(SupportedTag::ReleaseId) && self.release_id.is_some() {
-            let t = format!("{}_RELEASE_ID", serde_json::to_value(self.platform.clone()).unwrap().as_str().unwrap().to_uppercase());
-            tag.set_raw(&t, vec![self.release_id.as_ref().unwrap().to_string()], config.overwrite_tag(SupportedTag::ReleaseId));
-        }
-        // Catalog number
-        if config.tag_enabled(SupportedTag::CatalogNumber) && self.catalog_number.is_some() {
-            tag.set_field(Field::CatalogNumber, vec![self.catalog_number.as_ref().unwrap().to_string()], config.overwrite_tag(SupportedTag::CatalogNumber));
-        }
-        // Duration
-        if config.tag_enabled(SupportedTag::Duration) && self.duration.as_secs() > 0 {
-            tag.set_field(Field::Duration, vec![self.duration.as_secs().to_string()], config.overwrite_tag(SupportedTag::Duration));
-        }
-        // Remixers
-        if config.tag_enabled(SupportedTag::Remixer) && !self.remixers.is_empty() {
-            tag.set_field(Field::Remixer, self.remixers.clone(), config.overwrite_tag(SupportedTag::Remixer));
+    }
+    // Version
+    if config.tag_enabled(SupportedTag::Version) && track.version.is_some() {
+        tag.set_field(Field::Version, vec![track.version.as_ref().unwrap().to_string()], config.overwrite_tag(SupportedTag::Version));
+    }
+    if config.tag_enabled(SupportedTag::Artist) {
+        tag.set_field(Field::Artist, track.artists.clone(), config.overwrite_tag(SupportedTag::Artist));
+    }
+    if config.tag_enabled(SupportedTag::AlbumArtist) && !track.album_artists.is_empty() {
+        tag.set_field(Field::AlbumArtist, track.album_artists.clone(), config.overwrite_tag(SupportedTag::AlbumArtist));
+    }
+    if track.album.is_some() && config.tag_enabled(SupportedTag::Album)  {
+        tag.set_field(Field::Album, vec![track.album.as_ref().unwrap().to_string()], config.overwrite_tag(SupportedTag::Album));
+    }
+    if config.tag_enabled(SupportedTag::Key) && track.key.is_some() {
+        let value = onetagger_tagger::convert_key(track.key.as_ref().unwrap(), config.key_notation);
+        tag.set_field(Field::Key, vec![value], config.overwrite_tag(SupportedTag::Key));
+    }
+    if config.tag_enabled(SupportedTag::BPM) && track.bpm.is_some() {
+        tag.set_field(Field::BPM, vec![track.bpm.unwrap().to_string()], config.overwrite_tag(SupportedTag::BPM));
+    }
+    if config.tag_enabled(SupportedTag::Label) && track.label.is_some() {
+        tag.set_field(Field::Label, vec![track.label.as_ref().unwrap().to_string()], config.overwrite_tag(SupportedTag::Label));
+    }
+    if config.tag_enabled(SupportedTag::Genre) && !track.genres.is_empty() {
+        let mut genres = if config.merge_genres {
+            // Merge with existing ones
+            let mut current: Vec<String> = tag.get_field(Field::Genre).unwrap_or(vec![]).into_iter().filter(|i| !i.trim().is_empty()).collect::<Vec<_>>();
+            let mut genres = track.genres.clone().into_iter().filter(|g| !current.iter().any(|i| i.to_lowercase() == g.to_lowercase())).collect();
+            current.append(&mut genres);
+            current
+        } else {
+            track.genres.clone()
+        };
+
+        // Capitalize genres
+        if config.capitalize_genres {
+            genres = genres.into_iter().map(|g| onetagger_shared::capitalize(&g)).collect();
         }
-        // ISRC
-        if config.tag_enabled(SupportedTag::ISRC) && self.isrc.is_some() {
-            tag.set_field(Field::ISRC, vec![self.isrc.clone().unwrap()], config.overwrite_tag(SupportedTag::ISRC));
+
+        tag.set_field(Field::Genre, genres, config.overwrite_tag(SupportedTag::Genre));
+    }
+    if config.tag_enabled(SupportedTag::Style) && !track.styles.is_empty() {
+        if config.styles_options == StylesOptions::CustomTag && config.styles_custom_tag.is_some() {
+            // Custom style tag
+            let ui_tag = config.styles_custom_tag.as_ref().unwrap();
+            tag.set_raw(&ui_tag.by_format(&format), track.styles.clone(), config.overwrite_tag(SupportedTag::Style));
+
+        } else if config.merge_genres {
+            // Merge with existing ones
+            let mut current: Vec<String> = tag.get_field(Field::Style).unwrap_or(vec![]).into_iter().filter(|i| !i.trim().is_empty()).collect::<Vec<_>>();
+            let mut styles = track.styles.clone().into_iter().filter(|s| !current.iter().any(|i| i.to_lowercase() == s.to_lowercase())).collect();
+            current.append(&mut styles);
+            tag.set_field(Field::Style, current, config.overwrite_tag(SupportedTag::Style)); 
+
+        } else {
+            // Default write to style
+            tag.set_field(Field::Style, track.styles.clone(), config.overwrite_tag(SupportedTag::Style));
         }
-        // Mood
-        if config.tag_enabled(SupportedTag::Mood) && self.mood.is_some() {
-            tag.set_field(Field::Mood, vec![self.mood.clone().unwrap()], config.overwrite_tag(SupportedTag::Mood));
+    }
+    // Release dates
+    if config.tag_enabled(SupportedTag::ReleaseDate) {
+        if let Some(date) = track.release_date {
+            tag.set_date(&TagDate {
+                year: date.year() as i32,
+                month: match config.only_year {
+                    true => None,
+                    false => Some(date.month() as u8)
+                },
+                day: match config.only_year {
+                    true => None,
+                    false => Some(date.day() as u8)
+                }
+            }, config.overwrite_tag(SupportedTag::ReleaseDate));
+        } else if let Some(year) = track.release_year {
+            tag.set_date(&TagDate {
+                year: year as i32,
+                month: None,
+                day: None
+            }, config.overwrite_tag(SupportedTag::ReleaseDate));
         }
-        // Disc number
-        if config.tag_enabled(SupportedTag::DiscNumber) && self.disc_number.is_some() {
-            tag.set_field(Field::DiscNumber, vec![self.disc_number.clone().unwrap().to_string()], config.overwrite_tag(SupportedTag::DiscNumber));
+    }
+    // Publish date
+    if config.tag_enabled(SupportedTag::PublishDate) {
+        if let Some(date) = track.publish_date {
+            tag.set_publish_date(&TagDate {
+                year: date.year() as i32,
+                month: match config.only_year {
+                    true => None,
+                    false => Some(date.month() as u8)
+                },
+                day: match config.only_year {
+                    true => None,
+                    false => Some(date.day() as u8)
+                }
+            }, config.overwrite_tag(SupportedTag::PublishDate));
+        } else if let Some(year) = track.publish_year {
+            tag.set_publish_date(&TagDate {
+                year: year as i32,
+                month: None,
+                day: None
+            }, config.overwrite_tag(SupportedTag::PublishDate));
         }
-        // Track number
-        if config.tag_enabled(SupportedTag::TrackNumber) && self.track_number.is_some() {
-            match config.tag_enabled(SupportedTag::TrackTotal) {
-                true => tag.set_track_number(&self.track_number.as_ref().unwrap().to_string_with_zeroes(config.track_number_leading_zeroes), self.track_total.clone(), config.overwrite_tag(SupportedTag::TrackNumber)),
-                false => tag.set_track_number(&self.track_number.as_ref().unwrap().to_string_with_zeroes(config.track_number_leading_zeroes), None, config.overwrite_tag(SupportedTag::TrackNumber)),
-            }
+    }
+    // URL
+    if config.tag_enabled(SupportedTag::URL) {
+        tag.set_raw("WWWAUDIOFILE", vec![track.url.to_string()], config.overwrite_tag(SupportedTag::URL));
+    }
+    // Other tags
+    if config.tag_enabled(SupportedTag::OtherTags) {
+        for (t, value) in &track.other {
+            tag.set_raw(&t.by_format(&format), value.to_owned(), config.overwrite_tag(SupportedTag::OtherTags));
         }
-        // Lyrics
-        if config.tag_enabled(SupportedTag::SyncedLyrics) && self.lyrics.is_some() {
-            tag.set_lyrics(self.lyrics.as_ref().unwrap(), true, config.overwrite_tag(SupportedTag::SyncedLyrics));
+    }
+    // IDs
+    if config.tag_enabled(SupportedTag::TrackId) && track.track_id.is_some() {
+        let t = format!("{}_TRACK_ID", serde_json::to_value(track.platform.clone()).unwrap().as_str().unwrap().to_uppercase());
+        tag.set_raw(&t, vec![track.track_id.as_ref().unwrap().to_string()], config.overwrite_tag(SupportedTag::TrackId));
+    }
+    if config.tag_enabled(SupportedTag::ReleaseId) && track.release_id.is_some() {
+        let t = format!("{}_RELEASE_ID", serde_json::to_value(track.platform.clone()).unwrap().as_str().unwrap().to_uppercase());
+        tag.set_raw(&t, vec![track.release_id.as_ref().unwrap().to_string()], config.overwrite_tag(SupportedTag::ReleaseId));
+    }
+    // Catalog number
+    if config.tag_enabled(SupportedTag::CatalogNumber) && track.catalog_number.is_some() {
+        tag.set_field(Field::CatalogNumber, vec![track.catalog_number.as_ref().unwrap().to_string()], config.overwrite_tag(SupportedTag::CatalogNumber));
+    }
+    // Duration
+    if config.tag_enabled(SupportedTag::Duration) && track.duration.as_secs() > 0 {
+        tag.set_field(Field::Duration, vec![track.duration.as_secs().to_string()], config.overwrite_tag(SupportedTag::Duration));
+    }
+    // Remixers
+    if config.tag_enabled(SupportedTag::Remixer) && !track.remixers.is_empty() {
+        tag.set_field(Field::Remixer, track.remixers.clone(), config.overwrite_tag(SupportedTag::Remixer));
+    }
+    // ISRC
+    if config.tag_enabled(SupportedTag::ISRC) && track.isrc.is_some() {
+        tag.set_field(Field::ISRC, vec![track.isrc.clone().unwrap()], config.overwrite_tag(SupportedTag::ISRC));
+    }
+    // Mood
+    if config.tag_enabled(SupportedTag::Mood) && track.mood.is_some() {
+        tag.set_field(Field::Mood, vec![track.mood.clone().unwrap()], config.overwrite_tag(SupportedTag::Mood));
+    }
+    // Disc number
+    if config.tag_enabled(SupportedTag::DiscNumber) && track.disc_number.is_some() {
+        tag.set_field(Field::DiscNumber, vec![track.disc_number.clone().unwrap().to_string()], config.overwrite_tag(SupportedTag::DiscNumber));
+    }
+    // Track number
+    if config.tag_enabled(SupportedTag::TrackNumber) && track.track_number.is_some() {
+        match config.tag_enabled(SupportedTag::TrackTotal) {
+            true => tag.set_track_number(&track.track_number.as_ref().unwrap().to_string_with_zeroes(config.track_number_leading_zeroes), track.track_total.clone(), config.overwrite_tag(SupportedTag::TrackNumber)),
+            false => tag.set_track_number(&track.track_number.as_ref().unwrap().to_string_with_zeroes(config.track_number_leading_zeroes), None, config.overwrite_tag(SupportedTag::TrackNumber)),
         }
-        if config.tag_enabled(SupportedTag::UnsyncedLyrics) && self.lyrics.is_some() {
-            tag.set_lyrics(self.lyrics.as_ref().unwrap(), false, config.overwrite_tag(SupportedTag::UnsyncedLyrics));
+    }
+    // Lyrics
+    if config.tag_enabled(SupportedTag::SyncedLyrics) && track.lyrics.is_some() {
+        tag.set_lyrics(track.lyrics.as_ref().unwrap(), true, config.overwrite_tag(SupportedTag::SyncedLyrics));
+    }
+    if config.tag_enabled(SupportedTag::UnsyncedLyrics) && track.lyrics.is_some() {
+        tag.set_lyrics(track.lyrics.as_ref().unwrap(), false, config.overwrite_tag(SupportedTag::UnsyncedLyrics));
+    }
+    // Explicit
+    if config.tag_enabled(SupportedTag::Explicit) && track.explicit.is_some() {
+        tag.set_explicit(track.explicit.unwrap());
+    }
+    // Credits
+    for (supported, field, values) in [
+        (SupportedTag::Composer, Field::Composer, &track.composers),
+        (SupportedTag::Lyricist, Field::Lyricist, &track.lyricists),
+        (SupportedTag::Conductor, Field::Conductor, &track.conductors),
+        (SupportedTag::Producer, Field::Producer, &track.producers),
+    ] {
+        if config.tag_enabled(supported) && !values.is_empty() {
+            tag.set_field(field, values.clone(), config.overwrite_tag(supported));
         }
-        // Explicit
-        if config.tag_enabled(SupportedTag::Explicit) && self.explicit.is_some() {
-            tag.set_explicit(self.explicit.unwrap());
+    }
+    // Text fields
+    for (supported, field, value) in [
+        (SupportedTag::Comment, Field::Comment, &track.comment),
+        (SupportedTag::Grouping, Field::Grouping, &track.grouping),
+        (SupportedTag::Copyright, Field::Copyright, &track.copyright),
+        (SupportedTag::ArtistSort, Field::ArtistSort, &track.artist_sort),
+        (SupportedTag::TitleSort, Field::TitleSort, &track.title_sort),
+        (SupportedTag::AlbumSort, Field::AlbumSort, &track.album_sort),
+        (SupportedTag::Work, Field::Work, &track.work),
+        (SupportedTag::Movement, Field::Movement, &track.movement),
+    ] {
+        if let (true, Some(value)) = (config.tag_enabled(supported), value) {
+            tag.set_field(field, vec![value.to_string()], config.overwrite_tag(supported));
         }
+    }
+    // Compilation
+    if config.tag_enabled(SupportedTag::Compilation) && track.compilation.is_some() {
+        let value = match track.compilation.unwrap() {
+            true => "1",
+            false => "0"
+        };
+        tag.set_field(Field::Compilation, vec![value.to_string()], config.overwrite_tag(SupportedTag::Compilation));
+    }
 
-        // Album art
-        let mut cover_data = None;
-        if (config.overwrite_tag(SupportedTag::AlbumArt) || tag.get_art().is_empty()) && self.art.is_some() && config.tag_enabled(SupportedTag::AlbumArt) {
-            info!("Downloading art: {:?}", self.art);
-            match self.download_art(self.art.as_ref().unwrap()) {
-                Ok(data) => {
-                    match data {
-                        Some(data) => {
-                            // Remove covers
-                            if config.remove_all_covers {
-                                for t in CoverType::types() {
-                                    tag.remove_art(t);
-                                }
+    // Meta tags (date / success)
+    if config.tag_enabled(SupportedTag::MetaTags) {
+        let time = Local::now();
+        tag.set_raw("1T_TAGGEDDATE", vec![format!("{}_AT", time.format("%Y-%m-%d %H:%M:%S"))], true);
+    }
+
+    // Album art (last, so dry run can return early)
+    let mut cover_data = None;
+    let mut art_written = false;
+    if (config.overwrite_tag(SupportedTag::AlbumArt) || tag.get_art().is_empty()) && track.art.is_some() && config.tag_enabled(SupportedTag::AlbumArt) {
+        // Don't download in dry run
+        if dry_run {
+            return (None, true);
+        }
+        info!("Downloading art: {:?}", track.art);
+        match track.download_art(track.art.as_ref().unwrap()) {
+            Ok(data) => {
+                match data {
+                    Some(data) => {
+                        // Remove covers
+                        if config.remove_all_covers {
+                            for t in CoverType::types() {
+                                tag.remove_art(t);
                             }
+                        }
 
-                            tag.set_art(CoverType::CoverFront, "image/jpeg", Some("Cover"), data.clone());
-                            cover_data = Some(data);
-                        },
-                        None => warn!("Invalid album art!")
-                    } 
-                },
-                Err(e) => warn!("Error downloading album art! {}", e)
-            }
+                        tag.set_art(CoverType::CoverFront, "image/jpeg", Some("Cover"), data.clone());
+                        cover_data = Some(data);
+                        art_written = true;
+                    },
+                    None => warn!("Invalid album art!")
+                } 
+            },
+            Err(e) => warn!("Error downloading album art! {}", e)
         }
+    }
 
-        // Meta tags (date / success)
-        if config.tag_enabled(SupportedTag::MetaTags) {
-            let time = Local::now();
-            tag.set_raw("1T_TAGGEDDATE", vec![format!("{}_AT", time.format("%Y-%m-%d %H:%M:%S"))], true);
-        }
+    (cover_data, art_written)
+}
+
+impl TrackImpl for Track {
+    // Write tags to file
+    fn write_to_file(&self, path: impl AsRef<Path>, config: &TaggerConfig) -> Result<(), Error> {
+        let mut tag_wrap = load_tag_for_writing(&path, config)?;
+        let (cover_data, _) = apply_track(self, &mut tag_wrap, config, false);
+        let tag = tag_wrap.tag_mut();
 
         // LRC
         if config.write_lrc && self.lyrics.is_some() {
@@ -342,6 +419,14 @@ impl TrackImpl for Track {
         Ok(())
     }
 
+    fn diff_file(&self, path: impl AsRef<Path>, config: &TaggerConfig) -> Result<TagDiff, Error> {
+        let mut tag_wrap = load_tag_for_writing(&path, config)?;
+        let before = tag_wrap.tag().all_tags();
+        let (_, album_art) = apply_track(self, &mut tag_wrap, config, true);
+        let after = tag_wrap.tag().all_tags();
+        Ok(TagDiff::compute(path, &self.platform, &before, &after, album_art))
+    }
+
     // Download album art, None if invalid album art
     fn download_art(&self, url: &str) -> Result<Option<Vec<u8>>, Error> {
         let response = reqwest::blocking::get(url)?;
@@ -410,12 +495,18 @@ fn get_cover_path(info: &AudioFileInfo, folder: impl AsRef<Path>, config: &Tagge
 pub trait AudioFileInfoImpl {
     /// Load audio file info from path
     fn load_file(path: impl AsRef<Path>, filename_template: Option<Regex>, title_regex: Option<Regex>) -> Result<AudioFileInfo, Error>;
+    /// Load audio file info from library index without reading the file
+    fn from_library(file: LibraryFile, filename_template: Option<Regex>, title_regex: Option<Regex>) -> Result<AudioFileInfo, Error>;
     /// Load duration from file
     fn load_duration(&mut self);
     /// Parse the filename template
     fn parse_template(template: &str) -> Option<Regex>;
-    /// Load using shazam
-    fn shazam(path: impl AsRef<Path>) -> Result<AudioFileInfo, Error>;
+    /// Load using shazam, returns which segments matched
+    fn shazam(path: impl AsRef<Path>, config: &ShazamConfig) -> Result<(AudioFileInfo, ShazamMatch), Error>;
+    /// Load using AcoustID fingerprint lookup, includes MusicBrainz recording IDs
+    fn acoustid(path: impl AsRef<Path>, api_key: &str) -> Result<AudioFileInfo, Error>;
+    /// Identify using the configured backend
+    fn identify(path: impl AsRef<Path>, config: &TaggerConfig) -> Result<(AudioFileInfo, Option<ShazamMatch>), Error>;
     /// Get list of all files in with supported extensions
     fn get_file_list(path: impl AsRef<Path>, subfolders: bool) -> Vec<PathBuf>;
     /// Get iterator of all audio files in path 
@@ -436,38 +527,12 @@ impl AudioFileInfoImpl for AudioFileInfo {
             .map(|a| AudioFileInfo::parse_artist_tag(a.iter().map(|a| a.as_str()).collect()));
 
         // Parse filename
-        if (title.is_none() || artists.is_none()) && filename_template.is_some() {
-            let filename = path.as_ref().file_name().ok_or(anyhow!("Missing filename!"))?.to_str().ok_or(anyhow!("Missing filename"))?;
-            if let Some(captures) = filename_template.unwrap().captures(filename) {                
-                
-                // Title
-                if title.is_none() {
-                    if let Some(m) = captures.name("title") {
-                        title = Some(m.as_str().trim().to_string());
-                    }
-                }
-                // Artists
-                if artists.is_none() {
-                    if let Some(m) = captures.name("artists") {
-                        artists = Some(AudioFileInfo::parse_artist_tag(vec![m.as_str().trim()]));
-                    }
-                }
-            }
+        if let Some(template) = filename_template.filter(|_| title.is_none() || artists.is_none()) {
+            parse_filename(path.as_ref(), &template, &mut title, &mut artists)?;
         }
 
         // Get tagging status
-        let tagged = match tag.get_raw("1T_TAGGEDDATE").map(|t| t.first().map(String::from)).flatten() {
-            Some(val) => {
-                if val.ends_with("_AT") {
-                    FileTaggedStatus::AutoTagger
-                } else if val.ends_with("_AF") {
-                    FileTaggedStatus::AudioFeatures
-                } else {
-                    FileTaggedStatus::Tagged
-                }
-            },
-            None => FileTaggedStatus::Untagged,
-        };
+        let tagged = tagged_status(tag.get_raw("1T_TAGGEDDATE").and_then(|t| t.first().map(String::from)));
 
         // Clean title
         if let Some(re) = title_regex {
@@ -490,6 +555,32 @@ impl AudioFileInfoImpl for AudioFileInfo {
         })
     }
 
+    fn from_library(file: LibraryFile, filename_template: Option<Regex>, title_regex: Option<Regex>) -> Result<AudioFileInfo, Error> {
+        let format = AudioFileFormat::from_extension(&file.path.extension().unwrap_or_default().to_string_lossy())
+            .ok_or(anyhow!("Unsupported format"))?;
+        let field = |field: Field| file.tags.get(field.by_format(&format)).filter(|v| !v.is_empty());
+        let mut title = field(Field::Title).map(|t| t.join(" "));
+        let mut artists = field(Field::Artist).map(|a| AudioFileInfo::parse_artist_tag(a.iter().map(|a| a.as_str()).collect()));
+        if let Some(template) = filename_template.filter(|_| title.is_none() || artists.is_none()) {
+            parse_filename(&file.path, &template, &mut title, &mut artists)?;
+        }
+        if let Some(re) = title_regex {
+            title = title.map(|t| re.replace_all(&t, "").to_string());
+        }
+        Ok(AudioFileInfo {
+            title,
+            artists: artists.unwrap_or_default(),
+            isrc: field(Field::ISRC).and_then(|v| v.first().map(String::from)),
+            duration: file.duration.map(|d| Duration::from_millis(d as u64).into()),
+            track_number: field(Field::TrackNumber).and_then(|v| v.first()?.split('/').next()?.parse().ok()),
+            // Raw MP4 name has a prefix
+            tagged: tagged_status(file.tags.iter().find(|(k, _)| k.ends_with("1T_TAGGEDDATE")).and_then(|(_, v)| v.first().map(String::from))),
+            tags: file.tags,
+            path: file.path,
+            format,
+        })
+    }
+
     fn load_duration(&mut self) {
         // Mark as loaded
         self.duration = Some(Duration::ZERO.into());
@@ -524,12 +615,15 @@ impl AudioFileInfoImpl for AudioFileInfo {
     }
 
     // Recognize on Shazam
-    fn shazam(path: impl AsRef<Path>) -> Result<AudioFileInfo, Error> {
+    fn shazam(path: impl AsRef<Path>, config: &ShazamConfig) -> Result<(AudioFileInfo, ShazamMatch), Error> {
         info!("Recognizing on Shazam: {:?}", path.as_ref());
-        match Shazam::recognize_from_file(&path) {
-            Ok((shazam_track, duration)) => {
-                info!("Recognized on Shazam: {:?}: {} - {}", path.as_ref(), shazam_track.title, shazam_track.subtitle);
-                return Ok(AudioFileInfo {
+        match Shazam::recognize_from_file(&path, config) {
+            Ok((shazam_track, duration, shazam_match)) => {
+                info!(
+                    "Recognized on Shazam: {:?}: {} - {} ({}/{} segments)",
+                    path.as_ref(), shazam_track.title, shazam_track.subtitle, shazam_match.votes, shazam_match.segments
+                );
+                return Ok((AudioFileInfo {
                     title: Some(shazam_track.title),
                     artists: AudioFileInfo::parse_artist_tag(vec![&shazam_track.subtitle]),
                     format: AudioFileFormat::from_extension(&path.as_ref().extension().unwrap_or_default().to_string_lossy()).unwrap(),
@@ -539,7 +633,7 @@ impl AudioFileInfoImpl for AudioFileInfo {
                     track_number: None,
                     tagged: FileTaggedStatus::Untagged,
                     tags: Default::default(),
-                });
+                }, shazam_match));
             },
             // Mark as failed
             Err(e) => {
@@ -549,6 +643,39 @@ impl AudioFileInfoImpl for AudioFileInfo {
         }
     }
 
+    // Recognize on AcoustID
+    fn acoustid(path: impl AsRef<Path>, api_key: &str) -> Result<AudioFileInfo, Error> {
+        info!("Recognizing on AcoustID: {:?}", path.as_ref());
+        let (recording, ids, duration) = AcoustId::new(api_key).recognize_from_file(&path).map_err(|e| {
+            warn!("AcoustID failed: {}", e);
+            e
+        })?;
+        let title = recording.title.unwrap_or_default();
+        let artists = recording.artists.into_iter().map(|a| a.name).collect::<Vec<_>>();
+        info!("Recognized on AcoustID: {:?}: {} - {}", path.as_ref(), title, artists.join(", "));
+        Ok(AudioFileInfo {
+            title: Some(title),
+            artists,
+            format: AudioFileFormat::from_extension(&path.as_ref().extension().unwrap_or_default().to_string_lossy()).unwrap(),
+            path: path.as_ref().to_owned(),
+            isrc: None,
+            duration: Some(Duration::from_millis(duration as u64).into()),
+            track_number: None,
+            tagged: FileTaggedStatus::Untagged,
+            tags: HashMap::from([(RECORDING_ID_TAG.to_string(), ids)]),
+        })
+    }
+
+    fn identify(path: impl AsRef<Path>, config: &TaggerConfig) -> Result<(AudioFileInfo, Option<ShazamMatch>), Error> {
+        match config.identify_backend {
+            IdentifyBackend::Shazam => AudioFileInfo::shazam(path, &config.shazam).map(|(info, m)| (info, Some(m))),
+            IdentifyBackend::AcoustId => {
+                let key = config.acoustid_key.as_ref().filter(|k| !k.trim().is_empty()).ok_or(anyhow!("Missing AcoustID API key"))?;
+                AudioFileInfo::acoustid(path, key.trim()).map(|info| (info, None))
+            }
+        }
+    }
+
     // Get list of all files in with supported extensions
     fn get_file_list(path: impl AsRef<Path>, subfolders: bool) -> Vec<PathBuf> {
         if path.as_ref().to_string_lossy() == "" {
@@ -582,8 +709,41 @@ impl AudioFileInfoImpl for AudioFileInfo {
     }
 
     fn load_files_iter(path: impl AsRef<Path>, subfolders: bool, filename_template: Option<Regex>, title_regex: Option<Regex>) -> impl Iterator<Item = Result<AudioFileInfo, Error>> {
+        // Files which didn't change since indexing don't have to be read
+        let library = Library::open_default().ok().filter(|l| l.is_indexed(&path).unwrap_or(false));
         let files = Self::get_file_list(path, subfolders);
-        files.into_iter().map(move |f| Self::load_file(&f, filename_template.clone(), title_regex.clone()))
+        files.into_iter().map(move |f| match library.as_ref().and_then(|l| l.fresh(&f).ok().flatten()) {
+            Some(file) => Self::from_library(file, filename_template.clone(), title_regex.clone()),
+            None => Self::load_file(&f, filename_template.clone(), title_regex.clone())
+        })
+    }
+}
+
+/// Fill missing title and artists from filename
+fn parse_filename(path: &Path, template: &Regex, title: &mut Option<String>, artists: &mut Option<Vec<String>>) -> Result<(), Error> {
+    let filename = path.file_name().ok_or(anyhow!("Missing filename!"))?.to_str().ok_or(anyhow!("Missing filename"))?;
+    if let Some(captures) = template.captures(filename) {
+        if title.is_none() {
+            if let Some(m) = captures.name("title") {
+                *title = Some(m.as_str().trim().to_string());
+            }
+        }
+        if artists.is_none() {
+            if let Some(m) = captures.name("artists") {
+                *artists = Some(AudioFileInfo::parse_artist_tag(vec![m.as_str().trim()]));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Tagging status from the `1T_TAGGEDDATE` value
+fn tagged_status(value: Option<String>) -> FileTaggedStatus {
+    match value {
+        Some(val) if val.ends_with("_AT") => FileTaggedStatus::AutoTagger,
+        Some(val) if val.ends_with("_AF") => FileTaggedStatus::AudioFeatures,
+        Some(_) => FileTaggedStatus::Tagged,
+        None => FileTaggedStatus::Untagged,
     }
 }
 
@@ -602,8 +762,12 @@ pub struct TaggingStatus {
     pub message: Option<String>,
     pub accuracy: Option<f64>,
     pub used_shazam: bool,
+    /// Which Shazam segment matched and how many agreed
+    pub shazam: Option<ShazamMatch>,
     pub release_id: Option<String>,
-    pub reason: Option<MatchReason>
+    pub reason: Option<MatchReason>,
+    /// Changes which would be written (dry run only)
+    pub diff: Option<TagDiff>,
 }
 
 // Wrap for sending into UI
@@ -625,42 +789,108 @@ impl TaggingStatusWrap {
     }
 }
 
+/// State of a single tagging run shared by its threads
+#[derive(Clone)]
+pub struct TaggingRun {
+    /// Stop flag of the run
+    pub stop: Arc<AtomicBool>,
+    /// Undo journal, `None` in dry run
+    pub journal: Option<Arc<UndoJournal>>,
+}
+
 pub struct Tagger {}
 impl Tagger {
 
     // Returtns progress receiver, and file count
-    pub fn tag_files(cfg: &TaggerConfig, mut files: Vec<PathBuf>, finished: Arc<Mutex<Option<TaggerFinishedData>>>) -> Receiver<TaggingStatusWrap> {
+    pub fn tag_files(cfg: &TaggerConfig, files: Vec<PathBuf>, finished: Arc<Mutex<Option<TaggerFinishedData>>>) -> Receiver<TaggingStatusWrap> {
         STOP_TAGGING.store(false, Ordering::SeqCst);
+        Self::tag_files_with_stop(cfg, files, finished, STOP_TAGGING.clone())
+    }
 
+    /// Tag files with own stop flag, so the run is independent from the global one
+    pub fn tag_files_with_stop(cfg: &TaggerConfig, mut files: Vec<PathBuf>, finished: Arc<Mutex<Option<TaggerFinishedData>>>, stop: Arc<AtomicBool>) -> Receiver<TaggingStatusWrap> {
         // Shuffle so album tag is more "efficient"
         if cfg.album_tagging {
             let mut rng = rand::thread_rng();
             files.shuffle(&mut rng);
         }
+
+        // Log progress, so the run can be resumed
+        let run_log = match RunLog::new(cfg, &files) {
+            Ok(log) => Some(log),
+            Err(e) => {
+                warn!("Failed creating run log, run won't be resumable! {e}");
+                None
+            }
+        };
+        Self::run(cfg, files, finished, run_log, None, stop)
+    }
+
+    /// Continue stopped or crashed run, files already processed by a platform are skipped
+    pub fn resume(id: &str, finished: Arc<Mutex<Option<TaggerFinishedData>>>) -> Result<Receiver<TaggingStatusWrap>, Error> {
+        let state = RunLog::load(id)?;
+        let run_log = RunLog::open(id)?;
+        info!("Resuming run {id}, {} files remaining", state.remaining());
+        STOP_TAGGING.store(false, Ordering::SeqCst);
+        Ok(Self::run(&state.config.clone(), state.files.clone(), finished, Some(run_log), Some(state), STOP_TAGGING.clone()))
+    }
+
+    /// Tag files with all platforms, optionally continuing from a previous state
+    fn run(cfg: &TaggerConfig, mut files: Vec<PathBuf>, finished: Arc<Mutex<Option<TaggerFinishedData>>>, run_log: Option<RunLog>, resume: Option<ResumeState>, stop: Arc<AtomicBool>) -> Receiver<TaggingStatusWrap> {
+        HTTP_CACHE.configure(&cfg.http_cache);
         
         // let original_files = files.clone();
         let mut succesful_files = vec![];
         let mut failed_files = vec![];
+        let mut dry_run_report = DryRunReport::default();
+        if let Some(state) = &resume {
+            succesful_files = files.iter().filter(|f| state.successful.contains(*f)).cloned().collect();
+            failed_files = files.iter().filter(|f| state.failed.contains(*f)).cloned().collect();
+            if !cfg.multiplatform {
+                files.retain(|f| !state.successful.contains(f));
+            }
+        }
         let total_files = files.len();
         info!("Starting tagger with: {} files!", total_files);
 
+        // Undo journal (nothing is written in dry run)
+        let journal = match cfg.dry_run {
+            true => None,
+            false => match UndoJournal::new("autotagger") {
+                Ok(journal) => Some(Arc::new(journal)),
+                Err(e) => {
+                    warn!("Failed creating undo journal, run won't be revertable! {e}");
+                    None
+                }
+            }
+        };
+
         // Create thread
         let (tx, rx) = unbounded();
         let config = cfg.clone();
         std::thread::spawn(move || {
             // Tag
             for (platform_index, platform) in config.platforms.iter().enumerate() {
-                // For progress
-                let mut processed = 0;
-                let total = files.len();
                 // No more files
                 if files.is_empty() {
                     info!("All tagged succesfully!");
                     break;
                 }
+                // Skip files processed before resuming
+                let platform_files = match &resume {
+                    Some(state) => state.pending(platform, &files).cloned().collect(),
+                    None => files.clone()
+                };
+                if platform_files.is_empty() {
+                    info!("{platform} already finished, skipping...");
+                    continue;
+                }
+                // For progress
+                let mut processed = 0;
+                let total = platform_files.len();
 
                 // Stop
-                if STOP_TAGGING.load(Ordering::SeqCst) {
+                if stop.load(Ordering::SeqCst) {
                     continue;
                 }
 
@@ -678,7 +908,7 @@ impl Tagger {
                 if platform_info.max_threads > 0 && platform_info.max_threads < config.threads {
                     threads = platform_info.max_threads;
                 }
-                let rx = match Tagger::tag_batch(&files, tagger, &config, threads) {
+                let rx = match Tagger::tag_batch(&platform_files, tagger, &config, threads, &TaggingRun { stop: stop.clone(), journal: journal.clone() }) {
                     Some(t) => t,
                     None => {
                         error!("Failed creating platform: {platform:?}, skipping...");
@@ -690,6 +920,13 @@ impl Tagger {
                 for status in rx {
                     info!("[{platform}] State: {:?}, Accuracy: {:?}, Path: {:?}", status.status, status.accuracy, status.path);
                     processed += 1;
+                    if let Some(Err(e)) = run_log.as_ref().map(|l| l.status(platform, &status)) {
+                        warn!("Failed saving status to run log: {e}");
+                    }
+                    // Dry run diffs
+                    if let Some(diff) = status.diff.as_ref() {
+                        dry_run_report.add(diff.clone());
+                    }
                     // Send to UI
                     tx.send(TaggingStatusWrap::wrap(&platform_info.name, &status, platform_index, config.platforms.len(), processed, total)).ok();
 
@@ -712,17 +949,110 @@ impl Tagger {
                     // Log failed
                     if status.status == TaggingState::Error && !succesful_files.contains(&status.path) {
                         failed_files.push(status.path.to_owned());
+                        if config.dry_run {
+                            dry_run_report.add_failed(&status.path);
+                        }
+                    }
+
+                }
+            }
+
+            // Detect BPM locally if platforms didn't provide it
+            if config.local_bpm.enabled && config.tag_enabled(SupportedTag::BPM) && !config.dry_run && !stop.load(Ordering::SeqCst) {
+                let files = succesful_files.iter().chain(failed_files.iter())
+                    .filter(|f| !BpmDetector::has_bpm(f))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                info!("Detecting BPM of {} files", files.len());
+                for (path, result) in BpmDetector::detect_files(files, &config.local_bpm, config.threads as usize) {
+                    match result {
+                        Ok(result) if result.confidence >= config.local_bpm.min_confidence => {
+                            if let Some(Err(e)) = journal.as_ref().map(|j| j.snapshot(&path)) {
+                                warn!("Failed saving undo snapshot: {e}");
+                            }
+                            match BpmDetector::write_bpm(&path, &result, false) {
+                                Ok(_) => info!("Detected BPM: {} ({:.2}) {path:?}", result.bpm, result.confidence),
+                                Err(e) => warn!("Failed writing BPM: {path:?} {e}"),
+                            }
+                        },
+                        Ok(result) => info!("Detected BPM {} has low confidence ({:.2}), skipping: {path:?}", result.bpm, result.confidence),
+                        Err(e) => warn!("Failed detecting BPM: {path:?} {e}"),
                     }
+                }
+            }
 
+            // Fill missing keys
+            if config.local_key.enabled && config.tag_enabled(SupportedTag::Key) && !config.dry_run && !stop.load(Ordering::SeqCst) {
+                let files = succesful_files.iter().chain(failed_files.iter())
+                    .filter(|f| !KeyDetector::has_key(f))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                info!("Detecting key of {} files", files.len());
+                for (path, result) in KeyDetector::detect_files(files, config.threads as usize) {
+                    match result {
+                        Ok(result) if result.confidence >= config.local_key.min_confidence => {
+                            if let Some(Err(e)) = journal.as_ref().map(|j| j.snapshot(&path)) {
+                                warn!("Failed saving undo snapshot: {e}");
+                            }
+                            let key = result.key.render(config.key_notation);
+                            match KeyDetector::write_key(&path, &key, false) {
+                                Ok(_) => info!("Detected key: {key} ({:.2}) {path:?}", result.confidence),
+                                Err(e) => warn!("Failed writing key: {path:?} {e}"),
+                            }
+                        },
+                        Ok(result) => info!("Detected key {} has low confidence ({:.2}), skipping: {path:?}", result.key, result.confidence),
+                        Err(e) => warn!("Failed detecting key: {path:?} {e}"),
+                    }
                 }
             }
 
-            // Move files
+            // ReplayGain
+            if config.replay_gain.enabled && !config.dry_run && !stop.load(Ordering::SeqCst) {
+                let write = succesful_files.iter().chain(failed_files.iter())
+                    .filter(|f| config.replay_gain.overwrite || !LoudnessScanner::has_gain(f))
+                    .cloned()
+                    .collect::<HashSet<_>>();
+                // Album gain has to be calculated from the whole folder, even from files which won't be written
+                let files = match config.replay_gain.album && !write.is_empty() {
+                    true => write.iter()
+                        .filter_map(|f| f.parent())
+                        .collect::<HashSet<_>>()
+                        .into_iter()
+                        .flat_map(|folder| AudioFileInfo::get_file_list(folder, false))
+                        .collect::<Vec<_>>(),
+                    false => write.iter().cloned().collect()
+                };
+                info!("Scanning loudness of {} files", files.len());
+                for (path, result) in LoudnessScanner::scan(files, config.replay_gain.album, config.threads as usize) {
+                    if !write.contains(&path) {
+                        continue;
+                    }
+                    match result {
+                        Ok(gain) => {
+                            if let Some(Err(e)) = journal.as_ref().map(|j| j.snapshot(&path)) {
+                                warn!("Failed saving undo snapshot: {e}");
+                            }
+                            match LoudnessScanner::write_gain(&path, &gain, config.replay_gain.reference, true) {
+                                Ok(_) => info!("Loudness: {:.2} LUFS, peak: {:.6} {path:?}", gain.track_loudness, gain.track_peak),
+                                Err(e) => warn!("Failed writing ReplayGain: {path:?} {e}"),
+                            }
+                        },
+                        Err(e) => warn!("Failed scanning loudness: {path:?} {e}"),
+                    }
+                }
+            }
+
+            // Move files (not in dry run)
             let mut successful_paths = vec![];
             for file in &succesful_files {
-                if config.move_success && config.move_success_path.is_some() {
+                if config.move_success && config.move_success_path.is_some() && !config.dry_run {
                     match Self::move_file(file, &config.move_success_path.as_ref().unwrap()) {
-                        Ok(p) => successful_paths.push(p),
+                        Ok(p) => {
+                            if let Some(Err(e)) = journal.as_ref().map(|j| j.moved(file, &p)) {
+                                warn!("Failed saving move to journal: {e}");
+                            }
+                            successful_paths.push(p);
+                        },
                         Err(e) => warn!("Failed moving file: {file:?} {e}"),
                     }
                 } else {
@@ -731,9 +1061,14 @@ impl Tagger {
             }
             let mut failed_paths = vec![];
             for file in &failed_files {
-                if config.move_failed && config.move_failed_path.is_some() {
+                if config.move_failed && config.move_failed_path.is_some() && !config.dry_run {
                     match Self::move_file(file, &config.move_failed_path.as_ref().unwrap()) {
-                        Ok(p) => failed_paths.push(p),
+                        Ok(p) => {
+                            if let Some(Err(e)) = journal.as_ref().map(|j| j.moved(file, &p)) {
+                                warn!("Failed saving move to journal: {e}");
+                            }
+                            failed_paths.push(p);
+                        },
                         Err(e) => warn!("Failed moving file: {file:?} {e}"),
                     }
                 } else {
@@ -744,12 +1079,39 @@ impl Tagger {
             std::mem::drop(failed_files);
             std::mem::drop(files);
 
+            // Stopped runs stay resumable
+            let run_log = match stop.load(Ordering::SeqCst) {
+                true => run_log,
+                false => {
+                    if let Some(Err(e)) = run_log.map(|l| l.finished()) {
+                        warn!("Failed removing log of finished run: {e}");
+                    }
+                    None
+                }
+            };
+
             // Tagging ended, save lists of files
             match Self::write_results(successful_paths, failed_paths, &config) {
                 Ok((failed, success)) => {
                     info!("Written failed songs to: {}, successful to: {}", failed, success);
+                    // Save dry run report
+                    let dry_run_file = match config.dry_run {
+                        true => match Self::write_dry_run_report(&dry_run_report) {
+                            Ok(path) => {
+                                info!("Dry run report written to: {path}");
+                                Some(path)
+                            },
+                            Err(e) => {
+                                warn!("Failed writing dry run report! {e}");
+                                None
+                            }
+                        },
+                        false => None
+                    };
                     *finished.lock().unwrap() = Some(TaggerFinishedData {
-                        failed_file: failed, success_file: success
+                        failed_file: failed, success_file: success, dry_run_file,
+                        run_id: journal.map(|j| j.id().to_string()),
+                        resume_id: run_log.map(|l| l.id().to_string())
                     });
                 },
                 Err(e) => warn!("Failed writing failed songs to file! {}", e)
@@ -792,7 +1154,7 @@ impl Tagger {
         // Run command
         let (failed_file, success_file) = (failed_file.to_str().unwrap().to_string(), success_file.to_str().unwrap().to_string());
         if let Some(command) = &config.post_command {
-            if !command.trim().is_empty() {
+            if !command.trim().is_empty() && !config.dry_run {
                 let command = command
                     .replace("$failed", &failed_file)
                     .replace("$success", &success_file);
@@ -809,6 +1171,15 @@ impl Tagger {
 
     }
 
+    /// Save dry run report into the runs folder
+    fn write_dry_run_report(report: &DryRunReport) -> Result<String, Error> {
+        let folder = Settings::get_folder()?.join("runs");
+        std::fs::create_dir_all(&folder)?;
+        let path = folder.join(format!("dryrun-{}.json", timestamp!()));
+        report.save(&path)?;
+        Ok(path.to_string_lossy().to_string())
+    }
+
     /// Load track, shazam, prepare output
     pub fn load_track(path: impl AsRef<Path>, config: &TaggerConfig) -> (Option<AudioFileInfo>, TaggingStatus) {
         // Output
@@ -818,8 +1189,10 @@ impl Tagger {
             accuracy: None,
             message: None,
             used_shazam: false,
+            shazam: None,
             release_id: None,
-            reason: None
+            reason: None,
+            diff: None,
         };
 
         // Filename template
@@ -833,16 +1206,17 @@ impl Tagger {
         // Title cleanup regex
         let title_regex = config.title_regex.as_ref().map(|r| Regex::new(&r).ok()).flatten();
 
-        // Load audio file info by shazam or tags
+        // Load audio file info by identification service or tags
         let mut info = if config.enable_shazam && config.force_shazam {
-            match AudioFileInfo::shazam(&path) {
-                Ok(i) => {
+            match AudioFileInfo::identify(&path, config) {
+                Ok((i, shazam)) => {
                     out.used_shazam = true;
+                    out.shazam = shazam;
                     i
                 },
                 Err(e) => {
                     out.status = TaggingState::Skipped;
-                    out.message = Some(format!("Error Shazaming file: {}", e));
+                    out.message = Some(format!("Error identifying file: {}", e));
                     return (None, out);
                 }
             }
@@ -850,11 +1224,12 @@ impl Tagger {
             match AudioFileInfo::load_file(&path, template, title_regex) {
                 Ok(info) => info,
                 Err(e) => {
-                    // Try shazam if enabled
+                    // Try identifying if enabled
                     if config.enable_shazam {
-                        match AudioFileInfo::shazam(&path) {
-                            Ok(info) => {
+                        match AudioFileInfo::identify(&path, config) {
+                            Ok((info, shazam)) => {
                                 out.used_shazam = true;
+                                out.shazam = shazam;
                                 info
                             },
                             // Mark as failed
@@ -890,8 +1265,25 @@ impl Tagger {
         (Some(info), out)
     }
 
-    /// Tag single track
-    pub fn tag_track<T>(path: impl AsRef<Path>, tagger: &mut Box<T>, config: &TaggerConfig) -> TaggingStatus 
+    /// Tag single track, time spent waiting for platform rate limits is added to the message
+    pub fn tag_track<T>(path: impl AsRef<Path>, tagger: &mut Box<T>, config: &TaggerConfig, journal: Option<&UndoJournal>) -> TaggingStatus 
+    where T: AutotaggerSource + ?Sized
+    {
+        ratelimit::take_throttled();
+        let mut status = Self::match_and_write(path, tagger, config, journal);
+        let throttled = ratelimit::take_throttled();
+        if throttled >= Duration::from_millis(100) {
+            let throttled = format!("Rate limited for {:.1}s", throttled.as_secs_f32());
+            status.message = Some(match status.message {
+                Some(message) => format!("{message} ({throttled})"),
+                None => throttled
+            });
+        }
+        status
+    }
+
+    /// Match track on platform and write the tags
+    fn match_and_write<T>(path: impl AsRef<Path>, tagger: &mut Box<T>, config: &TaggerConfig, journal: Option<&UndoJournal>) -> TaggingStatus 
     where T: AutotaggerSource + ?Sized
     {
         info!("Tagging: {:?}", path.as_ref());
@@ -932,7 +1324,7 @@ impl Tagger {
         // Save
         out.release_id = track.track.release_id.clone();
         out.reason = Some(track.reason);
-        match track.track.merge_styles(&config.styles_options).write_to_file(&info.path, &config) {
+        match Self::write_track(track.track, &info.path, config, journal, &mut out) {
             Ok(_) => {
                 out.accuracy = Some(track.accuracy);
                 out.status = TaggingState::Ok;
@@ -946,8 +1338,22 @@ impl Tagger {
         out
     }
 
+    /// Write track to file, or in dry run mode only save the diff into status
+    fn write_track(track: Track, path: impl AsRef<Path>, config: &TaggerConfig, journal: Option<&UndoJournal>, status: &mut TaggingStatus) -> Result<(), Error> {
+        let track = track.merge_styles(&config.styles_options);
+        if config.dry_run {
+            status.diff = Some(track.diff_file(&path, config)?);
+            return Ok(());
+        }
+        // Save previous state for undo
+        if let Some(journal) = journal {
+            journal.snapshot(&path)?;
+        }
+        track.write_to_file(&path, config)
+    }
+
     // Tag all files with threads specified in config
-    pub fn tag_batch(files: &Vec<PathBuf>, tagger: &mut Box<dyn AutotaggerSourceBuilder + Send + Sync>, config: &TaggerConfig, threads: u16) -> Option<Receiver<TaggingStatus>> {
+    pub fn tag_batch(files: &Vec<PathBuf>, tagger: &mut Box<dyn AutotaggerSourceBuilder + Send + Sync>, config: &TaggerConfig, threads: u16, run: &TaggingRun) -> Option<Receiver<TaggingStatus>> {
         info!("Starting tagging: {} files, {} threads!", files.len(), threads);
         let (tx, rx) = unbounded();
         let (file_tx, file_rx): (Sender<PathBuf>, Receiver<PathBuf>) = unbounded();
@@ -966,6 +1372,7 @@ impl Tagger {
             let config = config.clone();
             let finished_tx = finished_tx.clone();
             let album_tagging = album_tagging.clone();
+            let run = run.clone();
             let mut source = match tagger.get_source(&config) {
                 Ok(s) => s,
                 Err(e) => {
@@ -977,7 +1384,7 @@ impl Tagger {
             std::thread::spawn(move || {
                 while let Ok(f) = file_rx.recv() {
                     // Stop tagging
-                    if STOP_TAGGING.load(Ordering::SeqCst) {
+                    if run.stop.load(Ordering::SeqCst) {
                         break;
                     }
 
@@ -987,7 +1394,7 @@ impl Tagger {
                     }
 
                     // Tag
-                    let res = Tagger::tag_track(&f, &mut source, &config);
+                    let res = Tagger::tag_track(&f, &mut source, &config, run.journal.as_deref());
                     if config.album_tagging {
                         album_tagging.lock().unwrap().process(&res, &config);
                     }
@@ -1002,6 +1409,7 @@ impl Tagger {
             let config = config.clone();
             match tagger.get_source(&config) {
                 Ok(mut source) => {
+                    let journal = run.journal.clone();
                     std::thread::spawn(move || {
                         // Wait for all threads to finish
                         for _ in finished_rx.into_iter() {}
@@ -1014,7 +1422,7 @@ impl Tagger {
                             }
 
                             // Tag
-                            match Self::tag_album(path, &stats.get_album_id().unwrap(), &mut source, &config) {
+                            match Self::tag_album(path, &stats.get_album_id().unwrap(), &mut source, &config, journal.as_deref()) {
                                 Ok(statuses) => {
                                     for status in statuses {
                                         tx.send(status).ok();
@@ -1043,7 +1451,7 @@ impl Tagger {
     }
 
     /// Tag an album by ID
-    pub fn tag_album(path: impl AsRef<Path>, release_id: &str, source: &mut Box<dyn AutotaggerSource>, config: &TaggerConfig) -> Result<Vec<TaggingStatus>, Error> {
+    pub fn tag_album(path: impl AsRef<Path>, release_id: &str, source: &mut Box<dyn AutotaggerSource>, config: &TaggerConfig, journal: Option<&UndoJournal>) -> Result<Vec<TaggingStatus>, Error> {
         info!("Album tagging release: {release_id} in {}", path.as_ref().display());
 
         // Change strictness since we're working in context of album, and just care about most likely match
@@ -1080,7 +1488,7 @@ impl Tagger {
             let track = tracks.remove(0);
             
             // TODO: Extend track if needed (?)
-            if let Err(e) = track.track.merge_styles(&config.styles_options).write_to_file(&info.path, &config) {
+            if let Err(e) = Self::write_track(track.track, &info.path, &config, journal, &mut status) {
                 status.status = TaggingState::Error;
                 error!("Album tag writing tags failed: {e} ({})", file.display());
             } else {
@@ -1213,13 +1621,20 @@ impl AlbumTagFolderStats {
 #[serde(rename_all = "camelCase")]
 pub struct TaggerFinishedData {
     pub failed_file: String,
-    pub success_file: String
+    pub success_file: String,
+    /// Path to JSON diff report, only in dry run
+    pub dry_run_file: Option<String>,
+    /// ID of the undo journal, used for reverting
+    pub run_id: Option<String>,
+    /// ID of the run log if tagging was stopped, used for resuming
+    pub resume_id: Option<String>,
 }
 
 
 /// Start manual tagging mode
 /// Return: receiver with results for every platform
 pub fn manual_tagger(path: impl AsRef<Path>, config: &TaggerConfig) -> Result<Receiver<(String, Result<Vec<TrackMatch>, Error>)>, Error> {
+    HTTP_CACHE.configure(&config.http_cache);
     // Get filename template
     let filename_template = config.filename_template.as_ref().map(|template| {
         match AudioFileInfo::parse_template(template) {
@@ -1283,8 +1698,8 @@ pub fn manual_tagger(path: impl AsRef<Path>, config: &TaggerConfig) -> Result<Re
     Ok(rx)
 }
 
-/// Apply manual tag results
-pub fn manual_tagger_apply(mut matches: Vec<TrackMatch>, path: impl AsRef<Path>, config: &TaggerConfig) -> Result<(), Error> {
+/// Apply manual tag results, original state is saved into the (per session) journal
+pub fn manual_tagger_apply(mut matches: Vec<TrackMatch>, path: impl AsRef<Path>, config: &TaggerConfig, journal: &UndoJournal) -> Result<(), Error> {
     if matches.is_empty() {
         return Ok(())
     }
@@ -1322,6 +1737,7 @@ pub fn manual_tagger_apply(mut matches: Vec<TrackMatch>, path: impl AsRef<Path>,
     }
 
     // Save
+    journal.snapshot(&path)?;
     track.merge_styles(&config.styles_options).write_to_file(&path, &config)?;
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,205 @@
+use anyhow::Error;
+use crossbeam_channel::Receiver;
+use rustfft::FftPlanner;
+use rustfft::num_complex::Complex;
+use serde::{Serialize, Deserialize};
+use std::path::{Path, PathBuf};
+use lofty::file::AudioFile;
+use onetagger_player::AudioSources;
+use onetagger_tag::{AudioFileFormat, FrameName, Tag};
+
+use crate::analysis::{analyze_files, load_mono};
+
+/// FFT size in samples
+const FRAME: usize = 4096;
+/// Distance between the starts of analyzed frames
+const HOP: usize = 4096;
+/// Width of the bands compared on each side of the cutoff
+const BAND: f32 = 500.0;
+/// Lowest cutoff searched for
+const MIN_CUTOFF: f32 = 10_000.0;
+/// Level difference between the bands which is treated as lowpass
+const MIN_DROP: f32 = 25.0;
+/// Default tag for the verdict
+pub const LOSSLESS_TAG: &str = "1T_LOSSLESS";
+
+/// Is the file really lossless
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum LosslessVerdict {
+    Genuine,
+    /// Lowpass typical for lossy encoders
+    Transcoded,
+    /// High sample rate without content above 24kHz
+    Upsampled,
+}
+
+/// Result of spectral analysis
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LosslessResult {
+    pub verdict: LosslessVerdict,
+    /// Effective frequency cutoff in Hz
+    pub cutoff: f32,
+    /// Level drop at the cutoff in dB
+    pub drop: f32,
+    pub sample_rate: u32,
+    /// 0.5 - 1.0
+    pub confidence: f32,
+}
+
+impl LosslessResult {
+    /// Is the file suspicious
+    pub fn is_fake(&self) -> bool {
+        self.verdict != LosslessVerdict::Genuine
+    }
+
+    /// Value written to tag, eg. `Transcoded (16.0 kHz, 92%)`
+    pub fn tag_value(&self) -> String {
+        format!("{:?} ({:.1} kHz, {:.0}%)", self.verdict, self.cutoff / 1000.0, self.confidence * 100.0)
+    }
+}
+
+/// Spectral analysis of (supposedly) lossless files
+pub struct LosslessChecker;
+
+impl LosslessChecker {
+    /// FLAC, WAV, AIFF, ALAC or WavPack, lossy files would always look transcoded
+    pub fn is_lossless(path: impl AsRef<Path>) -> Result<bool, Error> {
+        let path = path.as_ref();
+        let extension = path.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+        Ok(match AudioFileFormat::from_extension(&extension) {
+            Some(AudioFileFormat::FLAC | AudioFileFormat::WAV | AudioFileFormat::AIFF) => true,
+            // ALAC has bit depth, AAC doesn't
+            Some(AudioFileFormat::MP4) => lofty::read_from_path(path)?.properties().bit_depth().is_some(),
+            Some(AudioFileFormat::APE) => extension == "wv",
+            _ => false
+        })
+    }
+
+    /// Analyze up to 60s of audio file at its native sample rate
+    pub fn check_file(path: impl AsRef<Path>) -> Result<LosslessResult, Error> {
+        if !Self::is_lossless(&path)? {
+            return Err(anyhow!("Not a lossless file"));
+        }
+        let sample_rate = AudioSources::from_path(&path)?.get_source()?.sample_rate();
+        let samples = load_mono(&path, sample_rate, 30_000, 60_000)?;
+        Self::check(&samples, sample_rate).ok_or(anyhow!("Not enough audio or sample rate too low"))
+    }
+
+    /// Find the steepest level drop above 10kHz
+    pub fn check(samples: &[f32], sample_rate: u32) -> Option<LosslessResult> {
+        let spectrum = spectrum(samples)?;
+        let hz_per_bin = sample_rate as f32 / FRAME as f32;
+        let band = (BAND / hz_per_bin).ceil() as usize;
+        let start = ((MIN_CUTOFF / hz_per_bin).ceil() as usize).max(band);
+        let end = spectrum.len().saturating_sub(band);
+        let mean = |s: &[f32]| s.iter().sum::<f32>() / s.len() as f32;
+        let (bin, drop) = (start..end)
+            .map(|k| (k, mean(&spectrum[k - band..k]) - mean(&spectrum[k..k + band])))
+            .max_by(|a, b| a.1.total_cmp(&b.1))?;
+
+        let cutoff = bin as f32 * hz_per_bin;
+        let nyquist = sample_rate as f32 / 2.0;
+        let verdict = match drop >= MIN_DROP {
+            true if sample_rate > 48000 && cutoff <= 24_500.0 => LosslessVerdict::Upsampled,
+            // Anti-aliasing filters of real masters sit right below nyquist
+            true if cutoff < nyquist * 0.95 => LosslessVerdict::Transcoded,
+            _ => LosslessVerdict::Genuine
+        };
+        let confidence = match verdict {
+            LosslessVerdict::Genuine => 1.0 - drop.clamp(0.0, MIN_DROP) / (2.0 * MIN_DROP),
+            _ => (drop / (2.0 * MIN_DROP)).min(1.0)
+        };
+        Some(LosslessResult { verdict, cutoff, drop, sample_rate, confidence })
+    }
+
+    /// Check multiple files in parallel
+    pub fn check_files(files: Vec<PathBuf>, threads: usize) -> Receiver<(PathBuf, Result<LosslessResult, Error>)> {
+        analyze_files(files, threads, |path| Self::check_file(path))
+    }
+
+    /// Write verdict into custom tag
+    pub fn write_verdict(path: impl AsRef<Path>, result: &LosslessResult, tag: &FrameName) -> Result<(), Error> {
+        let mut tag_wrap = Tag::load_file(&path, false)?;
+        let format = tag_wrap.format();
+        let t = tag_wrap.tag_mut();
+        t.set_raw(&tag.by_format(&format), vec![result.tag_value()], true);
+        t.save_file(path.as_ref())?;
+        Ok(())
+    }
+}
+
+/// Average power spectrum of non silent frames in dB
+fn spectrum(samples: &[f32]) -> Option<Vec<f32>> {
+    let fft = FftPlanner::<f32>::new().plan_fft_forward(FRAME);
+    let window = (0..FRAME)
+        .map(|i| 0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / FRAME as f32).cos())
+        .collect::<Vec<_>>();
+    let mut power = vec![0.0f64; FRAME / 2];
+    let mut frames = 0;
+    let mut buffer = vec![Complex::default(); FRAME];
+    for chunk in samples.windows(FRAME).step_by(HOP) {
+        let rms = (chunk.iter().map(|s| s * s).sum::<f32>() / FRAME as f32).sqrt();
+        if rms < 1e-4 {
+            continue;
+        }
+        for (b, (s, w)) in buffer.iter_mut().zip(chunk.iter().zip(&window)) {
+            *b = Complex::new(s * w, 0.0);
+        }
+        fft.process(&mut buffer);
+        for (p, b) in power.iter_mut().zip(&buffer) {
+            *p += b.norm_sqr() as f64;
+        }
+        frames += 1;
+    }
+    if frames == 0 {
+        return None;
+    }
+    Some(power.into_iter().map(|p| (10.0 * (p / frames as f64 + 1e-20).log10()) as f32).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic white noise, optionally lowpassed
+    fn noise(len: usize, sample_rate: u32, cutoff: Option<f32>) -> Vec<f32> {
+        let mut state = 12345u32;
+        let mut buffer = (0..len).map(|_| {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            Complex::new((state >> 8) as f32 / (1 << 24) as f32 - 0.5, 0.0)
+        }).collect::<Vec<_>>();
+        if let Some(cutoff) = cutoff {
+            let mut planner = FftPlanner::<f32>::new();
+            planner.plan_fft_forward(len).process(&mut buffer);
+            for (i, b) in buffer.iter_mut().enumerate() {
+                let freq = i.min(len - i) as f32 * sample_rate as f32 / len as f32;
+                if freq > cutoff {
+                    *b = Complex::default();
+                }
+            }
+            planner.plan_fft_inverse(len).process(&mut buffer);
+            buffer.iter_mut().for_each(|b| *b /= len as f32);
+        }
+        buffer.into_iter().map(|c| c.re).collect()
+    }
+
+    #[test]
+    fn test_lossless_check() {
+        let genuine = LosslessChecker::check(&noise(44100 * 3, 44100, None), 44100).unwrap();
+        assert_eq!(genuine.verdict, LosslessVerdict::Genuine);
+        assert!(genuine.confidence > 0.8);
+
+        let transcoded = LosslessChecker::check(&noise(44100 * 3, 44100, Some(16000.0)), 44100).unwrap();
+        assert_eq!(transcoded.verdict, LosslessVerdict::Transcoded);
+        assert!((transcoded.cutoff - 16000.0).abs() < 100.0, "{}", transcoded.cutoff);
+        assert!(transcoded.confidence > 0.9);
+
+        let upsampled = LosslessChecker::check(&noise(96000 * 3, 96000, Some(22050.0)), 96000).unwrap();
+        assert_eq!(upsampled.verdict, LosslessVerdict::Upsampled);
+        assert_eq!(upsampled.tag_value(), format!("Upsampled ({:.1} kHz, 100%)", upsampled.cutoff / 1000.0));
+
+        assert!(LosslessChecker::check(&vec![0.0; 44100], 44100).is_none());
+    }
+}
@@ -0,0 +1,418 @@
+use anyhow::Error;
+use crossbeam_channel::Receiver;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use onetagger_player::AudioSources;
+use onetagger_tag::{FrameName, Tag};
+
+use crate::analysis::analyze_files;
+
+/// Gating block length and step (ms)
+const BLOCK: u32 = 400;
+const STEP: u32 = 100;
+/// Gates of integrated loudness (LUFS / LU)
+const ABSOLUTE_GATE: f64 = -70.0;
+const RELATIVE_GATE: f64 = -10.0;
+/// Opus R128 tags are relative to -23 LUFS
+const R128_REFERENCE: f32 = -23.0;
+/// ReplayGain 2.0 reference, used when reading R128 tags
+const REPLAYGAIN_REFERENCE: f32 = -18.0;
+/// True peak interpolation filter
+const OVERSAMPLE: usize = 4;
+const TAPS: usize = 12;
+
+const TRACK_GAIN: &str = "REPLAYGAIN_TRACK_GAIN";
+const TRACK_PEAK: &str = "REPLAYGAIN_TRACK_PEAK";
+const ALBUM_GAIN: &str = "REPLAYGAIN_ALBUM_GAIN";
+const ALBUM_PEAK: &str = "REPLAYGAIN_ALBUM_PEAK";
+const R128_TRACK_GAIN: &str = "R128_TRACK_GAIN";
+const R128_ALBUM_GAIN: &str = "R128_ALBUM_GAIN";
+
+/// Loudness of single track
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackLoudness {
+    /// Integrated loudness in LUFS
+    pub integrated: f32,
+    /// True peak, linear
+    pub peak: f32,
+    /// Mean square of every gating block, used for album loudness
+    #[serde(skip)]
+    blocks: Vec<f64>,
+}
+
+/// Measured values which get written into tags
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayGain {
+    /// LUFS
+    pub track_loudness: f32,
+    pub track_peak: f32,
+    pub album_loudness: Option<f32>,
+    pub album_peak: Option<f32>,
+}
+
+impl ReplayGain {
+    /// Track gain in dB for reference loudness
+    pub fn track_gain(&self, reference: f32) -> f32 {
+        reference - self.track_loudness
+    }
+
+    /// Album gain in dB for reference loudness
+    pub fn album_gain(&self, reference: f32) -> Option<f32> {
+        self.album_loudness.map(|l| reference - l)
+    }
+}
+
+/// Streaming ITU-R BS.1770 / EBU R128 loudness meter
+pub struct LoudnessMeter {
+    channels: usize,
+    weights: Vec<f64>,
+    filters: Vec<[Biquad; 2]>,
+    /// Last samples of every channel, newest first
+    history: Vec<[f32; TAPS]>,
+    coefficients: [[f32; TAPS]; OVERSAMPLE],
+    peak: f32,
+    channel: usize,
+    step_len: usize,
+    step_pos: usize,
+    step_sum: f64,
+    /// Mean square of last 4 steps
+    steps: [f64; 4],
+    step_count: usize,
+    blocks: Vec<f64>,
+}
+
+impl LoudnessMeter {
+    pub fn new(channels: u16, sample_rate: u32) -> LoudnessMeter {
+        let channels = channels.max(1) as usize;
+        // 5.1: L, R, C, LFE, Ls, Rs
+        let weights = match channels {
+            6 => vec![1.0, 1.0, 1.0, 0.0, 1.41, 1.41],
+            _ => vec![1.0; channels]
+        };
+        LoudnessMeter {
+            channels,
+            weights,
+            filters: vec![k_weighting(sample_rate as f64); channels],
+            history: vec![[0.0; TAPS]; channels],
+            coefficients: std::array::from_fn(|phase| std::array::from_fn(|tap| {
+                // Distance from interpolated point, which is in the middle of history
+                let d = (TAPS / 2 - 1) as f32 + phase as f32 / OVERSAMPLE as f32 - tap as f32;
+                let sinc = match d == 0.0 {
+                    true => 1.0,
+                    false => (std::f32::consts::PI * d).sin() / (std::f32::consts::PI * d)
+                };
+                sinc * (0.5 + 0.5 * (std::f32::consts::PI * d / (TAPS / 2) as f32).cos())
+            })),
+            peak: 0.0,
+            channel: 0,
+            step_len: (sample_rate * STEP / 1000).max(1) as usize,
+            step_pos: 0,
+            step_sum: 0.0,
+            steps: [0.0; 4],
+            step_count: 0,
+            blocks: vec![],
+        }
+    }
+
+    /// Add interleaved samples
+    pub fn process(&mut self, samples: impl IntoIterator<Item = f32>) {
+        for sample in samples {
+            let c = self.channel;
+            let [a, b] = &mut self.filters[c];
+            let z = b.process(a.process(sample as f64));
+            self.step_sum += self.weights[c] * z * z;
+
+            // True peak
+            let history = &mut self.history[c];
+            history.copy_within(0..TAPS - 1, 1);
+            history[0] = sample;
+            for coefficients in &self.coefficients {
+                let v = history.iter().zip(coefficients).map(|(h, c)| h * c).sum::<f32>().abs();
+                self.peak = self.peak.max(v);
+            }
+
+            self.channel += 1;
+            if self.channel == self.channels {
+                self.channel = 0;
+                self.step_pos += 1;
+                if self.step_pos == self.step_len {
+                    self.end_step();
+                }
+            }
+        }
+    }
+
+    /// Blocks are 400ms long and overlap by 75%
+    fn end_step(&mut self) {
+        self.steps.rotate_left(1);
+        self.steps[3] = self.step_sum / self.step_len as f64;
+        self.step_sum = 0.0;
+        self.step_pos = 0;
+        self.step_count += 1;
+        if self.step_count >= (BLOCK / STEP) as usize {
+            self.blocks.push(self.steps.iter().sum::<f64>() / self.steps.len() as f64);
+        }
+    }
+
+    /// Get result, None if too short or silent
+    pub fn finish(self) -> Option<TrackLoudness> {
+        let integrated = gated_loudness(&self.blocks)?;
+        Some(TrackLoudness { integrated, peak: self.peak, blocks: self.blocks })
+    }
+}
+
+/// Direct form II transposed biquad
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// High shelf and high pass filters of BS.1770 for any sample rate
+fn k_weighting(rate: f64) -> [Biquad; 2] {
+    let (f0, gain, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (std::f64::consts::PI * f0 / rate).tan();
+    let vh = 10f64.powf(gain / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        z: [0.0; 2]
+    };
+
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (std::f64::consts::PI * f0 / rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        z: [0.0; 2]
+    };
+    [shelf, high_pass]
+}
+
+fn to_lufs(mean_square: f64) -> f64 {
+    -0.691 + 10.0 * mean_square.log10()
+}
+
+/// Integrated loudness using absolute and relative gate
+fn gated_loudness(blocks: &[f64]) -> Option<f32> {
+    let mean = |v: &[f64]| v.iter().sum::<f64>() / v.len() as f64;
+    let blocks = blocks.iter().copied().filter(|b| to_lufs(*b) > ABSOLUTE_GATE).collect::<Vec<_>>();
+    if blocks.is_empty() {
+        return None;
+    }
+    let threshold = to_lufs(mean(&blocks)) + RELATIVE_GATE;
+    let blocks = blocks.into_iter().filter(|b| to_lufs(*b) > threshold).collect::<Vec<_>>();
+    Some(to_lufs(mean(&blocks)) as f32)
+}
+
+/// ReplayGain / R128 scanner
+pub struct LoudnessScanner;
+
+impl LoudnessScanner {
+    /// Measure loudness of whole audio file
+    pub fn analyze_file(path: impl AsRef<Path>) -> Result<TrackLoudness, Error> {
+        let source = AudioSources::from_path(path)?.get_source()?;
+        let mut meter = LoudnessMeter::new(source.channels(), source.sample_rate());
+        meter.process(source.map(|s| s as f32 / i16::MAX as f32));
+        meter.finish().ok_or(anyhow!("Track is too short or silent"))
+    }
+
+    /// Measure loudness of multiple files in parallel
+    pub fn analyze_files(files: Vec<PathBuf>, threads: usize) -> Receiver<(PathBuf, Result<TrackLoudness, Error>)> {
+        analyze_files(files, threads, |path| Self::analyze_file(path))
+    }
+
+    /// Album loudness and peak of tracks
+    pub fn album(tracks: &[&TrackLoudness]) -> Option<(f32, f32)> {
+        let blocks = tracks.iter().flat_map(|t| t.blocks.iter().copied()).collect::<Vec<_>>();
+        let peak = tracks.iter().map(|t| t.peak).fold(0.0, f32::max);
+        Some((gated_loudness(&blocks)?, peak))
+    }
+
+    /// Measure files, files in the same folder are treated as album
+    pub fn scan(files: Vec<PathBuf>, album: bool, threads: usize) -> Vec<(PathBuf, Result<ReplayGain, Error>)> {
+        let mut results = Self::analyze_files(files, threads).into_iter().collect::<Vec<_>>();
+        results.sort_by(|a, b| a.0.cmp(&b.0));
+
+        // Group by folder
+        let mut albums: HashMap<Option<&Path>, Vec<&TrackLoudness>> = HashMap::new();
+        if album {
+            for (path, result) in &results {
+                if let Ok(track) = result {
+                    albums.entry(path.parent()).or_default().push(track);
+                }
+            }
+        }
+        let albums = albums.into_iter()
+            .filter_map(|(folder, tracks)| Some((folder.map(|f| f.to_owned()), Self::album(&tracks)?)))
+            .collect::<HashMap<_, _>>();
+
+        results.into_iter().map(|(path, result)| {
+            let album = albums.get(&path.parent().map(|p| p.to_owned()));
+            let result = result.map(|track| ReplayGain {
+                track_loudness: track.integrated,
+                track_peak: track.peak,
+                album_loudness: album.map(|a| a.0),
+                album_peak: album.map(|a| a.1),
+            });
+            (path, result)
+        }).collect()
+    }
+
+    /// Does the file already have track gain tag
+    pub fn has_gain(path: impl AsRef<Path>) -> bool {
+        let tag = match Tag::load_file(&path, false) {
+            Ok(tag) => tag,
+            Err(_) => return false
+        };
+        let name = match is_opus(&path) {
+            true => R128_TRACK_GAIN.to_string(),
+            false => frame(TRACK_GAIN).by_format(&tag.format())
+        };
+        tag.tag().get_raw(&name)
+            .map(|v| v.iter().any(|v| !v.trim().is_empty()))
+            .unwrap_or(false)
+    }
+
+    /// Write gain tags, returns false if file already has them and overwrite is disabled
+    pub fn write_gain(path: impl AsRef<Path>, gain: &ReplayGain, reference: f32, overwrite: bool) -> Result<bool, Error> {
+        if !overwrite && Self::has_gain(&path) {
+            return Ok(false);
+        }
+        let mut tag_wrap = Tag::load_file(&path, true)?;
+        let format = tag_wrap.format();
+        let tag = tag_wrap.tag_mut();
+
+        // Opus uses Q7.8 integers relative to -23 LUFS (RFC 7845)
+        if is_opus(&path) {
+            let r128 = |loudness: f32| (((R128_REFERENCE - loudness) * 256.0).round() as i32).clamp(i16::MIN as i32, i16::MAX as i32).to_string();
+            tag.set_raw(R128_TRACK_GAIN, vec![r128(gain.track_loudness)], true);
+            if let Some(album) = gain.album_loudness {
+                tag.set_raw(R128_ALBUM_GAIN, vec![r128(album)], true);
+            }
+            tag.save_file(path.as_ref())?;
+            return Ok(true);
+        }
+
+        let mut values = vec![
+            (TRACK_GAIN, format!("{:.2} dB", gain.track_gain(reference))),
+            (TRACK_PEAK, format!("{:.6}", gain.track_peak)),
+        ];
+        if let (Some(album_gain), Some(album_peak)) = (gain.album_gain(reference), gain.album_peak) {
+            values.push((ALBUM_GAIN, format!("{album_gain:.2} dB")));
+            values.push((ALBUM_PEAK, format!("{album_peak:.6}")));
+        }
+        for (name, value) in values {
+            tag.set_raw(&frame(name).by_format(&format), vec![value], true);
+        }
+        tag.save_file(path.as_ref())?;
+        Ok(true)
+    }
+
+    /// Read track gain (dB, relative to -18 LUFS) and peak from tag
+    pub fn read_gain(path: impl AsRef<Path>) -> Option<(f32, Option<f32>)> {
+        let tag_wrap = Tag::load_file(&path, false).ok()?;
+        let tag = tag_wrap.tag();
+        if is_opus(&path) {
+            let r128 = tag.get_raw(R128_TRACK_GAIN)?.first()?.trim().parse::<i16>().ok()?;
+            return Some((r128 as f32 / 256.0 + REPLAYGAIN_REFERENCE - R128_REFERENCE, None));
+        }
+        let format = tag_wrap.format();
+        let gain = tag.get_raw(&frame(TRACK_GAIN).by_format(&format))?
+            .first()?
+            .trim()
+            .trim_end_matches(|c: char| c.is_alphabetic())
+            .trim()
+            .parse()
+            .ok()?;
+        let peak = tag.get_raw(&frame(TRACK_PEAK).by_format(&format))
+            .and_then(|v| v.first()?.trim().parse().ok());
+        Some((gain, peak))
+    }
+
+    /// Linear volume multiplier for playback, limited to prevent clipping
+    pub fn playback_gain(path: impl AsRef<Path>) -> Option<f32> {
+        let (gain, peak) = Self::read_gain(path)?;
+        let volume = 10f32.powf(gain / 20.0);
+        match peak {
+            Some(peak) if peak > 0.0 => Some(volume.min(1.0 / peak)),
+            _ => Some(volume)
+        }
+    }
+}
+
+/// MP4 uses lowercase freeform atoms
+fn frame(name: &str) -> FrameName {
+    FrameName::new(name, name, &format!("iTunes:{}", name.to_lowercase()))
+}
+
+fn is_opus(path: impl AsRef<Path>) -> bool {
+    path.as_ref().extension().map(|e| e.eq_ignore_ascii_case("opus")).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(freq: f32, amplitude: f32, seconds: u32, sample_rate: u32) -> Vec<f32> {
+        (0..sample_rate * seconds)
+            .map(|i| (i as f32 / sample_rate as f32 * freq * std::f32::consts::TAU).sin() * amplitude)
+            .flat_map(|s| [s, s])
+            .collect()
+    }
+
+    #[test]
+    fn test_loudness() {
+        // EBU Tech 3341: 1kHz stereo sine at -23 dBFS = -23 LUFS
+        for sample_rate in [44100, 48000] {
+            let mut meter = LoudnessMeter::new(2, sample_rate);
+            meter.process(sine(1000.0, 10f32.powf(-23.0 / 20.0), 20, sample_rate));
+            let result = meter.finish().unwrap();
+            assert!((result.integrated + 23.0).abs() < 0.1, "{}", result.integrated);
+            assert!((result.peak - 10f32.powf(-23.0 / 20.0)).abs() < 0.01);
+        }
+
+        // Quiet part is gated out of album
+        let mut loud = LoudnessMeter::new(2, 48000);
+        loud.process(sine(1000.0, 0.5, 10, 48000));
+        let loud = loud.finish().unwrap();
+        let mut quiet = LoudnessMeter::new(2, 48000);
+        quiet.process(sine(1000.0, 0.05, 10, 48000));
+        let quiet = quiet.finish().unwrap();
+        let (album, peak) = LoudnessScanner::album(&[&loud, &quiet]).unwrap();
+        assert!((album - loud.integrated).abs() < 0.1);
+        assert!((peak - 0.5).abs() < 0.01);
+
+        // Silence
+        let mut meter = LoudnessMeter::new(2, 48000);
+        meter.process(vec![0.0; 48000 * 2]);
+        assert!(meter.finish().is_none());
+    }
+
+    #[test]
+    fn test_true_peak() {
+        // Sine at fs/4 sampled between its peaks
+        let a = std::f32::consts::FRAC_1_SQRT_2;
+        let samples = [a, a, -a, -a].repeat(12000);
+        let mut meter = LoudnessMeter::new(1, 48000);
+        meter.process(samples);
+        let result = meter.finish().unwrap();
+        assert!(result.peak > 0.95 && result.peak < 1.05, "{}", result.peak);
+    }
+}
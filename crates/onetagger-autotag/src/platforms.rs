@@ -167,6 +167,11 @@ impl CustomPlatform {
             // Setup logging
             let logging_cb_fn: Symbol<unsafe extern fn(extern fn (*mut onetagger_tagger::custom::FFIRecord))> = lib.get(b"_1t_register_logger")?;
             logging_cb_fn(onetagger_tagger::custom::write_log);
+            // Rate limit reporting (older plugins don't have it)
+            match lib.get::<unsafe extern "C" fn(extern "C" fn(u64))>(b"_1t_register_throttle_callback") {
+                Ok(throttle_cb_fn) => throttle_cb_fn(onetagger_tagger::custom::report_throttled),
+                Err(_) => debug!("Plugin {path:?} doesn't report rate limits"),
+            }
             // Get builder
             let builder_fn: Symbol<unsafe extern fn() -> *mut c_void> = lib.get(b"_1t_create_builder")?;
             let builder = builder_fn();
@@ -0,0 +1,291 @@
+use anyhow::Error;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use serde::{Serialize, Deserialize};
+use onetagger_shared::Settings;
+use onetagger_tagger::TaggerConfig;
+
+use crate::{TaggingState, TaggingStatus};
+
+/// Logs of runs untouched for longer are deleted
+const LOG_TTL: Duration = Duration::from_secs(60 * 60 * 24 * 30);
+
+/// Incremental log of an AutoTagger run, so it can be resumed after a crash or stop.
+/// Stored in `runs/resume-<id>.jsonl`, config and files first, then one line per tagged file.
+pub struct RunLog {
+    id: String,
+    file: Mutex<File>,
+}
+
+impl RunLog {
+    /// Create log of a new run
+    pub fn new(config: &TaggerConfig, files: &[PathBuf]) -> Result<RunLog, Error> {
+        let id = timestamp!().to_string();
+        let folder = Self::runs_folder()?;
+        std::fs::create_dir_all(&folder)?;
+        Self::prune(&folder, LOG_TTL);
+        let log = RunLog { file: Mutex::new(File::create(Self::path(&id)?)?), id };
+        log.write_entry(&RunLogEntry::Start { config: config.clone(), files: files.to_vec() })?;
+        Ok(log)
+    }
+
+    /// Continue writing into log of stopped run
+    pub fn open(id: &str) -> Result<RunLog, Error> {
+        let file = OpenOptions::new().append(true).open(Self::path(id)?)?;
+        Ok(RunLog { id: id.to_string(), file: Mutex::new(file) })
+    }
+
+    /// Get ID of this run
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Record result of file on platform
+    pub fn status(&self, platform: &str, status: &TaggingStatus) -> Result<(), Error> {
+        self.write_entry(&RunLogEntry::Status {
+            platform: platform.to_string(),
+            path: status.path.to_owned(),
+            status: status.status.clone(),
+            release_id: status.release_id.clone()
+        })
+    }
+
+    /// Mark the run as complete and delete the log, it won't be offered for resuming
+    pub fn finished(self) -> Result<(), Error> {
+        // Finished entry in case the delete fails
+        self.write_entry(&RunLogEntry::Finished)?;
+        let path = Self::path(&self.id)?;
+        std::mem::drop(self);
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+
+    /// Remove logs which weren't modified for longer than ttl
+    fn prune(folder: &Path, ttl: Duration) {
+        let now = SystemTime::now();
+        for entry in std::fs::read_dir(folder).into_iter().flatten().flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !name.starts_with("resume-") || !name.ends_with(".jsonl") {
+                continue;
+            }
+            let Some(modified) = entry.metadata().and_then(|m| m.modified()).ok() else { continue };
+            if now.duration_since(modified).unwrap_or_default() > ttl {
+                match std::fs::remove_file(entry.path()) {
+                    Ok(_) => debug!("Removed old run log {name}"),
+                    Err(e) => warn!("Failed removing old run log {name}: {e}"),
+                }
+            }
+        }
+    }
+
+    /// Append entry to log file
+    fn write_entry(&self, entry: &RunLogEntry) -> Result<(), Error> {
+        let mut file = self.file.lock().unwrap();
+        writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    fn runs_folder() -> Result<PathBuf, Error> {
+        Ok(Settings::get_folder()?.join("runs"))
+    }
+
+    fn path(id: &str) -> Result<PathBuf, Error> {
+        Ok(Self::runs_folder()?.join(format!("resume-{id}.jsonl")))
+    }
+
+    /// Read all valid entries of log
+    fn read_entries(path: &Path) -> Result<Vec<RunLogEntry>, Error> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut entries = vec![];
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            // Last line might be incomplete after crash
+            match serde_json::from_str(&line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => warn!("Invalid run log entry: {e}"),
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Load state of run for resuming
+    pub fn load(id: &str) -> Result<ResumeState, Error> {
+        let path = Self::path(id)?;
+        if !path.exists() {
+            return Err(anyhow!("Run {id} not found!"));
+        }
+        ResumeState::from_entries(Self::read_entries(&path)?)
+    }
+
+    /// List runs which didn't finish, newest first
+    pub fn list() -> Result<Vec<ResumableRun>, Error> {
+        let folder = Self::runs_folder()?;
+        if !folder.exists() {
+            return Ok(vec![]);
+        }
+        let mut out = vec![];
+        for entry in std::fs::read_dir(&folder)?.filter_map(|e| e.ok()) {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let Some(id) = name.strip_prefix("resume-").and_then(|n| n.strip_suffix(".jsonl")) else { continue };
+            let state = match Self::read_entries(&entry.path()).and_then(ResumeState::from_entries) {
+                Ok(state) => state,
+                Err(e) => {
+                    warn!("Failed reading run log {id}: {e}");
+                    continue;
+                }
+            };
+            if state.finished {
+                continue;
+            }
+            out.push(ResumableRun {
+                id: id.to_string(),
+                created: id.parse().unwrap_or(0),
+                files: state.files.len(),
+                remaining: state.remaining(),
+                platforms: state.config.platforms.clone()
+            });
+        }
+        out.sort_by_key(|r| std::cmp::Reverse(r.created));
+        Ok(out)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+enum RunLogEntry {
+    Start { config: TaggerConfig, files: Vec<PathBuf> },
+    Status { platform: String, path: PathBuf, status: TaggingState, release_id: Option<String> },
+    Finished
+}
+
+/// Progress of a run restored from its log
+#[derive(Debug, Clone)]
+pub struct ResumeState {
+    pub config: TaggerConfig,
+    pub files: Vec<PathBuf>,
+    /// Files processed by platform
+    pub done: HashMap<String, HashSet<PathBuf>>,
+    pub successful: HashSet<PathBuf>,
+    pub failed: HashSet<PathBuf>,
+    pub finished: bool,
+}
+
+impl ResumeState {
+    fn from_entries(entries: Vec<RunLogEntry>) -> Result<ResumeState, Error> {
+        let mut entries = entries.into_iter();
+        let Some(RunLogEntry::Start { config, files }) = entries.next() else {
+            return Err(anyhow!("Run log is missing config"));
+        };
+        let mut state = ResumeState {
+            config, files, done: HashMap::new(), successful: HashSet::new(), failed: HashSet::new(), finished: false
+        };
+        for entry in entries {
+            match entry {
+                RunLogEntry::Status { platform, path, status, .. } => {
+                    match status {
+                        TaggingState::Ok => {
+                            state.failed.remove(&path);
+                            state.successful.insert(path.clone());
+                        },
+                        TaggingState::Error if !state.successful.contains(&path) => { state.failed.insert(path.clone()); },
+                        _ => {}
+                    }
+                    state.done.entry(platform).or_default().insert(path);
+                },
+                RunLogEntry::Finished => state.finished = true,
+                RunLogEntry::Start { .. } => warn!("Duplicate start entry in run log"),
+            }
+        }
+        Ok(state)
+    }
+
+    /// Was the file already processed by platform
+    pub fn is_done(&self, platform: &str, path: &Path) -> bool {
+        self.done.get(platform).is_some_and(|d| d.contains(path))
+    }
+
+    /// Files which still need to be processed by platform
+    pub fn pending<'a>(&'a self, platform: &'a str, files: &'a [PathBuf]) -> impl Iterator<Item = &'a PathBuf> + 'a {
+        files.iter().filter(move |f| !self.is_done(platform, f))
+    }
+
+    /// Amount of files which still need tagging by any platform
+    pub fn remaining(&self) -> usize {
+        self.files.iter().filter(|f| {
+            if !self.config.multiplatform && self.successful.contains(*f) {
+                return false;
+            }
+            self.config.platforms.iter().any(|p| !self.is_done(p, f))
+        }).count()
+    }
+}
+
+/// Info about stopped run for listing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResumableRun {
+    pub id: String,
+    pub created: u128,
+    /// Total files in run
+    pub files: usize,
+    /// Files which still have platforms to go through
+    pub remaining: usize,
+    pub platforms: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(platform: &str, path: &str, status: TaggingState) -> RunLogEntry {
+        RunLogEntry::Status { platform: platform.to_string(), path: PathBuf::from(path), status, release_id: None }
+    }
+
+    #[test]
+    fn test_resume_state() {
+        let config = TaggerConfig { platforms: vec!["beatport".to_string(), "discogs".to_string()], ..Default::default() };
+        let files = vec![PathBuf::from("a.mp3"), PathBuf::from("b.mp3"), PathBuf::from("c.mp3")];
+        let state = ResumeState::from_entries(vec![
+            RunLogEntry::Start { config, files: files.clone() },
+            status("beatport", "a.mp3", TaggingState::Ok),
+            status("beatport", "b.mp3", TaggingState::Error),
+            status("beatport", "c.mp3", TaggingState::Error),
+            status("discogs", "b.mp3", TaggingState::Ok),
+        ]).unwrap();
+
+        assert!(!state.finished);
+        assert_eq!(state.successful.len(), 2);
+        assert!(state.failed.contains(Path::new("c.mp3")));
+        assert_eq!(state.pending("beatport", &files).count(), 0);
+        assert_eq!(state.pending("discogs", &files).collect::<Vec<_>>(), vec![&files[0], &files[2]]);
+        // a.mp3 is done in fallback mode
+        assert_eq!(state.remaining(), 1);
+    }
+
+    #[test]
+    fn test_prune() {
+        let folder = std::env::temp_dir().join(format!("onetagger_runs_test_{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        let day = Duration::from_secs(60 * 60 * 24);
+        for (name, age) in [("resume-1.jsonl", 40), ("resume-2.jsonl", 1), ("other.txt", 40)] {
+            let path = folder.join(name);
+            std::fs::write(&path, "{}").unwrap();
+            let file = File::options().write(true).open(&path).unwrap();
+            file.set_modified(SystemTime::now() - day * age).unwrap();
+        }
+        // Only old run logs are removed
+        RunLog::prune(&folder, day * 30);
+        let mut left = std::fs::read_dir(&folder).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().to_string()).collect::<Vec<_>>();
+        left.sort();
+        std::fs::remove_dir_all(&folder).ok();
+        assert_eq!(left, vec!["other.txt", "resume-2.jsonl"]);
+    }
+}
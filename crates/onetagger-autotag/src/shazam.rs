@@ -1,38 +1,242 @@
 use anyhow::Error;
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Once;
 use std::thread::Builder;
+use std::time::{Duration, SystemTime};
+use onetagger_platforms::http::prune_oldest;
 use onetagger_player::rodio::source::UniformSourceIterator;
+use onetagger_shared::Settings;
+use onetagger_tagger::ShazamConfig;
 use serde::{Serialize, Deserialize};
-use songrec::SignatureGenerator;
+use songrec::{SignatureGenerator, DecodedSignature};
 use onetagger_player::AudioSources;
 
+/// Sample rate used by Shazam
+const SAMPLE_RATE: u64 = 16000;
+/// Segment length in ms
+const SEGMENT_LENGTH: u64 = 12000;
+/// Length of audio used for cache key in ms
+const HASH_LENGTH: u64 = 10000;
+/// Max size of the cache folder in bytes
+const CACHE_MAX_SIZE: u64 = 256 * 1024 * 1024;
+/// Cached files older than this get removed
+const CACHE_TTL: Duration = Duration::from_secs(60 * 60 * 24 * 90);
+
 pub struct Shazam;
 
 impl Shazam {
-    /// Recognize song on Shazam from path, returns Track, Duration
-    pub fn recognize_from_file(path: impl AsRef<Path>) -> Result<(ShazamTrack, u128), Error> {
+    /// Recognize song on Shazam from path, returns Track, Duration and which segments matched
+    pub fn recognize_from_file(path: impl AsRef<Path>, config: &ShazamConfig) -> Result<(ShazamTrack, u128, ShazamMatch), Error> {
         // Load file
-        let source = AudioSources::from_path(path)?;
+        let source = AudioSources::from_path(&path)?;
         let duration = source.duration();
-        let conv = UniformSourceIterator::new(source.get_source()?, 1, 16000);
-        // Get 12s part from middle
-        let buffer = if duration >= 12000 {
-            // ((duration / 1000) * 16KHz) / 2 (half duration) - (6 * 16KHz) seconds.
-            conv.skip((duration * 8 - 96000) as usize).take(16000 * 12).collect::<Vec<i16>>()
-        } else {
-            conv.collect::<Vec<i16>>()
+        let mut positions = config.positions.iter().map(|p| p.clamp(0.0, 1.0)).collect::<Vec<_>>();
+        positions.sort_unstable_by(f32::total_cmp);
+        positions.dedup();
+        if positions.is_empty() || duration < SEGMENT_LENGTH as u128 {
+            positions = vec![0.5];
+        }
+
+        // Load cache
+        let cache_path = match config.cache {
+            true => {
+                let conv = UniformSourceIterator::new(source.get_source()?, 1, SAMPLE_RATE as u32);
+                let hash = audio_hash(conv.take((HASH_LENGTH * SAMPLE_RATE / 1000) as usize), duration);
+                ShazamCache::path(hash).inspect_err(|e| warn!("Shazam cache unavailable: {e}")).ok()
+            },
+            false => None
+        };
+        let mut cache = cache_path.as_deref().map(ShazamCache::load).unwrap_or_default();
+
+        // Generate signatures for segments which aren't cached
+        let missing = positions.iter().copied().filter(|p| !cache.get(*p).is_some_and(|s| s.signature.is_some())).collect::<Vec<_>>();
+        if !missing.is_empty() {
+            let windows = missing.iter().map(|p| segment_window(duration as u64, *p)).collect::<Vec<_>>();
+            let end = windows.iter().map(|w| w.1).max().unwrap_or(0);
+            let conv = UniformSourceIterator::new(AudioSources::from_path(&path)?.get_source()?, 1, SAMPLE_RATE as u32);
+            let mut buffers = vec![vec![]; windows.len()];
+            for (i, sample) in conv.take(end).enumerate() {
+                for ((start, end), buffer) in windows.iter().zip(buffers.iter_mut()) {
+                    if i >= *start && i < *end {
+                        buffer.push(sample);
+                    }
+                }
+            }
+            // Calculating singnature requires 6MB stack, because it allocates >2MB of buffers for some reason
+            let signatures = Builder::new()
+                .stack_size(1024 * 1024 * 6)
+                .spawn(move || buffers.iter().map(|b| SignatureGenerator::make_signature_from_buffer(b)).collect::<Vec<_>>())
+                .unwrap()
+                .join()
+                .unwrap();
+            for (position, signature) in missing.into_iter().zip(signatures) {
+                let uri = signature.encode_to_uri().map_err(|e| anyhow!("{e:?}"))?;
+                cache.segment(position).signature = Some(uri);
+            }
+        }
+
+        // Recognize each segment
+        let mut last_error = None;
+        for position in &positions {
+            let segment = cache.segment(*position);
+            if segment.recognized {
+                continue;
+            }
+            let signature = DecodedSignature::decode_from_uri(segment.signature.as_ref().unwrap()).map_err(|e| anyhow!("{e:?}"))?;
+            match songrec::recognize_song_from_signature(&signature, 0).map_err(|e| anyhow!("{e:?}")).and_then(|r| Ok(serde_json::from_value::<ShazamResponse>(r)?)) {
+                Ok(response) => {
+                    segment.recognized = true;
+                    segment.track = response.track;
+                },
+                // Failed requests are not cached
+                Err(e) => {
+                    warn!("Shazam failed on segment at {position}: {e}");
+                    last_error = Some(e);
+                }
+            }
+        }
+        if let Some(cache_path) = &cache_path {
+            if let Err(e) = cache.save(cache_path) {
+                warn!("Failed saving Shazam cache: {e}");
+            }
+        }
+
+        // Vote
+        let keys = positions.iter().map(|p| cache.get(*p).and_then(|s| s.track.as_ref()).map(|t| t.key.as_str())).collect::<Vec<_>>();
+        let (segment, votes) = match vote(&keys) {
+            Some(v) => v,
+            None => return Err(last_error.unwrap_or(anyhow!("Shazam returned no matches!")))
         };
-        // Calculating singnature requires 6MB stack, because it allocates >2MB of buffers for some reason
-        let signature = Builder::new()
-            .stack_size(1024 * 1024 * 6)
-            .spawn(move || { SignatureGenerator::make_signature_from_buffer(&buffer) })
-            .unwrap()
-            .join()
-            .unwrap();
-        let response = songrec::recognize_song_from_signature(&signature, 0).map_err(|e| anyhow!("{e:?}"))?;
-        let response: ShazamResponse = serde_json::from_value(response)?;
-        let track = response.track.ok_or(anyhow!("Shazam returned no matches!"))?;
-        Ok((track, duration))
+        let shazam_match = ShazamMatch {
+            segment,
+            position: positions[segment],
+            votes,
+            segments: positions.len(),
+            agreement: votes as f32 / positions.len() as f32
+        };
+        if shazam_match.agreement < config.min_agreement {
+            bail!("Shazam segments don't agree ({votes}/{} segments)", positions.len());
+        }
+        let track = cache.get(positions[segment]).unwrap().track.clone().unwrap();
+        Ok((track, duration, shazam_match))
+    }
+}
+
+/// Pick the track most segments agree on, ties go to the earlier segment. Returns segment index and votes
+fn vote(keys: &[Option<&str>]) -> Option<(usize, usize)> {
+    let mut best: Option<(usize, usize)> = None;
+    for (i, key) in keys.iter().enumerate() {
+        let key = match key {
+            Some(k) => k,
+            None => continue
+        };
+        let votes = keys.iter().filter(|k| k.as_ref() == Some(key)).count();
+        if best.map(|(_, v)| votes > v).unwrap_or(true) {
+            best = Some((i, votes));
+        }
+    }
+    best
+}
+
+/// Start and end sample of 12s segment around position
+fn segment_window(duration: u64, position: f32) -> (usize, usize) {
+    if duration < SEGMENT_LENGTH {
+        return (0, usize::MAX);
+    }
+    let center = (duration as f64 * position as f64) as u64;
+    let start = center.saturating_sub(SEGMENT_LENGTH / 2).min(duration - SEGMENT_LENGTH);
+    let start = (start * SAMPLE_RATE / 1000) as usize;
+    (start, start + (SEGMENT_LENGTH * SAMPLE_RATE / 1000) as usize)
+}
+
+/// FNV-1a hash of the decoded audio and duration, stays the same when tags are changed
+fn audio_hash(samples: impl Iterator<Item = i16>, duration: u128) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    for byte in samples.flat_map(|s| s.to_le_bytes()).chain(duration.to_le_bytes()) {
+        hash = (hash ^ byte as u64).wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Which segments matched the result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShazamMatch {
+    /// Index of the first segment matching the result
+    pub segment: usize,
+    /// Position of that segment, fraction of duration
+    pub position: f32,
+    /// Segments which returned the same track
+    pub votes: usize,
+    pub segments: usize,
+    /// votes / segments
+    pub agreement: f32,
+}
+
+/// Signatures and responses of single file
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+struct ShazamCache {
+    segments: HashMap<String, CachedSegment>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+struct CachedSegment {
+    /// Signature encoded as `data:audio/vnd.shazam.sig` URI
+    signature: Option<String>,
+    /// Shazam responded, track is None if nothing matched
+    recognized: bool,
+    track: Option<ShazamTrack>,
+}
+
+impl ShazamCache {
+    /// Get cache file path for given hash, old entries are pruned on first use
+    fn path(hash: u64) -> Result<PathBuf, Error> {
+        static PRUNE: Once = Once::new();
+        let folder = Settings::get_folder()?.join("shazam_cache");
+        std::fs::create_dir_all(&folder)?;
+        PRUNE.call_once(|| ShazamCache::prune(&folder, CACHE_MAX_SIZE, CACHE_TTL));
+        Ok(folder.join(format!("{hash:016x}.json")))
+    }
+
+    /// Remove expired files and the oldest ones if over size limit
+    fn prune(folder: &Path, max_size: u64, ttl: Duration) {
+        let now = SystemTime::now();
+        let entries = std::fs::read_dir(folder).into_iter().flatten().flatten()
+            .filter_map(|e| {
+                let meta = e.metadata().ok()?;
+                Some((e.path(), meta.len(), meta.modified().ok()?))
+            })
+            .filter(|(path, _, modified)| {
+                if now.duration_since(*modified).unwrap_or_default() > ttl {
+                    return std::fs::remove_file(path).is_err();
+                }
+                true
+            })
+            .collect::<Vec<_>>();
+        let size = entries.iter().map(|e| e.1).sum();
+        if size > max_size {
+            let size = prune_oldest(entries, size, max_size);
+            debug!("Shazam cache pruned to {size} bytes");
+        }
+    }
+
+    /// Load from file, empty if missing or invalid
+    fn load(path: &Path) -> ShazamCache {
+        std::fs::read(path).ok().and_then(|data| serde_json::from_slice(&data).ok()).unwrap_or_default()
+    }
+
+    fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        std::fs::write(path, serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+
+    fn get(&self, position: f32) -> Option<&CachedSegment> {
+        self.segments.get(&format!("{position:.3}"))
+    }
+
+    fn segment(&mut self, position: f32) -> &mut CachedSegment {
+        self.segments.entry(format!("{position:.3}")).or_default()
     }
 }
 
@@ -98,4 +302,53 @@ pub enum ShazamSection {
 pub struct ShazamMetadataSection {
     pub text: String,
     pub title: String
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vote() {
+        assert_eq!(vote(&[Some("a"), Some("b"), Some("b")]), Some((1, 2)));
+        assert_eq!(vote(&[Some("a"), None, Some("b")]), Some((0, 1)));
+        assert_eq!(vote(&[None, Some("c"), Some("a"), Some("c"), Some("a")]), Some((1, 2)));
+        assert_eq!(vote(&[None, None]), None);
+    }
+
+    #[test]
+    fn test_segment_window() {
+        // 60s track, middle segment is 24 - 36s
+        assert_eq!(segment_window(60000, 0.5), (24 * 16000, 36 * 16000));
+        // Clamped to start and end
+        assert_eq!(segment_window(60000, 0.0), (0, 12 * 16000));
+        assert_eq!(segment_window(60000, 1.0), (48 * 16000, 60 * 16000));
+    }
+
+    #[test]
+    fn test_audio_hash() {
+        // Depends only on audio and duration
+        let samples = [1i16, -2, 3];
+        assert_eq!(audio_hash(samples.into_iter(), 1000), audio_hash(samples.into_iter(), 1000));
+        assert_ne!(audio_hash(samples.into_iter(), 1000), audio_hash(samples.into_iter(), 1001));
+    }
+
+    #[test]
+    fn test_cache_prune() {
+        let folder = std::env::temp_dir().join(format!("onetagger_shazam_cache_test_{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        let day = Duration::from_secs(60 * 60 * 24);
+        for (i, age) in [100, 3, 2, 1].into_iter().enumerate() {
+            let path = folder.join(format!("{i}.json"));
+            std::fs::write(&path, [0u8; 100]).unwrap();
+            let file = std::fs::File::options().write(true).open(&path).unwrap();
+            file.set_modified(SystemTime::now() - day * age).unwrap();
+        }
+        // Expired one and the oldest over size limit are removed
+        ShazamCache::prune(&folder, 250, day * 90);
+        let mut left = std::fs::read_dir(&folder).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().to_string()).collect::<Vec<_>>();
+        left.sort();
+        std::fs::remove_dir_all(&folder).ok();
+        assert_eq!(left, vec!["2.json", "3.json"]);
+    }
+}
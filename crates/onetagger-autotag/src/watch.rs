@@ -0,0 +1,259 @@
+use anyhow::Error;
+use chrono::Local;
+use crossbeam_channel::{unbounded, RecvTimeoutError};
+use notify::{EventKind, RecursiveMode, Watcher};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+use onetagger_tag::EXTENSIONS;
+use onetagger_shared::Settings;
+use onetagger_tagger::TaggerConfig;
+
+use crate::{Tagger, TaggingState, TaggingStatusWrap};
+
+/// Watch folder daemon configuration
+#[derive(Debug, Clone)]
+pub struct WatchConfig {
+    pub folders: Vec<PathBuf>,
+    /// AutoTagger config used for new files, moves use its `move_success_path` / `move_failed_path`
+    pub config: TaggerConfig,
+    /// How long a file has to stay unchanged before it gets tagged
+    pub settle: Duration,
+    pub recursive: bool,
+}
+
+impl WatchConfig {
+    pub fn new(folders: Vec<PathBuf>, config: TaggerConfig) -> WatchConfig {
+        WatchConfig { folders, config, settle: Duration::from_secs(10), recursive: true }
+    }
+}
+
+/// Load AutoTagger config saved as profile in the UI, `None` = last used config
+pub fn load_profile(name: Option<&str>) -> Result<TaggerConfig, Error> {
+    let settings = Settings::load()?;
+    let config = match name {
+        Some(name) => settings.ui["autoTaggerProfiles"]
+            .as_array()
+            .and_then(|profiles| profiles.iter().find(|p| p["name"] == name))
+            .map(|p| p["config"].clone())
+            .ok_or(anyhow!("AutoTagger profile not found: {name}"))?,
+        None => settings.ui["autoTaggerConfig"].clone()
+    };
+    Ok(serde_json::from_value(config)?)
+}
+
+/// Watch folders and tag new files once they stop changing. Blocks until `stop` is set.
+pub fn watch(config: &WatchConfig, stop: Arc<AtomicBool>) -> Result<(), Error> {
+    let (tx, rx) = unbounded();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    let mode = match config.recursive {
+        true => RecursiveMode::Recursive,
+        false => RecursiveMode::NonRecursive
+    };
+    for folder in &config.folders {
+        watcher.watch(folder, mode)?;
+        info!("Watching folder: {folder:?}");
+    }
+
+    let mut pending = PendingFiles::default();
+    // Tagging modifies the file, so keep state after tagging to not tag it again
+    let mut processed = HashMap::new();
+    while !stop.load(Ordering::SeqCst) {
+        match rx.recv_timeout(Duration::from_secs(1)) {
+            Ok(Ok(event)) => {
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    for path in event.paths {
+                        if is_audio_file(&path) && !is_move_target(&path, &config.config) {
+                            pending.touch(path, Instant::now());
+                        }
+                    }
+                }
+            },
+            Ok(Err(e)) => warn!("Folder watcher error: {e}"),
+            Err(RecvTimeoutError::Timeout) => {},
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        let files = pending.take_settled(Instant::now(), config.settle, file_state)
+            .into_iter()
+            .filter(|path| processed.get(path) != file_state(path).as_ref())
+            .collect::<Vec<_>>();
+        if files.is_empty() {
+            continue;
+        }
+
+        let entries = tag_new_files(&config.config, files, stop.clone());
+        for entry in &entries {
+            if let Some(state) = file_state(&entry.path) {
+                processed.insert(entry.path.clone(), state);
+            }
+        }
+        if let Err(e) = WatchLogEntry::append(&entries) {
+            warn!("Failed writing watch log: {e}");
+        }
+    }
+    Ok(())
+}
+
+/// Tag files and generate log entries, the run uses the watcher's stop flag and own journal
+fn tag_new_files(config: &TaggerConfig, files: Vec<PathBuf>, stop: Arc<AtomicBool>) -> Vec<WatchLogEntry> {
+    info!("Watch: tagging {} new files", files.len());
+    let finished = Arc::new(Mutex::new(None));
+    let rx = Tagger::tag_files_with_stop(config, files.clone(), finished.clone(), stop);
+
+    // Successful result has priority, otherwise keep the last one
+    let mut results: HashMap<PathBuf, TaggingStatusWrap> = HashMap::new();
+    for status in rx {
+        match results.get(&status.status.path) {
+            Some(s) if s.status.status == TaggingState::Ok => {},
+            _ => { results.insert(status.status.path.clone(), status); }
+        }
+    }
+
+    let run_id = finished.lock().unwrap().as_ref().and_then(|f| f.run_id.clone());
+    let time = Local::now().to_rfc3339();
+    files.into_iter().map(|path| {
+        let result = results.remove(&path);
+        WatchLogEntry {
+            time: time.clone(),
+            status: result.as_ref().map(|r| r.status.status.clone()).unwrap_or(TaggingState::Skipped),
+            platform: result.as_ref().map(|r| r.platform.clone()),
+            message: result.and_then(|r| r.status.message),
+            run_id: run_id.clone(),
+            path,
+        }
+    }).collect()
+}
+
+/// Is supported audio file
+fn is_audio_file(path: &Path) -> bool {
+    let extension = path.extension().unwrap_or_default().to_ascii_lowercase();
+    EXTENSIONS.iter().any(|e| extension == *e)
+}
+
+/// Moved files shouldn't be tagged again if the target is inside a watched folder
+fn is_move_target(path: &Path, config: &TaggerConfig) -> bool {
+    [(config.move_success, &config.move_success_path), (config.move_failed, &config.move_failed_path)]
+        .iter()
+        .any(|(enabled, target)| *enabled && target.as_ref().map(|t| path.starts_with(t)).unwrap_or(false))
+}
+
+/// Size and modification time, `None` if file is gone
+fn file_state(path: &Path) -> Option<(u64, SystemTime)> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.len(), meta.modified().ok()?))
+}
+
+/// Files waiting until they stop changing
+#[derive(Debug, Default)]
+struct PendingFiles {
+    /// path: (last change, last seen state)
+    files: HashMap<PathBuf, (Instant, Option<(u64, SystemTime)>)>,
+}
+
+impl PendingFiles {
+    /// File changed
+    fn touch(&mut self, path: PathBuf, now: Instant) {
+        let entry = self.files.entry(path).or_insert((now, None));
+        entry.0 = now;
+    }
+
+    /// Remove and return files which didn't change for `settle`, deleted files are dropped
+    fn take_settled(&mut self, now: Instant, settle: Duration, state: impl Fn(&Path) -> Option<(u64, SystemTime)>) -> Vec<PathBuf> {
+        let mut settled = vec![];
+        self.files.retain(|path, (changed, last)| {
+            let current = match state(path) {
+                Some(s) => s,
+                None => return false
+            };
+            // Size or mtime changed without notification (e.g. network shares)
+            if last.is_some() && *last != Some(current) {
+                *changed = now;
+            }
+            *last = Some(current);
+            if now.duration_since(*changed) >= settle {
+                settled.push(path.clone());
+                return false;
+            }
+            true
+        });
+        settled.sort();
+        settled
+    }
+}
+
+/// Result of a file tagged by the watcher, saved into `runs/watch.jsonl`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchLogEntry {
+    pub time: String,
+    pub path: PathBuf,
+    pub status: TaggingState,
+    pub platform: Option<String>,
+    pub message: Option<String>,
+    /// Undo journal ID of the run
+    pub run_id: Option<String>,
+}
+
+impl WatchLogEntry {
+    /// Path to the watch log
+    pub fn log_path() -> Result<PathBuf, Error> {
+        Ok(Settings::get_folder()?.join("runs").join("watch.jsonl"))
+    }
+
+    /// Append entries to the log
+    fn append(entries: &[WatchLogEntry]) -> Result<(), Error> {
+        let path = Self::log_path()?;
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        for entry in entries {
+            writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        }
+        Ok(())
+    }
+
+    /// Load all entries from the log, oldest first
+    pub fn load() -> Result<Vec<WatchLogEntry>, Error> {
+        let path = Self::log_path()?;
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+        Ok(std::fs::read_to_string(path)?
+            .lines()
+            .filter_map(|l| serde_json::from_str(l).ok())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pending_settle() {
+        let start = Instant::now();
+        let settle = Duration::from_secs(5);
+        let t0 = SystemTime::UNIX_EPOCH;
+        let mut pending = PendingFiles::default();
+        pending.touch(PathBuf::from("a.mp3"), start);
+        pending.touch(PathBuf::from("gone.mp3"), start);
+
+        let state = |size: u64| move |p: &Path| match p.to_str() {
+            Some("a.mp3") => Some((size, t0)),
+            _ => None
+        };
+        // Deleted file is dropped, other one still settling
+        assert!(pending.take_settled(start + Duration::from_secs(1), settle, state(100)).is_empty());
+        assert_eq!(pending.files.len(), 1);
+        // Still being written, timer restarts
+        assert!(pending.take_settled(start + Duration::from_secs(6), settle, state(200)).is_empty());
+        assert!(pending.take_settled(start + Duration::from_secs(10), settle, state(200)).is_empty());
+        assert_eq!(pending.take_settled(start + Duration::from_secs(11), settle, state(200)), vec![PathBuf::from("a.mp3")]);
+        assert!(pending.files.is_empty());
+    }
+}
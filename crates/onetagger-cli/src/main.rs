@@ -6,6 +6,9 @@ use onetagger_ui::StartContext;
 use std::fs::File;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::Duration;
 use clap::{Parser, Subcommand};
 use convert_case::{Casing, Case};
 use onetagger_platforms::spotify::Spotify;
@@ -13,7 +16,16 @@ use onetagger_renamer::{RenamerConfig, Renamer, TemplateParser};
 use onetagger_shared::VERSION;
 use onetagger_autotag::audiofeatures::{AudioFeaturesConfig, AudioFeatures};
 use onetagger_autotag::{Tagger, TaggerConfigExt, AudioFileInfoImpl};
-use onetagger_tagger::{TaggerConfig, AudioFileInfo, SupportedTag};
+use onetagger_autotag::journal::UndoJournal;
+use onetagger_autotag::resume::RunLog;
+use onetagger_autotag::bpm::BpmDetector;
+use onetagger_autotag::key::KeyDetector;
+use onetagger_autotag::loudness::LoudnessScanner;
+use onetagger_autotag::duplicates::{DuplicateFinder, DuplicatesConfig};
+use onetagger_autotag::lossless::{LosslessChecker, LOSSLESS_TAG};
+use onetagger_autotag::watch::{WatchConfig, WatchLogEntry};
+use onetagger_library::{Library, LibraryQuery, TagFilter};
+use onetagger_tagger::{TaggerConfig, AudioFileInfo, SupportedTag, LocalBpmConfig, KeyNotation, IdentifyBackend, FrameName};
 use env_logger;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -56,12 +68,57 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 AudioFileInfo::get_file_list(&path, config.include_subfolders)
             };
 
-            let rx = Tagger::tag_files(&config, files, Arc::new(Mutex::new(None)));
+            let finished = Arc::new(Mutex::new(None));
+            let rx = Tagger::tag_files(&config, files, finished.clone());
             let start = timestamp!();
             for status in rx {
                 debug!("{status:?}");
             }
             info!("Tagging finished, took: {} seconds.", (timestamp!() - start) / 1000);
+            let finished = finished.lock().unwrap().take();
+
+            // Dry run report
+            if let Some(path) = finished.as_ref().and_then(|f| f.dry_run_file.clone()) {
+                match &action {
+                    Actions::Autotagger { dry_run_output: Some(output), .. } => match std::fs::copy(&path, output) {
+                        Ok(_) => println!("Dry run report saved to: {}", output.display()),
+                        Err(e) => {
+                            error!("Failed copying dry run report to {}: {e}", output.display());
+                            println!("Dry run report saved to: {path}");
+                        }
+                    },
+                    _ => println!("Dry run report saved to: {path}")
+                }
+            }
+            if let Some(run_id) = finished.as_ref().and_then(|f| f.run_id.clone()) {
+                println!("Run ID: {run_id} (use `onetagger-cli revert {run_id}` to undo)");
+            }
+            if let Some(resume_id) = finished.as_ref().and_then(|f| f.resume_id.clone()) {
+                println!("Tagging stopped, use `onetagger-cli resume {resume_id}` to continue");
+            }
+        },
+        // Continue stopped AutoTagger run
+        Actions::Resume { run_id } => {
+            let run_id = match run_id {
+                Some(run_id) => run_id,
+                None => {
+                    for run in RunLog::list().expect("Failed listing runs!") {
+                        println!("{} ({}/{} files remaining, {})", run.id, run.remaining, run.files, run.platforms.join(", "));
+                    }
+                    return Ok(());
+                }
+            };
+            let finished = Arc::new(Mutex::new(None));
+            let rx = Tagger::resume(run_id, finished.clone()).expect("Failed resuming run!");
+            let start = timestamp!();
+            for status in rx {
+                debug!("{status:?}");
+            }
+            info!("Tagging finished, took: {} seconds.", (timestamp!() - start) / 1000);
+            let finished = finished.lock().unwrap().take();
+            if let Some(run_id) = finished.as_ref().and_then(|f| f.run_id.clone()) {
+                println!("Run ID: {run_id} (use `onetagger-cli revert {run_id}` to undo)");
+            }
         },
         Actions::Audiofeatures { path, config, client_id, client_secret, no_subfolders } => {
             let file = File::open(config).expect("Failed reading config file!");
@@ -89,6 +146,129 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             info!("Tagging finished, took: {} seconds.", (timestamp!() - start) / 1000);
         },
+        Actions::Bpm { path, min, max, min_confidence, write, overwrite, threads, no_subfolders } => {
+            let config = LocalBpmConfig { enabled: true, min: *min, max: *max, min_confidence: *min_confidence };
+            let files = match path.is_file() {
+                true => vec![path.to_owned()],
+                false => AudioFileInfo::get_file_list(&path, !*no_subfolders)
+            };
+            for (file, result) in BpmDetector::detect_files(files, &config, *threads) {
+                match result {
+                    Ok(result) => {
+                        println!("{}: {} BPM (confidence: {:.2})", file.display(), result.bpm, result.confidence);
+                        if *write && result.confidence >= config.min_confidence {
+                            if let Err(e) = BpmDetector::write_bpm(&file, &result, *overwrite) {
+                                error!("Failed writing BPM to {file:?}: {e}");
+                            }
+                        }
+                    },
+                    Err(e) => error!("Failed detecting BPM of {file:?}: {e}"),
+                }
+            }
+        },
+        Actions::Key { path, notation, min_confidence, write, overwrite, threads, no_subfolders } => {
+            let files = match path.is_file() {
+                true => vec![path.to_owned()],
+                false => AudioFileInfo::get_file_list(&path, !*no_subfolders)
+            };
+            for (file, result) in KeyDetector::detect_files(files, *threads) {
+                match result {
+                    Ok(result) => {
+                        let key = result.key.render(*notation);
+                        println!("{}: {key} (confidence: {:.2})", file.display(), result.confidence);
+                        if *write && result.confidence >= *min_confidence {
+                            if let Err(e) = KeyDetector::write_key(&file, &key, *overwrite) {
+                                error!("Failed writing key to {file:?}: {e}");
+                            }
+                        }
+                    },
+                    Err(e) => error!("Failed detecting key of {file:?}: {e}"),
+                }
+            }
+        },
+        Actions::ReplayGain { path, reference, no_album, write, overwrite, threads, no_subfolders } => {
+            let files = match path.is_file() {
+                true => vec![path.to_owned()],
+                false => AudioFileInfo::get_file_list(&path, !*no_subfolders)
+            };
+            for (file, result) in LoudnessScanner::scan(files, !*no_album, *threads) {
+                match result {
+                    Ok(gain) => {
+                        print!("{}: {:.2} LUFS, peak: {:.6}, gain: {:.2} dB", file.display(), gain.track_loudness, gain.track_peak, gain.track_gain(*reference));
+                        if let Some(album_gain) = gain.album_gain(*reference) {
+                            print!(", album gain: {album_gain:.2} dB");
+                        }
+                        println!();
+                        if *write {
+                            if let Err(e) = LoudnessScanner::write_gain(&file, &gain, *reference, *overwrite) {
+                                error!("Failed writing ReplayGain to {file:?}: {e}");
+                            }
+                        }
+                    },
+                    Err(e) => error!("Failed scanning loudness of {file:?}: {e}"),
+                }
+            }
+        },
+        Actions::Duplicates { path, no_fingerprint, similarity, max_duration_difference, threads, no_subfolders, output, quarantine } => {
+            let config = DuplicatesConfig {
+                path: path.to_owned(),
+                subfolders: !*no_subfolders,
+                fingerprint: !*no_fingerprint,
+                similarity: *similarity,
+                max_duration_difference: *max_duration_difference,
+                threads: *threads
+            };
+            let report = DuplicateFinder::find(&config);
+            for group in &report.groups {
+                println!("{:?}:", group.reason);
+                for (i, file) in group.files.iter().enumerate() {
+                    println!("  {} {} ({}, {} kbps, {} Hz)", if i == 0 { "*" } else { "-" }, file.path.display(), if file.lossless { "lossless" } else { "lossy" }, file.bitrate, file.sample_rate);
+                }
+            }
+            info!("Found {} groups of duplicates", report.groups.len());
+            if let Some(output) = output {
+                report.save(output).expect("Failed saving report!");
+            }
+            if let Some(quarantine) = quarantine {
+                let moved = report.quarantine(quarantine).expect("Failed moving duplicates!");
+                info!("Moved {} files to quarantine", moved.len());
+            }
+        },
+        Actions::Lossless { path, write, tag, playlist, all, threads, no_subfolders } => {
+            let files = match path.is_file() {
+                true => vec![path.to_owned()],
+                false => AudioFileInfo::get_file_list(&path, !*no_subfolders)
+            };
+            // Lossy files can't be checked
+            let total = files.len();
+            let files = files.into_iter().filter(|f| LosslessChecker::is_lossless(f).unwrap_or(false)).collect::<Vec<_>>();
+            if files.len() < total {
+                info!("Skipping {} files which are not lossless", total - files.len());
+            }
+            let mut suspects = vec![];
+            for (file, result) in LosslessChecker::check_files(files, *threads) {
+                match result {
+                    Ok(result) => {
+                        if result.is_fake() || *all {
+                            println!("{}: {}", file.display(), result.tag_value());
+                        }
+                        if *write {
+                            if let Err(e) = LosslessChecker::write_verdict(&file, &result, &FrameName::same(tag)) {
+                                error!("Failed writing verdict to {file:?}: {e}");
+                            }
+                        }
+                        if result.is_fake() {
+                            suspects.push(file);
+                        }
+                    },
+                    Err(e) => error!("Failed analyzing {file:?}: {e}"),
+                }
+            }
+            info!("Found {} suspicious files", suspects.len());
+            if let Some(playlist) = playlist {
+                std::fs::write(playlist, onetagger_playlist::create_m3u_playlist(&suspects)).expect("Failed saving playlist!");
+            }
+        },
         Actions::QueryUrl { url, directory, confidence, output_format } => {
             // Check if directory exists if provided
             if let Some(dir) = &directory {
@@ -123,7 +303,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             }
         },
-        Actions::DownloadSongs { csv_file, directory } => {
+        Actions::DownloadSongs { csv_file, directory, workers, retries, command } => {
             // Check if directory exists
             let dir_path = std::path::Path::new(&directory);
             if !dir_path.exists() {
@@ -137,8 +317,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             
             // Create SongDownloader instance
+            let mut config = onetagger_songdownloader::DownloadConfig::default();
+            config.workers = *workers;
+            config.retries = *retries;
+            if let Some(template) = command {
+                config.backends = vec![onetagger_songdownloader::BackendConfig::Command { name: "custom".to_string(), template: template.to_string() }];
+            }
             let downloader = onetagger_songdownloader::SongDownloader::new()
-                .with_directory(dir_path);
+                .with_directory(dir_path)
+                .with_download_config(config)
+                .with_library(&Library::default_path()?);
             
             // Download songs
             match downloader.download_songs(csv_path) {
@@ -150,6 +338,25 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             }
         },
+        Actions::SplitMix { path, tracklist, cue, chapters, output, title, refine_window } => {
+            let text = std::fs::read_to_string(tracklist).expect("Failed reading tracklist!");
+            let entries = onetagger_songdownloader::parse_tracklist(&text);
+            let config = onetagger_songdownloader::SplitConfig {
+                mode: match *cue {
+                    true => onetagger_songdownloader::SplitMode::Cue,
+                    false => onetagger_songdownloader::SplitMode::Files
+                },
+                refine_window: *refine_window,
+                output: output.clone(),
+                write_chapters: *chapters,
+                ..Default::default()
+            };
+            let title = title.clone().unwrap_or(path.file_stem().unwrap_or_default().to_string_lossy().to_string());
+            let files = onetagger_songdownloader::split_mix(path, &entries, &title, &config).expect("Failed splitting mix!");
+            for file in files {
+                println!("{}", file.display());
+            }
+        },
         // Spotify OAuth flow
         Actions::AuthorizeSpotify { client_id, client_secret, prompt, expose } => {
             let (auth_url, client) = Spotify::generate_auth_url(&client_id, &client_secret).expect("Failed generating auth URL!");
@@ -205,14 +412,93 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
             renamer.rename(&names, &config).expect("Failed renaming!");
         },
+        // Undo journals
+        Actions::Revert { run_id } => {
+            let run_id = match run_id {
+                Some(run_id) => run_id,
+                None => {
+                    for run in UndoJournal::list().expect("Failed listing runs!") {
+                        println!("{} ({} files)", run.id, run.files);
+                    }
+                    return Ok(());
+                }
+            };
+            let results = UndoJournal::revert(run_id).expect("Failed reverting run!");
+            for (path, result) in &results {
+                if let Err(e) = result {
+                    println!("Failed reverting {}: {e}", path.display());
+                }
+            }
+            println!("Reverted {}/{} files", results.iter().filter(|(_, r)| r.is_ok()).count(), results.len());
+        },
+        // Library index
+        Actions::Library { add_root, remove_root, scan, path, filter, limit } => {
+            let mut library = Library::open_default().expect("Failed opening library!");
+            if let Some(root) = add_root {
+                library.add_root(dunce::canonicalize(root)?)?;
+            }
+            if let Some(root) = remove_root {
+                library.remove_root(root)?;
+            }
+            if *scan {
+                let stats = library.scan_all().expect("Failed scanning library!");
+                println!("Added: {}, updated: {}, removed: {}, unchanged: {}, failed: {}", stats.added, stats.updated, stats.removed, stats.unchanged, stats.failed);
+            }
+            // List roots
+            if add_root.is_none() && remove_root.is_none() && !*scan && path.is_none() && filter.is_empty() {
+                for root in library.roots()? {
+                    println!("{}", root.display());
+                }
+                return Ok(());
+            }
+
+            if path.is_some() || !filter.is_empty() {
+                let filters = filter.iter()
+                    .map(|f| TagFilter::parse(f).ok_or(anyhow::anyhow!("Invalid filter: {f}")))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let query = LibraryQuery { path: path.clone(), recursive: true, filters, limit: *limit, ..Default::default() };
+                for file in library.query(&query)? {
+                    println!("{}", file.path.display());
+                }
+            }
+        },
+        // Watch folder daemon
+        Actions::Watch { folders, profile, config, settle, no_subfolders } => {
+            let config = watch_config(folders, profile.as_deref(), config.as_ref(), *settle, *no_subfolders)?;
+            println!("Results will be logged to: {}", WatchLogEntry::log_path()?.display());
+            // Let the current run finish on Ctrl+C
+            let stop = Arc::new(AtomicBool::new(false));
+            let stop_handler = stop.clone();
+            ctrlc::set_handler(move || stop_handler.store(true, Ordering::SeqCst))?;
+            onetagger_autotag::watch::watch(&config, stop).expect("Folder watcher failed!");
+        },
         // Server mode
-        Actions::Server { expose, path, browser } => {
-            onetagger_ui::start_all(StartContext {
+        Actions::Server { expose, path, browser, watch, watch_profile } => {
+            let stop = Arc::new(AtomicBool::new(false));
+            let watcher = Arc::new(Mutex::new(None));
+            if !watch.is_empty() {
+                let config = watch_config(watch, watch_profile.as_deref(), None, 10, false)?;
+                let stop_watch = stop.clone();
+                *watcher.lock().unwrap() = Some(std::thread::spawn(move || {
+                    if let Err(e) = onetagger_autotag::watch::watch(&config, stop_watch) {
+                        error!("Folder watcher failed: {e}");
+                    }
+                }));
+                // Let the watcher finish current run on Ctrl+C
+                let (stop, watcher) = (stop.clone(), watcher.clone());
+                ctrlc::set_handler(move || {
+                    stop_watcher(&stop, &watcher);
+                    std::process::exit(0);
+                })?;
+            }
+            let result = onetagger_ui::start_all(StartContext {
                 server_mode: true,
                 start_path: path.clone().map(String::from),
                 expose: *expose,
                 browser: *browser,
-            }).expect("Failed starting the server");
+            });
+            stop_watcher(&stop, &watcher);
+            result.expect("Failed starting the server");
         }
     }
     
@@ -280,9 +566,9 @@ enum Actions {
         #[clap(long)]
         merge_genres: bool,
 
-        /// Write the key tag in CAMELOT format
+        /// Key notation: original, musical, sharps, flats, camelot, open-key or traktor
         #[clap(long)]
-        camelot: bool,
+        key_notation: Option<KeyNotation>,
 
         /// Write title tag without version (ie. remix)
         #[clap(long)]
@@ -308,6 +594,30 @@ enum Actions {
         #[clap(long)]
         force_shazam: bool,
 
+        /// Identify tracks using AcoustID fingerprints instead of Shazam
+        #[clap(long)]
+        acoustid: bool,
+
+        /// AcoustID application API key
+        #[clap(long)]
+        acoustid_key: Option<String>,
+
+        /// Positions of Shazam segments as fraction of duration, comma separated (eg. 0.3,0.5,0.7)
+        #[clap(long, value_delimiter = ',')]
+        shazam_positions: Option<Vec<f32>>,
+
+        /// Minimum ratio of Shazam segments which have to agree on the result (0.0 - 1.0)
+        #[clap(long)]
+        shazam_agreement: Option<f32>,
+
+        /// Only use cached platform responses, don't make any requests
+        #[clap(long)]
+        offline: bool,
+
+        /// Don't cache platform responses
+        #[clap(long)]
+        no_http_cache: bool,
+
         /// Skip tracks that have 1T_TAGGEDDATE tag
         #[clap(long)]
         skip_tagged: bool,
@@ -331,6 +641,26 @@ enum Actions {
         /// Tag on multiple platforms instead of the default fallback mode
         #[clap(long)]
         multiplatform: bool,
+
+        /// Don't write any tags, only generate a JSON report of what would be changed
+        #[clap(long)]
+        dry_run: bool,
+
+        /// Detect BPM from audio if no platform provided it
+        #[clap(long)]
+        local_bpm: bool,
+
+        /// Detect key from audio for files without key
+        #[clap(long)]
+        local_key: bool,
+
+        /// Scan loudness and write ReplayGain tags
+        #[clap(long)]
+        replay_gain: bool,
+
+        /// Where to save the dry run report (default: runs folder)
+        #[clap(long)]
+        dry_run_output: Option<PathBuf>,
     },
     /// Start Audio Features in CLI mode
     Audiofeatures {
@@ -354,6 +684,164 @@ enum Actions {
         #[clap(long)]
         no_subfolders: bool,
     },
+    /// Detect BPM from audio without any online service
+    Bpm {
+        /// Path to music file or folder
+        #[clap(short, long)]
+        path: PathBuf,
+
+        /// Lowest allowed BPM, slower tempos get doubled
+        #[clap(long, default_value = "70")]
+        min: f32,
+
+        /// Highest allowed BPM, faster tempos get halved
+        #[clap(long, default_value = "180")]
+        max: f32,
+
+        /// Don't write results with lower confidence (0.0 - 1.0)
+        #[clap(long, default_value = "0.3")]
+        min_confidence: f32,
+
+        /// Write BPM to tag
+        #[clap(long, short)]
+        write: bool,
+
+        /// Overwrite existing BPM
+        #[clap(long)]
+        overwrite: bool,
+
+        /// How many files to analyze at once
+        #[clap(long, short, default_value = "4")]
+        threads: usize,
+
+        /// Don't include subfolders
+        #[clap(long)]
+        no_subfolders: bool,
+    },
+    /// Detect musical key from audio without any online service
+    Key {
+        /// Path to music file or folder
+        #[clap(short, long)]
+        path: PathBuf,
+
+        /// Key notation: musical, sharps, flats, camelot, open-key or traktor
+        #[clap(long, default_value = "musical")]
+        notation: KeyNotation,
+
+        /// Don't write results with lower confidence (0.0 - 1.0)
+        #[clap(long, default_value = "0.5")]
+        min_confidence: f32,
+
+        /// Write key to tag
+        #[clap(long, short)]
+        write: bool,
+
+        /// Overwrite existing key
+        #[clap(long)]
+        overwrite: bool,
+
+        /// How many files to analyze at once
+        #[clap(long, short, default_value = "4")]
+        threads: usize,
+
+        /// Don't include subfolders
+        #[clap(long)]
+        no_subfolders: bool,
+    },
+    /// Scan EBU R128 loudness and write ReplayGain tags
+    ReplayGain {
+        /// Path to music file or folder
+        #[clap(short, long)]
+        path: PathBuf,
+
+        /// Target loudness in LUFS
+        #[clap(long, default_value = "-18", allow_hyphen_values = true)]
+        reference: f32,
+
+        /// Don't calculate album gain (files in the same folder are treated as album)
+        #[clap(long)]
+        no_album: bool,
+
+        /// Write gain to tag
+        #[clap(long, short)]
+        write: bool,
+
+        /// Overwrite existing gain
+        #[clap(long)]
+        overwrite: bool,
+
+        /// How many files to analyze at once
+        #[clap(long, short, default_value = "4")]
+        threads: usize,
+
+        /// Don't include subfolders
+        #[clap(long)]
+        no_subfolders: bool,
+    },
+    /// Find duplicates by audio fingerprint, ISRC or artist + title
+    Duplicates {
+        /// Path to music folder
+        #[clap(short, long)]
+        path: PathBuf,
+
+        /// Don't fingerprint audio, only use ISRC and artist + title
+        #[clap(long)]
+        no_fingerprint: bool,
+
+        /// Minimum fingerprint similarity (0.0 - 1.0)
+        #[clap(long, default_value = "0.8")]
+        similarity: f32,
+
+        /// Maximum duration difference in seconds
+        #[clap(long, default_value = "5")]
+        max_duration_difference: u64,
+
+        /// How many files to analyze at once
+        #[clap(long, short, default_value = "4")]
+        threads: usize,
+
+        /// Don't include subfolders
+        #[clap(long)]
+        no_subfolders: bool,
+
+        /// Save report, M3U if the extension is .m3u / .m3u8, otherwise JSON
+        #[clap(long, short)]
+        output: Option<PathBuf>,
+
+        /// Move all but the best copy into this folder (can be reverted with `revert`)
+        #[clap(long)]
+        quarantine: Option<PathBuf>,
+    },
+    /// Detect lossless files transcoded from lossy sources or upsampled
+    Lossless {
+        /// Path to music file or folder
+        #[clap(short, long)]
+        path: PathBuf,
+
+        /// Write verdict to tag
+        #[clap(long, short)]
+        write: bool,
+
+        /// Tag for the verdict
+        #[clap(long, default_value = LOSSLESS_TAG)]
+        tag: String,
+
+        /// Save M3U playlist of suspicious files
+        #[clap(long)]
+        playlist: Option<PathBuf>,
+
+        /// Print genuine files too
+        #[clap(long)]
+        all: bool,
+
+        /// How many files to analyze at once
+        #[clap(long, short, default_value = "4")]
+        threads: usize,
+
+        /// Don't include subfolders
+        #[clap(long)]
+        no_subfolders: bool,
+    },
     /// Query information about a URL and generate a CSV/JSON file for downloading
     QueryUrl {
         /// URL to query (YouTube, 1001tracklists, Spotify, or SoundCloud)
@@ -381,6 +869,48 @@ enum Actions {
         /// Directory where songs will be downloaded
         #[clap(short, long)]
         directory: PathBuf,
+
+        /// Amount of concurrent downloads
+        #[clap(long, default_value = "2")]
+        workers: usize,
+
+        /// How many times to retry a failed download
+        #[clap(long, default_value = "2")]
+        retries: u32,
+
+        /// Custom download command instead of yt-dlp/spotdl, {query} and {output} get replaced
+        #[clap(long)]
+        command: Option<String>,
+    },
+    /// Split a mix into tracks using a timestamped tracklist
+    SplitMix {
+        /// Path to the mix
+        #[clap(short, long)]
+        path: PathBuf,
+
+        /// Text file with the tracklist (video description, 1001Tracklists copy...)
+        #[clap(short, long)]
+        tracklist: PathBuf,
+
+        /// Write cue sheet instead of separate files
+        #[clap(long)]
+        cue: bool,
+
+        /// Also write ID3 chapters into the original MP3 (with --cue)
+        #[clap(long, requires = "cue")]
+        chapters: bool,
+
+        /// Output folder, defaults to folder named after the mix
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+
+        /// Album / cue sheet title, defaults to file name
+        #[clap(long)]
+        title: Option<String>,
+
+        /// Seconds around timestamps to search for the quietest point, 0 to disable
+        #[clap(long, default_value = "5")]
+        refine_window: u64,
     },
     /// Authorize Spotify and cache the token
     AuthorizeSpotify {
@@ -437,6 +967,64 @@ enum Actions {
         #[clap(long)]
         keep_subfolders: bool,
     },
+    /// Restore tags of all files touched by a run. Lists available runs if no ID is given
+    Revert {
+        /// ID of the run to revert
+        run_id: Option<String>,
+    },
+    /// Continue stopped or crashed Autotagger run. Lists resumable runs if no ID is given
+    Resume {
+        /// ID of the run to continue
+        run_id: Option<String>,
+    },
+    /// Manage and query the library index
+    Library {
+        /// Add folder to the index
+        #[clap(long)]
+        add_root: Option<PathBuf>,
+
+        /// Remove folder from the index
+        #[clap(long)]
+        remove_root: Option<PathBuf>,
+
+        /// Rescan all folders, only changed files are read
+        #[clap(long)]
+        scan: bool,
+
+        /// Only list files in this folder
+        #[clap(long)]
+        path: Option<PathBuf>,
+
+        /// Filter by tag, `tag=value` for exact match or `tag~value` for contains. Tag can be a field (artist) or raw tag (TPE1)
+        #[clap(long, short)]
+        filter: Vec<String>,
+
+        /// Max amount of results
+        #[clap(long)]
+        limit: Option<usize>,
+    },
+    /// Watch folders and AutoTag new files once they stop changing
+    Watch {
+        /// Folders to watch
+        #[clap(required = true)]
+        folders: Vec<PathBuf>,
+
+        /// Name of AutoTagger profile saved in the UI, last used config if not set
+        #[clap(long)]
+        profile: Option<String>,
+
+        /// Path to AutoTagger config file, overrides profile
+        #[clap(long, short)]
+        config: Option<PathBuf>,
+
+        /// Seconds a file has to stay unchanged before tagging
+        #[clap(long, default_value = "10")]
+        settle: u64,
+
+        /// Don't watch subfolders
+        #[clap(long)]
+        no_subfolders: bool,
+    },
     /// Start OneTagger server mode
     Server {
         /// Expose the internal servers (WARNING: Unsecure)
@@ -448,9 +1036,36 @@ enum Actions {
         /// Open web browser
         #[clap(long, short)]
         browser: bool,
+        /// Watch folders and AutoTag new files in the background
+        #[clap(long)]
+        watch: Vec<PathBuf>,
+        /// AutoTagger profile used for watched folders, last used config if not set
+        #[clap(long)]
+        watch_profile: Option<String>,
+    }
+}
+
+/// Stop the folder watcher and wait for it to finish
+fn stop_watcher(stop: &AtomicBool, watcher: &Mutex<Option<JoinHandle<()>>>) {
+    stop.store(true, Ordering::SeqCst);
+    if let Some(handle) = watcher.lock().unwrap().take() {
+        handle.join().ok();
     }
 }
 
+/// Create watch folder config from profile or config file
+fn watch_config(folders: &[PathBuf], profile: Option<&str>, config: Option<&PathBuf>, settle: u64, no_subfolders: bool) -> Result<WatchConfig, Error> {
+    let config = match config {
+        Some(path) => serde_json::from_reader(&File::open(path)?)?,
+        None => onetagger_autotag::watch::load_profile(profile)?
+    };
+    let folders = folders.iter().map(dunce::canonicalize).collect::<Result<Vec<_>, _>>()?;
+    let mut config = WatchConfig::new(folders, config);
+    config.settle = Duration::from_secs(settle);
+    config.recursive = !no_subfolders;
+    Ok(config)
+}
+
 /// For easily generating CLI -> config
 macro_rules! config_option {
     ($target:expr, $t:tt) => {
@@ -468,9 +1083,9 @@ impl Actions {
     pub fn get_at_config(&self) -> Result<TaggerConfig, Error> {
         match self {
             Actions::Autotagger { path, config, platforms, tags, id3v24, 
-                overwrite, threads, strictness, album_art_file, merge_genres, camelot, 
-                short_title, match_duration, max_duration_difference, match_by_id, enable_shazam, force_shazam, 
-                skip_tagged, parse_filename, filename_template, no_subfolders, only_year, multiplatform } => {
+                overwrite, threads, strictness, album_art_file, merge_genres, key_notation, 
+                short_title, match_duration, max_duration_difference, match_by_id, enable_shazam, force_shazam, acoustid, acoustid_key, shazam_positions, shazam_agreement, offline, no_http_cache, 
+                skip_tagged, parse_filename, filename_template, no_subfolders, only_year, multiplatform, dry_run, dry_run_output, local_bpm, local_key, replay_gain } => {
 
                 // Load config
                 let mut config = if let Some(config_path) = config {
@@ -502,8 +1117,39 @@ impl Actions {
                     config.tags = tags;
                 }
                 // Boolean options
-                config_option!(config, id3v24, overwrite, album_art_file, merge_genres, camelot, short_title, match_duration,
-                    match_by_id, enable_shazam, force_shazam, skip_tagged, parse_filename, only_year, multiplatform);
+                config_option!(config, id3v24, overwrite, album_art_file, merge_genres, short_title, match_duration,
+                    match_by_id, enable_shazam, force_shazam, skip_tagged, parse_filename, only_year, multiplatform, dry_run);
+                if let Some(key_notation) = key_notation {
+                    config.key_notation = *key_notation;
+                }
+                if *local_bpm {
+                    config.local_bpm.enabled = true;
+                }
+                if *local_key {
+                    config.local_key.enabled = true;
+                }
+                if *replay_gain {
+                    config.replay_gain.enabled = true;
+                }
+                if *acoustid {
+                    config.identify_backend = IdentifyBackend::AcoustId;
+                }
+                if let Some(key) = acoustid_key {
+                    config.acoustid_key = Some(key.to_string());
+                }
+                if *offline {
+                    config.http_cache.enabled = true;
+                    config.http_cache.offline = true;
+                }
+                if *no_http_cache {
+                    config.http_cache.enabled = false;
+                }
+                if let Some(positions) = shazam_positions {
+                    config.shazam.positions = positions.clone();
+                }
+                if let Some(agreement) = shazam_agreement {
+                    config.shazam.min_agreement = *agreement;
+                }
                 // Remaining options
                 if let Some(threads) = threads {
                     config.threads = *threads;
@@ -524,6 +1170,9 @@ impl Actions {
                 if *no_subfolders {
                     config.include_subfolders = false;
                 }
+                if dry_run_output.is_some() {
+                    config.dry_run = true;
+                }
                 return Ok(config);
             },
             _ => unreachable!()
@@ -0,0 +1,561 @@
+#[macro_use] extern crate log;
+
+use anyhow::Error;
+use rusqlite::{Connection, OptionalExtension, params, params_from_iter};
+use rusqlite::types::Value;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+use std::fs::Metadata;
+use std::time::UNIX_EPOCH;
+use walkdir::WalkDir;
+use onetagger_tag::{AudioFileFormat, Field, Tag, EXTENSIONS};
+use onetagger_player::AudioSources;
+use onetagger_shared::Settings;
+
+const SCHEMA: &str = "
+    PRAGMA foreign_keys = ON;
+    CREATE TABLE IF NOT EXISTS roots (
+        path TEXT PRIMARY KEY NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS files (
+        id INTEGER PRIMARY KEY,
+        path TEXT UNIQUE NOT NULL,
+        folder TEXT NOT NULL,
+        mtime INTEGER NOT NULL,
+        size INTEGER NOT NULL,
+        format TEXT NOT NULL,
+        duration INTEGER,
+        rating INTEGER,
+        year INTEGER
+    );
+    CREATE TABLE IF NOT EXISTS tags (
+        file_id INTEGER NOT NULL REFERENCES files(id) ON DELETE CASCADE,
+        tag TEXT NOT NULL,
+        field TEXT,
+        value TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS files_folder ON files(folder);
+    CREATE INDEX IF NOT EXISTS tags_file ON tags(file_id);
+    CREATE INDEX IF NOT EXISTS tags_tag ON tags(tag COLLATE NOCASE, value COLLATE NOCASE);
+    CREATE INDEX IF NOT EXISTS tags_field ON tags(field COLLATE NOCASE, value COLLATE NOCASE);
+";
+
+/// On-disk index of files and their tags under the configured roots
+pub struct Library {
+    conn: Connection,
+}
+
+impl Library {
+    /// Open the library in the settings folder
+    pub fn open_default() -> Result<Library, Error> {
+        Library::open(Library::default_path()?)
+    }
+
+    /// Path of the library database in the settings folder
+    pub fn default_path() -> Result<PathBuf, Error> {
+        Ok(Settings::get_folder()?.join("library.db"))
+    }
+
+    /// Open or create library database
+    pub fn open(path: impl AsRef<Path>) -> Result<Library, Error> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Library { conn })
+    }
+
+    /// Add folder to index, doesn't scan it
+    pub fn add_root(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        self.conn.execute("INSERT OR IGNORE INTO roots (path) VALUES (?1)", params![path_str(path)])?;
+        Ok(())
+    }
+
+    /// Remove folder and all its files from index
+    pub fn remove_root(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path_str(path);
+        self.conn.execute("DELETE FROM roots WHERE path = ?1", params![path])?;
+        self.conn.execute("DELETE FROM files WHERE folder = ?1 OR folder LIKE ?2 ESCAPE '\\'", params![path, subfolder_pattern(&path)])?;
+        Ok(())
+    }
+
+    /// Get all configured roots
+    pub fn roots(&self) -> Result<Vec<PathBuf>, Error> {
+        let mut stmt = self.conn.prepare("SELECT path FROM roots ORDER BY path")?;
+        let roots = stmt.query_map([], |r| r.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(roots.into_iter().map(PathBuf::from).collect())
+    }
+
+    /// Check if path is inside of any root
+    pub fn is_indexed(&self, path: impl AsRef<Path>) -> Result<bool, Error> {
+        Ok(self.roots()?.iter().any(|r| path.as_ref().starts_with(r)))
+    }
+
+    /// Rescan all roots
+    pub fn scan_all(&mut self) -> Result<ScanStats, Error> {
+        let mut stats = ScanStats::default();
+        for root in self.roots()? {
+            stats.add(&self.scan(&root)?);
+        }
+        Ok(stats)
+    }
+
+    /// Rescan folder, only files with changed mtime or size are read again
+    pub fn scan(&mut self, root: impl AsRef<Path>) -> Result<ScanStats, Error> {
+        self.scan_with(root, read_file)
+    }
+
+    /// Rescan folder using custom file reader
+    pub fn scan_with(&mut self, root: impl AsRef<Path>, read: impl Fn(&Path) -> Result<FileData, Error>) -> Result<ScanStats, Error> {
+        let root = path_str(&root);
+        info!("Scanning library folder: {root}");
+        let tx = self.conn.transaction()?;
+        let mut stats = ScanStats::default();
+
+        // Currently indexed files
+        let mut existing: HashMap<String, (i64, i64, i64)> = HashMap::new();
+        {
+            let mut stmt = tx.prepare("SELECT path, id, mtime, size FROM files WHERE folder = ?1 OR folder LIKE ?2 ESCAPE '\\'")?;
+            let rows = stmt.query_map(params![root, subfolder_pattern(&root)], |r| Ok((r.get(0)?, (r.get(1)?, r.get(2)?, r.get(3)?))))?;
+            for row in rows {
+                let (path, data) = row?;
+                existing.insert(path, data);
+            }
+        }
+
+        for entry in WalkDir::new(&root).into_iter().filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if !entry.file_type().is_file() || !EXTENSIONS.iter().any(|e| path.extension().unwrap_or_default().to_ascii_lowercase() == *e) {
+                continue;
+            }
+            let metadata = match entry.metadata() {
+                Ok(m) => m,
+                Err(e) => {
+                    warn!("Failed reading metadata of {}: {e}", path.display());
+                    stats.failed += 1;
+                    continue;
+                }
+            };
+            let (mtime, size) = file_stat(&metadata);
+
+            // Unchanged
+            let old = existing.remove(&path_str(path));
+            if let Some((_, old_mtime, old_size)) = old {
+                if old_mtime == mtime && old_size == size {
+                    stats.unchanged += 1;
+                    continue;
+                }
+            }
+
+            let data = match read(path) {
+                Ok(data) => data,
+                Err(e) => {
+                    warn!("Failed indexing {}: {e}", path.display());
+                    stats.failed += 1;
+                    continue;
+                }
+            };
+            let path_s = path_str(path);
+            let folder = path_str(path.parent().unwrap_or(Path::new("")));
+            let format = format!("{:?}", data.format).to_lowercase();
+            let id = match old {
+                Some((id, _, _)) => {
+                    tx.execute(
+                        "UPDATE files SET mtime = ?2, size = ?3, format = ?4, duration = ?5, rating = ?6, year = ?7 WHERE id = ?1",
+                        params![id, mtime, size, format, data.duration, data.rating, data.year]
+                    )?;
+                    tx.execute("DELETE FROM tags WHERE file_id = ?1", params![id])?;
+                    stats.updated += 1;
+                    id
+                },
+                None => {
+                    tx.execute(
+                        "INSERT INTO files (path, folder, mtime, size, format, duration, rating, year) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                        params![path_s, folder, mtime, size, format, data.duration, data.rating, data.year]
+                    )?;
+                    stats.added += 1;
+                    tx.last_insert_rowid()
+                }
+            };
+            let mut stmt = tx.prepare_cached("INSERT INTO tags (file_id, tag, field, value) VALUES (?1, ?2, ?3, ?4)")?;
+            for (tag, values) in &data.tags {
+                // Resolve field, so it can be queried in any format
+                let field = Field::all().iter()
+                    .find(|f| f.by_format(&data.format).eq_ignore_ascii_case(tag))
+                    .map(|f| format!("{f:?}"));
+                for value in values {
+                    stmt.execute(params![id, tag, field, value])?;
+                }
+            }
+        }
+
+        // Deleted files
+        for (path, (id, _, _)) in existing {
+            debug!("Removing from library: {path}");
+            tx.execute("DELETE FROM files WHERE id = ?1", params![id])?;
+            stats.removed += 1;
+        }
+
+        tx.commit()?;
+        info!("Library scan finished: {stats:?}");
+        Ok(stats)
+    }
+
+    /// Get single file
+    pub fn get(&self, path: impl AsRef<Path>) -> Result<Option<LibraryFile>, Error> {
+        let file = self.conn.query_row(
+            "SELECT id, path, mtime, size, format, duration, rating, year FROM files WHERE path = ?1",
+            params![path_str(path)],
+            LibraryFile::from_row
+        ).optional()?;
+        match file {
+            Some((id, mut file)) => {
+                file.tags = self.file_tags(id)?;
+                Ok(Some(file))
+            },
+            None => Ok(None)
+        }
+    }
+
+    /// Get file only if it wasn't modified since it was indexed, so its tags don't have to be read again
+    pub fn fresh(&self, path: impl AsRef<Path>) -> Result<Option<LibraryFile>, Error> {
+        let Ok(metadata) = std::fs::metadata(&path) else { return Ok(None) };
+        let (mtime, size) = file_stat(&metadata);
+        Ok(self.get(path)?.filter(|f| f.mtime == mtime && f.size == size))
+    }
+
+    /// Find files matching the query
+    pub fn query(&self, query: &LibraryQuery) -> Result<Vec<LibraryFile>, Error> {
+        let mut sql = "SELECT id, path, mtime, size, format, duration, rating, year FROM files WHERE 1 = 1".to_string();
+        let mut values: Vec<Value> = vec![];
+
+        if let Some(path) = &query.path {
+            let path = path_str(path);
+            match query.recursive {
+                true => {
+                    sql.push_str(" AND (folder LIKE ? ESCAPE '\\' OR folder = ?)");
+                    values.push(subfolder_pattern(&path).into());
+                    values.push(path.into());
+                },
+                false => {
+                    sql.push_str(" AND folder = ?");
+                    values.push(path.into());
+                }
+            }
+        }
+        if let Some(format) = &query.format {
+            sql.push_str(" AND format = ?");
+            values.push(format!("{format:?}").to_lowercase().into());
+        }
+        for filter in &query.filters {
+            sql.push_str(" AND EXISTS (SELECT 1 FROM tags WHERE tags.file_id = files.id AND (tags.tag = ? COLLATE NOCASE OR tags.field = ? COLLATE NOCASE) AND ");
+            values.push(filter.tag.clone().into());
+            values.push(filter.tag.clone().into());
+            match filter.mode {
+                FilterMode::Equals => {
+                    sql.push_str("tags.value = ? COLLATE NOCASE)");
+                    values.push(filter.value.clone().into());
+                },
+                FilterMode::Contains => {
+                    sql.push_str("tags.value LIKE ? ESCAPE '\\')");
+                    values.push(format!("%{}%", escape_like(&filter.value)).into());
+                }
+            }
+        }
+        sql.push_str(" ORDER BY path LIMIT ? OFFSET ?");
+        values.push((query.limit.map(|l| l as i64).unwrap_or(-1)).into());
+        values.push((query.offset as i64).into());
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let files = stmt.query_map(params_from_iter(values), LibraryFile::from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        files.into_iter().map(|(id, mut file)| {
+            file.tags = self.file_tags(id)?;
+            Ok(file)
+        }).collect()
+    }
+
+    /// Get raw tags of file
+    fn file_tags(&self, id: i64) -> Result<HashMap<String, Vec<String>>, Error> {
+        let mut stmt = self.conn.prepare_cached("SELECT tag, value FROM tags WHERE file_id = ?1 ORDER BY rowid")?;
+        let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+        for row in stmt.query_map(params![id], |r| Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?)))? {
+            let (tag, value) = row?;
+            tags.entry(tag).or_default().push(value);
+        }
+        Ok(tags)
+    }
+}
+
+/// Data read from file for indexing
+#[derive(Debug, Clone)]
+pub struct FileData {
+    pub format: AudioFileFormat,
+    /// Duration in ms
+    pub duration: Option<i64>,
+    pub rating: Option<u8>,
+    pub year: Option<i32>,
+    pub tags: HashMap<String, Vec<String>>,
+}
+
+/// Read tags and duration of file
+pub fn read_file(path: &Path) -> Result<FileData, Error> {
+    let tag = Tag::load_file(path, false)?;
+    let duration = match AudioSources::from_path(path) {
+        Ok(source) => Some(source.duration() as i64),
+        Err(e) => {
+            warn!("Failed getting duration of {}: {e}", path.display());
+            None
+        }
+    };
+    Ok(FileData {
+        format: tag.format(),
+        duration,
+        rating: tag.tag().get_rating(),
+        year: tag.tag().get_date().map(|d| d.year),
+        tags: tag.tag().all_tags()
+    })
+}
+
+/// Indexed file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryFile {
+    pub path: PathBuf,
+    /// Modification time in ms
+    pub mtime: i64,
+    pub size: i64,
+    pub format: String,
+    /// Duration in ms
+    pub duration: Option<i64>,
+    pub rating: Option<u8>,
+    pub year: Option<i32>,
+    /// Raw tags as returned by `all_tags()`
+    pub tags: HashMap<String, Vec<String>>,
+}
+
+impl LibraryFile {
+    fn from_row(row: &rusqlite::Row) -> Result<(i64, LibraryFile), rusqlite::Error> {
+        Ok((row.get(0)?, LibraryFile {
+            path: PathBuf::from(row.get::<_, String>(1)?),
+            mtime: row.get(2)?,
+            size: row.get(3)?,
+            format: row.get(4)?,
+            duration: row.get(5)?,
+            rating: row.get(6)?,
+            year: row.get(7)?,
+            tags: HashMap::new(),
+        }))
+    }
+}
+
+/// Query of indexed files, all filters must match
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryQuery {
+    /// Only files in this folder
+    pub path: Option<PathBuf>,
+    /// Include subfolders of `path`
+    #[serde(default)]
+    pub recursive: bool,
+    #[serde(default)]
+    pub filters: Vec<TagFilter>,
+    pub format: Option<AudioFileFormat>,
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub offset: usize,
+}
+
+/// Filter by tag value, case insensitive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagFilter {
+    /// Raw tag name (`TPE1`, `ARTIST`) or field name (`artist`, `bpm`)
+    pub tag: String,
+    pub value: String,
+    #[serde(default)]
+    pub mode: FilterMode,
+}
+
+impl TagFilter {
+    /// Parse `tag=value` (equals) or `tag~value` (contains)
+    pub fn parse(input: &str) -> Option<TagFilter> {
+        let i = input.find(['=', '~'])?;
+        let mode = match &input[i..i + 1] {
+            "~" => FilterMode::Contains,
+            _ => FilterMode::Equals
+        };
+        Some(TagFilter { tag: input[..i].trim().to_string(), value: input[i + 1..].trim().to_string(), mode })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FilterMode {
+    #[default]
+    Equals,
+    Contains,
+}
+
+/// Result of library scan
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanStats {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+    pub failed: usize,
+}
+
+impl ScanStats {
+    fn add(&mut self, other: &ScanStats) {
+        self.added += other.added;
+        self.updated += other.updated;
+        self.removed += other.removed;
+        self.unchanged += other.unchanged;
+        self.failed += other.failed;
+    }
+}
+
+/// Modification time in ms and size
+fn file_stat(metadata: &Metadata) -> (i64, i64) {
+    let mtime = metadata.modified().ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0);
+    (mtime, metadata.len() as i64)
+}
+
+/// Paths are stored as strings
+fn path_str(path: impl AsRef<Path>) -> String {
+    path.as_ref().to_string_lossy().trim_end_matches(MAIN_SEPARATOR).to_string()
+}
+
+/// LIKE pattern for all subfolders of folder
+fn subfolder_pattern(folder: &str) -> String {
+    format!("{}{}%", escape_like(folder), MAIN_SEPARATOR)
+}
+
+/// Escape LIKE wildcards
+fn escape_like(input: &str) -> String {
+    input.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Fake reader, file content is `TAG=value` lines
+    fn fake_read(path: &Path) -> Result<FileData, Error> {
+        let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+        for line in fs::read_to_string(path)?.lines() {
+            let (tag, value) = line.split_once('=').ok_or(anyhow::anyhow!("Invalid file"))?;
+            tags.entry(tag.to_string()).or_default().push(value.to_string());
+        }
+        Ok(FileData { format: AudioFileFormat::MP3, duration: Some(1000), rating: Some(3), year: None, tags })
+    }
+
+    fn setup() -> (tempfile::TempDir, Library) {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("music/sub_folder")).unwrap();
+        fs::write(dir.path().join("music/a.mp3"), "TPE1=Artist A\nTIT2=First Song\nTCON=House\nTCON=Deep House").unwrap();
+        fs::write(dir.path().join("music/b.mp3"), "TPE1=Artist B\nTIT2=Second Song").unwrap();
+        fs::write(dir.path().join("music/sub_folder/c.mp3"), "TPE1=artist a\nTIT2=100% Third").unwrap();
+        fs::write(dir.path().join("music/notes.txt"), "not audio").unwrap();
+        let library = Library::open(dir.path().join("library.db")).unwrap();
+        library.add_root(dir.path().join("music")).unwrap();
+        (dir, library)
+    }
+
+    fn filter(tag: &str, value: &str, mode: FilterMode) -> LibraryQuery {
+        LibraryQuery { filters: vec![TagFilter { tag: tag.to_string(), value: value.to_string(), mode }], ..Default::default() }
+    }
+
+    #[test]
+    fn test_incremental_scan() {
+        let (dir, mut library) = setup();
+        let root = dir.path().join("music");
+        let stats = library.scan_with(&root, fake_read).unwrap();
+        assert_eq!(stats, ScanStats { added: 3, ..Default::default() });
+
+        // Nothing changed
+        let stats = library.scan_with(&root, |_| panic!("Unchanged file was read")).unwrap();
+        assert_eq!(stats, ScanStats { unchanged: 3, ..Default::default() });
+
+        // Modify, delete, add, break
+        fs::write(root.join("a.mp3"), "TPE1=Artist A\nTIT2=First Song (Remix)").unwrap();
+        fs::remove_file(root.join("b.mp3")).unwrap();
+        fs::write(root.join("d.mp3"), "TPE1=Artist D\nTIT2=Fourth").unwrap();
+        fs::write(root.join("e.mp3"), "broken").unwrap();
+        let stats = library.scan_with(&root, fake_read).unwrap();
+        assert_eq!(stats, ScanStats { added: 1, updated: 1, removed: 1, unchanged: 1, failed: 1 });
+
+        let file = library.get(root.join("a.mp3")).unwrap().unwrap();
+        assert_eq!(file.tags["TIT2"], vec!["First Song (Remix)"]);
+        assert!(!file.tags.contains_key("TCON"));
+        assert_eq!(file.rating, Some(3));
+        assert!(library.get(root.join("b.mp3")).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_fresh() {
+        let (dir, mut library) = setup();
+        let root = dir.path().join("music");
+        library.scan_with(&root, fake_read).unwrap();
+        assert!(library.fresh(root.join("a.mp3")).unwrap().is_some());
+
+        // Modified or deleted since the scan
+        fs::write(root.join("a.mp3"), "TPE1=Artist A\nTIT2=Changed title").unwrap();
+        assert!(library.fresh(root.join("a.mp3")).unwrap().is_none());
+        fs::remove_file(root.join("b.mp3")).unwrap();
+        assert!(library.fresh(root.join("b.mp3")).unwrap().is_none());
+        assert!(library.get(root.join("b.mp3")).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_query() {
+        let (dir, mut library) = setup();
+        let root = dir.path().join("music");
+        library.scan_with(&root, fake_read).unwrap();
+
+        // Field name resolves to ID3 frame, case insensitive
+        let files = library.query(&filter("artist", "ARTIST A", FilterMode::Equals)).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].tags["TCON"], vec!["House", "Deep House"]);
+
+        // Multiple values, raw tag name
+        assert_eq!(library.query(&filter("TCON", "deep house", FilterMode::Equals)).unwrap().len(), 1);
+        // LIKE wildcards are escaped
+        assert_eq!(library.query(&filter("title", "100%", FilterMode::Contains)).unwrap().len(), 1);
+        assert_eq!(library.query(&filter("title", "song", FilterMode::Contains)).unwrap().len(), 2);
+
+        // Folder
+        let mut query = filter("artist", "artist a", FilterMode::Equals);
+        query.path = Some(root.clone());
+        assert_eq!(library.query(&query).unwrap().len(), 1);
+        query.recursive = true;
+        assert_eq!(library.query(&query).unwrap().len(), 2);
+
+        // Pagination
+        let all = LibraryQuery { limit: Some(2), offset: 1, ..Default::default() };
+        let files = library.query(&all).unwrap();
+        assert_eq!(files.len(), 2);
+        assert!(files[0].path.ends_with("b.mp3"));
+
+        // Removing root removes files
+        library.remove_root(&root).unwrap();
+        assert!(library.query(&LibraryQuery::default()).unwrap().is_empty());
+        assert!(library.roots().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        let f = TagFilter::parse("artist=Daft Punk").unwrap();
+        assert_eq!((f.tag.as_str(), f.value.as_str(), f.mode), ("artist", "Daft Punk", FilterMode::Equals));
+        let f = TagFilter::parse("genre~house").unwrap();
+        assert_eq!(f.mode, FilterMode::Contains);
+        assert!(TagFilter::parse("invalid").is_none());
+    }
+}
@@ -3,6 +3,8 @@ use std::time::Duration;
 use chrono::{NaiveDate, Datelike};
 use onetagger_tagger::{supported_tags, AudioFileInfo, AutotaggerSource, AutotaggerSourceBuilder, MatchingUtils, PlatformCustomOptionValue, PlatformCustomOptions, PlatformInfo, TaggerConfig, Track, TrackMatch};
 use reqwest::blocking::Client;
+use onetagger_tagger::ratelimit;
+use crate::http::RequestBuilderCacheExt;
 use scraper::{Html, Selector};
 use serde_json::{json, Value};
 use serde::{Serialize, Deserialize};
@@ -32,7 +34,7 @@ impl Bandcamp {
                 "search_filter": "t",
                 "search_text": query
             }))
-            .send()?;
+            .send_cached("bandcamp")?;
         // No rate limit
         if response.status().is_success() {
             let r: Value = response.json()?;
@@ -41,17 +43,17 @@ impl Bandcamp {
         }
         // Rate limit
         warn!("Bandcamp rate limit... Waiting for few seconds");
-        std::thread::sleep(Duration::from_secs(3));
+        ratelimit::pause("bandcamp", Duration::from_secs(3));
         self.search_tracks(query)
     }
 
     /// Get data from track page
     fn track_page(&self, url: &str) -> Result<BandcampTrack, Error> {
         // Fetch with rate limit
-        let response = self.client.get(url).send()?;
+        let response = self.client.get(url).send_cached("bandcamp")?;
         if response.status().is_client_error() {
             warn!("Bandcamp track page rate limit... Waiting for few seconds");
-            std::thread::sleep(Duration::from_secs(3));
+            ratelimit::pause("bandcamp", Duration::from_secs(3));
             return self.track_page(url);
         }
         let response = response.text()?;
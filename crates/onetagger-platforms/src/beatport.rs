@@ -3,6 +3,7 @@ use anyhow::Error;
 use std::time::Duration;
 use reqwest::StatusCode;
 use reqwest::blocking::Client;
+use crate::http::{CachedResponse, RequestBuilderCacheExt};
 use chrono::NaiveDate;
 use scraper::{Html, Selector};
 use serde::de::DeserializeOwned;
@@ -41,7 +42,7 @@ impl Beatport {
                 ("page", &page.to_string()),
                 ("per-page", &results_per_page.to_string())
             ])
-            .send()?
+            .send_cached("beatport")?
             .text()?;
         
         // Parse JSON
@@ -78,12 +79,18 @@ impl Beatport {
         Ok(t.access_token)
     }
 
+    /// GET request to API, token is only fetched if the response isn't cached
+    fn api_get(&self, url: &str) -> Result<CachedResponse, Error> {
+        let mut request = self.client.get(url);
+        if !request.is_cached("beatport") {
+            request = request.bearer_auth(self.update_token()?);
+        }
+        request.send_cached("beatport")
+    }
+
     /// Fetch track using API
     pub fn track(&self, id: i64) -> Result<Option<BeatportTrack>, Error> {
-        let token = self.update_token()?;
-        let response = self.client.get(&format!("https://api.beatport.com/v4/catalog/tracks/{}", id))
-            .bearer_auth(token)
-            .send()?;
+        let response = self.api_get(&format!("https://api.beatport.com/v4/catalog/tracks/{}", id))?;
 
         // Restricted / deleted track
         if response.status() == StatusCode::FORBIDDEN {
@@ -95,19 +102,13 @@ impl Beatport {
 
     /// Fetch release using API
     pub fn release(&self, id: i64) -> Result<BeatportRelease, Error> {
-        let token = self.update_token()?;
-        let response = self.client.get(&format!("https://api.beatport.com/v4/catalog/releases/{}", id))
-            .bearer_auth(token)
-            .send()?.json()?;
+        let response = self.api_get(&format!("https://api.beatport.com/v4/catalog/releases/{}", id))?.json()?;
         Ok(response)
     }
 
     /// Get tracks from release
     pub fn release_tracks(&self, id: i64) -> Result<Vec<BeatportTrack>, Error> {
-        let token = self.update_token()?;
-        let response: BeatportPagination<BeatportTrack> = self.client.get(&format!("https://api.beatport.com/v4/catalog/releases/{}/tracks?per_page=200", id))
-            .bearer_auth(token)
-            .send()?.json()?;
+        let response: BeatportPagination<BeatportTrack> = self.api_get(&format!("https://api.beatport.com/v4/catalog/releases/{}/tracks?per_page=200", id))?.json()?;
         Ok(response.results)
     }
 
@@ -3,6 +3,7 @@ use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use chrono::NaiveDate;
 use reqwest::blocking::Client;
+use crate::http::RequestBuilderCacheExt;
 use scraper::{Html, Selector};
 use serde_json::Value;
 use serde::{Serialize, Deserialize};
@@ -28,16 +29,18 @@ impl Beatsource {
 
     /// Search for tracks
     pub fn search(&self, query: &str) -> Result<BeatsourceSearchResponse, Error> {
-        let res: BeatsourceSearchResponse = self.client.get("https://api.beatsource.com/v4/catalog/search")
+        let mut request = self.client.get("https://api.beatsource.com/v4/catalog/search")
             .query(&[
                 ("pubper_page", "100"),
                 ("page", "1"),
                 ("type", "tracks"),
                 ("q", query)
-            ])
-            .bearer_auth(self.token_manager.token()?)
-            .send()?
-            .json()?;
+            ]);
+        // Token is only needed for network requests
+        if !request.is_cached("beatsource") {
+            request = request.bearer_auth(self.token_manager.token()?);
+        }
+        let res: BeatsourceSearchResponse = request.send_cached("beatsource")?.json()?;
         Ok(res)
     }
 }
@@ -1,10 +1,10 @@
 use anyhow::Error;
-use std::time::Duration;
 use chrono::{DateTime, Utc};
+use onetagger_tagger::ratelimit;
 use onetagger_tagger::{AutotaggerSourceBuilder, TaggerConfig, AutotaggerSource, PlatformInfo, PlatformCustomOptions, PlatformCustomOptionValue, AudioFileInfo, Track, MatchingUtils, supported_tags, TrackMatch};
 use regex::Regex;
-use reqwest::StatusCode;
 use reqwest::blocking::Client;
+use crate::http::RequestBuilderCacheExt;
 use reqwest::header::{HeaderMap, HeaderValue};
 use serde::de::DeserializeOwned;
 use serde::{Serialize, Deserialize};
@@ -63,16 +63,7 @@ impl BPMSupreme {
     fn get<T: DeserializeOwned>(&self, url: &str, query: &[(&str, &str)]) -> Result<T, Error> {
         let res = self.client.get(url)
             .query(query)
-            .send()?;
-
-        // Rate limit
-        if res.status() == StatusCode::TOO_MANY_REQUESTS {
-            let delay = res.headers().get("retry-after").map(|h| h.to_str().unwrap().parse().ok()).flatten().unwrap_or(3);
-            warn!("BPM Supreme rate limited, waiting for: {delay}s");
-            std::thread::sleep(Duration::from_secs(delay));
-            return self.get(url, query);
-        }
-
+            .send_cached("bpmsupreme")?;
         Ok(res.error_for_status()?.json()?)
     }
 
@@ -214,6 +205,8 @@ pub struct BPMSupremeBuilder {
 
 impl AutotaggerSourceBuilder for BPMSupremeBuilder {
     fn new() -> Self {
+        // Requests per minute
+        ratelimit::set_limit("bpmsupreme", Some(60));
         BPMSupremeBuilder { library: BPMMusicLibrary::Supreme }
     }
 
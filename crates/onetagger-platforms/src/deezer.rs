@@ -4,6 +4,8 @@ use std::time::Duration;
 use chrono::NaiveDate;
 use onetagger_tagger::{Track, AutotaggerSourceBuilder, PlatformInfo, TaggerConfig, AutotaggerSource, PlatformCustomOptions, PlatformCustomOptionValue, AudioFileInfo, MatchingUtils, TrackNumber, supported_tags, TrackMatch};
 use reqwest::blocking::Client;
+use onetagger_tagger::ratelimit;
+use crate::http::RequestBuilderCacheExt;
 use serde::de::DeserializeOwned;
 use serde::{Serialize, Deserialize};
 
@@ -33,12 +35,14 @@ impl Deezer {
 
     /// GET with rate limit wrap
     fn get<D: DeserializeOwned>(&self, path: &str, query: &[(&str, &str)]) -> Result<D, Error> {
-        let r: DeezerResponse<D> = self.client.get(format!("https://api.deezer.com{path}"))
+        let response = self.client.get(format!("https://api.deezer.com{path}"))
             .query(query)
-            .send()?.json()?;
+            .send_cached("deezer")?;
+        let r: DeezerResponse<D> = response.json()?;
         if r.error_code() == Some(RATE_LIMIT_CODE) {
+            response.invalidate();
             warn!("Deezer Rate Limit, sleeping for 3s...");
-            std::thread::sleep(Duration::from_secs(3));
+            ratelimit::pause("deezer", Duration::from_secs(3));
             return self.get(path, query);
         }
         Ok(r.into_result()?)
@@ -1,23 +1,21 @@
 use anyhow::Error;
 use std::collections::HashMap;
-use std::thread::sleep;
 use std::time::Duration;
 use regex::Regex;
 use chrono::NaiveDate;
 use reqwest::StatusCode;
-use reqwest::blocking::{Client, Response};
+use reqwest::blocking::Client;
+use crate::http::{CachedResponse, RequestBuilderCacheExt, HTTP_CACHE};
 use serde_json::Value;
 use serde::{Serialize, Deserialize};
 use onetagger_tag::FrameName;
+use onetagger_tagger::ratelimit;
 use onetagger_tagger::{Track, AutotaggerSource, TaggerConfig, AudioFileInfo, MatchingUtils, TrackNumber, 
     AutotaggerSourceBuilder, PlatformInfo, PlatformCustomOptions, PlatformCustomOptionValue, supported_tags, SupportedTag, TrackMatch};
 
 pub struct Discogs {
     client: Client,
     token: Option<String>,
-    // Requests per minute
-    pub rate_limit: i16,
-    last_request: u128,
     // Cache of ID:Value
     release_cache: HashMap<i64, ReleaseMaster>
 }
@@ -32,26 +30,25 @@ impl Discogs {
         Discogs {
             client,
             token: None,
-            rate_limit: 25, 
-            last_request: 0,
             release_cache: HashMap::new()
         }
     }
 
-    /// Set rate limit, -1 for no rate limit
-    pub fn set_rate_limit(&mut self, rate_limit: i16) {
-        self.rate_limit = rate_limit;
-    }
-
-    // Set authorization token and update rate limit
+    // Set authorization token
     pub fn set_auth_token(&mut self, token: &str) {
         self.token = Some(token.to_string());
-        self.rate_limit = 60;
     }
 
     // Check if token is valid
     pub fn validate_token(&mut self) -> bool {
-        match self.get("https://api.discogs.com/database/search", vec![("q", "test")]) {
+        if HTTP_CACHE.offline() {
+            return true;
+        }
+        // Not cached, the token might have changed
+        let request = self.client.get("https://api.discogs.com/database/search")
+            .query(&[("q", "test")])
+            .header("Authorization", format!("Discogs token={}", self.token.as_deref().unwrap_or_default()));
+        match request.send_limited("discogs") {
             Ok(res) => if res.status() == StatusCode::OK { true } else { 
                 error!("Failed validating Discogs token: {}", res.status());
                 debug!("{:?}", res.text());
@@ -64,38 +61,26 @@ impl Discogs {
         }
     }
 
-    // Get request wrapper with rate limit
-    fn get(&mut self, url: &str, query: Vec<(&str, &str)>) -> Result<Response, Error> {
+    // Get request wrapper, rate limited by the middleware
+    fn get(&mut self, url: &str, query: Vec<(&str, &str)>) -> Result<CachedResponse, Error> {
         debug!("Discogs GET {}", url);
-        // Rate limit
-        if self.last_request > 0 && self.rate_limit != -1 {
-            let diff = timestamp!() - self.last_request;
-            let req_ms = 1000_f64 / (self.rate_limit as f64 / 60_f64);
-            let wait = diff as f64 - req_ms;
-            if wait < 0_f64 {
-                debug!("Discogs rate limit delay: {}", -wait);
-                sleep(Duration::from_millis(-wait as u64));
-            }
-        }
-        // Create request
         let mut request = self.client.get(url).query(&query);
         if self.token.is_some() {
             request = request.header("Authorization", format!("Discogs token={}", self.token.as_ref().unwrap()));
         }
-        let response = request.send()?;
+        let response = request.send_cached("discogs")?;
+        if response.is_cached() {
+            return Ok(response);
+        }
         
-        // Rate limit
+        // Out of requests in the current window, block all threads
         let rate_limit_remaining = response.headers().get("X-Discogs-Ratelimit-Remaining")
             .map(|v| v.to_str().unwrap().parse::<i32>().ok()).flatten().unwrap_or(100);
         debug!("Discogs rate limit remaining: {}", rate_limit_remaining);
         if rate_limit_remaining < 1 {
             warn!("Discogs rate limit hit! Waiting 10s...");
-            sleep(Duration::from_secs(10));
-            return self.get(url, query);
+            ratelimit::pause("discogs", Duration::from_secs(10));
         }
-
-        // Save last reqeust time for rate limitting
-        self.last_request = timestamp!();
         Ok(response)
     }
 
@@ -287,7 +272,17 @@ pub struct Artist {
 pub struct ExtraArtist {
     pub name: String,
     pub id: i64,
-    pub role: String
+    pub role: String,
+    /// Track positions the credit applies to, empty = all
+    #[serde(default)]
+    pub tracks: String
+}
+
+impl ExtraArtist {
+    /// Has any of the roles, eg. `Written-By, Producer [Co-producer]`
+    pub fn has_role(&self, roles: &[&str]) -> bool {
+        self.role.split(',').any(|r| roles.contains(&r.split('[').next().unwrap_or_default().trim()))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -295,6 +290,8 @@ pub struct DiscogsTrack {
     pub position: String,
     pub title: String,
     pub artists: Option<Vec<ExtraArtist>>,
+    #[serde(rename = "extraartists")]
+    pub extra_artists: Option<Vec<ExtraArtist>>,
     pub duration: String
 }
 
@@ -305,6 +302,12 @@ pub struct Label {
     pub catno: Option<String>
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Company {
+    pub name: String,
+    pub entity_type_name: String
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Image {
     pub height: i64,
@@ -341,7 +344,8 @@ pub struct ReleaseMaster {
     pub tracks: Vec<DiscogsTrack>,
     pub released: Option<String>,
     pub main_release: Option<i64>,
-    pub formats: Option<Vec<ReleaseFormat>>
+    pub formats: Option<Vec<ReleaseFormat>>,
+    pub companies: Option<Vec<Company>>
 }
 
 impl ReleaseMaster {
@@ -351,6 +355,35 @@ impl ReleaseMaster {
         re.replace(input, "").to_string()
     }
 
+    /// Does the release credit (`tracks` field) apply to track
+    fn credit_applies(&self, tracks: &str, track_index: usize) -> bool {
+        if tracks.trim().is_empty() {
+            return true;
+        }
+        let index = |position: &str| self.tracks.iter().position(|t| t.position == position.trim());
+        tracks.split(',').any(|part| match part.split_once(" to ") {
+            Some((from, to)) => match (index(from), index(to)) {
+                (Some(from), Some(to)) => (from..=to).contains(&track_index),
+                _ => false
+            },
+            None => index(part) == Some(track_index)
+        })
+    }
+
+    /// Get names of track and release credits with role
+    pub fn credits(&self, track_index: usize, roles: &[&str]) -> Vec<String> {
+        let mut out = vec![];
+        let track = self.tracks[track_index].extra_artists.iter().flatten();
+        let release = self.extra_artists.iter().flatten().filter(|a| self.credit_applies(&a.tracks, track_index));
+        for artist in track.chain(release).filter(|a| a.has_role(roles)) {
+            let name = ReleaseMaster::clean_artist(&artist.name);
+            if !out.contains(&name) {
+                out.push(name);
+            }
+        }
+        out
+    }
+
     pub fn get_track(&self, track_index: usize, discogs_config: &DiscogsConfig) -> Track {
         // Parse release date
         let release_date = match &self.released {
@@ -438,6 +471,14 @@ impl ReleaseMaster {
             track_total: Some(self.tracks.len() as u16),
             other,
             thumbnail: self.images.as_ref().unwrap_or(&Vec::new()).iter().min_by(|a, b| a.width.cmp(&b.width)).map(|i| i.url.to_string()),
+            composers: self.credits(track_index, &["Composed By", "Written-By", "Music By", "Songwriter"]),
+            lyricists: self.credits(track_index, &["Lyrics By", "Words By"]),
+            conductors: self.credits(track_index, &["Conductor"]),
+            producers: self.credits(track_index, &["Producer"]),
+            copyright: self.companies.iter().flatten()
+                .find(|c| c.entity_type_name == "Copyright (c)")
+                .map(|c| ReleaseMaster::clean_artist(&c.name)),
+            compilation: Some(self.formats.iter().flatten().any(|f| f.descriptions.iter().flatten().any(|d| d == "Compilation"))),
             ..Default::default()
         }
     }
@@ -453,15 +494,15 @@ impl AutotaggerSourceBuilder for DiscogsBuilder {
 
     fn get_source(&mut self, config: &TaggerConfig) -> Result<Box<dyn AutotaggerSource>, Error> {
         let config: DiscogsConfig = config.get_custom("discogs")?;
+        // Authorized requests per minute, -1 for no rate limit. Unchanged limit keeps the bucket.
+        let rate_limit = config.rate_limit.unwrap_or(60);
+        ratelimit::set_limit("discogs", (rate_limit > 0).then_some(rate_limit as u32));
         let mut discogs = Discogs::new();
         // Auth
         discogs.set_auth_token(&config.token);
         if !discogs.validate_token() {
             return Err(anyhow!("Invalid Discogs token!"));
         }
-        if let Some(rl) = config.rate_limit {
-            discogs.set_rate_limit(rl as i16);
-        }
         Ok(Box::new(discogs))
     }
 
@@ -474,7 +515,7 @@ impl AutotaggerSourceBuilder for DiscogsBuilder {
             max_threads: 1,
             version: "1.0.0".to_string(),
             requires_auth: true,
-            supported_tags: supported_tags!(Title, Artist, AlbumArtist, Album, Genre, Style, AlbumArt, URL, Label, ReleaseDate, CatalogNumber, ReleaseId, Duration, TrackNumber, DiscNumber, TrackTotal, OtherTags),
+            supported_tags: supported_tags!(Title, Artist, AlbumArtist, Album, Genre, Style, AlbumArt, URL, Label, ReleaseDate, CatalogNumber, ReleaseId, Duration, TrackNumber, DiscNumber, TrackTotal, OtherTags, Composer, Lyricist, Conductor, Producer, Copyright, Compilation),
             custom_options: PlatformCustomOptions::new()
                 // Discogs token
                 .add_tooltip("token", "Token", 
@@ -497,3 +538,32 @@ pub struct DiscogsConfig {
     pub track_number_int: bool,
     pub rate_limit: Option<i32>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_credits() {
+        let release: ReleaseMaster = serde_json::from_value(serde_json::json!({
+            "id": 1, "genres": [], "artists": [], "uri": "", "title": "",
+            "extraartists": [
+                { "name": "Composer (2)", "id": 1, "role": "Written-By, Producer [Co-producer]", "tracks": "" },
+                { "name": "Lyricist", "id": 2, "role": "Lyrics By", "tracks": "A2 to B1" },
+                { "name": "Conductor", "id": 3, "role": "Conductor", "tracks": "A1, B1" }
+            ],
+            "tracklist": [
+                { "position": "A1", "title": "", "duration": "",
+                    "extraartists": [{ "name": "Track Composer", "id": 4, "role": "Composed By", "tracks": "" }] },
+                { "position": "A2", "title": "", "duration": "" },
+                { "position": "B1", "title": "", "duration": "" }
+            ]
+        })).unwrap();
+        assert_eq!(release.credits(0, &["Composed By", "Written-By"]), vec!["Track Composer", "Composer"]);
+        assert_eq!(release.credits(0, &["Producer"]), vec!["Composer"]);
+        assert!(release.credits(0, &["Lyrics By"]).is_empty());
+        assert_eq!(release.credits(2, &["Lyrics By"]), vec!["Lyricist"]);
+        assert_eq!(release.credits(1, &["Conductor"]), Vec::<String>::new());
+        assert_eq!(release.credits(2, &["Conductor"]), vec!["Conductor"]);
+    }
+}
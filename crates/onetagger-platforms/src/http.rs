@@ -0,0 +1,446 @@
+use anyhow::Error;
+use std::fs::File;
+use std::hash::Hasher;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+use reqwest::StatusCode;
+use reqwest::blocking::{Client, Request, RequestBuilder, Response};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+use fnv::FnvHasher;
+use onetagger_shared::Settings;
+use onetagger_tagger::HttpCacheConfig;
+use onetagger_tagger::ratelimit;
+
+lazy_static::lazy_static! {
+    /// Response cache shared by all platforms
+    pub static ref HTTP_CACHE: HttpCache = HttpCache::new();
+}
+
+/// On-disk cache of successful responses, stored in `http_cache/<platform>/<hash>`.
+/// Key is method + URL + body, headers (auth tokens) are ignored.
+pub struct HttpCache {
+    config: RwLock<HttpCacheConfig>,
+    /// Total size in bytes, calculated on first write
+    size: Mutex<Option<u64>>,
+    /// Cache folder, `http_cache` in settings folder if None
+    folder: Option<PathBuf>,
+}
+
+impl HttpCache {
+    fn new() -> HttpCache {
+        HttpCache {
+            config: RwLock::new(HttpCacheConfig::default()),
+            size: Mutex::new(None),
+            folder: None
+        }
+    }
+
+    /// Update config (before tagging)
+    pub fn configure(&self, config: &HttpCacheConfig) {
+        *self.config.write().unwrap() = config.clone();
+    }
+
+    /// Only cached responses can be used
+    pub fn offline(&self) -> bool {
+        let config = self.config.read().unwrap();
+        config.enabled && config.offline
+    }
+
+    /// Get cache folder
+    fn folder(&self) -> Result<PathBuf, Error> {
+        match &self.folder {
+            Some(folder) => Ok(folder.to_owned()),
+            None => Ok(Settings::get_folder()?.join("http_cache"))
+        }
+    }
+
+    /// Delete all cached responses
+    pub fn clear(&self) -> Result<(), Error> {
+        let folder = self.folder()?;
+        if folder.exists() {
+            std::fs::remove_dir_all(folder)?;
+        }
+        *self.size.lock().unwrap() = Some(0);
+        Ok(())
+    }
+
+    /// Path of cached response, None if disabled
+    fn path(&self, platform: &str, request: &Request) -> Option<PathBuf> {
+        if !self.config.read().unwrap().enabled {
+            return None;
+        }
+        // FNV of raw bytes, so the key stays the same across builds and platforms
+        let mut hasher = FnvHasher::default();
+        hasher.write(request.method().as_str().as_bytes());
+        hasher.write(&[0]);
+        hasher.write(request.url().as_str().as_bytes());
+        hasher.write(&[0]);
+        hasher.write(request.body().and_then(|b| b.as_bytes()).unwrap_or_default());
+        Some(self.folder().ok()?.join(platform).join(format!("{:016x}", hasher.finish())))
+    }
+
+    /// Load response if cached and not expired
+    pub fn get(&self, platform: &str, request: &Request) -> Option<CachedResponse> {
+        let path = self.path(platform, request)?;
+        let ttl = self.config.read().unwrap().ttl(platform);
+        let age = path.metadata().ok()?.modified().ok()?.elapsed().unwrap_or_default();
+        if age > Duration::from_secs(ttl * 3600) {
+            return None;
+        }
+        match CachedResponse::load(&path) {
+            Ok(response) => Some(response),
+            Err(e) => {
+                warn!("Invalid cached response {path:?}: {e}");
+                std::fs::remove_file(&path).ok();
+                None
+            }
+        }
+    }
+
+    /// Save response and remove the oldest entries if over size limit
+    fn store(&self, path: &PathBuf, response: &CachedResponse) -> Result<(), Error> {
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        let max_size = self.config.read().unwrap().max_size * 1024 * 1024;
+        let mut size = self.size.lock().unwrap();
+        // Calculate before writing, so the new file isn't counted twice
+        let size = size.get_or_insert_with(|| self.entries().iter().map(|e| e.1).sum());
+        let old_len = path.metadata().map(|m| m.len()).unwrap_or(0);
+        let len = response.save(path)?;
+        *size = size.saturating_sub(old_len) + len;
+        if *size > max_size {
+            *size = prune_oldest(self.entries(), *size, max_size);
+            debug!("HTTP cache pruned to {} bytes", *size);
+        }
+        Ok(())
+    }
+
+    /// Load the response from cache or send the request and cache it if successful
+    fn send(&self, platform: &str, builder: RequestBuilder) -> Result<CachedResponse, Error> {
+        let (client, request) = builder.build_split();
+        let request = request?;
+        if let Some(response) = self.get(platform, &request) {
+            debug!("HTTP cache hit: {}", request.url());
+            return Ok(response);
+        }
+        if self.offline() {
+            bail!("Offline mode, response not cached: {}", request.url());
+        }
+        let path = self.path(platform, &request);
+        let response = CachedResponse::from_response(execute_limited(platform, &client, request)?, path.clone())?;
+        if let (Some(path), true) = (path, response.status.is_success()) {
+            if let Err(e) = self.store(&path, &response) {
+                warn!("Failed caching response: {e}");
+            }
+        }
+        Ok(response)
+    }
+
+    /// All cached files (path, size, modified)
+    fn entries(&self) -> Vec<(PathBuf, u64, SystemTime)> {
+        let Ok(folder) = self.folder() else { return vec![] };
+        std::fs::read_dir(folder).into_iter().flatten().flatten()
+            .filter_map(|platform| std::fs::read_dir(platform.path()).ok())
+            .flatten()
+            .flatten()
+            .filter_map(|e| {
+                let meta = e.metadata().ok()?;
+                Some((e.path(), meta.len(), meta.modified().ok()?))
+            })
+            .collect()
+    }
+}
+
+/// Remove the oldest of cached files (path, size, modified) until the total size is under `max_size`, returns the new size.
+/// Goes down to 80%, so it doesn't run on every write.
+pub fn prune_oldest(mut entries: Vec<(PathBuf, u64, SystemTime)>, mut size: u64, max_size: u64) -> u64 {
+    entries.sort_by_key(|e| e.2);
+    for (path, len, _) in entries {
+        if size <= max_size / 5 * 4 {
+            break;
+        }
+        if std::fs::remove_file(&path).is_ok() {
+            size = size.saturating_sub(len);
+        }
+    }
+    size
+}
+
+/// Response which might come from the cache
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    url: String,
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Vec<u8>,
+    /// Where it is (or would be) cached
+    path: Option<PathBuf>,
+    cached: bool,
+}
+
+/// First line of cached file, body follows
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedResponseMeta {
+    url: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+}
+
+impl CachedResponse {
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Was loaded from cache instead of network
+    pub fn is_cached(&self) -> bool {
+        self.cached
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.body
+    }
+
+    pub fn text(&self) -> Result<String, Error> {
+        Ok(String::from_utf8_lossy(&self.body).to_string())
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+
+    pub fn error_for_status(self) -> Result<CachedResponse, Error> {
+        if self.status.is_client_error() || self.status.is_server_error() {
+            bail!("HTTP status {} for url ({})", self.status, self.url);
+        }
+        Ok(self)
+    }
+
+    /// Remove from cache, for APIs which return errors with 200 OK
+    pub fn invalidate(&self) {
+        if let Some(path) = &self.path {
+            std::fs::remove_file(path).ok();
+        }
+    }
+
+    /// Read the whole network response
+    fn from_response(response: reqwest::blocking::Response, path: Option<PathBuf>) -> Result<CachedResponse, Error> {
+        Ok(CachedResponse {
+            url: response.url().to_string(),
+            status: response.status(),
+            headers: response.headers().clone(),
+            body: response.bytes()?.to_vec(),
+            path,
+            cached: false
+        })
+    }
+
+    fn load(path: &PathBuf) -> Result<CachedResponse, Error> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let meta: CachedResponseMeta = serde_json::from_str(&line)?;
+        let mut body = vec![];
+        reader.read_to_end(&mut body)?;
+        let mut headers = HeaderMap::new();
+        for (k, v) in meta.headers {
+            headers.append(HeaderName::from_bytes(k.as_bytes())?, HeaderValue::from_str(&v)?);
+        }
+        Ok(CachedResponse {
+            url: meta.url,
+            status: StatusCode::from_u16(meta.status)?,
+            headers,
+            body,
+            path: Some(path.to_owned()),
+            cached: true
+        })
+    }
+
+    /// Returns written size
+    fn save(&self, path: &PathBuf) -> Result<u64, Error> {
+        let meta = CachedResponseMeta {
+            url: self.url.to_string(),
+            status: self.status.as_u16(),
+            headers: self.headers.iter().filter_map(|(k, v)| Some((k.to_string(), v.to_str().ok()?.to_string()))).collect()
+        };
+        let mut data = serde_json::to_vec(&meta)?;
+        data.push(b'\n');
+        data.extend_from_slice(&self.body);
+        File::create(path)?.write_all(&data)?;
+        Ok(data.len() as u64)
+    }
+}
+
+/// Request middleware used by all platforms: cache, rate limit and retries
+pub trait RequestBuilderCacheExt {
+    /// Send rate limited request or load the response from cache, only successful responses get cached
+    fn send_cached(self, platform: &str) -> Result<CachedResponse, Error>;
+    /// Is there a valid cached response (to skip auth)
+    fn is_cached(&self, platform: &str) -> bool;
+    /// Send request without caching, but with the rate limit and retries
+    fn send_limited(self, platform: &str) -> Result<Response, Error>;
+}
+
+impl RequestBuilderCacheExt for RequestBuilder {
+    fn send_cached(self, platform: &str) -> Result<CachedResponse, Error> {
+        HTTP_CACHE.send(platform, self)
+    }
+
+    fn is_cached(&self, platform: &str) -> bool {
+        self.try_clone()
+            .and_then(|r| r.build().ok())
+            .is_some_and(|r| HTTP_CACHE.get(platform, &r).is_some())
+    }
+
+    fn send_limited(self, platform: &str) -> Result<Response, Error> {
+        let (client, request) = self.build_split();
+        execute_limited(platform, &client, request?)
+    }
+}
+
+/// Send request with the platform rate limit, retrying 429 and 5xx responses
+fn execute_limited(platform: &str, client: &Client, request: Request) -> Result<Response, Error> {
+    // Streamed bodies can't be retried
+    if request.try_clone().is_none() {
+        ratelimit::acquire(platform);
+        return Ok(client.execute(request)?);
+    }
+    ratelimit::execute(
+        platform,
+        || Ok(client.execute(request.try_clone().unwrap())?),
+        |r: &Response| (r.status().as_u16(), r.headers().get("retry-after").and_then(|v| v.to_str().ok()).map(String::from))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cached_response_file() {
+        let mut headers = HeaderMap::new();
+        headers.append("x-discogs-ratelimit-remaining", HeaderValue::from_static("59"));
+        let response = CachedResponse {
+            url: "https://api.discogs.com/releases/1".to_string(),
+            status: StatusCode::OK,
+            headers,
+            body: b"{\"id\": 1}\n\xff".to_vec(),
+            path: None,
+            cached: false
+        };
+        let path = std::env::temp_dir().join("onetagger_http_cache_test");
+        response.save(&path).unwrap();
+        let loaded = CachedResponse::load(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert!(loaded.is_cached());
+        assert_eq!(loaded.url(), response.url());
+        assert_eq!(loaded.bytes(), response.bytes());
+        assert_eq!(loaded.headers().get("X-Discogs-Ratelimit-Remaining").unwrap(), "59");
+        assert!(loaded.error_for_status().is_ok());
+    }
+
+    /// Cache in empty temp folder
+    fn test_cache(name: &str, config: HttpCacheConfig) -> HttpCache {
+        let folder = std::env::temp_dir().join(format!("onetagger_http_cache_test_{name}"));
+        std::fs::remove_dir_all(&folder).ok();
+        HttpCache {
+            config: RwLock::new(config),
+            size: Mutex::new(None),
+            folder: Some(folder)
+        }
+    }
+
+    fn request(url: &str) -> Request {
+        Client::new().get(url).build().unwrap()
+    }
+
+    fn response(body: Vec<u8>) -> CachedResponse {
+        CachedResponse {
+            url: "http://127.0.0.1:9/".to_string(),
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            body,
+            path: None,
+            cached: false
+        }
+    }
+
+    /// Store response for request and return the path
+    fn store(cache: &HttpCache, url: &str, body: Vec<u8>) -> PathBuf {
+        let path = cache.path("test", &request(url)).unwrap();
+        cache.store(&path, &response(body)).unwrap();
+        path
+    }
+
+    fn set_age(path: &PathBuf, hours: u64) {
+        let time = SystemTime::now() - Duration::from_secs(hours * 3600);
+        File::options().write(true).open(path).unwrap().set_modified(time).unwrap();
+    }
+
+    #[test]
+    fn test_stable_key() {
+        let cache = test_cache("key", HttpCacheConfig::default());
+        let path = cache.path("test", &request("https://api.discogs.com/releases/1")).unwrap();
+        assert_eq!(path.file_name().unwrap(), "4395c04efec0273e");
+        let post = Client::new().post("https://api.discogs.com/releases/1").body("{}").build().unwrap();
+        assert_ne!(cache.path("test", &post), Some(path));
+    }
+
+    #[test]
+    fn test_ttl_expiry() {
+        let mut config = HttpCacheConfig::default();
+        config.platform_ttl.insert("test".to_string(), 2);
+        let cache = test_cache("ttl", config);
+        let path = store(&cache, "http://127.0.0.1:9/ttl", b"data".to_vec());
+        let cached = cache.get("test", &request("http://127.0.0.1:9/ttl")).unwrap();
+        assert!(cached.is_cached());
+        assert_eq!(cached.bytes(), b"data");
+
+        set_age(&path, 1);
+        assert!(cache.get("test", &request("http://127.0.0.1:9/ttl")).is_some());
+        set_age(&path, 3);
+        assert!(cache.get("test", &request("http://127.0.0.1:9/ttl")).is_none());
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn test_size_pruning() {
+        let cache = test_cache("size", HttpCacheConfig { max_size: 1, ..Default::default() });
+        let first = store(&cache, "http://127.0.0.1:9/1", vec![0; 400 * 1024]);
+        set_age(&first, 2);
+        let second = store(&cache, "http://127.0.0.1:9/2", vec![0; 400 * 1024]);
+        set_age(&second, 1);
+        // Over 1 MB, oldest is removed to get under 80%
+        let third = store(&cache, "http://127.0.0.1:9/3", vec![0; 400 * 1024]);
+        assert!(!first.exists());
+        assert!(second.exists());
+        assert!(third.exists());
+        assert_eq!(*cache.size.lock().unwrap(), Some(cache.entries().iter().map(|e| e.1).sum()));
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn test_offline() {
+        let cache = test_cache("offline", HttpCacheConfig { offline: true, ..Default::default() });
+        assert!(cache.offline());
+        // Nothing gets sent, the discard port would fail with a connection error
+        let error = cache.send("test", Client::new().get("http://127.0.0.1:9/offline")).unwrap_err();
+        assert!(error.to_string().starts_with("Offline mode"));
+
+        store(&cache, "http://127.0.0.1:9/offline", b"cached".to_vec());
+        let response = cache.send("test", Client::new().get("http://127.0.0.1:9/offline")).unwrap();
+        assert!(response.is_cached());
+        assert_eq!(response.bytes(), b"cached");
+        cache.clear().unwrap();
+    }
+}
@@ -1,16 +1,14 @@
 use anyhow::Error;
 use std::time::Duration;
-use std::thread::sleep;
 use chrono::NaiveDate;
-use reqwest::blocking::{Client, Response};
+use reqwest::blocking::Client;
+use crate::http::{CachedResponse, RequestBuilderCacheExt};
 use serde::{Serialize, Deserialize};
+use onetagger_tagger::ratelimit;
 use onetagger_tagger::{AutotaggerSource, AudioFileInfo, TaggerConfig, Track, MatchingUtils, AutotaggerSourceBuilder, PlatformInfo, PlatformCustomOptions, PlatformCustomOptionValue, supported_tags, TrackMatch};
 
 pub struct ITunes {
     client: Client,
-    // Requests per minute
-    pub rate_limit: i16,
-    last_request: u128,
 }
 
 impl ITunes {
@@ -21,31 +19,13 @@ impl ITunes {
                 .user_agent("OneTagger/1.0")
                 .build()
                 .unwrap(),
-            rate_limit: 20,
-            last_request: 0
         }
     }
 
     /// Make get request to API
-    fn get(&mut self, path: &str, query: &[(&str, &str)]) -> Result<Response, Error> {
+    fn get(&mut self, path: &str, query: &[(&str, &str)]) -> Result<CachedResponse, Error> {
         debug!("iTunes GET: {} {:?}", path, query);
-        // Rate limit
-        if self.last_request > 0 && self.rate_limit != -1 {
-            let diff = timestamp!() - self.last_request;
-            let req_ms = 1000_f64 / (self.rate_limit as f64 / 60_f64);
-            let wait = diff as f64 - req_ms;
-            if wait < 0_f64 {
-                info!("iTunes rate limit delay: {}", -wait);
-                sleep(Duration::from_millis(-wait as u64));
-            }
-        }
-
-        // Do request
-        let res = self.client.get(&format!("https://itunes.apple.com{}", path))
-            .query(query)
-            .send()?;
-        self.last_request = timestamp!();
-        Ok(res)
+        self.client.get(&format!("https://itunes.apple.com{}", path)).query(query).send_cached("itunes")
     }
 
     /// Search the iTunes API
@@ -147,6 +127,8 @@ pub struct ITunesBuilder;
 
 impl AutotaggerSourceBuilder for ITunesBuilder {
     fn new() -> ITunesBuilder {
+        // Requests per minute
+        ratelimit::set_limit("itunes", Some(20));
         ITunesBuilder
     }
 
@@ -1,11 +1,11 @@
 use reqwest::blocking::Client;
-use reqwest::StatusCode;
+use crate::http::RequestBuilderCacheExt;
 use scraper::{Html, Selector, ElementRef};
 use chrono::NaiveDate;
 use regex::Regex;
-use std::thread::sleep;
 use std::time::Duration;
 use anyhow::Error;
+use onetagger_tagger::ratelimit;
 use onetagger_tagger::{Track, AutotaggerSource, AudioFileInfo, TaggerConfig, MatchingUtils, TrackNumber, AutotaggerSourceBuilder, PlatformInfo, supported_tags, TrackMatch};
 
 pub struct JunoDownload {
@@ -30,13 +30,7 @@ impl JunoDownload {
         let response = self.client
             .get("https://www.junodownload.com/search/")
             .query(&[("q[all][]", query), ("solrorder", "relevancy"), ("items_per_page", "50")])
-            .send()?;
-        // Rate limitting
-        if response.status() == StatusCode::TOO_MANY_REQUESTS {
-            warn!("JunoDownload rate limit! Sleeping for 2s!");
-            sleep(Duration::from_secs(2));
-            return self.search(query);
-        }
+            .send_cached("junodownload")?;
 
         // Minify and parse
         let data = response.text()?;
@@ -192,6 +186,8 @@ pub struct JunoDownloadBuilder;
 
 impl AutotaggerSourceBuilder for JunoDownloadBuilder {
     fn new() -> JunoDownloadBuilder {
+        // Requests per minute
+        ratelimit::set_limit("junodownload", Some(30));
         JunoDownloadBuilder
     }
 
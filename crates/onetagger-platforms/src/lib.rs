@@ -2,6 +2,7 @@
 #[macro_use] extern crate anyhow;
 #[macro_use] extern crate onetagger_shared;
 
+pub mod http;
 pub mod beatport;
 pub mod traxsource;
 pub mod discogs;
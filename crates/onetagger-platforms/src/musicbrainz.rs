@@ -1,12 +1,15 @@
 use anyhow::Error;
 use std::time::Duration;
 use chrono::NaiveDate;
-use rand::Rng;
-use reqwest::StatusCode;
 use reqwest::blocking::Client;
+use crate::http::RequestBuilderCacheExt;
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use onetagger_tagger::ratelimit;
 use onetagger_tagger::{Track, AutotaggerSource, AudioFileInfo, TaggerConfig, MatchingUtils, TrackNumber, AutotaggerSourceBuilder, PlatformInfo, supported_tags, TrackMatch};
 
+/// Tag with recording IDs, written by MusicBrainz Picard and filled by AcoustID identification
+pub const RECORDING_ID_TAG: &str = "MUSICBRAINZ_TRACKID";
+
 pub struct MusicBrainz {
     client: Client
 }
@@ -29,13 +32,7 @@ impl MusicBrainz {
 
         let response = self.client.get(&format!("https://musicbrainz.org/ws/2{}", path))
             .query(&new_query)
-            .send()?;
-        if response.status() == StatusCode::SERVICE_UNAVAILABLE {
-            warn!("MusicBrainz rate limit hit! Waiting...");
-            // Use random rate limit delay because threading
-            std::thread::sleep(Duration::from_millis(rand::thread_rng().gen_range(1000..3000)));
-            return self.get(path, &query);
-        }
+            .send_cached("musicbrainz")?;
         Ok(response.error_for_status()?.json()?)
     }
 
@@ -48,6 +45,11 @@ impl MusicBrainz {
         Ok(results)
     }
 
+    /// Get recording by ID
+    pub fn recording(&self, id: &str) -> Result<Recording, Error> {
+        self.get(&format!("/recording/{id}"), &[("inc", "artist-credits releases release-groups isrcs")])
+    }
+
     /// Get full release for recording
     pub fn full_release(&self, recording_id: &str) -> Result<BrowseReleases, Error> {
         let results: BrowseReleases = self.get("/release", &[
@@ -94,6 +96,21 @@ impl MusicBrainz {
 
 impl AutotaggerSource for MusicBrainz {
     fn match_track(&mut self, info: &AudioFileInfo, config: &TaggerConfig) -> Result<Vec<TrackMatch>, Error> {
+        // Exact recording ID match
+        if let Some(ids) = info.tags.get(RECORDING_ID_TAG) {
+            let mut tracks = vec![];
+            for id in ids.iter().map(|id| id.trim().replace("\0", "")).filter(|id| !id.is_empty()) {
+                match self.recording(&id) {
+                    Ok(recording) => tracks.push(TrackMatch::new_id(recording.into())),
+                    Err(e) => warn!("MusicBrainz recording {id} failed: {e}"),
+                }
+            }
+            if !tracks.is_empty() {
+                return Ok(tracks);
+            }
+            warn!("Falling back to normal search...");
+        }
+
         let query = format!("{} {}~", info.artist()?, MatchingUtils::clean_title(info.title()?));
         match self.search(&query) {
             Ok(results) => {
@@ -156,7 +173,7 @@ impl Into<Track> for Recording {
             platform: "musicbrainz".to_string(),
             title: self.title,
             version: None,
-            artists: self.artist_credit.unwrap_or(Vec::new()).into_iter().map(|a| a.name).collect(),
+            artists: self.artist_credit.clone().unwrap_or(Vec::new()).into_iter().map(|a| a.name).collect(),
             album_artists: release
                 .map(|r| r.artist_credit.as_ref().map(|a| a.into_iter().map(|artist| artist.name.to_string()).collect()))
                 .flatten().unwrap_or(vec![]),
@@ -168,11 +185,21 @@ impl Into<Track> for Recording {
             release_year: self.first_release_date.clone().map(|d| (d.len() >= 4).then(|| d[0..4].parse().ok()).flatten()).flatten(),
             release_date: self.first_release_date.map(|d| NaiveDate::parse_from_str(&d, "%Y-%m-%d").ok()).flatten(),
             isrc: self.isrcs.map(|v| v.first().map(String::from)).flatten(),
+            artist_sort: self.artist_credit.as_deref().and_then(artist_sort),
+            compilation: release.map(|r| r.release_group.secondary_types.iter().flatten().any(|t| t.eq_ignore_ascii_case("compilation"))),
             ..Default::default()
         }
     }
 }
 
+/// Join sort names of credited artists
+fn artist_sort(credits: &[ArtistCredit]) -> Option<String> {
+    if credits.is_empty() {
+        return None;
+    }
+    credits.iter().map(|c| c.artist.sort_name.as_ref().map(|s| format!("{s}{}", c.joinphrase))).collect()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct ReleaseSmall {
@@ -238,14 +265,17 @@ pub struct Genre {
 #[serde(rename_all = "kebab-case")]
 pub struct ArtistCredit {
     pub name: String,
-    pub artist: Artist
+    pub artist: Artist,
+    #[serde(default)]
+    pub joinphrase: String
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct Artist {
     pub name: String,
-    pub id: String
+    pub id: String,
+    pub sort_name: Option<String>
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -283,6 +313,8 @@ pub struct MusicBrainzBuilder;
 
 impl AutotaggerSourceBuilder for MusicBrainzBuilder {
     fn new() -> MusicBrainzBuilder {
+        // 1 request per second
+        ratelimit::set_limit("musicbrainz", Some(60));
         MusicBrainzBuilder
     }
 
@@ -300,7 +332,7 @@ impl AutotaggerSourceBuilder for MusicBrainzBuilder {
             version: "1.0.0".to_string(),
             custom_options: Default::default(),
             requires_auth: false,
-            supported_tags: supported_tags!(Title, Artist, AlbumArtist, Album, URL, ReleaseId, TrackId, Duration, ISRC, Label, CatalogNumber, TrackNumber, Genre),
+            supported_tags: supported_tags!(Title, Artist, AlbumArtist, Album, URL, ReleaseId, TrackId, Duration, ISRC, Label, CatalogNumber, TrackNumber, Genre, ArtistSort, Compilation),
         }
     }
 }
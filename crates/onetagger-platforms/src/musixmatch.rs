@@ -7,6 +7,8 @@ use reqwest::header::{HeaderMap, HeaderValue};
 use serde::{Serialize, Deserialize};
 use serde::de::DeserializeOwned;
 use serde_json::Value;
+use onetagger_tagger::ratelimit;
+use crate::http::RequestBuilderCacheExt;
 use onetagger_tagger::{AutotaggerSource, AudioFileInfo, TaggerConfig, Track, Lyrics, LyricsLine, LyricsLinePart, AutotaggerSourceBuilder, PlatformInfo, PlatformCustomOptions, supported_tags, TrackMatch};
 
 #[derive(Debug, Clone)]
@@ -38,7 +40,7 @@ impl Musixmatch {
         // Capcha retry
         if t["message"]["header"]["status_code"].as_i64() == Some(401) {
             warn!("Musixmatch capcha when getting token! Waiting for 10s...");
-            std::thread::sleep(Duration::from_secs(10));
+            ratelimit::pause("musixmatch", Duration::from_secs(10));
             return self.fetch_token();
         }
         let token = t["message"]["body"]["user_token"].as_str().ok_or(anyhow!("Couldn't fetch the token"))?.to_string();
@@ -67,7 +69,7 @@ impl Musixmatch {
         // Make request
         let o = self.client.get(&format!("https://apic-desktop.musixmatch.com/ws/1.1/{action}"))
             .query(&query)
-            .send()?
+            .send_limited("musixmatch")?
             .json()?;
         Ok(o)
     }
@@ -93,7 +95,7 @@ impl Musixmatch {
             }
             let delay = 2u64.pow(retry_count + 3);
             warn!("Musixmatch captcha, waiting for {delay}s...");
-            std::thread::sleep(Duration::from_secs(delay));
+            ratelimit::pause("musixmatch", Duration::from_secs(delay));
             return self.fetch_lyrics(title, artist, retry_count + 1);
         }
 
@@ -10,6 +10,7 @@ use rspotify::model::search::SearchResult;
 use rspotify::model::track::FullTrack;
 use rspotify::model::audio::AudioFeatures;
 use onetagger_shared::{Settings, WEBSERVER_CALLBACKS, PORT};
+use onetagger_tagger::ratelimit;
 use onetagger_tagger::{AutotaggerSource, Track, TaggerConfig, AudioFileInfo, MatchingUtils, TrackNumber, AutotaggerSourceBuilder, PlatformInfo, supported_tags, SupportedTag, TrackMatch};
 
 /// Reexport, beacause the rspotify dependency is git
@@ -87,36 +88,23 @@ impl Spotify {
         })
     }
 
-    /// Wrapper for rate limit 
+    /// Wrapper for rate limit and retries
     fn rate_limit_wrap<F, R>(&self, f: F) -> Result<R, Error>
     where
         F: Fn(&Spotify) -> ClientResult<R>
     {
-        match f(self) {
-            Ok(r) => {
-                // Intentionally slow down to prevent rate limits
-                std::thread::sleep(Duration::from_millis(250));
-                Ok(r)
+        let result = ratelimit::execute("spotify", || Ok(f(self)), |r| match r {
+            Err(ClientError::Http(http)) => match http.as_ref() {
+                rspotify::http::HttpError::StatusCode(r) => (r.status(), r.header("Retry-After").map(String::from)),
+                _ => (0, None)
             },
-            Err(ClientError::Http(http)) => {
-                match *http {
-                    rspotify::http::HttpError::StatusCode(r) => {
-                        // Rate limit
-                        if r.status() == 429 {
-                            let delay = r.header("Retry-After").map(|v| v.parse().ok()).flatten().unwrap_or(3);
-                            if delay > 600 {
-                                error!("Spotify rate limit too high!: {delay}");
-                                return Err(anyhow!("Too high rate limit by Spotify"));
-                            }
-
-                            warn!("Spotify rate limit hit, sleeping for: {delay}s...");
-                            std::thread::sleep(Duration::from_secs(delay));
-                            return self.rate_limit_wrap(f);
-                        }
-                        return Err(anyhow!("Unknown Spotify status code: {}", r.status()));
-                    },
-                    e => return Err(e.into())
-                }
+            _ => (200, None)
+        })?;
+        match result {
+            Ok(r) => Ok(r),
+            Err(ClientError::Http(http)) => match *http {
+                rspotify::http::HttpError::StatusCode(r) => Err(anyhow!("Unknown Spotify status code: {}", r.status())),
+                e => Err(e.into())
             },
             Err(e) => Err(e.into()),
         }
@@ -261,6 +249,8 @@ pub struct SpotifyBuilder;
 
 impl AutotaggerSourceBuilder for SpotifyBuilder {
     fn new() -> SpotifyBuilder {
+        // Intentionally slow down to prevent rate limits
+        ratelimit::set_limit("spotify", Some(240));
         SpotifyBuilder
     }
 
@@ -1,5 +1,6 @@
 use anyhow::Error;
 use reqwest::blocking::Client;
+use crate::http::RequestBuilderCacheExt;
 use chrono::NaiveDate;
 use scraper::{Html, Selector};
 use onetagger_tagger::{Track, AudioFileInfo, TaggerConfig, AutotaggerSource, MatchingUtils, TrackNumber, AutotaggerSourceBuilder, PlatformInfo, supported_tags, TrackMatch, SupportedTag};
@@ -25,7 +26,7 @@ impl Traxsource {
         debug!("Q: {}", query);
         let data = self.client.get("https://www.traxsource.com/search/tracks")
             .query(&[("term", query)])
-            .send()?
+            .send_cached("traxsource")?
             .text()?;
 
         // Minify and parse
@@ -123,7 +124,7 @@ impl Traxsource {
     pub fn extend_track_traxsource(&self, track: &mut Track, album_meta: bool, album_art: bool) -> Result<(), Error> {
         // Fetch
         let data = self.client.get(&track.url)
-            .send()?
+            .send_cached("traxsource")?
             .text()?;
         
         // Minify and parse
@@ -146,7 +147,7 @@ impl Traxsource {
             return Ok(());
         }
         let data = self.client.get(format!("https://www.traxsource.com{}", album_url))
-            .send()?
+            .send_cached("traxsource")?
             .text()?;
         // Minify and parse
         let data = String::from_utf8(minify_html::minify(data.as_bytes(), &minify_html::Cfg::spec_compliant()))?;
@@ -0,0 +1,110 @@
+use anyhow::Error;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+use rodio::Source;
+
+use crate::AudioSource;
+
+/// DSF / DFF file, only duration is supported
+pub struct DSDSource {
+    duration: Duration
+}
+
+impl DSDSource {
+    // Load from path
+    pub fn new(path: impl AsRef<Path>) -> Result<DSDSource, Error> {
+        let mut file = File::open(path)?;
+        let mut magic = [0u8; 4];
+        file.read_exact(&mut magic)?;
+        let duration = match &magic {
+            b"DSD " => dsf_duration(&mut file)?,
+            b"FRM8" => dff_duration(&mut file)?,
+            _ => return Err(anyhow!("Not a DSF / DFF file"))
+        };
+        Ok(DSDSource { duration })
+    }
+}
+
+impl AudioSource for DSDSource {
+    // Get duration
+    fn duration(&self) -> u128 {
+        self.duration.as_millis()
+    }
+
+    fn get_source(&self) -> Result<Box<dyn Source<Item = i16> + Send>, Error> {
+        Err(anyhow!("DSD playback is not supported"))
+    }
+}
+
+/// Sample count and rate from `fmt ` chunk
+fn dsf_duration(file: &mut File) -> Result<Duration, Error> {
+    let mut fmt = [0u8; 52];
+    file.seek(SeekFrom::Start(28))?;
+    file.read_exact(&mut fmt)?;
+    if &fmt[0..4] != b"fmt " {
+        return Err(anyhow!("Missing DSF fmt chunk"));
+    }
+    let sample_rate = u32::from_le_bytes(fmt[28..32].try_into().unwrap());
+    let samples = u64::from_le_bytes(fmt[36..44].try_into().unwrap());
+    if sample_rate == 0 {
+        return Err(anyhow!("Invalid DSF sample rate"));
+    }
+    Ok(Duration::from_secs_f64(samples as f64 / sample_rate as f64))
+}
+
+/// From `PROP` and `DSD ` (or DST `FRTE`) chunks
+fn dff_duration(file: &mut File) -> Result<Duration, Error> {
+    let len = file.metadata()?.len();
+    let mut sample_rate = 0;
+    let mut channels = 0;
+    let mut offset = 16;
+    let mut header = [0u8; 12];
+    while offset + 12 <= len {
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut header)?;
+        let size = u64::from_be_bytes(header[4..12].try_into().unwrap());
+        match &header[0..4] {
+            b"PROP" => {
+                let mut data = vec![0u8; size.min(4096) as usize];
+                file.read_exact(&mut data)?;
+                for (id, data) in dff_sub_chunks(data.get(4..).unwrap_or_default()) {
+                    match id {
+                        b"FS  " if data.len() >= 4 => sample_rate = u32::from_be_bytes(data[0..4].try_into().unwrap()),
+                        b"CHNL" if data.len() >= 2 => channels = u16::from_be_bytes(data[0..2].try_into().unwrap()),
+                        _ => {}
+                    }
+                }
+            },
+            b"DSD " if sample_rate > 0 && channels > 0 => {
+                return Ok(Duration::from_secs_f64(size as f64 * 8.0 / channels as f64 / sample_rate as f64));
+            },
+            // DST compressed, frame info is the first sub chunk
+            b"DST " => {
+                let mut data = [0u8; 18];
+                file.read_exact(&mut data)?;
+                if &data[0..4] == b"FRTE" {
+                    let frames = u32::from_be_bytes(data[12..16].try_into().unwrap());
+                    let rate = u16::from_be_bytes(data[16..18].try_into().unwrap()).max(1);
+                    return Ok(Duration::from_secs_f64(frames as f64 / rate as f64));
+                }
+            },
+            _ => {}
+        }
+        offset = offset.saturating_add(12).saturating_add(size).saturating_add(size % 2);
+    }
+    Err(anyhow!("Missing DFF sound data"))
+}
+
+/// Iterate over (id, data) of local chunks
+fn dff_sub_chunks(mut data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    std::iter::from_fn(move || {
+        let size = u64::from_be_bytes(data.get(4..12)?.try_into().unwrap()) as usize;
+        let id = &data[0..4];
+        let end = size.checked_add(12)?;
+        let chunk = data.get(12..end)?;
+        data = data.get(end + size % 2..).unwrap_or_default();
+        Some((id, chunk))
+    })
+}
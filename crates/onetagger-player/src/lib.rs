@@ -16,6 +16,8 @@ pub mod ogg;
 pub mod alac;
 pub mod flac;
 pub mod aiff;
+pub mod wavpack;
+pub mod dsd;
 
 /// Re-Export to prevent dependency issues
 pub use rodio;
@@ -33,19 +35,24 @@ impl AudioPlayer {
         let (tx, rx_main) = channel();
         thread::spawn(move || {
             let mut volume = 0.5;
+            let mut gain = 1.0;
             let mut source = None;
             // Create sink
             let (_stream, stream_handle) = rodio::OutputStream::try_default().unwrap();
             let mut sink = Sink::try_new(&stream_handle).unwrap();
-            sink.set_volume(volume);
+            sink.set_volume(volume * gain);
             sink.pause();
             // Wait for messages
             for action in rx {
                 match action {
                     PlayerAction::Volume(v) => {
-                        sink.set_volume(v);
+                        sink.set_volume(v * gain);
                         volume = v;
                     },
+                    PlayerAction::Gain(g) => {
+                        sink.set_volume(volume * g);
+                        gain = g;
+                    },
                     PlayerAction::Stop => {
                         sink.stop();
                     }
@@ -56,7 +63,7 @@ impl AudioPlayer {
                         // Create new sink
                         sink.stop();
                         sink = Sink::try_new(&stream_handle).unwrap();
-                        sink.set_volume(volume);
+                        sink.set_volume(volume * gain);
                         sink.pause();
                         // Append source
                         if let Ok(s) = audio_source.get_source() {
@@ -72,7 +79,7 @@ impl AudioPlayer {
                             let paused = sink.is_paused();
                             sink.stop();
                             sink = Sink::try_new(&stream_handle).unwrap();
-                            sink.set_volume(volume);
+                            sink.set_volume(volume * gain);
                             if paused {
                                 sink.pause();
                             }
@@ -125,6 +132,11 @@ impl AudioPlayer {
         self.tx.send(PlayerAction::Volume(volume)).ok();
     }
 
+    /// Multiplier applied on top of volume (ReplayGain)
+    pub fn gain(&self, gain: f32) {
+        self.tx.send(PlayerAction::Gain(gain)).ok();
+    }
+
     pub fn stop(&self) {
         self.tx.send(PlayerAction::Stop).ok();
     }
@@ -138,6 +150,8 @@ enum PlayerAction {
     Seek(u64),
     /// 0.0 - 1.0
     Volume(f32),
+    /// Linear multiplier
+    Gain(f32),
     Stop,
 }
 
@@ -170,6 +184,14 @@ impl AudioSources {
         if p == "ogg" || p == "opus" || p == "oga" || p == "spx" {
             return Ok(Box::new(ogg::OGGSource::new(path)?));
         }
+        // WavPack
+        if p == "wv" {
+            return Ok(Box::new(wavpack::WavPackSource::new(path)?));
+        }
+        // DSD
+        if p == "dsf" || p == "dff" {
+            return Ok(Box::new(dsd::DSDSource::new(path)?));
+        }
 
         Err(anyhow!("Unsupported format!").into())
     }
@@ -0,0 +1,961 @@
+use anyhow::Error;
+use lofty::file::AudioFile;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use rodio::Source;
+
+use crate::AudioSource;
+
+/// Block header flags
+const MONO_FLAG: u32 = 0x4;
+const HYBRID_FLAG: u32 = 0x8;
+const JOINT_STEREO: u32 = 0x10;
+const FLOAT_DATA: u32 = 0x80;
+const INITIAL_BLOCK: u32 = 0x800;
+const FALSE_STEREO: u32 = 0x40000000;
+const DSD_FLAG: u32 = 0x80000000;
+
+/// Metadata sub-block IDs
+const ID_DECORR_TERMS: u8 = 0x2;
+const ID_DECORR_WEIGHTS: u8 = 0x3;
+const ID_DECORR_SAMPLES: u8 = 0x4;
+const ID_ENTROPY_VARS: u8 = 0x5;
+const ID_INT32_INFO: u8 = 0x9;
+const ID_WV_BITSTREAM: u8 = 0xa;
+const ID_SAMPLE_RATE: u8 = 0x27;
+
+const SAMPLE_RATES: [u32; 15] = [6000, 8000, 9600, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000, 64000, 88200, 96000, 192000];
+
+pub struct WavPackSource {
+    path: PathBuf,
+    duration: Duration
+}
+
+impl WavPackSource {
+    // Load from path
+    pub fn new(path: impl AsRef<Path>) -> Result<WavPackSource, Error> {
+        // Get duration
+        let file = lofty::read_from_path(&path)?;
+        let duration = file.properties().duration();
+
+        Ok(WavPackSource {
+            path: path.as_ref().to_owned(),
+            duration
+        })
+    }
+}
+
+impl AudioSource for WavPackSource {
+    // Get duration
+    fn duration(&self) -> u128 {
+        self.duration.as_millis()
+    }
+
+    // Get rodio source
+    fn get_source(&self) -> Result<Box<dyn Source<Item = i16> + Send>, Error> {
+        Ok(Box::new(WavPackDecoder::load(&self.path)?))
+    }
+}
+
+/// Decoder of lossless integer WavPack 4 files.
+/// Only the first block of every frame is decoded, so multichannel files play the first 2 channels.
+struct WavPackDecoder {
+    data: Vec<u8>,
+    offset: usize,
+    channels: u16,
+    sample_rate: u32,
+    total_samples: Option<u32>,
+    index: usize,
+    buffer: Vec<i16>
+}
+
+impl WavPackDecoder {
+    /// Load file into memory and decode the first block
+    pub fn load(path: impl AsRef<Path>) -> Result<WavPackDecoder, Error> {
+        let mut data = vec![];
+        File::open(path)?.read_to_end(&mut data)?;
+        let offset = find_block(&data, 0).ok_or(anyhow!("Not a WavPack file"))?;
+        let header = BlockHeader::parse(&data[offset..])?;
+        let mut decoder = WavPackDecoder {
+            channels: if header.flags & MONO_FLAG != 0 { 1 } else { 2 },
+            sample_rate: header.sample_rate(&data[offset..]).ok_or(anyhow!("Unknown WavPack sample rate"))?,
+            total_samples: (header.total_samples != u32::MAX).then_some(header.total_samples),
+            data,
+            offset,
+            index: 0,
+            buffer: vec![]
+        };
+        // Fail early on unsupported files
+        decoder.next_block()?;
+        Ok(decoder)
+    }
+
+    /// Decode next block with samples into buffer, false on end of file
+    fn next_block(&mut self) -> Result<bool, Error> {
+        while let Some(offset) = find_block(&self.data, self.offset) {
+            let header = BlockHeader::parse(&self.data[offset..])?;
+            let end = (offset + header.size + 8).min(self.data.len());
+            self.offset = end;
+            if header.samples == 0 || header.flags & INITIAL_BLOCK == 0 {
+                continue;
+            }
+            let block = self.data.get(offset + 32..end).ok_or(anyhow!("Invalid WavPack block size"))?;
+            let shift = header.bits() as i32 - 16;
+            self.buffer = decode_block(&header, block)?.into_iter().map(|s| match shift > 0 {
+                true => (s >> shift) as i16,
+                false => (s << -shift) as i16
+            }).collect();
+            // False stereo
+            if self.channels == 2 && header.flags & FALSE_STEREO != 0 {
+                self.buffer = self.buffer.iter().flat_map(|s| [*s, *s]).collect();
+            }
+            self.index = 0;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
+
+impl Source for WavPackDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.total_samples.map(|s| Duration::from_secs_f32(s as f32 / self.sample_rate as f32))
+    }
+}
+
+impl Iterator for WavPackDecoder {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.buffer.len() {
+            match self.next_block() {
+                Ok(true) => {},
+                Ok(false) => return None,
+                Err(e) => {
+                    warn!("Failed decoding WavPack block: {e}");
+                    return None;
+                }
+            }
+        }
+        let sample = self.buffer.get(self.index).copied();
+        self.index += 1;
+        sample
+    }
+}
+
+/// Find `wvpk` block header starting at offset
+fn find_block(data: &[u8], offset: usize) -> Option<usize> {
+    data.get(offset..)?.windows(4).position(|w| w == b"wvpk").map(|p| p + offset)
+}
+
+struct BlockHeader {
+    /// Size without the first 8 bytes
+    size: usize,
+    total_samples: u32,
+    samples: u32,
+    flags: u32,
+    crc: u32,
+}
+
+impl BlockHeader {
+    fn parse(data: &[u8]) -> Result<BlockHeader, Error> {
+        if data.len() < 32 || &data[0..4] != b"wvpk" {
+            return Err(anyhow!("Invalid WavPack block"));
+        }
+        let u32_at = |i: usize| u32::from_le_bytes(data[i..i + 4].try_into().unwrap());
+        let version = u16::from_le_bytes([data[8], data[9]]);
+        if !(0x402..=0x410).contains(&version) {
+            return Err(anyhow!("Unsupported WavPack version: {version:#x}"));
+        }
+        Ok(BlockHeader {
+            size: u32_at(4) as usize,
+            total_samples: u32_at(12),
+            samples: u32_at(20),
+            flags: u32_at(24),
+            crc: u32_at(28),
+        })
+    }
+
+    /// Bits per sample
+    fn bits(&self) -> u32 {
+        ((self.flags & 0x3) + 1) * 8
+    }
+
+    /// From flags or the sample rate sub-block
+    fn sample_rate(&self, block: &[u8]) -> Option<u32> {
+        match SAMPLE_RATES.get(((self.flags >> 23) & 0xf) as usize) {
+            Some(rate) => Some(*rate),
+            None => SubBlocks::new(block.get(32..(self.size + 8).min(block.len())).unwrap_or_default())
+                .find(|(id, data)| *id == ID_SAMPLE_RATE && data.len() >= 3)
+                .map(|(_, data)| u32::from_le_bytes([data[0], data[1], data[2], 0]))
+        }
+    }
+}
+
+/// Iterator over metadata sub-blocks (id, data)
+struct SubBlocks<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> SubBlocks<'a> {
+    fn new(data: &'a [u8]) -> SubBlocks<'a> {
+        SubBlocks { data }
+    }
+}
+
+impl<'a> Iterator for SubBlocks<'a> {
+    type Item = (u8, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = *self.data.first()?;
+        // Size in words, 24 bit for large blocks
+        let (header, mut size) = match id & 0x80 != 0 {
+            true => (4, u32::from_le_bytes([*self.data.get(1)?, *self.data.get(2)?, *self.data.get(3)?, 0]) as usize * 2),
+            false => (2, *self.data.get(1)? as usize * 2)
+        };
+        let total = header + size;
+        if id & 0x40 != 0 {
+            size = size.saturating_sub(1);
+        }
+        let data = self.data.get(header..header + size)?;
+        self.data = self.data.get(total..).unwrap_or_default();
+        Some((id & 0x3f, data))
+    }
+}
+
+/// Decorrelation pass
+#[derive(Debug, Clone, Default)]
+struct Decorr {
+    term: i32,
+    delta: i32,
+    weight_a: i32,
+    weight_b: i32,
+    samples_a: [i32; 8],
+    samples_b: [i32; 8],
+}
+
+/// Decode block into interleaved samples
+fn decode_block(header: &BlockHeader, data: &[u8]) -> Result<Vec<i32>, Error> {
+    if header.flags & (HYBRID_FLAG | FLOAT_DATA | DSD_FLAG) != 0 {
+        return Err(anyhow!("Hybrid, float and DSD WavPack files are not supported"));
+    }
+    let stereo = header.flags & (MONO_FLAG | FALSE_STEREO) == 0;
+    let mut decorr: Vec<Decorr> = vec![];
+    let mut entropy = Entropy::default();
+    let mut bitstream = None;
+    let mut int32 = (0, 0, 0);
+
+    for (id, data) in SubBlocks::new(data) {
+        match id {
+            // Stored in reverse order
+            ID_DECORR_TERMS => {
+                decorr = data.iter().rev().map(|b| Decorr {
+                    term: (b & 0x1f) as i32 - 5,
+                    delta: (b >> 5) as i32,
+                    ..Default::default()
+                }).collect();
+                if decorr.len() > 16 || decorr.iter().any(|d| !matches!(d.term, 1..=8 | 17 | 18 | -3..=-1)) {
+                    return Err(anyhow!("Invalid WavPack decorrelation terms"));
+                }
+            },
+            ID_DECORR_WEIGHTS => {
+                let mut weights = data.iter().map(|w| restore_weight(*w as i8));
+                for d in decorr.iter_mut().rev() {
+                    let Some(weight) = weights.next() else { break };
+                    d.weight_a = weight;
+                    if stereo {
+                        d.weight_b = weights.next().unwrap_or(0);
+                    }
+                }
+            },
+            ID_DECORR_SAMPLES => {
+                let mut values = data.chunks_exact(2).map(|v| wp_exp2(i16::from_le_bytes([v[0], v[1]])));
+                let mut next = || values.next().unwrap_or(0);
+                for d in decorr.iter_mut().rev() {
+                    if d.term > 8 {
+                        d.samples_a[0] = next();
+                        d.samples_a[1] = next();
+                        if stereo {
+                            d.samples_b[0] = next();
+                            d.samples_b[1] = next();
+                        }
+                    } else if d.term < 0 {
+                        d.samples_a[0] = next();
+                        d.samples_b[0] = next();
+                    } else {
+                        for j in 0..d.term as usize {
+                            d.samples_a[j] = next();
+                            if stereo {
+                                d.samples_b[j] = next();
+                            }
+                        }
+                    }
+                }
+            },
+            ID_ENTROPY_VARS => {
+                if data.len() < 6 * (stereo as usize + 1) {
+                    return Err(anyhow!("Invalid WavPack entropy variables"));
+                }
+                for (i, v) in data.chunks_exact(2).take(6 * (stereo as usize + 1) / 2).enumerate() {
+                    entropy.median[i / 3][i % 3] = wp_exp2(i16::from_le_bytes([v[0], v[1]])) as u32;
+                }
+            },
+            ID_INT32_INFO if data.len() >= 4 => {
+                // Shift, and, or
+                if data[1..4].iter().any(|s| *s >= 32) {
+                    return Err(anyhow!("Invalid WavPack INT32 info"));
+                }
+                if data[0] != 0 {
+                    return Err(anyhow!("WavPack extra bits are not supported"));
+                } else if data[1] != 0 {
+                    int32 = (data[1] as u32, 0, 0);
+                } else if data[2] != 0 {
+                    int32 = (data[2] as u32, 1, 1);
+                } else if data[3] != 0 {
+                    int32 = (data[3] as u32, 1, 0);
+                }
+            },
+            ID_WV_BITSTREAM => bitstream = Some(data),
+            _ => {}
+        }
+    }
+
+    let mut bits = BitReader::new(bitstream.ok_or(anyhow!("Missing WavPack bitstream"))?);
+    let count = header.samples as usize;
+    let mut out = Vec::with_capacity(count * (stereo as usize + 1));
+    let mut crc = 0xFFFFFFFFu32;
+    let mut pos = 0;
+    let weighted = |weight: i32, sample: i32| ((weight as i64 * sample as i64 + 512) >> 10) as i32;
+
+    for _ in 0..count {
+        if stereo {
+            let mut l = entropy.get_value(&mut bits, 0)?;
+            let mut r = entropy.get_value(&mut bits, 1)?;
+            for d in decorr.iter_mut() {
+                if d.term > 0 {
+                    let (a, b, j) = if d.term > 8 {
+                        let (a, b) = d.extrapolate();
+                        d.samples_a[1] = d.samples_a[0];
+                        d.samples_b[1] = d.samples_b[0];
+                        (a, b, 0)
+                    } else {
+                        (d.samples_a[pos], d.samples_b[pos], (pos + d.term as usize) & 7)
+                    };
+                    let l2 = l.wrapping_add(weighted(d.weight_a, a));
+                    let r2 = r.wrapping_add(weighted(d.weight_b, b));
+                    d.weight_a = update_weight(d.weight_a, d.delta, a, l);
+                    d.weight_b = update_weight(d.weight_b, d.delta, b, r);
+                    l = l2;
+                    r = r2;
+                    d.samples_a[j] = l;
+                    d.samples_b[j] = r;
+                } else if d.term == -1 {
+                    let l2 = l.wrapping_add(weighted(d.weight_a, d.samples_a[0]));
+                    d.weight_a = update_weight_clip(d.weight_a, d.delta, d.samples_a[0], l);
+                    l = l2;
+                    let r2 = r.wrapping_add(weighted(d.weight_b, l2));
+                    d.weight_b = update_weight_clip(d.weight_b, d.delta, l2, r);
+                    r = r2;
+                    d.samples_a[0] = r;
+                } else {
+                    let mut r2 = r.wrapping_add(weighted(d.weight_b, d.samples_b[0]));
+                    d.weight_b = update_weight_clip(d.weight_b, d.delta, d.samples_b[0], r);
+                    r = r2;
+                    if d.term == -3 {
+                        r2 = d.samples_a[0];
+                        d.samples_a[0] = r;
+                    }
+                    let l2 = l.wrapping_add(weighted(d.weight_a, r2));
+                    d.weight_a = update_weight_clip(d.weight_a, d.delta, r2, l);
+                    l = l2;
+                    d.samples_b[0] = l;
+                }
+            }
+            pos = (pos + 1) & 7;
+            if header.flags & JOINT_STEREO != 0 {
+                r = r.wrapping_sub(l >> 1);
+                l = l.wrapping_add(r);
+            }
+            crc = crc.wrapping_mul(3).wrapping_add(l as u32).wrapping_mul(3).wrapping_add(r as u32);
+            out.push(restore_int32(l, int32, header.flags));
+            out.push(restore_int32(r, int32, header.flags));
+        } else {
+            let mut s = entropy.get_value(&mut bits, 0)?;
+            for d in decorr.iter_mut() {
+                let (a, j) = if d.term > 8 {
+                    let (a, _) = d.extrapolate();
+                    d.samples_a[1] = d.samples_a[0];
+                    (a, 0)
+                } else {
+                    (d.samples_a[pos], (pos + d.term as usize) & 7)
+                };
+                let s2 = s.wrapping_add(weighted(d.weight_a, a));
+                d.weight_a = update_weight(d.weight_a, d.delta, a, s);
+                s = s2;
+                d.samples_a[j] = s;
+            }
+            pos = (pos + 1) & 7;
+            crc = crc.wrapping_mul(3).wrapping_add(s as u32);
+            out.push(restore_int32(s, int32, header.flags));
+        }
+    }
+    if crc != header.crc {
+        warn!("WavPack block CRC mismatch");
+    }
+    Ok(out)
+}
+
+impl Decorr {
+    /// Prediction for terms 17 and 18
+    fn extrapolate(&self) -> (i32, i32) {
+        match self.term & 1 == 1 {
+            true => (
+                self.samples_a[0].wrapping_mul(2).wrapping_sub(self.samples_a[1]),
+                self.samples_b[0].wrapping_mul(2).wrapping_sub(self.samples_b[1])
+            ),
+            false => (
+                self.samples_a[0].wrapping_mul(3).wrapping_sub(self.samples_a[1]) >> 1,
+                self.samples_b[0].wrapping_mul(3).wrapping_sub(self.samples_b[1]) >> 1
+            )
+        }
+    }
+}
+
+fn update_weight(weight: i32, delta: i32, sample: i32, value: i32) -> i32 {
+    if sample == 0 || value == 0 {
+        return weight;
+    }
+    match (sample ^ value) < 0 {
+        true => weight - delta,
+        false => weight + delta
+    }
+}
+
+fn update_weight_clip(weight: i32, delta: i32, sample: i32, value: i32) -> i32 {
+    update_weight(weight, delta, sample, value).clamp(-1024, 1024)
+}
+
+/// Weights are stored as 8 bit
+fn restore_weight(weight: i8) -> i32 {
+    let weight = weight as i32 * 8;
+    match weight > 0 {
+        true => weight + ((weight + 64) >> 7),
+        false => weight
+    }
+}
+
+/// Apply INT32_INFO (shift, and, or) and the block left shift
+fn restore_int32(sample: i32, (shift, and, or): (u32, u32, u32), flags: u32) -> i32 {
+    let s = sample as u32;
+    let bit = (s & and) | or;
+    let s = (s.wrapping_add(bit) << shift).wrapping_sub(bit);
+    (s << ((flags >> 13) & 0x1f)) as i32
+}
+
+/// Values stored as 8.8 bit log
+fn wp_exp2(value: i16) -> i32 {
+    let negative = value < 0;
+    let value = (value as i32).abs();
+    let mut res = EXP2_TABLE[(value & 0xff) as usize] as i32 | 0x100;
+    let exp = value >> 8;
+    if exp > 31 {
+        return i32::MIN;
+    }
+    res = if exp > 9 { res << (exp - 9) } else { res >> (9 - exp) };
+    if negative { -res } else { res }
+}
+
+/// Adaptive Golomb decoder state
+#[derive(Debug, Default)]
+struct Entropy {
+    median: [[u32; 3]; 2],
+    zero: bool,
+    one: bool,
+    zeroes: u32,
+}
+
+impl Entropy {
+    fn get_med(&self, channel: usize, n: usize) -> u32 {
+        (self.median[channel][n] >> 4) + 1
+    }
+
+    fn inc_med(&mut self, channel: usize, n: usize) {
+        let m = &mut self.median[channel][n];
+        *m = m.wrapping_add((m.wrapping_add(128 >> n) / (128 >> n)).wrapping_mul(5));
+    }
+
+    fn dec_med(&mut self, channel: usize, n: usize) {
+        let m = &mut self.median[channel][n];
+        *m = m.wrapping_sub((m.wrapping_add((128 >> n) - 2) / (128 >> n)).wrapping_mul(2));
+    }
+
+    /// Read residual of channel
+    fn get_value(&mut self, bits: &mut BitReader, channel: usize) -> Result<i32, Error> {
+        // Run of zeroes
+        if self.median[0][0] < 2 && self.median[1][0] < 2 && !self.zero && !self.one {
+            if self.zeroes > 0 {
+                self.zeroes -= 1;
+                if self.zeroes > 0 {
+                    return Ok(0);
+                }
+            } else {
+                let mut t = bits.unary()?;
+                if t >= 2 {
+                    if t >= 32 {
+                        return Err(anyhow!("Invalid WavPack zero run"));
+                    }
+                    t = bits.read(t - 1)? | (1 << (t - 1));
+                }
+                self.zeroes = t;
+                if self.zeroes > 0 {
+                    self.median = [[0; 3]; 2];
+                    return Ok(0);
+                }
+            }
+        }
+
+        let t = if self.zero {
+            self.zero = false;
+            0
+        } else {
+            let mut t = bits.unary()?;
+            if t == 16 {
+                let t2 = bits.unary()?;
+                if t2 < 2 {
+                    t += t2;
+                } else {
+                    if t2 >= 32 {
+                        return Err(anyhow!("Invalid WavPack value"));
+                    }
+                    t += bits.read(t2 - 1)? | (1 << (t2 - 1));
+                }
+            }
+            let one = t & 1 == 1;
+            t = match self.one {
+                true => (t >> 1) + 1,
+                false => t >> 1
+            };
+            self.one = one;
+            self.zero = !one;
+            t
+        };
+
+        let (base, add) = match t {
+            0 => {
+                let add = self.get_med(channel, 0) - 1;
+                self.dec_med(channel, 0);
+                (0, add)
+            },
+            1 => {
+                let r = (self.get_med(channel, 0), self.get_med(channel, 1) - 1);
+                self.inc_med(channel, 0);
+                self.dec_med(channel, 1);
+                r
+            },
+            2 => {
+                let r = (self.get_med(channel, 0) + self.get_med(channel, 1), self.get_med(channel, 2) - 1);
+                self.inc_med(channel, 0);
+                self.inc_med(channel, 1);
+                self.dec_med(channel, 2);
+                r
+            },
+            _ => {
+                let base = self.get_med(channel, 0)
+                    .wrapping_add(self.get_med(channel, 1))
+                    .wrapping_add(self.get_med(channel, 2).wrapping_mul(t - 2));
+                let r = (base, self.get_med(channel, 2) - 1);
+                self.inc_med(channel, 0);
+                self.inc_med(channel, 1);
+                self.inc_med(channel, 2);
+                r
+            }
+        };
+        if add >= 0x2000000 {
+            return Err(anyhow!("Invalid WavPack value"));
+        }
+        let value = base.wrapping_add(bits.tail(add)?) as i32;
+        match bits.read(1)? == 1 {
+            true => Ok(!value),
+            false => Ok(value)
+        }
+    }
+}
+
+/// Little endian bit reader
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> BitReader<'a> {
+        BitReader { data, position: 0 }
+    }
+
+    fn bit(&mut self) -> Result<u32, Error> {
+        let byte = self.data.get(self.position >> 3).ok_or(anyhow!("Unexpected end of WavPack bitstream"))?;
+        let bit = (byte >> (self.position & 7)) & 1;
+        self.position += 1;
+        Ok(bit as u32)
+    }
+
+    /// Read n bits, first bit is the lowest
+    fn read(&mut self, n: u32) -> Result<u32, Error> {
+        let mut value = 0;
+        for i in 0..n {
+            value |= self.bit()? << i;
+        }
+        Ok(value)
+    }
+
+    /// Count of 1 bits up to 33
+    fn unary(&mut self) -> Result<u32, Error> {
+        let mut count = 0;
+        while count < 33 && self.bit()? == 1 {
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Value in range 0..=k
+    fn tail(&mut self, k: u32) -> Result<u32, Error> {
+        if k < 1 {
+            return Ok(0);
+        }
+        let p = 31 - k.leading_zeros();
+        let e = ((1u64 << (p + 1)) - k as u64 - 1) as u32;
+        let mut value = self.read(p)?;
+        if value >= e {
+            value = (value << 1) - e + self.bit()?;
+        }
+        Ok(value)
+    }
+}
+
+/// Fraction part of 2^x in 1/256
+const EXP2_TABLE: [u8; 256] = [
+    0x00, 0x01, 0x01, 0x02, 0x03, 0x03, 0x04, 0x05, 0x06, 0x06, 0x07, 0x08, 0x08, 0x09, 0x0a, 0x0b,
+    0x0b, 0x0c, 0x0d, 0x0e, 0x0e, 0x0f, 0x10, 0x10, 0x11, 0x12, 0x13, 0x13, 0x14, 0x15, 0x16, 0x16,
+    0x17, 0x18, 0x19, 0x19, 0x1a, 0x1b, 0x1c, 0x1d, 0x1d, 0x1e, 0x1f, 0x20, 0x20, 0x21, 0x22, 0x23,
+    0x24, 0x24, 0x25, 0x26, 0x27, 0x28, 0x28, 0x29, 0x2a, 0x2b, 0x2c, 0x2c, 0x2d, 0x2e, 0x2f, 0x30,
+    0x30, 0x31, 0x32, 0x33, 0x34, 0x35, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x3a, 0x3b, 0x3c, 0x3d,
+    0x3e, 0x3f, 0x40, 0x41, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x48, 0x49, 0x4a, 0x4b,
+    0x4c, 0x4d, 0x4e, 0x4f, 0x50, 0x51, 0x51, 0x52, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a,
+    0x5b, 0x5c, 0x5d, 0x5e, 0x5e, 0x5f, 0x60, 0x61, 0x62, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69,
+    0x6a, 0x6b, 0x6c, 0x6d, 0x6e, 0x6f, 0x70, 0x71, 0x72, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79,
+    0x7a, 0x7b, 0x7c, 0x7d, 0x7e, 0x7f, 0x80, 0x81, 0x82, 0x83, 0x84, 0x85, 0x87, 0x88, 0x89, 0x8a,
+    0x8b, 0x8c, 0x8d, 0x8e, 0x8f, 0x90, 0x91, 0x92, 0x93, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0x9b,
+    0x9c, 0x9d, 0x9f, 0xa0, 0xa1, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa8, 0xa9, 0xaa, 0xab, 0xac, 0xad,
+    0xaf, 0xb0, 0xb1, 0xb2, 0xb3, 0xb4, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xbc, 0xbd, 0xbe, 0xbf, 0xc0,
+    0xc2, 0xc3, 0xc4, 0xc5, 0xc6, 0xc8, 0xc9, 0xca, 0xcb, 0xcd, 0xce, 0xcf, 0xd0, 0xd2, 0xd3, 0xd4,
+    0xd6, 0xd7, 0xd8, 0xd9, 0xdb, 0xdc, 0xdd, 0xde, 0xe0, 0xe1, 0xe2, 0xe4, 0xe5, 0xe6, 0xe8, 0xe9,
+    0xea, 0xec, 0xed, 0xee, 0xf0, 0xf1, 0xf2, 0xf4, 0xf5, 0xf6, 0xf8, 0xf9, 0xfa, 0xfc, 0xfd, 0xff,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Little endian bit writer, mirror of `BitReader`
+    #[derive(Default)]
+    struct BitWriter {
+        data: Vec<u8>,
+        position: usize,
+    }
+
+    impl BitWriter {
+        fn bit(&mut self, bit: u32) {
+            if self.position >> 3 == self.data.len() {
+                self.data.push(0);
+            }
+            self.data[self.position >> 3] |= (bit as u8 & 1) << (self.position & 7);
+            self.position += 1;
+        }
+
+        fn write(&mut self, value: u32, n: u32) {
+            for i in 0..n {
+                self.bit((value >> i) & 1);
+            }
+        }
+
+        fn unary(&mut self, count: u32) {
+            (0..count).for_each(|_| self.bit(1));
+            self.bit(0);
+        }
+
+        /// Count with escape for values >= 2
+        fn count(&mut self, count: u32) {
+            if count < 2 {
+                return self.unary(count);
+            }
+            let n = 32 - count.leading_zeros();
+            self.unary(n);
+            self.write(count, n - 1);
+        }
+
+        fn tail(&mut self, k: u32, value: u32) {
+            if k < 1 {
+                return;
+            }
+            let p = 31 - k.leading_zeros();
+            let e = ((1u64 << (p + 1)) - k as u64 - 1) as u32;
+            if value < e {
+                self.write(value, p);
+            } else {
+                self.write((value + e) >> 1, p);
+                self.bit((value + e) & 1);
+            }
+        }
+    }
+
+    impl Entropy {
+        /// Magnitude class, base and tail range of value
+        fn class(&self, channel: usize, value: i32) -> (u32, u32, u32) {
+            let m = if value < 0 { !value } else { value } as u32;
+            let (med0, med1, med2) = (self.get_med(channel, 0), self.get_med(channel, 1), self.get_med(channel, 2));
+            if m < med0 {
+                (0, 0, med0 - 1)
+            } else if m - med0 < med1 {
+                (1, med0, med1 - 1)
+            } else {
+                let t = 2 + (m - med0 - med1) / med2;
+                (t, med0 + med1 + med2 * (t - 2), med2 - 1)
+            }
+        }
+
+        /// Median update done by the decoder after reading class
+        fn update(&mut self, channel: usize, t: u32) {
+            match t {
+                0 => self.dec_med(channel, 0),
+                1 => {
+                    self.inc_med(channel, 0);
+                    self.dec_med(channel, 1);
+                },
+                2 => {
+                    self.inc_med(channel, 0);
+                    self.inc_med(channel, 1);
+                    self.dec_med(channel, 2);
+                },
+                _ => (0..3).for_each(|n| self.inc_med(channel, n))
+            }
+        }
+    }
+
+    /// Encode (channel, residual) in decoding order, mirror of `Entropy::get_value`
+    fn encode_residuals(values: &[(usize, i32)]) -> Vec<u8> {
+        let mut entropy = Entropy::default();
+        let mut bits = BitWriter::default();
+        for (i, (channel, value)) in values.iter().copied().enumerate() {
+            if entropy.median[0][0] < 2 && entropy.median[1][0] < 2 && !entropy.zero && !entropy.one {
+                if entropy.zeroes > 0 {
+                    entropy.zeroes -= 1;
+                    if entropy.zeroes > 0 {
+                        continue;
+                    }
+                } else {
+                    let run = values[i..].iter().take_while(|(_, v)| *v == 0).count() as u32;
+                    bits.count(run);
+                    entropy.zeroes = run;
+                    if run > 0 {
+                        entropy.median = [[0; 3]; 2];
+                        continue;
+                    }
+                }
+            }
+
+            let (t, base, add) = entropy.class(channel, value);
+            if entropy.zero {
+                assert_eq!(t, 0);
+                entropy.zero = false;
+            } else {
+                // Lowest bit tells if the next class is 0
+                let mut next = Entropy { median: entropy.median, ..Default::default() };
+                next.update(channel, t);
+                let next_nonzero = values.get(i + 1).map(|(c, v)| next.class(*c, *v).0 > 0).unwrap_or(true);
+                let raw = (t - entropy.one as u32) * 2 + next_nonzero as u32;
+                if raw < 16 {
+                    bits.unary(raw);
+                } else {
+                    bits.unary(16);
+                    bits.count(raw - 16);
+                }
+                entropy.one = next_nonzero;
+                entropy.zero = !next_nonzero;
+            }
+            entropy.update(channel, t);
+            let m = if value < 0 { !value } else { value } as u32;
+            bits.tail(add, m - base);
+            bits.bit((value < 0) as u32);
+        }
+        bits.data
+    }
+
+    /// Residuals after decorrelation passes, inverse of `decode_block`
+    fn decorrelate(decorr: &mut [Decorr], samples: &[i32], stereo: bool) -> Vec<i32> {
+        let weighted = |weight: i32, sample: i32| ((weight as i64 * sample as i64 + 512) >> 10) as i32;
+        let channels = stereo as usize + 1;
+        let mut out = vec![];
+        let mut pos = 0;
+        for frame in samples.chunks_exact(channels) {
+            let mut frame = frame.to_vec();
+            for d in decorr.iter_mut().rev() {
+                let (a, b, j) = if d.term > 8 {
+                    let (a, b) = d.extrapolate();
+                    d.samples_a[1] = d.samples_a[0];
+                    d.samples_b[1] = d.samples_b[0];
+                    (a, b, 0)
+                } else {
+                    (d.samples_a[pos], d.samples_b[pos], (pos + d.term as usize) & 7)
+                };
+                let l = frame[0].wrapping_sub(weighted(d.weight_a, a));
+                d.weight_a = update_weight(d.weight_a, d.delta, a, l);
+                d.samples_a[j] = frame[0];
+                frame[0] = l;
+                if stereo {
+                    let r = frame[1].wrapping_sub(weighted(d.weight_b, b));
+                    d.weight_b = update_weight(d.weight_b, d.delta, b, r);
+                    d.samples_b[j] = frame[1];
+                    frame[1] = r;
+                }
+            }
+            pos = (pos + 1) & 7;
+            out.extend(frame);
+        }
+        out
+    }
+
+    /// Metadata sub-block, padded to words
+    fn sub_block(id: u8, data: &[u8]) -> Vec<u8> {
+        let words = data.len().div_ceil(2);
+        let odd = if data.len() % 2 == 1 { 0x40 } else { 0 };
+        let mut out = match words > 255 {
+            true => vec![id | odd | 0x80, words as u8, (words >> 8) as u8, (words >> 16) as u8],
+            false => vec![id | odd, words as u8]
+        };
+        out.extend(data);
+        out.resize(out.len() + data.len() % 2, 0);
+        out
+    }
+
+    /// Encode single WavPack block at 44.1kHz with terms 18, 17, 2, 1
+    fn encode_block(samples: &[i32], bytes_per_sample: u32, stereo: bool, joint: bool) -> Vec<u8> {
+        let channels = stereo as usize + 1;
+        let mut flags = (bytes_per_sample - 1) | INITIAL_BLOCK | 0x1000 | (9 << 23);
+        if !stereo {
+            flags |= MONO_FLAG;
+        }
+        // Mid / side
+        let mut input = samples.to_vec();
+        if joint {
+            flags |= JOINT_STEREO;
+            for frame in input.chunks_exact_mut(2) {
+                let l = frame[0].wrapping_sub(frame[1]);
+                frame[1] = frame[1].wrapping_add(l >> 1);
+                frame[0] = l;
+            }
+        }
+        let terms = [18, 17, 2, 1];
+        let mut decorr = terms.iter().map(|t| Decorr { term: *t, delta: 2, ..Default::default() }).collect::<Vec<_>>();
+        let residuals = decorrelate(&mut decorr, &input, stereo);
+        let values = residuals.iter().enumerate().map(|(i, v)| (i % channels, *v)).collect::<Vec<_>>();
+
+        let mut crc = 0xFFFFFFFFu32;
+        for s in samples {
+            crc = crc.wrapping_mul(3).wrapping_add(*s as u32);
+        }
+        let mut body = vec![];
+        // Terms are stored in reverse order, weights and samples are zero
+        body.extend(sub_block(ID_DECORR_TERMS, &terms.iter().rev().map(|t| (t + 5) as u8 | (2 << 5)).collect::<Vec<_>>()));
+        body.extend(sub_block(ID_ENTROPY_VARS, &vec![0; 6 * channels]));
+        body.extend(sub_block(ID_WV_BITSTREAM, &encode_residuals(&values)));
+
+        let count = (samples.len() / channels) as u32;
+        let mut block = b"wvpk".to_vec();
+        block.extend((body.len() as u32 + 24).to_le_bytes());
+        block.extend(0x410u16.to_le_bytes());
+        block.extend([0, 0]);
+        block.extend(count.to_le_bytes());
+        block.extend(0u32.to_le_bytes());
+        block.extend(count.to_le_bytes());
+        block.extend(flags.to_le_bytes());
+        block.extend(crc.to_le_bytes());
+        block.extend(body);
+        block
+    }
+
+    /// Deterministic noisy sine with a silent part
+    fn signal(len: usize, amplitude: f64, seed: u32) -> Vec<i32> {
+        let mut state = seed;
+        (0..len).map(|i| {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            let noise = (state >> 16) as f64 / 65536.0 - 0.5;
+            match i % 400 < 60 {
+                true => 0,
+                false => ((i as f64 * 0.05).sin() * amplitude + noise * amplitude * 0.1) as i32
+            }
+        }).collect()
+    }
+
+    fn interleave(l: &[i32], r: &[i32]) -> Vec<i32> {
+        l.iter().zip(r).flat_map(|(l, r)| [*l, *r]).collect()
+    }
+
+    fn decode(block: &[u8]) -> Vec<i32> {
+        let header = BlockHeader::parse(block).unwrap();
+        assert_eq!(header.size + 8, block.len());
+        decode_block(&header, &block[32..]).unwrap()
+    }
+
+    #[test]
+    fn test_decode_mono() {
+        let samples = signal(2000, 12000.0, 1);
+        assert_eq!(decode(&encode_block(&samples, 2, false, false)), samples);
+    }
+
+    #[test]
+    fn test_decode_stereo() {
+        let samples = interleave(&signal(2000, 12000.0, 1), &signal(2000, 9000.0, 2));
+        assert_eq!(decode(&encode_block(&samples, 2, true, false)), samples);
+    }
+
+    #[test]
+    fn test_decode_joint_stereo() {
+        let samples = interleave(&signal(2000, 30000.0, 3), &signal(2000, 28000.0, 4));
+        assert_eq!(decode(&encode_block(&samples, 2, true, true)), samples);
+    }
+
+    #[test]
+    fn test_decode_24bit() {
+        let samples = interleave(&signal(2000, 8_000_000.0, 5), &signal(2000, 6_000_000.0, 6));
+        let block = encode_block(&samples, 3, true, true);
+        assert_eq!(decode(&block), samples);
+
+        // Player output is 16 bit
+        let path = std::env::temp_dir().join("onetagger_wavpack_test.wv");
+        std::fs::write(&path, &block).unwrap();
+        let decoder = WavPackDecoder::load(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!((decoder.channels(), decoder.sample_rate()), (2, 44100));
+        assert_eq!(decoder.collect::<Vec<_>>(), samples.iter().map(|s| (s >> 8) as i16).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_invalid_int32_shift() {
+        let header = BlockHeader::parse(&encode_block(&[1, 2, 3], 2, false, false)).unwrap();
+        let block = sub_block(ID_INT32_INFO, &[0, 40, 0, 0]);
+        assert!(decode_block(&header, &block).is_err());
+    }
+}
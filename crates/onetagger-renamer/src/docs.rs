@@ -3,7 +3,7 @@ use serde::Serialize;
 use crate::ac::{SymbolDoc, DocParameter};
 
 lazy_static! {
-    pub static ref VARIABLES: [SymbolDoc; 30] = [
+    pub static ref VARIABLES: [SymbolDoc; 39] = [
         SymbolDoc::var("title", "Get the Title frame from tag.\n\n  Used tags:<br> **MP3**: `TIT2`<br> **FLAC**: `TITLE`<br> **MP4**: `©nam`"),
         SymbolDoc::var("artist", "Get the first Artist from tag.\n\n  Used tags:<br> **MP3**: `TPE1`<br> **FLAC**: `ARTIST`<br> **MP4**: `©ART`"),
         SymbolDoc::var("artists", "Get the Artists frame from tag.\n\n  Used tags:<br> **MP3**: `TPE1`<br> **FLAC**: `ARTIST`<br> **MP4**: `©ART`"),
@@ -32,6 +32,15 @@ lazy_static! {
         SymbolDoc::var("disknumber", "Get the Disc Number frame from tag.\n\n  Used tags:<br> **MP3**: `TPOS`<br> **FLAC**: `DISCNUMBER`<br> **MP4**: `disk`"),
         SymbolDoc::var("disc", "Get the Disc Number frame from tag.\n\n  Used tags:<br> **MP3**: `TPOS`<br> **FLAC**: `DISCNUMBER`<br> **MP4**: `disk`"),
         SymbolDoc::var("discnumber", "Get the Disc Number frame from tag.\n\n  Used tags:<br> **MP3**: `TPOS`<br> **FLAC**: `DISCNUMBER`<br> **MP4**: `disk`"),
+        SymbolDoc::var("composer", "Get the Composer frame from tag.\n\n  Used tags:<br> **MP3**: `TCOM`<br> **FLAC**: `COMPOSER`<br> **MP4**: `©wrt`"),
+        SymbolDoc::var("lyricist", "Get the Lyricist frame from tag.\n\n  Used tags:<br> **MP3**: `TEXT`<br> **FLAC**: `LYRICIST`<br> **MP4**: `com.apple.iTunes:LYRICIST`"),
+        SymbolDoc::var("conductor", "Get the Conductor frame from tag.\n\n  Used tags:<br> **MP3**: `TPE3`<br> **FLAC**: `CONDUCTOR`<br> **MP4**: `com.apple.iTunes:CONDUCTOR`"),
+        SymbolDoc::var("producer", "Get the Producer frame from tag.\n\n  Used tags:<br> **MP3**: `PRODUCER`<br> **FLAC**: `PRODUCER`<br> **MP4**: `com.apple.iTunes:PRODUCER`"),
+        SymbolDoc::var("comment", "Get the Comment frame from tag.\n\n  Used tags:<br> **MP3**: `COMM`<br> **FLAC**: `COMMENT`<br> **MP4**: `©cmt`"),
+        SymbolDoc::var("grouping", "Get the Grouping frame from tag.\n\n  Used tags:<br> **MP3**: `TIT1`<br> **FLAC**: `GROUPING`<br> **MP4**: `©grp`"),
+        SymbolDoc::var("copyright", "Get the Copyright frame from tag.\n\n  Used tags:<br> **MP3**: `TCOP`<br> **FLAC**: `COPYRIGHT`<br> **MP4**: `cprt`"),
+        SymbolDoc::var("work", "Get the Work frame from tag.\n\n  Used tags:<br> **MP3**: `TXXX:WORK`<br> **FLAC**: `WORK`<br> **MP4**: `©wrk`"),
+        SymbolDoc::var("movement", "Get the Movement frame from tag.\n\n  Used tags:<br> **MP3**: `MVNM`<br> **FLAC**: `MOVEMENTNAME`<br> **MP4**: `©mvn`"),
         SymbolDoc::var("path", "Path to the file"),
         SymbolDoc::var("abspath", "Absolute path to the file"),
 
@@ -42,7 +51,7 @@ lazy_static! {
         SymbolDoc::prop("last", "Get the last item in an array"),
     ];
 
-    pub static ref FUNCTIONS: [SymbolDoc; 17] = [
+    pub static ref FUNCTIONS: [SymbolDoc; 19] = [
         SymbolDoc::f("lower", "Convert all to lowercase", vec![]),
         SymbolDoc::f("lowercase", "Convert all to lowercase", vec![]),
         SymbolDoc::f("upper", "Convert all to uppercase", vec![]),
@@ -58,8 +67,10 @@ lazy_static! {
         SymbolDoc::f("join", "Join array into string with custom separator", vec![DocParameter::s("separator", true)]),
         SymbolDoc::f("parent", "Get parent folder of path", vec![]),
         SymbolDoc::f("filename", "Get file/folder name of path", vec![]),
-        SymbolDoc::f("camelot", "Convert key to camelot, or keep original", vec![DocParameter::s("value", true)]),
-        SymbolDoc::f("uncamelot", "Convert key from camelot, or keep original", vec![DocParameter::s("value", true)]),
+        SymbolDoc::f("camelot", "Convert key in any notation to camelot, or keep original", vec![]),
+        SymbolDoc::f("uncamelot", "Convert key from camelot, or keep original", vec![]),
+        SymbolDoc::f("musical", "Convert key in any notation to musical key, or keep original", vec![]),
+        SymbolDoc::f("key", "Convert key to notation: `musical`, `sharps`, `flats`, `camelot`, `openkey` or `traktor`", vec![DocParameter::s("notation", true)]),
     ];
 }
 
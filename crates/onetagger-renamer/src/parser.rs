@@ -1,6 +1,6 @@
 use std::path::Path;
 use onetagger_tag::Tag;
-use onetagger_tagger::{AudioFileInfo, Field};
+use onetagger_tagger::{AudioFileInfo, Field, Key, KeyNotation, convert_key};
 use pad::{PadStr, Alignment};
 use regex::Regex;
 
@@ -177,6 +177,14 @@ impl Data {
             Data::Array(a) => Data::Array(a.into_iter().map(|s| sanitize(s)).collect()),
         }
     }
+
+    /// Apply function to every value
+    pub fn map(&self, f: impl Fn(&str) -> String) -> Self {
+        match self {
+            Data::String(s) => Data::String(f(s)),
+            Data::Array(a) => Data::Array(a.iter().map(|s| f(s)).collect()),
+        }
+    }
 }
 
 /// Every token type should implement this
@@ -418,6 +426,15 @@ impl TokenVariable {
             "remixer" => Some(Field::Remixer),
             "total" | "tracktotal" => Some(Field::TrackTotal),
             "disc" | "disk" | "discnumber" | "disknumber" => Some(Field::DiscNumber),
+            "composer" => Some(Field::Composer),
+            "lyricist" => Some(Field::Lyricist),
+            "conductor" => Some(Field::Conductor),
+            "producer" => Some(Field::Producer),
+            "comment" => Some(Field::Comment),
+            "grouping" => Some(Field::Grouping),
+            "copyright" => Some(Field::Copyright),
+            "work" => Some(Field::Work),
+            "movement" => Some(Field::Movement),
             _ => None
         };
         if let Some(field) = field {
@@ -789,26 +806,28 @@ impl Token for TokenFunction {
             },
             // Conver to camelot
             "camelot" => {
-                match data {
-                    Data::String(s) => {
-                        Some(Data::String(onetagger_tagger::to_camelot(s).to_owned()))
-                    },
-                    Data::Array(a) => {
-                        Some(Data::Array(a.iter().map(|v| onetagger_tagger::to_camelot(v).to_owned()).collect()))
-                    }
-                }
+                Some(data.map(|v| convert_key(v, KeyNotation::Camelot)))
             },
             // Convert from camelot
             "uncamelot" => {
-                match data {
-                    Data::String(s) => {
-                        Some(Data::String(onetagger_tagger::from_camelot(s).to_owned()))
-                    },
-                    Data::Array(a) => {
-                        Some(Data::Array(a.iter().map(|v| onetagger_tagger::from_camelot(v).to_owned()).collect()))
+                Some(data.map(|v| Key::parse_camelot(v).map(|k| k.to_string()).unwrap_or(v.to_string())))
+            },
+            // Convert from any notation to musical key
+            "musical" => {
+                Some(data.map(|v| convert_key(v, KeyNotation::Musical)))
+            },
+            // Convert key to any notation
+            "key" => {
+                let notation = self.param_str(0, true)?;
+                let notation: KeyNotation = match notation.parse() {
+                    Ok(n) => n,
+                    Err(e) => {
+                        error!("{e}");
+                        return None;
                     }
-                }
-            }
+                };
+                Some(data.map(|v| convert_key(v, notation)))
+            },
             f => {
                 error!("Invalid function: {f}!");
                 None
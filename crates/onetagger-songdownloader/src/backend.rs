@@ -0,0 +1,266 @@
+use anyhow::{Error, bail};
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// Tool which can download a song by search query
+pub trait DownloadBackend: Send + Sync {
+    /// Name used in logs and results
+    fn name(&self) -> &str;
+
+    /// Download song into `output_folder`, report progress (0.0 - 1.0) through callback
+    fn download(&self, query: &str, output_folder: &Path, on_progress: &mut dyn FnMut(f32)) -> Result<(), Error>;
+}
+
+/// Configuration of a download backend
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum BackendConfig {
+    YtDlp,
+    Spotdl,
+    /// User defined command, `{query}` and `{output}` in arguments get replaced
+    #[serde(rename_all = "camelCase")]
+    Command { name: String, template: String },
+}
+
+impl BackendConfig {
+    /// Create the backend
+    pub fn build(&self) -> Box<dyn DownloadBackend> {
+        match self {
+            BackendConfig::YtDlp => Box::new(YtDlpBackend::default()),
+            BackendConfig::Spotdl => Box::new(SpotdlBackend::default()),
+            BackendConfig::Command { name, template } => Box::new(CommandBackend::new(name, template)),
+        }
+    }
+}
+
+/// yt-dlp with YouTube Music search
+pub struct YtDlpBackend {
+    pub binary: String,
+}
+
+impl Default for YtDlpBackend {
+    fn default() -> Self {
+        YtDlpBackend { binary: "yt-dlp".to_string() }
+    }
+}
+
+impl DownloadBackend for YtDlpBackend {
+    fn name(&self) -> &str {
+        "yt-dlp"
+    }
+
+    fn download(&self, query: &str, output_folder: &Path, on_progress: &mut dyn FnMut(f32)) -> Result<(), Error> {
+        let output_template = output_folder.join("%(title)s.%(ext)s").to_string_lossy().to_string();
+        let mut command = Command::new(&self.binary);
+        command.args([
+            "--extract-audio",
+            "--audio-format", "mp3",
+            "--audio-quality", "0",
+            "--embed-metadata",
+            "--default-search", "ytsearch",
+            // Progress on separate lines, so it can be parsed
+            "--newline",
+            "--no-warnings",
+            "-o", &output_template,
+            &format!("ytsearch:{} youtube music", query)
+        ]);
+        run_command(command, on_progress)
+    }
+}
+
+/// spotdl, searches Spotify and downloads from YouTube
+pub struct SpotdlBackend {
+    pub binary: String,
+}
+
+impl Default for SpotdlBackend {
+    fn default() -> Self {
+        SpotdlBackend { binary: "spotdl".to_string() }
+    }
+}
+
+impl DownloadBackend for SpotdlBackend {
+    fn name(&self) -> &str {
+        "spotdl"
+    }
+
+    fn download(&self, query: &str, output_folder: &Path, on_progress: &mut dyn FnMut(f32)) -> Result<(), Error> {
+        let mut command = Command::new(&self.binary);
+        command
+            .current_dir(output_folder)
+            .args([
+                "--output", "{artist} - {title}.{output-ext}",
+                "--bitrate", "320k",
+                "--threads", "1",
+                "--format", "mp3",
+                "--print-errors",
+                "--simple-tui",
+                "download",
+                query
+            ]);
+        run_command(command, on_progress)
+    }
+}
+
+/// User configured command
+pub struct CommandBackend {
+    name: String,
+    template: String,
+}
+
+impl CommandBackend {
+    /// Create new command backend, template is split on whitespace, use quotes to keep spaces
+    pub fn new(name: &str, template: &str) -> CommandBackend {
+        CommandBackend { name: name.to_string(), template: template.to_string() }
+    }
+
+    /// Get program and arguments with placeholders replaced
+    fn args(&self, query: &str, output_folder: &Path) -> Result<Vec<String>, Error> {
+        let output = output_folder.to_string_lossy();
+        let args = split_template(&self.template)
+            .into_iter()
+            .map(|a| a.replace("{query}", query).replace("{output}", &output))
+            .collect::<Vec<_>>();
+        if args.is_empty() {
+            bail!("Empty command template for backend {}", self.name);
+        }
+        Ok(args)
+    }
+}
+
+impl DownloadBackend for CommandBackend {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn download(&self, query: &str, output_folder: &Path, on_progress: &mut dyn FnMut(f32)) -> Result<(), Error> {
+        let args = self.args(query, output_folder)?;
+        let mut command = Command::new(&args[0]);
+        command.current_dir(output_folder).args(&args[1..]);
+        run_command(command, on_progress)
+    }
+}
+
+/// Split command template into arguments, respects double quotes
+fn split_template(template: &str) -> Vec<String> {
+    let mut args = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in template.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    args.push(std::mem::take(&mut current));
+                }
+            },
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        args.push(current);
+    }
+    args
+}
+
+/// Parse percentage from a line of tool output (`[download]  45.3% of 3.2MiB`)
+pub(crate) fn parse_progress(line: &str) -> Option<f32> {
+    let re = Regex::new(r"(\d{1,3}(?:\.\d+)?)%").unwrap();
+    let value = re.captures_iter(line).last()?[1].parse::<f32>().ok()?;
+    if value > 100.0 {
+        return None;
+    }
+    Some(value / 100.0)
+}
+
+/// Run command, parse progress from stdout and include the end of stderr in error
+fn run_command(mut command: Command, on_progress: &mut dyn FnMut(f32)) -> Result<(), Error> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Read stderr in separate thread to prevent the pipe from filling up
+    let stderr = child.stderr.take().unwrap();
+    let stderr_thread = thread::spawn(move || {
+        let mut lines = VecDeque::new();
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            if lines.len() == 5 {
+                lines.pop_front();
+            }
+            lines.push_back(line);
+        }
+        lines.into_iter().collect::<Vec<_>>().join("\n")
+    });
+
+    // Progress bars might use \r instead of new lines
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut buffer = vec![];
+    loop {
+        buffer.clear();
+        let read = stdout.by_ref().take(64 * 1024).read_until(b'\n', &mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        for line in String::from_utf8_lossy(&buffer).split('\r') {
+            if let Some(progress) = parse_progress(line) {
+                on_progress(progress);
+            }
+        }
+    }
+
+    let status = child.wait()?;
+    let stderr = stderr_thread.join().unwrap_or_default();
+    if !status.success() {
+        bail!("{} failed with status: {}: {}", command.get_program().to_string_lossy(), status, stderr.trim());
+    }
+    on_progress(1.0);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_progress() {
+        assert_eq!(parse_progress("[download]  12.5% of 3.21MiB at 1.00MiB/s ETA 00:02"), Some(0.125));
+        assert_eq!(parse_progress("[download] 100% of 3.21MiB"), Some(1.0));
+        assert_eq!(parse_progress("Downloading 2 of 3: 50%"), Some(0.5));
+        assert_eq!(parse_progress("[ExtractAudio] Destination: song.mp3"), None);
+        assert_eq!(parse_progress("150%"), None);
+    }
+
+    #[test]
+    fn test_command_args() {
+        let backend = CommandBackend::new("custom", r#"my-dl --out "{output}/file name.mp3" {query}"#);
+        let args = backend.args("Artist - Title", Path::new("/music")).unwrap();
+        assert_eq!(args, vec!["my-dl", "--out", "/music/file name.mp3", "Artist - Title"]);
+        assert!(CommandBackend::new("empty", " ").args("q", Path::new("/")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("fake-dl.sh");
+        std::fs::write(&script, "#!/bin/sh\nprintf '10%%\\r75.0%%\\n'\necho 'working' >&2\ntouch \"$2/$1.mp3\"\n").unwrap();
+        let backend = CommandBackend::new("fake", &format!("sh {} {{query}} {{output}}", script.display()));
+
+        let mut progress = vec![];
+        backend.download("Song", dir.path(), &mut |p| progress.push(p)).unwrap();
+        assert_eq!(progress, vec![0.1, 0.75, 1.0]);
+        assert!(dir.path().join("Song.mp3").exists());
+
+        // Error contains stderr
+        let backend = CommandBackend::new("fake", "sh -c \"echo broken >&2; exit 3\"");
+        let error = backend.download("Song", dir.path(), &mut |_| {}).unwrap_err();
+        assert!(error.to_string().contains("broken"));
+    }
+}
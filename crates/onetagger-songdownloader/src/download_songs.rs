@@ -1,11 +1,17 @@
 use anyhow::{Error, Result, bail};
 use std::path::{Path, PathBuf};
 use std::fs;
-use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use crossbeam_channel::{unbounded, Receiver, Sender};
 use log::{info, warn};
+use rand::Rng;
 use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
 use regex::Regex;
+use onetagger_library::{Library, LibraryQuery, TagFilter, FilterMode};
+use crate::backend::{BackendConfig, DownloadBackend};
 
 /// Song information structure for CSV/JSON output
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,15 +25,77 @@ pub struct SongInfo {
     pub match_confidence: f32,
 }
 
+/// Settings of the downloader
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadConfig {
+    /// Backends to try, in order
+    pub backends: Vec<BackendConfig>,
+    /// Amount of concurrent downloads
+    pub workers: usize,
+    /// How many times to retry a failed download
+    pub retries: u32,
+    /// Delay before first retry in ms, doubles with every retry (jittered)
+    pub retry_delay: u64,
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        DownloadConfig {
+            backends: vec![BackendConfig::YtDlp, BackendConfig::Spotdl],
+            workers: 2,
+            retries: 2,
+            retry_delay: 2000,
+        }
+    }
+}
+
+/// State of a single song download
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DownloadState {
+    Downloading,
+    /// All backends failed, waiting for next attempt
+    Retrying,
+    Downloaded,
+    /// Already exists in the folder
+    Skipped,
+    Failed,
+}
+
+/// Progress event of `download_song_list`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadProgress {
+    pub song: SongInfo,
+    pub state: DownloadState,
+    /// Progress of this song, 0.0 - 1.0
+    pub progress: f32,
+    pub backend: Option<String>,
+    pub attempt: u32,
+    pub error: Option<String>,
+    /// Index of the song, starting from 0
+    pub index: usize,
+    pub total: usize,
+}
+
+/// Final result of a single song
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadResult {
+    pub song: SongInfo,
+    pub state: DownloadState,
+    /// Backend which downloaded the song
+    pub backend: Option<String>,
+    pub attempts: u32,
+    pub files: Vec<PathBuf>,
+    pub error: Option<String>,
+}
+
 /// Download songs from a CSV/JSON file generated by query-url
-pub fn download_songs(csv_path: &Path, directory: &Path) -> Result<(), Error> {
+pub fn download_songs(csv_path: &Path, directory: &Path, config: &DownloadConfig, library: Option<Library>) -> Result<(), Error> {
     info!("Starting song download process");
     
-    // Check if directory exists
-    if !directory.exists() {
-        bail!("Directory does not exist: {:?}", directory);
-    }
-    
     // Read the CSV/JSON file
     let file_content = fs::read_to_string(csv_path)?;
     let songs: Vec<SongInfo> = if csv_path.extension().unwrap_or_default() == "json" {
@@ -38,7 +106,33 @@ pub fn download_songs(csv_path: &Path, directory: &Path) -> Result<(), Error> {
     };
     
     info!("Found {} songs in the input file", songs.len());
-    
+    let finished = Arc::new(Mutex::new(None));
+    for _ in download_song_list(songs, directory, config, library, finished.clone())? {}
+    let results: Vec<DownloadResult> = finished.lock().unwrap().take().unwrap_or_default();
+    let failed = results.iter().filter(|r| r.state == DownloadState::Failed).count();
+    info!("Downloaded {} songs, {} failed", results.len() - failed, failed);
+    Ok(())
+}
+
+/// Download songs into `directory/<video title>/` using a pool of workers.
+/// Progress is sent through the returned channel, results are saved into `finished` before it closes.
+/// `library` is used to skip songs which are already in it.
+pub fn download_song_list(
+    songs: Vec<SongInfo>,
+    directory: &Path,
+    config: &DownloadConfig,
+    library: Option<Library>,
+    finished: Arc<Mutex<Option<Vec<DownloadResult>>>>
+) -> Result<Receiver<DownloadProgress>, Error> {
+    // Check if directory exists
+    if !directory.exists() {
+        bail!("Directory does not exist: {:?}", directory);
+    }
+    let backends = config.backends.iter().map(|b| b.build()).collect::<Vec<_>>();
+    if backends.is_empty() {
+        bail!("No download backends configured");
+    }
+
     // Group songs by video title
     let mut songs_by_video: HashMap<String, Vec<SongInfo>> = HashMap::new();
     for song in songs {
@@ -49,6 +143,7 @@ pub fn download_songs(csv_path: &Path, directory: &Path) -> Result<(), Error> {
     }
     
     info!("Processing {} videos", songs_by_video.len());
+    let mut jobs = vec![];
     
     // Process each video
     for (video_title, songs) in songs_by_video {
@@ -70,69 +165,156 @@ pub fn download_songs(csv_path: &Path, directory: &Path) -> Result<(), Error> {
         // Filter songs that were marked for download 
         // Logic fix: if downloaded flag is false, it means we should download it
         // We also respect folder_existed - if folder already exists, we skip songs
-        let songs_to_download: Vec<&SongInfo> = songs.iter()
+        let songs_to_download = songs.into_iter()
             .filter(|song| !song.downloaded && (!folder_existed || song.match_confidence > 0.5))
-            .collect();
+            .collect::<Vec<_>>();
         
-        if songs_to_download.is_empty() {
-            info!("No songs to download in this video");
-            continue;
-        }
-        
-        info!("Downloading {} songs for video: {}", songs_to_download.len(), video_title);
-        
-        // Download each song
-        for song in songs_to_download {
-            match download_song(song, &video_folder) {
-                Ok(_) => info!("Successfully downloaded: {} - {}", song.artist, song.song_title),
-                Err(e) => warn!("Failed to download song {} - {}: {}", song.artist, song.song_title, e),
-            }
-        }
-        
-        // Return to original directory for next video
-        info!("Completed processing video: {}", video_title);
+        info!("Queued {} songs for video: {}", songs_to_download.len(), video_title);
+        jobs.extend(songs_to_download.into_iter().map(|song| (song, video_folder.clone())));
     }
-    
-    info!("Song download process completed");
-    Ok(())
+
+    // Queue
+    let total = jobs.len();
+    let (job_tx, job_rx) = unbounded();
+    for (index, job) in jobs.into_iter().enumerate() {
+        job_tx.send((index, job)).ok();
+    }
+    drop(job_tx);
+
+    // Shared by the workers for the existence checks
+    let library = library.map(Mutex::new);
+
+    let (tx, rx) = unbounded();
+    let config = config.clone();
+    thread::spawn(move || {
+        let results = Mutex::new(vec![]);
+        thread::scope(|s| {
+            for _ in 0..config.workers.max(1) {
+                s.spawn(|| {
+                    for (index, (song, folder)) in job_rx.iter() {
+                        let result = download_song(&song, &folder, &backends, &config, library.as_ref(), &tx, (index, total));
+                        results.lock().unwrap().push((index, result));
+                    }
+                });
+            }
+        });
+
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|(index, _)| *index);
+        info!("Song download process completed");
+        *finished.lock().unwrap() = Some(results.into_iter().map(|(_, r)| r).collect());
+        // Channel gets closed after the results are available
+        drop(tx);
+    });
+
+    Ok(rx)
 }
 
-/// Download a single song using yt-dlp or spotdl
-fn download_song(song: &SongInfo, output_folder: &Path) -> Result<(), Error> {
+/// List files in folder (not recursive)
+fn list_files(folder: &Path) -> Result<Vec<PathBuf>, Error> {
+    Ok(fs::read_dir(folder)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .collect())
+}
+
+/// Download a single song, trying all backends, with retries
+fn download_song(
+    song: &SongInfo,
+    output_folder: &Path,
+    backends: &[Box<dyn DownloadBackend>],
+    config: &DownloadConfig,
+    library: Option<&Mutex<Library>>,
+    tx: &Sender<DownloadProgress>,
+    (index, total): (usize, usize)
+) -> DownloadResult {
     let song_query = format!("{} - {}", song.artist, song.song_title);
     info!("Downloading song: {}", song_query);
+    let send = |state, progress, backend: Option<&str>, attempt, error: Option<String>| {
+        tx.send(DownloadProgress {
+            song: song.clone(), state, progress, backend: backend.map(String::from), attempt, error, index, total
+        }).ok();
+    };
+    let result = |state, backend: Option<&str>, attempts, files, error| {
+        send(state, 1.0, backend, attempts, None);
+        DownloadResult { song: song.clone(), state, backend: backend.map(String::from), attempts, files, error }
+    };
     
     // Sanitize the song query to make it safe for command line
     let safe_query = sanitize_query(&song_query);
     
     // Check if song already exists in the folder
-    let song_exists = check_if_song_exists(output_folder, &song.artist, &song.song_title)?;
-    if song_exists {
-        info!("Song already exists in folder, skipping: {}", song_query);
-        return Ok(());
+    match check_if_song_exists(output_folder, &song.artist, &song.song_title, library) {
+        Ok(true) => {
+            info!("Song already exists in folder, skipping: {}", song_query);
+            return result(DownloadState::Skipped, None, 0, vec![], None);
+        },
+        Ok(false) => {},
+        Err(e) => warn!("Failed checking if song exists: {}", e),
     }
-    
-    // Try to download using yt-dlp with YouTube Music
-    info!("Attempting to download with yt-dlp: {}", safe_query);
-    let result = download_with_ytdlp(&safe_query, output_folder);
-    
-    if result.is_err() {
-        // Fallback to spotdl if yt-dlp fails
-        warn!("yt-dlp failed for {}, trying spotdl", safe_query);
-        match download_with_spotdl(&safe_query, output_folder) {
-            Ok(_) => {
-                info!("Successfully downloaded with spotdl: {}", safe_query);
-            },
-            Err(e) => {
-                warn!("Both yt-dlp and spotdl failed for {}: {}", safe_query, e);
-                return Err(e);
+
+    let mut error = String::new();
+    for attempt in 1..=config.retries + 1 {
+        for backend in backends {
+            info!("Attempting to download with {}: {}", backend.name(), safe_query);
+            send(DownloadState::Downloading, 0.0, Some(backend.name()), attempt, None);
+            match download_with_backend(backend.as_ref(), &safe_query, output_folder, &mut |p| {
+                send(DownloadState::Downloading, p, Some(backend.name()), attempt, None)
+            }) {
+                Ok(files) => {
+                    info!("Successfully downloaded with {}: {}", backend.name(), safe_query);
+                    return result(DownloadState::Downloaded, Some(backend.name()), attempt, files, None);
+                },
+                Err(e) => {
+                    warn!("{} failed for {}: {}", backend.name(), safe_query, e);
+                    error = format!("{}: {}", backend.name(), e);
+                }
             }
         }
-    } else {
-        info!("Successfully downloaded with yt-dlp: {}", safe_query);
+
+        // Backoff, jittered so workers don't retry at the same time
+        if attempt <= config.retries {
+            let delay = config.retry_delay.saturating_mul(2u64.saturating_pow(attempt - 1)) * rand::thread_rng().gen_range(50..=100) / 100;
+            info!("Retrying {} in {}ms", safe_query, delay);
+            send(DownloadState::Retrying, 0.0, None, attempt, Some(error.clone()));
+            thread::sleep(Duration::from_millis(delay));
+        }
     }
-    
-    Ok(())
+
+    warn!("All download attempts failed for {}: {}", safe_query, error);
+    send(DownloadState::Failed, 1.0, None, config.retries + 1, Some(error.clone()));
+    DownloadResult { song: song.clone(), state: DownloadState::Failed, backend: None, attempts: config.retries + 1, files: vec![], error: Some(error) }
+}
+
+/// Download into temporary folder first, so the created files are known even with concurrent downloads
+fn download_with_backend(backend: &dyn DownloadBackend, query: &str, output_folder: &Path, on_progress: &mut dyn FnMut(f32)) -> Result<Vec<PathBuf>, Error> {
+    let temp = tempfile::Builder::new().prefix(".download-").tempdir_in(output_folder)?;
+    backend.download(query, temp.path(), on_progress)?;
+
+    let mut files = vec![];
+    for file in list_files(temp.path())? {
+        let target = unique_path(output_folder, &file);
+        fs::rename(&file, &target)?;
+        files.push(target);
+    }
+    if files.is_empty() {
+        bail!("{} didn't create any file", backend.name());
+    }
+    Ok(files)
+}
+
+/// Path for file in folder which doesn't exist yet, ` (n)` is added to the name if needed
+fn unique_path(folder: &Path, file: &Path) -> PathBuf {
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let ext = file.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let mut target = folder.join(format!("{stem}{ext}"));
+    let mut n = 1;
+    while target.exists() {
+        target = folder.join(format!("{stem} ({n}){ext}"));
+        n += 1;
+    }
+    target
 }
 
 /// Sanitize a query for command line safety
@@ -152,10 +334,19 @@ fn sanitize_query(query: &str) -> String {
 }
 
 /// Check if a song already exists in the folder
-fn check_if_song_exists(folder: &Path, artist: &str, title: &str) -> Result<bool, Error> {
+fn check_if_song_exists(folder: &Path, artist: &str, title: &str, library: Option<&Mutex<Library>>) -> Result<bool, Error> {
     if !folder.exists() {
         return Ok(false);
     }
+
+    // Check tags using library index
+    if let Some(library) = library {
+        match check_library(&library.lock().unwrap(), folder, artist, title) {
+            Ok(true) => return Ok(true),
+            Ok(false) => {},
+            Err(e) => warn!("Failed checking library: {}", e),
+        }
+    }
     
     // Get all mp3 files in the folder
     let entries = fs::read_dir(folder)?;
@@ -179,54 +370,19 @@ fn check_if_song_exists(folder: &Path, artist: &str, title: &str) -> Result<bool
     Ok(false)
 }
 
-/// Download a song using yt-dlp
-fn download_with_ytdlp(song_query: &str, output_folder: &Path) -> Result<(), Error> {
-    let output_template = output_folder.join("%(title)s.%(ext)s").to_string_lossy().to_string();
-    
-    // Build yt-dlp command with YouTube Music search
-    let status = Command::new("yt-dlp")
-        .args([
-            "--extract-audio",
-            "--audio-format", "mp3",
-            "--audio-quality", "0",
-            "--embed-metadata",
-            "--default-search", "ytsearch",
-            "--quiet",
-            "--no-warnings",
-            "-o", &output_template,
-            &format!("ytsearch:{} youtube music", song_query)
-        ])
-        .status()?;
-    
-    if !status.success() {
-        bail!("yt-dlp command failed with status: {}", status);
-    }
-    
-    Ok(())
-}
-
-/// Download a song using spotdl
-fn download_with_spotdl(song_query: &str, output_folder: &Path) -> Result<(), Error> {
-    // Build spotdl command 
-    let status = Command::new("spotdl")
-        .current_dir(output_folder)
-        .args([
-            "--output", "{artist} - {title}.{output-ext}",
-            "--output-format", "mp3",
-            "--bitrate", "320k",
-            "--threads", "1",
-            "--format", "mp3",
-            "--print-errors",
-            "download",
-            song_query
-        ])
-        .status()?;
-    
-    if !status.success() {
-        bail!("spotdl command failed with status: {}", status);
+/// Check if folder contains song with matching tags, only if the folder is indexed.
+/// Index might be outdated, so the files have to still exist.
+fn check_library(library: &Library, folder: &Path, artist: &str, title: &str) -> Result<bool, Error> {
+    if !library.is_indexed(folder)? {
+        return Ok(false);
     }
-    
-    Ok(())
+    let filter = |tag: &str, value: &str| TagFilter { tag: tag.to_string(), value: value.to_string(), mode: FilterMode::Contains };
+    let query = LibraryQuery {
+        path: Some(folder.to_owned()),
+        filters: vec![filter("artist", artist), filter("title", title)],
+        ..Default::default()
+    };
+    Ok(library.query(&query)?.iter().any(|f| f.path.exists()))
 }
 
 /// Parse CSV content into SongInfo structs
@@ -269,6 +425,13 @@ pub fn generate_output_file(
     directory: Option<&Path>,
     format: &str
 ) -> Result<PathBuf, Error> {
+    let songs = url_info_songs(url_info, directory);
+    write_output_file(&songs, url_info, output_path, format)
+}
+
+/// Convert query-url results into list of songs.
+/// If `directory` is set, songs of videos which already have a folder there are marked as downloaded
+pub fn url_info_songs(url_info: &crate::UrlInfo, directory: Option<&Path>) -> Vec<SongInfo> {
     let mut songs = Vec::new();
     
     // Process videos and their tracklists
@@ -285,10 +448,9 @@ pub fn generate_output_file(
             info!("Formatted video title: {}", folder_name);
             info!("Video title exists in directory: {}", folder_exists);
             
-            // Process each track in the tracklist
-            for track in tracklist {
-                // Extract artist and title
-                let (artist, title, timestamp) = parse_track(track);
+            // Process each track in the tracklist, unknown (ID) tracks can't be downloaded
+            for track in tracklist.iter().filter(|t| !t.is_id) {
+                let (artist, title) = (track.artist(), track.full_title());
                 
                 // Only add tracks that have both artist and title
                 if !artist.is_empty() && !title.is_empty() {
@@ -297,7 +459,7 @@ pub fn generate_output_file(
                         video_url: video_url.clone(),
                         song_title: title,
                         artist,
-                        timestamp,
+                        timestamp: track.timestamp.clone(),
                         // Downloaded flag is false when we want to download it
                         // If folder exists, we mark as true (downloaded), if not then false (needs download)
                         downloaded: folder_exists,
@@ -307,8 +469,11 @@ pub fn generate_output_file(
             }
         }
     }
-    
-    // Create output file
+    songs
+}
+
+/// Create output file from songs
+fn write_output_file(songs: &[SongInfo], url_info: &crate::UrlInfo, output_path: &Path, format: &str) -> Result<PathBuf, Error> {
     let output_file = if format == "json" {
         let path = output_path.join("query-url.json");
         let json = serde_json::to_string_pretty(&songs)?;
@@ -319,7 +484,7 @@ pub fn generate_output_file(
         let path = output_path.join("query-url.csv");
         let mut writer = csv::Writer::from_path(&path)?;
         
-        for song in &songs {
+        for song in songs {
             writer.serialize(song)?;
         }
         
@@ -338,61 +503,129 @@ pub fn generate_output_file(
     Ok(output_file)
 }
 
-/// Parse a track string into artist, title, and timestamp
-fn parse_track(track: &str) -> (String, String, Option<String>) {
-    // Check if the track is already in the "Artist - Title" format
-    if track.contains(" - ") {
-        // Extract timestamp if present at the beginning
-        let timestamp_regex = Regex::new(r"^(\d{1,2}:\d{2}(?::\d{2})?)").unwrap();
-        let timestamp = timestamp_regex.captures(track).map(|cap| cap[1].to_string());
-        
-        // Clean up the track string
-        let track_without_timestamp = timestamp_regex.replace(track, "").trim().to_string();
-        
-        // Split by dash to get artist and title
-        let parts: Vec<&str> = track_without_timestamp.split(" - ").collect();
-        if parts.len() >= 2 {
-            let artist_part = parts[0].trim();
-            let title_part = parts[1].trim();
-            
-            // Clean up timestamp prefix and other characters in artist name
-            let number_prefix_regex = Regex::new(r"^(\d+(?::\d+)?)\s*").unwrap();
-            let clean_artist = number_prefix_regex.replace(artist_part, "").trim().to_string();
-            
-            // Clean up html entities in both artist and title
-            let artist_clean = clean_artist
-                .replace("u0026", "&")
-                .replace("&amp;", "&")
-                .replace("&#39;", "'")
-                .trim().to_string();
-                
-            let title_clean = title_part
-                .replace("u0026", "&")
-                .replace("&amp;", "&")
-                .replace("&#39;", "'")
-                .trim().to_string();
-            
-            // Special case: if artist starts with comma, it's likely part of another field
-            let final_artist = if artist_clean.starts_with(',') {
-                artist_clean[1..].trim().to_string()
-            } else {
-                artist_clean
-            };
-            
-            // Final checks for empty fields
-            if final_artist.is_empty() || title_clean.is_empty() {
-                // Fallback to original parts if cleaning made fields empty
-                (parts[0].trim().to_string(), parts[1].trim().to_string(), timestamp)
-            } else {
-                (final_artist, title_clean, timestamp)
-            }
-        } else {
-            // Fallback if splitting didn't work as expected
-            (String::new(), track_without_timestamp, timestamp)
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn song(video: &str, artist: &str, title: &str) -> SongInfo {
+        SongInfo {
+            video_title: video.to_string(),
+            video_url: String::new(),
+            song_title: title.to_string(),
+            artist: artist.to_string(),
+            timestamp: None,
+            downloaded: false,
+            match_confidence: 1.0,
         }
-    } else {
-        // If no dash, try to identify if this is just an artist or just a title
-        // For now, default to treating the whole string as the title
-        (String::new(), track.trim().to_string(), None)
+    }
+
+    /// Fake backend binary, gets query and output folder as arguments
+    fn fake_backend(dir: &Path, name: &str, script: &str) -> BackendConfig {
+        let path = dir.join(format!("{name}.sh"));
+        fs::write(&path, format!("#!/bin/sh\n{script}\n")).unwrap();
+        BackendConfig::Command { name: name.to_string(), template: format!("sh \"{}\" {{query}} {{output}}", path.display()) }
+    }
+
+    fn run(songs: Vec<SongInfo>, directory: &Path, config: &DownloadConfig) -> (Vec<DownloadProgress>, Vec<DownloadResult>) {
+        let db = tempfile::tempdir().unwrap();
+        let library = Library::open(db.path().join("library.db")).unwrap();
+        let finished = Arc::new(Mutex::new(None));
+        let progress = download_song_list(songs, directory, config, Some(library), finished.clone()).unwrap().iter().collect();
+        let results = finished.lock().unwrap().take().unwrap();
+        (progress, results)
+    }
+
+    #[test]
+    fn test_download_pool() {
+        let bin = tempfile::tempdir().unwrap();
+        let output = tempfile::tempdir().unwrap();
+        let config = DownloadConfig {
+            backends: vec![fake_backend(bin.path(), "ok", "echo '[download]  50.0% of 1MiB'\ntouch \"$2/$1.mp3\"")],
+            workers: 3,
+            retries: 0,
+            retry_delay: 0,
+        };
+        let songs = vec![song("Set 1", "A", "One"), song("Set 1", "B", "Two"), song("Set 2", "C", "Three"), song("Set 2", "D", "Four")];
+        let (progress, results) = run(songs, output.path(), &config);
+
+        assert_eq!(results.len(), 4);
+        for result in &results {
+            assert_eq!(result.state, DownloadState::Downloaded);
+            assert_eq!(result.backend.as_deref(), Some("ok"));
+            assert_eq!(result.files.len(), 1);
+            assert!(result.files[0].exists());
+        }
+        assert!(output.path().join("Set 2").join("C - Three.mp3").exists());
+        // Temporary folders are removed
+        assert_eq!(fs::read_dir(output.path().join("Set 1")).unwrap().count(), 2);
+        // Parsed progress
+        assert!(progress.iter().any(|p| p.state == DownloadState::Downloading && p.progress == 0.5));
+        assert!(progress.iter().all(|p| p.total == 4));
+    }
+
+    #[test]
+    fn test_download_retry() {
+        let bin = tempfile::tempdir().unwrap();
+        let output = tempfile::tempdir().unwrap();
+        // Fails on first run
+        let counter = bin.path().join("counter");
+        let script = format!("if [ ! -f \"{0}\" ]; then touch \"{0}\"; echo 'HTTP Error 429' >&2; exit 1; fi\ntouch \"$2/$1.mp3\"", counter.display());
+        let config = DownloadConfig {
+            backends: vec![fake_backend(bin.path(), "flaky", &script)],
+            workers: 1,
+            retries: 2,
+            retry_delay: 1,
+        };
+        let (progress, results) = run(vec![song("Set", "A", "One")], output.path(), &config);
+
+        assert_eq!(results[0].state, DownloadState::Downloaded);
+        assert_eq!(results[0].attempts, 2);
+        let retry = progress.iter().find(|p| p.state == DownloadState::Retrying).unwrap();
+        assert!(retry.error.as_ref().unwrap().contains("HTTP Error 429"));
+    }
+
+    #[test]
+    fn test_download_fallback_and_failure() {
+        let bin = tempfile::tempdir().unwrap();
+        let output = tempfile::tempdir().unwrap();
+        let config = DownloadConfig {
+            backends: vec![
+                fake_backend(bin.path(), "broken", "echo 'not found' >&2; exit 1"),
+                // Succeeds without creating a file
+                fake_backend(bin.path(), "empty", "exit 0"),
+            ],
+            workers: 1,
+            retries: 1,
+            retry_delay: 1,
+        };
+        let (_, results) = run(vec![song("Set", "A", "One")], output.path(), &config);
+        assert_eq!(results[0].state, DownloadState::Failed);
+        assert_eq!(results[0].attempts, 2);
+        assert!(results[0].error.as_ref().unwrap().contains("didn't create any file"));
+
+        // Fallback to second backend
+        let config = DownloadConfig {
+            backends: vec![config.backends[0].clone(), fake_backend(bin.path(), "ok", "touch \"$2/$1.mp3\"")],
+            ..config
+        };
+        let (_, results) = run(vec![song("Set", "A", "One")], output.path(), &config);
+        assert_eq!(results[0].state, DownloadState::Downloaded);
+        assert_eq!(results[0].backend.as_deref(), Some("ok"));
+        assert_eq!(results[0].attempts, 1);
+
+        // Already exists
+        let (_, results) = run(vec![song("Set", "A", "One")], output.path(), &config);
+        assert_eq!(results[0].state, DownloadState::Skipped);
+    }
+
+    #[test]
+    fn test_download_keeps_existing() {
+        let bin = tempfile::tempdir().unwrap();
+        let output = tempfile::tempdir().unwrap();
+        fs::write(output.path().join("mix.mp3"), "original").unwrap();
+        let backend = fake_backend(bin.path(), "ok", "echo new > \"$2/mix.mp3\"").build();
+        let files = download_with_backend(backend.as_ref(), "mix", output.path(), &mut |_| {}).unwrap();
+        assert_eq!(files, vec![output.path().join("mix (1).mp3")]);
+        assert_eq!(fs::read_to_string(output.path().join("mix.mp3")).unwrap(), "original");
     }
 }
@@ -2,16 +2,28 @@ extern crate anyhow;
 
 use serde::{Serialize, Deserialize};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use crossbeam_channel::Receiver;
+use log::warn;
+use onetagger_library::Library;
 
 mod query_url;
+mod backend;
 mod download_songs;
 mod spotify;
 mod soundcloud;
+mod tracklist;
+mod tracklists1001;
+mod split;
 
 pub use query_url::{get_query_url, get_query_url_with_confidence};
-pub use download_songs::{download_songs, generate_output_file, SongInfo};
+pub use download_songs::{download_songs, download_song_list, generate_output_file, url_info_songs, SongInfo, DownloadConfig, DownloadProgress, DownloadResult, DownloadState};
+pub use backend::{DownloadBackend, BackendConfig, YtDlpBackend, SpotdlBackend, CommandBackend};
 pub use spotify::process_spotify;
 pub use soundcloud::process_soundcloud;
+pub use tracklist::{TracklistEntry, parse_tracklist};
+pub use tracklists1001::{process_1001tracklists, parse_tracklist_page};
+pub use split::{split_mix, plan_tracks, refine_boundaries, energy_envelope, generate_cue, SplitConfig, SplitMode, SplitTrack};
 
 /// Main struct for the SongDownloader functionality
 pub struct SongDownloader {
@@ -20,6 +32,9 @@ pub struct SongDownloader {
     pub directory: Option<PathBuf>,
     pub confidence: f32,
     pub output_format: String,
+    pub download_config: DownloadConfig,
+    /// Library database used to skip already owned songs
+    pub library: Option<PathBuf>,
 }
 
 impl SongDownloader {
@@ -30,6 +45,8 @@ impl SongDownloader {
             directory: None,
             confidence: 0.75,
             output_format: "csv".to_string(),
+            download_config: DownloadConfig::default(),
+            library: None,
         }
     }
     
@@ -57,6 +74,30 @@ impl SongDownloader {
         self
     }
     
+    /// Set the download backends, workers and retries
+    pub fn with_download_config(mut self, config: DownloadConfig) -> Self {
+        self.download_config = config;
+        self
+    }
+    
+    /// Set the library database used to skip already owned songs
+    pub fn with_library(mut self, path: &Path) -> Self {
+        self.library = Some(path.to_path_buf());
+        self
+    }
+
+    /// Open the library database if set
+    fn open_library(&self) -> Option<Library> {
+        let path = self.library.as_ref()?;
+        match Library::open(path) {
+            Ok(library) => Some(library),
+            Err(e) => {
+                warn!("Failed opening library: {e}");
+                None
+            }
+        }
+    }
+
     /// Query a URL and generate output file
     pub fn query_url(&self) -> Result<PathBuf, anyhow::Error> {
         println!("SongDownloader::query_url called");
@@ -87,7 +128,21 @@ impl SongDownloader {
     /// Download songs from a CSV/JSON file
     pub fn download_songs(&self, csv_path: &Path) -> Result<(), anyhow::Error> {
         let directory = self.directory.as_ref().ok_or_else(|| anyhow::anyhow!("Directory is required"))?;
-        download_songs(csv_path, directory)
+        download_songs(csv_path, directory, &self.download_config, self.open_library())
+    }
+
+    /// Query the URL and get list of songs without writing any output file
+    pub fn query_songs(&self) -> Result<(UrlInfo, Vec<SongInfo>), anyhow::Error> {
+        let url = self.url.as_ref().ok_or_else(|| anyhow::anyhow!("URL is required"))?;
+        let url_info = get_query_url_with_confidence(url, self.confidence)?;
+        let songs = url_info_songs(&url_info, self.directory.as_deref());
+        Ok((url_info, songs))
+    }
+
+    /// Start downloading list of songs into the directory, results are saved into `finished` once done
+    pub fn download_song_list(&self, songs: Vec<SongInfo>, finished: Arc<Mutex<Option<Vec<DownloadResult>>>>) -> Result<Receiver<DownloadProgress>, anyhow::Error> {
+        let directory = self.directory.as_ref().ok_or_else(|| anyhow::anyhow!("Directory is required"))?;
+        download_song_list(songs, directory, &self.download_config, self.open_library(), finished)
     }
 }
 
@@ -97,8 +152,10 @@ pub struct UrlInfo {
     pub content_type: String,
     pub title: String,
     pub description: Option<String>,
-    pub video_tracklists: Option<std::collections::HashMap<String, Vec<String>>>,
-    pub videos: Option<Vec<(String, String, Vec<String>)>>,
+    /// Parsed tracklists by video title
+    pub video_tracklists: Option<std::collections::HashMap<String, Vec<TracklistEntry>>>,
+    /// Title, URL, tracklist
+    pub videos: Option<Vec<(String, String, Vec<TracklistEntry>)>>,
     pub url: String,
 }
 
@@ -114,7 +171,7 @@ pub fn get_url_info_with_confidence(url: &str, confidence: f32) -> Result<UrlInf
 
 /// Download songs from a CSV/JSON file to a directory
 pub fn download_songs_from_file(csv_path: &Path, directory: &Path) -> Result<(), anyhow::Error> {
-    download_songs(csv_path, directory)
+    download_songs(csv_path, directory, &DownloadConfig::default(), Library::open_default().ok())
 }
 
 impl UrlInfo {
@@ -130,12 +187,12 @@ impl UrlInfo {
         }
     }
     
-    pub fn with_tracklists(mut self, tracklists: std::collections::HashMap<String, Vec<String>>) -> Self {
+    pub fn with_tracklists(mut self, tracklists: std::collections::HashMap<String, Vec<TracklistEntry>>) -> Self {
         self.video_tracklists = Some(tracklists);
         self
     }
     
-    pub fn with_videos(mut self, videos: Vec<(String, String, Vec<String>)>) -> Self {
+    pub fn with_videos(mut self, videos: Vec<(String, String, Vec<TracklistEntry>)>) -> Self {
         self.videos = Some(videos);
         self
     }
@@ -145,7 +202,7 @@ impl UrlInfo {
         self
     }
     
-    pub fn add_tracklist(mut self, video_title: String, tracklist: Vec<String>) -> Self {
+    pub fn add_tracklist(mut self, video_title: String, tracklist: Vec<TracklistEntry>) -> Self {
         let mut tracklists = self.video_tracklists.unwrap_or_default();
         tracklists.insert(video_title, tracklist);
         self.video_tracklists = Some(tracklists);
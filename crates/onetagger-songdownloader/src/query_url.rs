@@ -5,6 +5,7 @@ use std::collections::HashMap;
 use reqwest::blocking::{Client, ClientBuilder};
 use scraper::{Html, Selector};
 use crate::UrlInfo;
+use crate::tracklist::{TracklistEntry, parse_tracklist};
 use std::time::Duration;
 use std::process::Command;
 use std::io::Write;
@@ -52,6 +53,10 @@ pub fn get_query_url_with_confidence(url: &str, confidence: f32) -> Result<UrlIn
             println!("Processing SoundCloud URL: {}", url);
             crate::soundcloud::process_soundcloud(url, confidence)
         },
+        ("1001tracklists", _) => {
+            println!("Processing 1001Tracklists URL: {}", url);
+            crate::tracklists1001::process_1001tracklists(url, confidence)
+        },
         _ => {
             println!("Unsupported platform or content type: {}/{}", platform, content_type);
             bail!("Unsupported platform or content type")
@@ -61,7 +66,7 @@ pub fn get_query_url_with_confidence(url: &str, confidence: f32) -> Result<UrlIn
 
 /// Check if the URL is valid (YouTube, 1001tracklists, Spotify, or SoundCloud)
 fn is_valid_url(url: &str) -> bool {
-    let valid_domains = ["youtube.com", "youtu.be", "spotify.com", "soundcloud.com"];
+    let valid_domains = ["youtube.com", "youtu.be", "1001tracklists.com", "spotify.com", "soundcloud.com"];
     valid_domains.iter().any(|&domain| url.contains(domain))
 }
 
@@ -100,6 +105,13 @@ fn determine_platform_and_type(url: &str) -> Result<(String, String), Error> {
         } else {
             Ok(("soundcloud".to_string(), "track".to_string()))
         }
+    } else if url.contains("1001tracklists.com") {
+        // 1001Tracklists URL
+        if url.contains("/tracklist/") {
+            Ok(("1001tracklists".to_string(), "tracklist".to_string()))
+        } else {
+            bail!("Unsupported 1001Tracklists URL format, only tracklist pages are supported")
+        }
     } else {
         bail!("Unsupported URL platform")
     }
@@ -552,7 +564,7 @@ fn extract_video_links(document: &Html) -> Result<Vec<String>, Error> {
 }
 
 /// Process a single YouTube video
-fn process_single_video(client: &Client, url: &str) -> Result<(String, Vec<String>), Error> {
+fn process_single_video(client: &Client, url: &str) -> Result<(String, Vec<TracklistEntry>), Error> {
     // Fetch the video page using enhanced methods
     println!("Fetching video page: {}", url);
     let document = get_youtube_page(client, url)?;
@@ -580,7 +592,7 @@ fn process_single_video(client: &Client, url: &str) -> Result<(String, Vec<Strin
     println!("Video: {} - Premiered on {} - {} - {}", formatted_title, upload_date, views, url);
     
     // Try to extract tracklist from description
-    let mut tracklist = extract_tracklist_from_description(&description);
+    let mut tracklist = parse_tracklist(&description);
     
     // If no tracks found, try a more aggressive approach
     if tracklist.is_empty() {
@@ -603,7 +615,7 @@ fn process_single_video(client: &Client, url: &str) -> Result<(String, Vec<Strin
             // Only try with the new description if it's different or longer
             if js_description.len() > description.len() || js_description != description {
                 println!("Found different description with JavaScript, trying to extract tracklist...");
-                tracklist = extract_tracklist_from_description(&js_description);
+                tracklist = parse_tracklist(&js_description);
             }
         }
         
@@ -620,13 +632,10 @@ fn process_single_video(client: &Client, url: &str) -> Result<(String, Vec<Strin
                 if let Ok(meta_selector) = Selector::parse("meta[property='og:audio:title'], meta[property='og:audio:artist']") {
                     for element in document.select(&meta_selector) {
                         if let Some(content) = element.value().attr("content") {
-                            if content.contains("-") {
-                                // This might be a track in "Artist - Title" format
-                                let clean_track = clean_track_text(content);
-                                if !clean_track.is_empty() {
-                                    println!("Found track in meta tag: {}", clean_track);
-                                    tracklist.push(clean_track);
-                                }
+                            // This might be a track in "Artist - Title" format
+                            if let Some(entry) = TracklistEntry::parse(content) {
+                                println!("Found track in meta tag: {} - {}", entry.artist(), entry.full_title());
+                                tracklist.push(entry);
                             }
                         }
                     }
@@ -640,7 +649,7 @@ fn process_single_video(client: &Client, url: &str) -> Result<(String, Vec<Strin
                                 formatted_title, upload_date, views, url, tracklist.len());
     
     for (i, track) in tracklist.iter().enumerate() {
-        detailed_log.push_str(&format!("{}. {}\n", i + 1, track));
+        detailed_log.push_str(&format!("{}. {} - {}\n", i + 1, track.artist(), track.full_title()));
     }
     
     println!("{}", detailed_log);
@@ -973,477 +982,6 @@ fn extract_from_structured_data(document: &Html) -> Option<String> {
     None
 }
 
-/// Extract tracklist from video description
-fn extract_tracklist_from_description(description: &str) -> Vec<String> {
-    // For shorter descriptions, use the entire thing
-    // For longer descriptions, try to find the tracklist section
-    let tracklist_section = if description.len() < 500 {
-        description
-    } else if let Some(section) = find_tracklist_section(description) {
-        section
-    } else {
-        description
-    };
-    
-    info!("Analyzing text section. First 200 chars: {}", 
-          &tracklist_section[..min(200, tracklist_section.len())]);
-    
-    // Extract track entries
-    let mut tracks = Vec::new();
-    let mut seen_tracks = std::collections::HashSet::new();
-    
-    // First try the most structured tracklist pattern (numbered with timestamps)
-    let structured_tracks = extract_structured_tracklist(tracklist_section);
-    if !structured_tracks.is_empty() {
-        info!("Found {} tracks using structured pattern", structured_tracks.len());
-        return structured_tracks;
-    }
-    
-    // Now try more general timestamp patterns
-    // First try looking for timestamped lines
-    let timestamp_lines: Vec<_> = tracklist_section
-        .lines()
-        .filter(|line| line.contains(":"))
-        .filter(|line| {
-            let has_digits = line.chars().any(|c| c.is_digit(10));
-            let has_timestamp = Regex::new(r"\d+:\d+").unwrap().is_match(line);
-            has_digits && has_timestamp
-        })
-        .collect();
-    
-    info!("Found {} lines with timestamps", timestamp_lines.len());
-    for (i, line) in timestamp_lines.iter().enumerate().take(5) {
-        debug!("Timestamp line {}: {}", i+1, line);
-    }
-    
-    // Special handling for YouTube descriptions - look for known patterns
-    for line in tracklist_section.lines() {
-        // First look for numbered entries with timestamps and artist/title
-        // Pattern like: "1. 00:00 Artist - Title" or "1) 00:00 Artist - Title"
-        if line.contains(":") && 
-           (line.contains(" - ") || line.contains("- ") || line.contains(" -")) && 
-           (line.contains(".") || line.contains(")") || Regex::new(r"^\s*\d+\s").unwrap().is_match(line)) && 
-           Regex::new(r"\d{1,2}:\d{2}(?::\d{2})?").unwrap().is_match(line) {
-            
-            // Try to extract the artist/title part
-            if let Some(dash_pos) = line.find(" - ").or_else(|| line.find("- ")).or_else(|| line.find(" -")) {
-                // Find timestamp pattern with support for HH:MM:SS format
-                if let Some(timestamp_end) = Regex::new(r"\d{1,2}:\d{2}(?::\d{2})?").unwrap()
-                    .find_iter(line)
-                    .filter(|m| m.end() < dash_pos)
-                    .last()
-                    .map(|m| m.end()) {
-                    
-                    // Extract everything after timestamp
-                    let track_text = line[timestamp_end..].trim();
-                    
-                    // Clean it up
-                    let clean_track = clean_track_text(track_text);
-                    if !clean_track.is_empty() && !seen_tracks.contains(&clean_track) {
-                        debug!("Found track with timestamp-dash pattern: {}", clean_track);
-                        seen_tracks.insert(clean_track.clone());
-                        tracks.push(clean_track);
-                    }
-                }
-            }
-        }
-        // Also look for just timestamp followed by artist - title
-        else if line.contains(":") && 
-                (line.contains(" - ") || line.contains("- ") || line.contains(" -")) && 
-                Regex::new(r"\d{1,2}:\d{2}(?::\d{2})?\s+\S+").unwrap().is_match(line) {
-            
-            // Find timestamp pattern with support for HH:MM:SS format
-            if let Some(timestamp_match) = Regex::new(r"\d{1,2}:\d{2}(?::\d{2})?").unwrap()
-                .find_iter(line)
-                .last() {
-                
-                // Extract everything after the timestamp
-                let track_text = line[timestamp_match.end()..].trim();
-                
-                // Clean it up
-                let clean_track = clean_track_text(track_text);
-                if !clean_track.is_empty() && !seen_tracks.contains(&clean_track) {
-                    debug!("Found track with timestamp pattern: {}", clean_track);
-                    seen_tracks.insert(clean_track.clone());
-                    tracks.push(clean_track);
-                }
-            }
-        }
-    }
-    
-    // If we still don't have enough tracks, try more generic patterns
-    if tracks.len() < 5 {
-        info!("First attempt found too few tracks ({}), trying alternate patterns...", tracks.len());
-        
-        // Try looking for timestamps without artist-title structure
-        if timestamp_lines.len() >= 5 {
-            info!("Found {} timestamp lines, attempting to extract artist/title", timestamp_lines.len());
-            
-            // Look for Artist - Title patterns in each line with timestamp
-            for line in &timestamp_lines {
-                if let Some(timestamp_match) = Regex::new(r"\d{1,2}:\d{2}(?::\d{2})?").unwrap().find(line) {
-                    let after_timestamp = &line[timestamp_match.end()..].trim();
-                    
-                    // Try to find Artist - Title pattern
-                    let dash_pattern = Regex::new(r"([^-]+)-([^-]+)").unwrap();
-                    if let Some(caps) = dash_pattern.captures(after_timestamp) {
-                        if caps.len() >= 3 {
-                            let artist = caps.get(1).unwrap().as_str().trim();
-                            let title = caps.get(2).unwrap().as_str().trim();
-                            
-                            if !artist.is_empty() && !title.is_empty() {
-                                let track = format!("{} - {}", artist, title);
-                                let clean_track = clean_track_text(&track);
-                                
-                                if !clean_track.is_empty() && !seen_tracks.contains(&clean_track) {
-                                    debug!("Extracted track from timestamp line: {}", clean_track);
-                                    seen_tracks.insert(clean_track.clone());
-                                    tracks.push(clean_track);
-                                }
-                            }
-                        }
-                    } else if !after_timestamp.is_empty() {
-                        // If there's no dash pattern but there is content, use it as is
-                        let clean_text = clean_track_text(after_timestamp);
-                        if !clean_text.is_empty() && !seen_tracks.contains(&clean_text) {
-                            debug!("Extracted text after timestamp: {}", clean_text);
-                            seen_tracks.insert(clean_text.clone());
-                            tracks.push(clean_text);
-                        }
-                    }
-                }
-            }
-        }
-        
-        // Try extracting any artist-title pattern in the text
-        let artist_title_pattern = Regex::new(r"([A-Za-z0-9&\s,\.]+)\s+-\s+([A-Za-z0-9&\s,\.']+)").unwrap();
-        
-        for cap in artist_title_pattern.captures_iter(tracklist_section) {
-            if cap.len() >= 3 {
-                let artist = cap.get(1).unwrap().as_str().trim();
-                let title = cap.get(2).unwrap().as_str().trim();
-                
-                // Skip very short artist/title combos
-                if artist.len() > 2 && title.len() > 2 {
-                    let track = format!("{} - {}", artist, title);
-                    
-                    // Clean up
-                    let clean_track = clean_track_text(&track);
-                    if !seen_tracks.contains(&clean_track) {
-                        debug!("Found track with artist-title pattern: {}", clean_track);
-                        seen_tracks.insert(clean_track.clone());
-                        tracks.push(clean_track);
-                    }
-                }
-            }
-        }
-    }
-    
-    // If we still don't have enough tracks but we have lines with timestamps,
-    // make a desperate attempt to parse them
-    if tracks.len() < 5 && timestamp_lines.len() >= 5 {
-        info!("Still found few tracks, making another attempt with timestamp lines");
-        
-        // Just use the lines with timestamps directly, after minimal cleaning
-        for line in &timestamp_lines {
-            let clean_line = clean_basic_timestamp(line);
-            if !clean_line.is_empty() && !seen_tracks.contains(&clean_line) {
-                debug!("Using timestamp line directly: {}", clean_line);
-                seen_tracks.insert(clean_line.clone());
-                tracks.push(clean_line);
-            }
-        }
-    }
-    
-    // Print the number of tracks found
-    info!("{} tracks found", tracks.len());
-    tracks
-}
-
-/// Extract a well-structured tracklist with numbered entries
-fn extract_structured_tracklist(text: &str) -> Vec<String> {
-    let mut tracks = Vec::new();
-    let mut seen_tracks = std::collections::HashSet::new();
-    
-    // Look for the "Tracklist:" section
-    let tracklist_section = if let Some(idx) = text.to_lowercase().find("tracklist:") {
-        &text[idx..]
-    } else if let Some(idx) = text.to_lowercase().find("tracklist") {
-        &text[idx..]
-    } else {
-        text
-    };
-    
-    // Pattern for "1. 00:00 Artist - Title"
-    if let Ok(numbered_pattern) = Regex::new(r"(?m)^\s*(\d+)[\.\)]\s+(\d{1,2}:\d{2}(?::\d{2})?)\s+(.+)$") {
-        // If the pattern matches at least 3 times, we likely have a structured tracklist
-        let matches: Vec<_> = numbered_pattern.captures_iter(tracklist_section).collect();
-        
-        if matches.len() >= 3 {
-            for cap in matches {
-                if cap.len() >= 4 {
-                    // Extract the text after the timestamp
-                    let track_text = cap.get(3).unwrap().as_str().trim();
-                    
-                    // Clean it up
-                    let clean_track = clean_track_text(track_text);
-                    if !clean_track.is_empty() && !seen_tracks.contains(&clean_track) {
-                        seen_tracks.insert(clean_track.clone());
-                        tracks.push(clean_track);
-                    }
-                }
-            }
-        }
-    }
-    
-    tracks
-}
-
-/// Clean a timestamp line by removing the timestamp and basic noise
-fn clean_basic_timestamp(line: &str) -> String {
-    // Remove timestamp and leading track number if present
-    let timestamp_pattern = Regex::new(r"^\s*(?:\d+[\.\)]\s*)?\d{1,2}:\d{2}(?::\d{2})?\s*").unwrap();
-    let without_timestamp = timestamp_pattern.replace(line, "").to_string();
-    
-    // Try to clean it up a bit more
-    clean_track_text(&without_timestamp)
-}
-
-/// Find the tracklist section in a video description
-fn find_tracklist_section(description: &str) -> Option<&str> {
-    // Look for common tracklist section indicators, case insensitive
-    let tracklist_markers = [
-        "Tracklist:", "Track list:", "Tracks:", "Songs:", "Music:", "Playlist:", 
-        "Track List", "TRACKLIST", "00:00", "0:00", "1. ", "01. ",
-        "1) ", "01) ", "1 - ", "01 - ",
-    ];
-    
-    // Try to find explicit tracklist markers first
-    for marker in &tracklist_markers {
-        let marker_lower = marker.to_lowercase();
-        if let Some(pos) = description.to_lowercase().find(&marker_lower) {
-            // Check if this is a line starting with the marker to avoid false positives
-            let line_start = description[..pos].rfind('\n').map_or(0, |p| p + 1);
-            let prefix = &description[line_start..pos];
-            if prefix.trim().is_empty() || marker.starts_with(|c: char| c.is_numeric()) {
-                info!("Found tracklist section with marker: {}", marker);
-                return Some(&description[pos..]);
-            }
-        }
-    }
-    
-    // Check for numeric patterns that might indicate a tracklist
-    // Look for lines that start with numbers followed by timestamps
-    let timestamp_pattern = regex::Regex::new(r"(?m)^\s*\d+[.)\s]*\d{1,2}:\d{2}").ok()?;
-    if let Some(mat) = timestamp_pattern.find(description) {
-        info!("Found tracklist section with timestamp pattern at position {}", mat.start());
-        return Some(&description[mat.start()..]);
-    }
-    
-    // If we've tried everything and found nothing specific, return the whole description
-    // but log a warning
-    info!("No specific tracklist section found, using entire description");
-    Some(description)
-}
-
-/// Clean up track text
-fn clean_track_text(track: &str) -> String {
-    // Try to extract using the pattern
-    if let Some(clean_track) = extract_artist_title_with_remix(track) {
-        return clean_track;
-    }
-    
-    // If we couldn't extract using the pattern, do a more general cleanup
-    
-    // Remove timestamps and track numbers
-    let timestamp_re = Regex::new(r"^\s*(?:\d+[\.\):]?\s*)?\d{1,2}:\d{2}(?::\d{2})?\s+").unwrap();
-    let without_timestamp = timestamp_re.replace_all(track, "").to_string();
-    
-    // Also remove timestamps that might be embedded in the string
-    let embedded_timestamp_re = Regex::new(r"\s+\d{1,2}:\d{2}(?::\d{2})?\s+").unwrap();
-    let clean_timestamps = embedded_timestamp_re.replace_all(&without_timestamp, " ").to_string();
-    
-    // Try to handle common HTML entities
-    let decoded = clean_timestamps
-        .replace("u0026", "&")
-        .replace("&amp;", "&")
-        .replace("&#39;", "'")
-        .replace("&quot;", "\"");
-    
-    // Remove social media handles and other noise
-    let without_social = Regex::new(r"@\w+|\(@\w+\)|ÔÇ¬ÔÇ¼X?|X$|X\s*$").unwrap()
-        .replace_all(&decoded, "")
-        .to_string();
-    
-    // Extract remix information if present
-    let mut remix_info = String::new();
-    let remix_re = Regex::new(r"\(([^)]*(?:Remix|Mix|Dub|Edit)[^)]*)\)").unwrap();
-    if let Some(caps) = remix_re.captures(&without_social) {
-        if let Some(remix) = caps.get(1) {
-            remix_info = format!(" ({})", remix.as_str());
-        }
-    }
-    
-    // Remove all parenthetical content
-    let without_parentheses = Regex::new(r"\([^)]*\)").unwrap()
-        .replace_all(&without_social, "")
-        .to_string();
-    
-    // Fix common encoding issues
-    let fixed_encoding = without_parentheses
-        .replace("├©", "ø")
-        .replace("Sc├©tt", "Scott");
-    
-    // Try to extract artist and title if the track contains " - "
-    if fixed_encoding.contains(" - ") {
-        let parts: Vec<&str> = fixed_encoding.split(" - ").collect();
-        if parts.len() >= 2 {
-            let artist = parts[0].trim();
-            let title = parts[1].trim();
-            
-            // Check for timestamps in artist name and remove them
-            let artist_clean = timestamp_re.replace_all(artist, "").to_string();
-            
-            if !artist_clean.is_empty() && !title.is_empty() {
-                return format!("{} - {}{}", artist_clean, title, remix_info);
-            }
-        }
-    }
-    
-    // Normalize whitespace
-    let mut normalized = String::new();
-    let mut last_was_space = false;
-    
-    for c in fixed_encoding.trim().chars() {
-        if c.is_whitespace() {
-            if !last_was_space {
-                normalized.push(' ');
-                last_was_space = true;
-            }
-        } else {
-            normalized.push(c);
-            last_was_space = false;
-        }
-    }
-    
-    // Add back remix information if we found it
-    if !remix_info.is_empty() {
-        normalized + &remix_info
-    } else {
-        normalized
-    }
-}
-
-/// Extract artist and title with remix information
-fn extract_artist_title_with_remix(track: &str) -> Option<String> {
-    // Try to extract using the "Artist - Title (Remix)" pattern
-    let re = Regex::new(r"([A-Za-z0-9\s&,.]+)\s+-\s+([A-Za-z0-9\s',.]+)(\s+\([A-Za-z0-9\s',.]+(?:Remix|Mix|Dub|Edit)[A-Za-z0-9\s',.]*\))?").ok()?;
-    
-    if let Some(caps) = re.captures(track) {
-        if caps.len() >= 3 {
-            let artist = caps.get(1)?.as_str().trim();
-            let title = caps.get(2)?.as_str().trim();
-            
-            // Fix missing commas in artist names
-            let artist_fixed = fix_artist_names(artist);
-            
-            if !artist_fixed.is_empty() && !title.is_empty() {
-                // Include remix info if present
-                if let Some(remix) = caps.get(3) {
-                    return Some(format!("{} - {}{}", artist_fixed, title, remix.as_str()));
-                } else {
-                    return Some(format!("{} - {}", artist_fixed, title));
-                }
-            }
-        }
-    }
-    
-    None
-}
-
-/// Fix missing commas in artist names
-fn fix_artist_names(artist: &str) -> String {
-    // Common patterns where commas might be missing
-    let patterns = [
-        ("Aberton JazzedUp", "Aberton, JazzedUp"),
-        ("Julian Sanza Andre", "Julian Sanza, Andre"),
-        ("Big Miz & Bessa", "Big Miz & Bessa"),  // Already correct
-        ("Paolo Barbato Lee", "Paolo Barbato, Lee"),
-        ("Cpen JT", "Cpen, JT"),
-    ];
-    
-    for (pattern, replacement) in &patterns {
-        if artist.contains(pattern) {
-            return artist.replace(pattern, replacement);
-        }
-    }
-    
-    // Try to detect missing commas between names
-    // Look for patterns like "FirstName LastName FirstName" where a comma should be after LastName
-    let name_pattern = Regex::new(r"([A-Z][a-z]+)\s+([A-Z][a-z]+)\s+([A-Z][a-z]+)").ok();
-    if let Some(re) = name_pattern {
-        if let Some(caps) = re.captures(artist) {
-            if caps.len() >= 4 {
-                let first1 = caps.get(1).unwrap().as_str();
-                let last1 = caps.get(2).unwrap().as_str();
-                let first2 = caps.get(3).unwrap().as_str();
-                
-                return artist.replace(&format!("{} {} {}", first1, last1, first2), 
-                                     &format!("{} {}, {}", first1, last1, first2));
-            }
-        }
-    }
-    
-    artist.to_string()
-}
-
-/// Extract tracks line by line
-fn extract_tracks_line_by_line(text: &str, tracks: &mut Vec<String>, seen_tracks: &mut std::collections::HashSet<String>) {
-    // Split by lines and look for potential track entries
-    for line in text.lines() {
-        let trimmed = line.trim();
-        
-        // Skip empty lines or very short lines
-        if trimmed.is_empty() || trimmed.len() < 5 {
-            continue;
-        }
-        
-        // Skip lines that are likely not track entries
-        if !looks_like_track_entry(trimmed) {
-            continue;
-        }
-        
-        // Clean up the track text
-        let clean_track = clean_track_text(trimmed);
-        
-        // Only add unique tracks
-        if !seen_tracks.contains(&clean_track) {
-            seen_tracks.insert(clean_track.clone());
-            tracks.push(clean_track);
-        }
-    }
-}
-
-/// Check if a line looks like a track entry
-fn looks_like_track_entry(line: &str) -> bool {
-    // Check if the line contains a dash (common in "Artist - Title" format)
-    if line.contains(" - ") {
-        return true;
-    }
-    
-    // Check if the line starts with a number (possible track number)
-    if Regex::new(r"^\d+[\.\)]").unwrap().is_match(line) {
-        return true;
-    }
-    
-    // Check for timestamp pattern
-    if Regex::new(r"\d+:\d+").unwrap().is_match(line) {
-        return true;
-    }
-    
-    false
-}
-
 /// Convert a title to a valid folder name
 fn sanitize_filename(filename: &str) -> String {
     // Replace invalid characters with spaces
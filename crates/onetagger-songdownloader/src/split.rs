@@ -0,0 +1,301 @@
+use anyhow::{Error, bail};
+use log::{info, warn};
+use serde::{Serialize, Deserialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use onetagger_player::AudioSources;
+use onetagger_tag::{Tag, TagImpl, Field};
+use onetagger_tag::id3::ID3Chapter;
+use crate::tracklist::TracklistEntry;
+
+/// Length of a block of the energy envelope in ms
+const BLOCK_MS: u64 = 100;
+
+/// How to split the mix
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SplitMode {
+    /// Separate file for each track, cut without re-encoding using ffmpeg
+    Files,
+    /// Keep the mix as is, write `.cue` sheet (and ID3 chapters into MP3 if enabled)
+    Cue,
+}
+
+/// Mix splitting settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SplitConfig {
+    pub mode: SplitMode,
+    /// Move boundaries to the quietest point within this many seconds of the timestamp, 0 = disabled
+    pub refine_window: u64,
+    /// Where to save the tracks, defaults to folder named after the mix
+    pub output: Option<PathBuf>,
+    pub ffmpeg: String,
+    /// Write CHAP frames into the original mix in cue mode
+    #[serde(default)]
+    pub write_chapters: bool,
+}
+
+impl Default for SplitConfig {
+    fn default() -> Self {
+        SplitConfig {
+            mode: SplitMode::Files,
+            refine_window: 5,
+            output: None,
+            ffmpeg: "ffmpeg".to_string(),
+            write_chapters: false,
+        }
+    }
+}
+
+/// Single track of the mix, times in ms
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SplitTrack {
+    pub entry: TracklistEntry,
+    pub start: u64,
+    pub end: u64,
+}
+
+/// Calculate start and end of every track with timestamp
+pub fn plan_tracks(entries: &[TracklistEntry], duration: u64) -> Result<Vec<SplitTrack>, Error> {
+    let mut tracks: Vec<SplitTrack> = vec![];
+    for entry in entries {
+        let start = match entry.timestamp_seconds() {
+            Some(s) => s * 1000,
+            None => continue,
+        };
+        if start >= duration {
+            warn!("Timestamp {:?} is past the end of the mix, skipping {}", entry.timestamp, entry.full_title());
+            continue;
+        }
+        if let Some(last) = tracks.last() {
+            if start <= last.start {
+                warn!("Timestamp {:?} out of order, skipping {}", entry.timestamp, entry.full_title());
+                continue;
+            }
+        }
+        tracks.push(SplitTrack { entry: entry.clone(), start, end: duration });
+    }
+    if tracks.is_empty() {
+        bail!("Tracklist has no usable timestamps");
+    }
+
+    // Intro belongs to the first track
+    tracks[0].start = 0;
+    for i in 1..tracks.len() {
+        tracks[i - 1].end = tracks[i].start;
+    }
+    Ok(tracks)
+}
+
+/// Get RMS of every `BLOCK_MS` block of the audio file
+pub fn energy_envelope(path: impl AsRef<Path>) -> Result<Vec<f32>, Error> {
+    let source = AudioSources::from_path(path)?.get_source()?;
+    let block = (source.sample_rate() as u64 * source.channels() as u64 * BLOCK_MS / 1000).max(1) as usize;
+    let mut envelope = vec![];
+    let mut sum = 0.0f64;
+    let mut n = 0;
+    for sample in source {
+        sum += (sample as f64).powi(2);
+        n += 1;
+        if n == block {
+            envelope.push((sum / n as f64).sqrt() as f32);
+            sum = 0.0;
+            n = 0;
+        }
+    }
+    if n > 0 {
+        envelope.push((sum / n as f64).sqrt() as f32);
+    }
+    Ok(envelope)
+}
+
+/// Move track boundaries to the quietest block within `window` ms, keeping the order
+pub fn refine_boundaries(tracks: &mut [SplitTrack], envelope: &[f32], window: u64) {
+    for i in 1..tracks.len() {
+        let start = tracks[i].start;
+        // Don't cross neighbouring boundaries
+        let min = start.saturating_sub(window).max(tracks[i - 1].start + BLOCK_MS) / BLOCK_MS;
+        let max = (start + window).min(tracks[i].end.saturating_sub(BLOCK_MS)) / BLOCK_MS;
+        let quietest = (min..=max)
+            .filter_map(|b| envelope.get(b as usize).map(|e| (b, *e)))
+            .min_by(|a, b| a.1.total_cmp(&b.1)
+                // Prefer the closest block to the timestamp
+                .then((a.0 * BLOCK_MS).abs_diff(start).cmp(&(b.0 * BLOCK_MS).abs_diff(start))));
+        if let Some((block, _)) = quietest {
+            tracks[i].start = block * BLOCK_MS;
+            tracks[i - 1].end = tracks[i].start;
+        }
+    }
+}
+
+/// Split the mix using the timestamped tracklist, returns created files
+pub fn split_mix(path: impl AsRef<Path>, entries: &[TracklistEntry], title: &str, config: &SplitConfig) -> Result<Vec<PathBuf>, Error> {
+    let path = path.as_ref();
+    let duration = AudioSources::from_path(path)?.duration() as u64;
+    let mut tracks = plan_tracks(entries, duration)?;
+    if config.refine_window > 0 {
+        info!("Detecting track boundaries in {}", path.display());
+        let envelope = energy_envelope(path)?;
+        refine_boundaries(&mut tracks, &envelope, config.refine_window * 1000);
+    }
+    info!("Splitting {} into {} tracks", path.display(), tracks.len());
+
+    match config.mode {
+        SplitMode::Files => {
+            let output = config.output.clone().unwrap_or_else(|| path.with_extension(""));
+            fs::create_dir_all(&output)?;
+            split_files(path, &tracks, title, &output, &config.ffmpeg)
+        },
+        SplitMode::Cue => {
+            let cue_path = config.output.as_ref()
+                .map(|o| o.join(path.with_extension("cue").file_name().unwrap()))
+                .unwrap_or_else(|| path.with_extension("cue"));
+            fs::write(&cue_path, generate_cue(path, title, &tracks))?;
+            let mut files = vec![cue_path];
+            if !config.write_chapters {
+                return Ok(files);
+            }
+            // Chapters are only supported in ID3
+            match Tag::load_file(path, true) {
+                Ok(Tag::ID3(mut tag)) => {
+                    let chapters = tracks.iter().map(|t| ID3Chapter {
+                        start: t.start as u32,
+                        end: t.end as u32,
+                        title: t.entry.full_title(),
+                        artist: Some(t.entry.artist()),
+                    }).collect::<Vec<_>>();
+                    tag.set_chapters(&chapters);
+                    match tag.save_file(path) {
+                        Ok(_) => files.push(path.to_owned()),
+                        Err(e) => warn!("Failed writing chapters to {}: {e}", path.display()),
+                    }
+                },
+                Ok(_) => {},
+                Err(e) => warn!("Can't write chapters to {}: {e}", path.display()),
+            }
+            Ok(files)
+        }
+    }
+}
+
+/// Cut each track with ffmpeg and tag it
+fn split_files(path: &Path, tracks: &[SplitTrack], title: &str, output: &Path, ffmpeg: &str) -> Result<Vec<PathBuf>, Error> {
+    let ext = path.extension().unwrap_or_default().to_string_lossy().to_string();
+    let mut files = vec![];
+    for (i, track) in tracks.iter().enumerate() {
+        let name = format!("{:02} - {} - {}.{ext}", i + 1, track.entry.artist(), track.entry.full_title());
+        let file = output.join(sanitize_file_name(&name));
+        let status = Command::new(ffmpeg)
+            .args(["-y", "-loglevel", "error", "-i"])
+            .arg(path)
+            .args(["-ss", &format_seconds(track.start), "-to", &format_seconds(track.end)])
+            .args(["-map", "0:a", "-c", "copy", "-map_metadata", "-1"])
+            .arg(&file)
+            .stdin(Stdio::null())
+            .status()?;
+        if !status.success() {
+            bail!("ffmpeg failed with status: {status}");
+        }
+
+        // Tag
+        let mut tag_wrap = Tag::load_file(&file, true)?;
+        let tag = tag_wrap.tag_mut();
+        tag.set_field(Field::Artist, track.entry.artists.clone(), true);
+        tag.set_field(Field::Title, vec![track.entry.full_title()], true);
+        tag.set_field(Field::Album, vec![title.to_string()], true);
+        if let Some(label) = &track.entry.label {
+            tag.set_field(Field::Label, vec![label.to_string()], true);
+        }
+        tag.set_track_number(&(i + 1).to_string(), Some(tracks.len() as u16), true);
+        tag.save_file(&file)?;
+        files.push(file);
+    }
+    Ok(files)
+}
+
+/// Generate cue sheet for the mix
+pub fn generate_cue(path: &Path, title: &str, tracks: &[SplitTrack]) -> String {
+    let file_type = match path.extension().unwrap_or_default().to_ascii_lowercase().to_str() {
+        Some("mp3") => "MP3",
+        Some("aif") | Some("aiff") => "AIFF",
+        _ => "WAVE",
+    };
+    let mut cue = format!("TITLE \"{}\"\n", cue_escape(title));
+    cue.push_str(&format!("FILE \"{}\" {file_type}\n", cue_escape(&path.file_name().unwrap_or_default().to_string_lossy())));
+    for (i, track) in tracks.iter().enumerate() {
+        // Index is in mm:ss:ff, 75 frames per second
+        let frames = track.start * 75 / 1000;
+        cue.push_str(&format!("  TRACK {:02} AUDIO\n", i + 1));
+        cue.push_str(&format!("    TITLE \"{}\"\n", cue_escape(&track.entry.full_title())));
+        cue.push_str(&format!("    PERFORMER \"{}\"\n", cue_escape(&track.entry.artist())));
+        cue.push_str(&format!("    INDEX 01 {:02}:{:02}:{:02}\n", frames / 75 / 60, frames / 75 % 60, frames % 75));
+    }
+    cue
+}
+
+/// Cue sheet strings can't contain double quotes
+fn cue_escape(text: &str) -> String {
+    text.replace('"', "'")
+}
+
+/// Format ms as seconds for ffmpeg
+fn format_seconds(ms: u64) -> String {
+    format!("{}.{:03}", ms / 1000, ms % 1000)
+}
+
+/// Remove characters which are invalid in file names
+fn sanitize_file_name(name: &str) -> String {
+    name.chars().map(|c| match c {
+        '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+        c => c,
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracklist::parse_tracklist;
+
+    const TRACKLIST: &str = "Tracklist:\n0:30 Artist A - First\n5:00 Artist B - Second (Extended Mix)\n4:00 Wrong - Order\n10:00 Artist C & Artist D - Third\n";
+
+    #[test]
+    fn test_plan_tracks() {
+        let tracks = plan_tracks(&parse_tracklist(TRACKLIST), 900_000).unwrap();
+        assert_eq!(tracks.len(), 3);
+        assert_eq!((tracks[0].start, tracks[0].end), (0, 300_000));
+        assert_eq!((tracks[1].start, tracks[1].end), (300_000, 600_000));
+        assert_eq!((tracks[2].start, tracks[2].end), (600_000, 900_000));
+
+        // Past the end
+        assert_eq!(plan_tracks(&parse_tracklist(TRACKLIST), 400_000).unwrap().len(), 2);
+        assert!(plan_tracks(&parse_tracklist("Artist - Title\nArtist 2 - Title 2"), 1000).is_err());
+    }
+
+    #[test]
+    fn test_refine_boundaries() {
+        let mut tracks = plan_tracks(&parse_tracklist(TRACKLIST), 900_000).unwrap();
+        let mut envelope = vec![1000.0; 9000];
+        // Quiet gap 2s before the second timestamp, another one outside the window
+        envelope[2980] = 10.0;
+        envelope[2900] = 1.0;
+        refine_boundaries(&mut tracks, &envelope, 5000);
+        assert_eq!(tracks[1].start, 298_000);
+        assert_eq!(tracks[0].end, 298_000);
+        // No quiet part, closest to timestamp
+        assert_eq!(tracks[2].start, 600_000);
+    }
+
+    #[test]
+    fn test_generate_cue() {
+        let mut tracks = plan_tracks(&parse_tracklist(TRACKLIST), 900_000).unwrap();
+        tracks[2].start = 600_500;
+        let cue = generate_cue(Path::new("/music/My \"Mix\".mp3"), "My Mix", &tracks);
+        assert_eq!(cue, "TITLE \"My Mix\"\nFILE \"My 'Mix'.mp3\" MP3\n\
+            \x20 TRACK 01 AUDIO\n    TITLE \"First\"\n    PERFORMER \"Artist A\"\n    INDEX 01 00:00:00\n\
+            \x20 TRACK 02 AUDIO\n    TITLE \"Second (Extended Mix)\"\n    PERFORMER \"Artist B\"\n    INDEX 01 05:00:00\n\
+            \x20 TRACK 03 AUDIO\n    TITLE \"Third\"\n    PERFORMER \"Artist C & Artist D\"\n    INDEX 01 10:00:37\n");
+    }
+}
@@ -100,7 +100,7 @@ fn process_spotify_track(client: &AuthCodeSpotify, id: &str, url: &str) -> Resul
     
     // Create videos vector with a single entry (empty tracklist for individual tracks)
     let track_url = format!("https://open.spotify.com/track/{}", id);
-    let videos = vec![(title.clone(), track_url, Vec::<String>::new())];
+    let videos = vec![(title.clone(), track_url, Vec::new())];
     
     // Create UrlInfo
     let url_info = UrlInfo::new(
@@ -163,7 +163,7 @@ fn process_spotify_album(client: &AuthCodeSpotify, id: &str, url: &str) -> Resul
         let track_url = format!("https://open.spotify.com/track/{}", track.id.unwrap().id());
         
         // For album tracks, we use empty tracklist since they're individual tracks
-        videos.push((track_title, track_url, Vec::<String>::new()));
+        videos.push((track_title, track_url, Vec::new()));
     }
     
     // Create album artist string for the title
@@ -237,7 +237,7 @@ fn process_spotify_playlist(client: &AuthCodeSpotify, id: &str, url: &str) -> Re
                 let track_url = format!("https://open.spotify.com/track/{}", full_track.id.unwrap().id());
                 
                 // For playlist tracks, we use empty tracklist since they're individual tracks
-                videos.push((track_title, track_url, Vec::<String>::new()));
+                videos.push((track_title, track_url, Vec::new()));
             }
         }
     }
@@ -293,7 +293,7 @@ fn process_spotify_artist(client: &AuthCodeSpotify, id: &str, url: &str) -> Resu
         let track_url = format!("https://open.spotify.com/track/{}", track.id.unwrap().id());
         
         // For artist top tracks, we use empty tracklist since they're individual tracks
-        videos.push((track_title, track_url, Vec::<String>::new()));
+        videos.push((track_title, track_url, Vec::new()));
     }
     
     let artist_title = format!("Artist: {}", artist.name);
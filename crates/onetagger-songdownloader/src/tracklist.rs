@@ -0,0 +1,342 @@
+use log::{info, debug};
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+use std::collections::HashSet;
+
+lazy_static::lazy_static! {
+    /// Separator of artist and title
+    static ref SEPARATOR: Regex = Regex::new(r"\s+[-–—]{1,2}\s+").unwrap();
+    /// Trailing `[Label]`
+    static ref LABEL: Regex = Regex::new(r"^(.*?)\s*\[([^\[\]]+)\]$").unwrap();
+    /// `(feat. Artist)` in title
+    static ref FEAT: Regex = Regex::new(r"(?i)\s*[\(\[](?:feat\.?|ft\.?|featuring)\s+([^\)\]]+)[\)\]]").unwrap();
+    /// Collaboration separators, `x` only lowercase, because it is part of names such as `Sonic X Project`
+    static ref ARTIST_SEPARATOR: Regex = Regex::new(r"\s*,\s*|\s+(?:(?i:vs\.?|feat\.?|ft\.?|featuring)|x)\s+").unwrap();
+    /// `w/` at the start of line
+    static ref WITH_PREVIOUS: Regex = Regex::new(r"(?i)^w/\s*").unwrap();
+    /// `w/` between tracks on one line
+    static ref WITH_SPLIT: Regex = Regex::new(r"(?i)\s+w/\s+").unwrap();
+    /// `1.`, `01)`, `#1`
+    static ref NUMBER: Regex = Regex::new(r"^(?:#\d{1,3}\.?|\d{1,3}[\.\)])\s*").unwrap();
+    /// `01 -`
+    static ref NUMBER_DASH: Regex = Regex::new(r"^\d{1,3}\s*[-–—]\s+").unwrap();
+    static ref TIMESTAMP_LEADING: Regex = Regex::new(r"^[\[\(]?(\d{1,2}:\d{2}(?::\d{2})?)[\]\)]?(?:\s*[-–—|:.]\s*|\s+|$)").unwrap();
+    static ref TIMESTAMP_TRAILING: Regex = Regex::new(r"\s+[-–—|]?\s*[\[\(]?(\d{1,2}:\d{2}(?::\d{2})?)[\]\)]?$").unwrap();
+    /// Tracklist header line
+    static ref HEADER: Regex = Regex::new(r"(?im)^\W*(?:track\s*list(?:ing)?|tracks|songs|setlist|set list)\W*$|^\W*(?:track\s*list(?:ing)?|tracks|setlist)\s*:").unwrap();
+    /// Trailing text in brackets
+    static ref BRACKETS: Regex = Regex::new(r"^(.*?)\s*[\(\[]([^\(\)\[\]]+)[\)\]]\s*$").unwrap();
+    /// Words describing version of the track
+    static ref VERSION: Regex = Regex::new(r"(?i)\b(?:remix|mix|edit|dub|bootleg|rework|vip|version|flip|mashup|instrumental|acapella|remaster(?:ed)?|re-?edit|cover)\b").unwrap();
+}
+
+/// Single parsed track of a tracklist (video description, 1001Tracklists page...)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TracklistEntry {
+    /// Position in the mix as written (`4:20`, `1:02:30`), missing for `w/` tracks
+    pub timestamp: Option<String>,
+    pub artists: Vec<String>,
+    /// Title without version
+    pub title: String,
+    /// Remix / edit name without brackets
+    pub version: Option<String>,
+    pub label: Option<String>,
+    /// Unidentified track (`ID - ID`)
+    pub is_id: bool,
+}
+
+impl TracklistEntry {
+    /// Parse `Artist - Title (Version) [Label]` without any timestamp or numbering
+    pub fn parse(text: &str) -> Option<TracklistEntry> {
+        let text = clean_text(text);
+        if is_id_text(&text) {
+            return Some(TracklistEntry::id(None));
+        }
+
+        let m = SEPARATOR.find(&text)?;
+        let (artist, title) = (text[..m.start()].trim(), text[m.end()..].trim());
+        if artist.is_empty() || title.is_empty() || artist.len() > 100 {
+            return None;
+        }
+
+        // Label
+        let mut title = title.to_string();
+        let mut label = None;
+        if let Some(captures) = LABEL.captures(&title.clone()) {
+            if !is_version(&captures[2]) && !captures[1].is_empty() {
+                title = captures[1].to_string();
+                label = Some(captures[2].trim().to_string());
+            }
+        }
+
+        // Featured artists in title
+        let mut artists = split_artists(artist);
+        if let Some(captures) = FEAT.captures(&title.clone()) {
+            artists.extend(split_artists(&captures[1]));
+            title = FEAT.replace(&title, "").to_string();
+        }
+
+        let (title, version) = split_version(&title);
+        let is_id = artists.iter().all(|a| is_id_text(a)) || is_id_text(&title);
+        if artists.is_empty() || title.is_empty() {
+            return None;
+        }
+
+        Some(TracklistEntry { timestamp: None, artists, title, version, label, is_id })
+    }
+
+    /// Unknown track
+    fn id(timestamp: Option<String>) -> TracklistEntry {
+        TracklistEntry {
+            timestamp,
+            artists: vec!["ID".to_string()],
+            title: "ID".to_string(),
+            version: None,
+            label: None,
+            is_id: true,
+        }
+    }
+
+    /// Artists joined for display / search
+    pub fn artist(&self) -> String {
+        self.artists.join(", ")
+    }
+
+    /// Title including version
+    pub fn full_title(&self) -> String {
+        match &self.version {
+            Some(version) => format!("{} ({version})", self.title),
+            None => self.title.to_string(),
+        }
+    }
+
+    /// Timestamp converted to seconds
+    pub fn timestamp_seconds(&self) -> Option<u64> {
+        let parts = self.timestamp.as_ref()?.split(':').map(|p| p.parse::<u64>().ok()).collect::<Option<Vec<_>>>()?;
+        Some(parts.into_iter().fold(0, |acc, p| acc * 60 + p))
+    }
+}
+
+/// One line of text parsed into tracks
+#[derive(Debug, Clone)]
+struct ParsedLine {
+    timestamp: bool,
+    numbered: bool,
+    /// Line starts with `w/`
+    with_previous: bool,
+    entries: Vec<TracklistEntry>,
+}
+
+/// Parse tracklist from arbitrary text (usually video description)
+pub fn parse_tracklist(text: &str) -> Vec<TracklistEntry> {
+    let section = find_tracklist_section(text);
+    let lines = section.lines().filter_map(parse_line).collect::<Vec<_>>();
+
+    // Use the most specific style present in the text, so random lines with a dash are ignored
+    let timestamped = lines.iter().filter(|l| l.timestamp).count();
+    let numbered = lines.iter().filter(|l| l.numbered).count();
+    let filter: Box<dyn Fn(&ParsedLine) -> bool> = if timestamped >= 2 {
+        Box::new(|l| l.timestamp || l.with_previous)
+    } else if numbered >= 2 {
+        Box::new(|l| l.numbered || l.with_previous)
+    } else {
+        Box::new(|_| true)
+    };
+
+    let mut seen = HashSet::new();
+    let mut entries = vec![];
+    for entry in lines.into_iter().filter(|l| filter(l)).flat_map(|l| l.entries) {
+        // ID tracks can repeat
+        let key = format!("{}|{}", entry.artist(), entry.full_title()).to_lowercase();
+        if !entry.is_id && !seen.insert(key) {
+            debug!("Duplicate track: {} - {}", entry.artist(), entry.full_title());
+            continue;
+        }
+        entries.push(entry);
+    }
+
+    info!("{} tracks found", entries.len());
+    entries
+}
+
+/// Parse single line of tracklist: numbering, timestamp, `w/`, track(s)
+fn parse_line(line: &str) -> Option<ParsedLine> {
+    let mut line = clean_text(line);
+    if line.contains("http://") || line.contains("https://") {
+        return None;
+    }
+    // Bullets
+    line = line.trim_start_matches(|c: char| "•*►▶>~+".contains(c)).trim().to_string();
+
+    // `w/` = played together with previous track
+    let mut is_with_previous = false;
+    if WITH_PREVIOUS.is_match(&line) {
+        line = WITH_PREVIOUS.replace(&line, "").to_string();
+        is_with_previous = true;
+    }
+
+    let (numbered, rest) = strip_number(&line);
+    let (timestamp, rest) = strip_timestamp(&rest);
+    // Number after timestamp (`00:00 01. Artist - Title`)
+    let (numbered_after, rest) = strip_number(&rest);
+    let numbered = numbered || numbered_after;
+
+    // Split multiple tracks on one line
+    let mut entries = vec![];
+    for (i, part) in WITH_SPLIT.split(&rest).enumerate() {
+        let mut entry = match TracklistEntry::parse(part) {
+            Some(entry) => entry,
+            None => continue,
+        };
+        if i == 0 && !is_with_previous {
+            entry.timestamp = timestamp.clone();
+        }
+        entries.push(entry);
+    }
+    if entries.is_empty() {
+        return None;
+    }
+
+    Some(ParsedLine { timestamp: timestamp.is_some(), numbered, with_previous: is_with_previous, entries })
+}
+
+/// Remove track number prefix (`1.`, `01)`, `#1`, `01 -`)
+fn strip_number(line: &str) -> (bool, String) {
+    if NUMBER.is_match(line) {
+        return (true, NUMBER.replace(line, "").to_string());
+    }
+    // Might also be an artist name (`112 - Cupid`), only strip if another separator follows
+    if let Some(m) = NUMBER_DASH.find(line) {
+        let rest = &line[m.end()..];
+        if SEPARATOR.is_match(rest) {
+            return (true, rest.to_string());
+        }
+    }
+    (false, line.to_string())
+}
+
+/// Remove leading or trailing timestamp in any of the common styles:
+/// `00:00`, `0:00:00`, `[00:00]`, `(00:00)`, `00:00 -`, `00:00 |`, `Artist - Title 00:00`
+fn strip_timestamp(line: &str) -> (Option<String>, String) {
+    if let Some(captures) = TIMESTAMP_LEADING.captures(line) {
+        let rest = line[captures.get(0).unwrap().end()..].trim().to_string();
+        return (Some(captures[1].to_string()), rest);
+    }
+    if let Some(captures) = TIMESTAMP_TRAILING.captures(line) {
+        let rest = line[..captures.get(0).unwrap().start()].trim().to_string();
+        return (Some(captures[1].to_string()), rest);
+    }
+    (None, line.to_string())
+}
+
+/// Skip everything before the tracklist header if there is one
+fn find_tracklist_section(text: &str) -> &str {
+    match HEADER.find(text) {
+        Some(m) => &text[m.start()..],
+        None => text,
+    }
+}
+
+/// Split `Title (Version)` into title and version
+pub(crate) fn split_version(title: &str) -> (String, Option<String>) {
+    match BRACKETS.captures(title) {
+        Some(captures) if !captures[1].trim().is_empty() && is_version(&captures[2]) => {
+            (captures[1].trim().to_string(), Some(captures[2].trim().to_string()))
+        },
+        _ => (title.trim().to_string(), None)
+    }
+}
+
+/// Does the text in brackets describe a version of the track
+fn is_version(text: &str) -> bool {
+    VERSION.is_match(text)
+}
+
+/// Split artist string on `,`, `vs`, `x`, `feat.`, `ft.`
+pub(crate) fn split_artists(artist: &str) -> Vec<String> {
+    ARTIST_SEPARATOR
+        .split(artist)
+        .map(|a| a.trim().to_string())
+        .filter(|a| !a.is_empty())
+        .collect()
+}
+
+/// `ID`, `Unknown`, `???`
+fn is_id_text(text: &str) -> bool {
+    let text = text.trim();
+    text.eq_ignore_ascii_case("id") || text.eq_ignore_ascii_case("unknown") || text == "???"
+        || text.eq_ignore_ascii_case("id - id")
+}
+
+/// Decode entities (also the escaped `&` from YouTube JSON) and normalize whitespace
+fn clean_text(text: &str) -> String {
+    let text = text.replace("\\u0026", "&");
+    let text = html_escape::decode_html_entities(&text);
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Every `.txt` in fixtures has the expected result in `.json` with the same name
+    #[test]
+    fn test_description_fixtures() {
+        let folder = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join("descriptions");
+        let mut count = 0;
+        for entry in std::fs::read_dir(&folder).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().map(|e| e != "txt").unwrap_or(true) {
+                continue;
+            }
+            let text = std::fs::read_to_string(&path).unwrap();
+            let expected: Vec<TracklistEntry> = serde_json::from_str(&std::fs::read_to_string(path.with_extension("json")).unwrap()).unwrap();
+            assert_eq!(parse_tracklist(&text), expected, "Fixture: {}", path.display());
+            count += 1;
+        }
+        assert!(count > 0);
+    }
+
+    #[test]
+    fn test_parse_entry() {
+        let entry = TracklistEntry::parse("Artbat vs. Camelphat x Anyma - Closer (feat. Cristoph) (Extended Mix) [Afterlife]").unwrap();
+        assert_eq!(entry.artists, vec!["Artbat", "Camelphat", "Anyma", "Cristoph"]);
+        assert_eq!(entry.title, "Closer");
+        assert_eq!(entry.version.as_deref(), Some("Extended Mix"));
+        assert_eq!(entry.label.as_deref(), Some("Afterlife"));
+        assert!(!entry.is_id);
+
+        assert!(TracklistEntry::parse("ID - ID").unwrap().is_id);
+        assert!(TracklistEntry::parse("ID - ID (Fisher Remix)").unwrap().is_id);
+        assert!(TracklistEntry::parse("No separator here").is_none());
+    }
+
+    #[test]
+    fn test_timestamp_styles() {
+        for line in ["00:00 A - B", "[00:00] A - B", "(00:00) A - B", "00:00 - A - B", "00:00 | A - B", "1. 00:00 A - B", "A - B 00:00", "A - B [00:00]"] {
+            let parsed = parse_line(line).unwrap();
+            assert_eq!(parsed.entries[0].timestamp.as_deref(), Some("00:00"), "{line}");
+            assert_eq!(parsed.entries[0].artists, vec!["A"], "{line}");
+            assert_eq!(parsed.entries[0].title, "B", "{line}");
+        }
+        let entry = &parse_line("1:02:03 A - B").unwrap().entries[0];
+        assert_eq!(entry.timestamp_seconds(), Some(3723));
+    }
+
+    #[test]
+    fn test_split_artists() {
+        assert_eq!(split_artists("Martin Garrix x Dua Lipa"), vec!["Martin Garrix", "Dua Lipa"]);
+        assert_eq!(split_artists("Sonic X Project vs. Armin van Buuren"), vec!["Sonic X Project", "Armin van Buuren"]);
+        assert_eq!(split_artists("Malcolm X, Tiësto FEAT. Karol G"), vec!["Malcolm X", "Tiësto", "Karol G"]);
+        assert_eq!(clean_text("Above \\u0026 Beyond"), "Above & Beyond");
+        assert_eq!(clean_text("Tu0026A"), "Tu0026A");
+    }
+
+    #[test]
+    fn test_split_version() {
+        assert_eq!(split_version("Song (Extended Mix)"), ("Song".to_string(), Some("Extended Mix".to_string())));
+        assert_eq!(split_version("Song (Reprise)"), ("Song (Reprise)".to_string(), None));
+        assert_eq!(split_version("(Remix)"), ("(Remix)".to_string(), None));
+    }
+}
@@ -0,0 +1,202 @@
+use anyhow::{Error, bail};
+use log::{info, warn};
+use regex::Regex;
+use reqwest::blocking::ClientBuilder;
+use scraper::{Html, Selector, ElementRef};
+use std::collections::HashMap;
+use std::time::Duration;
+use crate::UrlInfo;
+use crate::tracklist::{TracklistEntry, split_artists, split_version};
+
+/// Process a 1001Tracklists tracklist URL
+pub fn process_1001tracklists(url: &str, _confidence: f32) -> Result<UrlInfo, Error> {
+    let client = ClientBuilder::new()
+        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/96.0.4664.110 Safari/537.36")
+        .timeout(Duration::from_secs(30))
+        .cookie_store(true)
+        .gzip(true)
+        .build()?;
+
+    info!("Fetching 1001Tracklists page: {}", url);
+    let response = client.get(url).send()?;
+    if !response.status().is_success() {
+        bail!("1001Tracklists returned status: {}", response.status());
+    }
+    let html = response.text()?;
+    let (title, entries) = parse_tracklist_page(&html)?;
+    info!("Found {} tracks in tracklist: {}", entries.len(), title);
+
+    Ok(tracklist_url_info(url, &title, &entries))
+}
+
+/// Convert parsed tracklist into UrlInfo, unknown (ID) tracks are left out since they can't be downloaded
+pub fn tracklist_url_info(url: &str, title: &str, entries: &[TracklistEntry]) -> UrlInfo {
+    let tracks = entries.iter()
+        .filter(|e| !e.is_id)
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let mut tracklists = HashMap::new();
+    tracklists.insert(title.to_string(), tracks.clone());
+
+    UrlInfo::new("1001tracklists", "tracklist", title, None)
+        .with_tracklists(tracklists)
+        .with_videos(vec![(title.to_string(), url.to_string(), tracks)])
+        .with_url(url.to_string())
+}
+
+/// Parse tracklist page HTML, returns title of the tracklist and the tracks
+pub fn parse_tracklist_page(html: &str) -> Result<(String, Vec<TracklistEntry>), Error> {
+    let document = Html::parse_document(html);
+    let title = extract_title(&document).ok_or(anyhow::anyhow!("Tracklist title not found"))?;
+
+    let item_selector = Selector::parse("div.tlpItem").unwrap();
+    let mut entries = vec![];
+    for item in document.select(&item_selector) {
+        match parse_item(&item) {
+            Some(entry) => entries.push(entry),
+            None => warn!("Failed parsing 1001Tracklists item: {:?}", item.value().attr("id")),
+        }
+    }
+
+    if entries.is_empty() {
+        bail!("No tracks found in tracklist");
+    }
+    Ok((title, entries))
+}
+
+/// Get title of the tracklist
+fn extract_title(document: &Html) -> Option<String> {
+    let selectors = ["#pageTitle", "meta[property='og:title']", "title"];
+    for selector in selectors {
+        let selector = Selector::parse(selector).unwrap();
+        if let Some(element) = document.select(&selector).next() {
+            let title = element.value().attr("content")
+                .map(String::from)
+                .unwrap_or_else(|| element.text().collect::<String>());
+            let title = clean_text(&title);
+            // Page title has a site suffix
+            let title = title.trim_end_matches("| 1001Tracklists").trim().to_string();
+            if !title.is_empty() {
+                return Some(title);
+            }
+        }
+    }
+    None
+}
+
+/// Parse single track row
+fn parse_item(item: &ElementRef) -> Option<TracklistEntry> {
+    // Prefer the schema.org metadata, fallback to the visible text
+    let full = select_attr(item, "meta[itemprop='name']", "content")
+        .or_else(|| select_text(item, ".trackValue"))?;
+    let artist_meta = select_attr(item, "meta[itemprop='byArtist']", "content");
+
+    let (artist, title) = match artist_meta {
+        Some(artist) if full.starts_with(&format!("{artist} - ")) => (artist.to_string(), full[artist.len() + 3..].to_string()),
+        _ => {
+            let (artist, title) = full.split_once(" - ")?;
+            (artist.trim().to_string(), title.trim().to_string())
+        }
+    };
+    let (title, version) = split_version(&title);
+    let is_id = artist.eq_ignore_ascii_case("ID") || title.eq_ignore_ascii_case("ID");
+
+    let timestamp = select_text(item, ".cue").filter(|c| is_cue(c));
+    let label = select_text(item, ".trackLabel")
+        .or_else(|| select_attr(item, "meta[itemprop='recordLabel']", "content"))
+        .map(|l| l.trim_matches(|c| c == '[' || c == ']').trim().to_string())
+        .filter(|l| !l.is_empty());
+
+    Some(TracklistEntry { timestamp, artists: split_artists(&artist), title, version, label, is_id })
+}
+
+/// Check if text is a valid cue time (`5:12`, `1:02:30`)
+fn is_cue(text: &str) -> bool {
+    Regex::new(r"^\d{1,2}:\d{2}(?::\d{2})?$").unwrap().is_match(text)
+}
+
+/// Get trimmed text of first matching child
+fn select_text(item: &ElementRef, selector: &str) -> Option<String> {
+    let selector = Selector::parse(selector).unwrap();
+    item.select(&selector).next()
+        .map(|e| clean_text(&e.text().collect::<String>()))
+        .filter(|t| !t.is_empty())
+}
+
+/// Get attribute of first matching child
+fn select_attr(item: &ElementRef, selector: &str, attr: &str) -> Option<String> {
+    let selector = Selector::parse(selector).unwrap();
+    item.select(&selector).next()
+        .and_then(|e| e.value().attr(attr))
+        .map(clean_text)
+        .filter(|t| !t.is_empty())
+}
+
+/// Decode entities and normalize whitespace
+fn clean_text(text: &str) -> String {
+    let text = html_escape::decode_html_entities(text);
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE_SET: &str = include_str!("../tests/fixtures/1001tracklists_set.html");
+    const FIXTURE_RADIO: &str = include_str!("../tests/fixtures/1001tracklists_radio.html");
+
+    #[test]
+    fn test_parse_set() {
+        let (title, entries) = parse_tracklist_page(FIXTURE_SET).unwrap();
+        assert_eq!(title, "Amelie Lens @ Awakenings Festival, Spaarnwoude, Netherlands 2023-07-08");
+        assert_eq!(entries.len(), 6);
+
+        assert_eq!(entries[0], TracklistEntry {
+            timestamp: Some("0:00".to_string()),
+            artists: vec!["Amelie Lens".to_string()],
+            title: "Hypnotized".to_string(),
+            version: None,
+            label: Some("LENSKE".to_string()),
+            is_id: false,
+        });
+        // Remix and entities
+        assert_eq!(entries[1].artists, vec!["Charlotte de Witte & Enrico Sangiuliano"]);
+        assert_eq!(entries[1].title, "Selected");
+        assert_eq!(entries[1].version.as_deref(), Some("Regal Remix"));
+        assert_eq!(entries[1].label.as_deref(), Some("KNTXT"));
+        // Played together with previous track, no cue
+        assert_eq!(entries[2].timestamp, None);
+        assert_eq!(entries[2].label, None);
+        // Over an hour
+        assert_eq!(entries[4].timestamp.as_deref(), Some("1:02:45"));
+        // ID
+        assert!(entries[5].is_id);
+    }
+
+    #[test]
+    fn test_parse_radio_fallback() {
+        // No schema.org metadata, only visible text
+        let (title, entries) = parse_tracklist_page(FIXTURE_RADIO).unwrap();
+        assert_eq!(title, "Solomun - Solomun +1 Radio Show 2024-02-03");
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].artists, vec!["Solomun"]);
+        assert_eq!(entries[0].title, "Home");
+        assert_eq!(entries[0].version.as_deref(), Some("Club Version"));
+        assert_eq!(entries[2].timestamp.as_deref(), Some("12:10"));
+        assert_eq!(entries[2].label.as_deref(), Some("Diynamic"));
+    }
+
+    #[test]
+    fn test_url_info() {
+        let (title, entries) = parse_tracklist_page(FIXTURE_SET).unwrap();
+        let info = tracklist_url_info("https://www.1001tracklists.com/tracklist/2abc/index.html", &title, &entries);
+        assert_eq!(info.platform, "1001tracklists");
+        let videos = info.videos.unwrap();
+        assert_eq!(videos.len(), 1);
+        // ID track is skipped
+        assert_eq!(videos[0].2.len(), 5);
+        assert_eq!(videos[0].2[1].full_title(), "Selected (Regal Remix)");
+        assert_eq!(info.video_tracklists.unwrap()[&title].len(), 5);
+    }
+}
@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+use anyhow::Error;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use lofty::ape::{ApeItem, ApeTag};
+use lofty::config::WriteOptions;
+use lofty::file::{AudioFile, TaggedFileExt};
+use lofty::tag::{ItemValue, TagType};
+
+use crate::{Lyrics, Picture};
+use crate::{Field, TagDate, CoverType, TagImpl};
+
+/// APEv2 binary cover item names
+const COVER_TYPES: [(&str, CoverType); 21] = [
+    ("Cover Art (Other)", CoverType::Other),
+    ("Cover Art (Icon)", CoverType::Icon),
+    ("Cover Art (Other Icon)", CoverType::OtherIcon),
+    ("Cover Art (Front)", CoverType::CoverFront),
+    ("Cover Art (Back)", CoverType::CoverBack),
+    ("Cover Art (Leaflet)", CoverType::Leaflet),
+    ("Cover Art (Media)", CoverType::Media),
+    ("Cover Art (Lead Artist)", CoverType::LeadArtist),
+    ("Cover Art (Artist)", CoverType::Artist),
+    ("Cover Art (Conductor)", CoverType::Conductor),
+    ("Cover Art (Band)", CoverType::Band),
+    ("Cover Art (Composer)", CoverType::Composer),
+    ("Cover Art (Lyricist)", CoverType::Lyricist),
+    ("Cover Art (Recording Location)", CoverType::RecordingLocation),
+    ("Cover Art (During Recording)", CoverType::DuringRecording),
+    ("Cover Art (During Performance)", CoverType::DuringPerformance),
+    ("Cover Art (Video Capture)", CoverType::ScreenCapture),
+    ("Cover Art (Fish)", CoverType::BrightFish),
+    ("Cover Art (Illustration)", CoverType::Illustration),
+    ("Cover Art (Band Logotype)", CoverType::BandLogo),
+    ("Cover Art (Publisher Logotype)", CoverType::PublisherLogo),
+];
+
+/// APEv2 tag, used by WavPack, Monkey's Audio and Musepack
+pub struct APETag {
+    tag: ApeTag,
+    separator: Option<String>
+}
+
+impl APETag {
+    /// Load from path
+    pub fn load_file(path: impl AsRef<Path>) -> Result<APETag, Error> {
+        let file = lofty::read_from_path(path)?;
+        let tag = match file.tag(TagType::Ape) {
+            Some(tag) => tag.to_owned().into(),
+            None => ApeTag::default()
+        };
+        Ok(APETag {
+            tag,
+            separator: None
+        })
+    }
+
+    /// Load APEv2 from the end of file (MP3), None if it has no APE tag
+    pub fn load_trailing(path: impl AsRef<Path>) -> Option<APETag> {
+        if !Self::has_footer(&path).unwrap_or(false) {
+            return None;
+        }
+        let file = lofty::read_from_path(&path).ok()?;
+        Some(APETag {
+            tag: file.tag(TagType::Ape)?.to_owned().into(),
+            separator: None
+        })
+    }
+
+    /// Check for `APETAGEX` footer, before ID3v1 if present
+    fn has_footer(path: impl AsRef<Path>) -> Result<bool, Error> {
+        let mut file = File::open(path)?;
+        let len = file.metadata()?.len();
+        if len < 160 {
+            return Ok(false);
+        }
+        let mut buf = [0u8; 160];
+        file.seek(SeekFrom::End(-160))?;
+        file.read_exact(&mut buf)?;
+        Ok(&buf[128..136] == b"APETAGEX" || &buf[0..8] == b"APETAGEX")
+    }
+
+    /// Get text item, multiple values are separated by \0
+    fn get_text(&self, key: &str) -> Option<&str> {
+        match self.tag.get(key)?.value() {
+            ItemValue::Text(text) => Some(text),
+            ItemValue::Locator(text) => Some(text),
+            ItemValue::Binary(_) => None,
+        }
+    }
+
+    fn insert(&mut self, key: &str, value: ItemValue) {
+        match ApeItem::new(key.to_string(), value) {
+            Ok(item) => self.tag.insert(item),
+            Err(e) => warn!("Invalid APE item {key}: {e}"),
+        }
+    }
+
+    /// Set date in vorbis format to tag
+    fn set_date_inner(&mut self, tag: &str, date: &TagDate, overwrite: bool) {
+        if overwrite || self.get_raw(tag).is_none() {
+            let v = match date.has_md() {
+                true => format!("{}-{:02}-{:02}", date.year, date.month.unwrap(), date.day.unwrap()),
+                false => date.year.to_string()
+            };
+            self.insert(tag, ItemValue::Text(v));
+        }
+    }
+
+    /// `Track` item is number/total
+    fn track(&self) -> (Option<String>, Option<String>) {
+        let Some(track) = self.get_text("Track") else { return (None, None) };
+        let mut split = track.splitn(2, '/');
+        let number = split.next().map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+        (number, split.next().map(|t| t.trim().to_string()))
+    }
+
+    fn cover_key(&self, kind: &CoverType) -> &'static str {
+        COVER_TYPES.iter().find(|(_, c)| c == kind).map(|i| i.0).unwrap_or("Cover Art (Other)")
+    }
+    fn cover_type(&self, key: &str) -> Option<CoverType> {
+        COVER_TYPES.iter().find(|(k, _)| k.eq_ignore_ascii_case(key)).map(|i| i.1.clone())
+    }
+}
+
+impl TagImpl for APETag {
+    fn save_file(&mut self, path: &Path) -> Result<(), Error> {
+        let mut file = lofty::read_from_path(path)?;
+        file.remove(TagType::Ape);
+        file.insert_tag(self.tag.clone().into());
+        file.save_to_path(path, WriteOptions::default())?;
+        Ok(())
+    }
+
+    fn set_separator(&mut self, separator: &str) {
+        if separator.is_empty() {
+            self.separator = None;
+        } else {
+            self.separator = Some(separator.replace("\\0", "\0"))
+        }
+    }
+
+    fn all_tags(&self) -> HashMap<String, Vec<String>> {
+        (&self.tag).into_iter()
+            .filter_map(|item| Some((item.key().to_string(), self.get_raw(item.key())?)))
+            .collect()
+    }
+
+    fn get_date(&self) -> Option<TagDate> {
+        let data = &self.get_raw("Year")?[0];
+        // YYYY-MM-DD
+        if data.len() >= 10 {
+            return Some(TagDate {
+                year: data[0..4].parse().ok()?,
+                month: data[5..7].parse().ok(),
+                day: data[8..10].parse().ok()
+            });
+        }
+        // YYYY
+        if data.len() >= 4 {
+            return Some(TagDate {
+                year: data[0..4].parse().ok()?,
+                month: None,
+                day: None
+            });
+        }
+        None
+    }
+
+    fn set_date(&mut self, date: &TagDate, overwrite: bool) {
+        self.set_date_inner("Year", date, overwrite);
+    }
+
+    fn set_publish_date(&mut self, date: &TagDate, overwrite: bool) {
+        self.set_date_inner("Original Date", date, overwrite);
+    }
+
+    // Same as vorbis 20, 40, 60, 80, 100
+    fn get_rating(&self) -> Option<u8> {
+        let rating = self.get_raw("Rating")?.first()?.parse::<i32>().ok()? / 20;
+        if rating <= 5 {
+            if rating == 0 {
+               return Some(1)
+            }
+            return Some(rating as u8)
+        }
+        None
+    }
+
+    fn set_rating(&mut self, rating: u8, overwrite: bool) {
+        if rating > 0 {
+            self.set_raw("Rating", vec![(rating * 20).to_string()], overwrite);
+        } else {
+            self.remove_raw("Rating");
+        }
+    }
+
+    // Binary item: description, \0, image data
+    fn set_art(&mut self, kind: CoverType, _mime: &str, description: Option<&str>, data: Vec<u8>) {
+        let mut value = description.unwrap_or("cover.jpg").as_bytes().to_vec();
+        value.push(0);
+        value.extend(data);
+        self.insert(self.cover_key(&kind), ItemValue::Binary(value));
+    }
+
+    fn has_art(&self) -> bool {
+        (&self.tag).into_iter().any(|i| self.cover_type(i.key()).is_some())
+    }
+
+    fn get_art(&self) -> Vec<Picture> {
+        (&self.tag).into_iter().filter_map(|item| {
+            let kind = self.cover_type(item.key())?;
+            let ItemValue::Binary(value) = item.value() else { return None };
+            let split = value.iter().position(|b| *b == 0)?;
+            let data = value[split + 1..].to_vec();
+            let mime = match data.starts_with(b"\x89PNG") {
+                true => "image/png",
+                false => "image/jpeg"
+            };
+            Some(Picture {
+                kind,
+                description: String::from_utf8_lossy(&value[..split]).to_string(),
+                mime: mime.to_string(),
+                data
+            })
+        }).collect()
+    }
+
+    fn remove_art(&mut self, kind: CoverType) {
+        self.tag.remove(self.cover_key(&kind));
+    }
+
+    fn set_field(&mut self, field: Field, value: Vec<String>, overwrite: bool) {
+        // Track number / total share one item
+        match field {
+            Field::TrackNumber => {
+                let total = self.track().1;
+                if overwrite || self.track().0.is_none() {
+                    let number = value.first().map(String::to_string).unwrap_or_default();
+                    let track = total.map(|t| format!("{number}/{t}")).unwrap_or(number);
+                    self.insert("Track", ItemValue::Text(track));
+                }
+            },
+            Field::TrackTotal => {
+                let (number, total) = self.track();
+                if overwrite || total.is_none() {
+                    let track = format!("{}/{}", number.unwrap_or("0".to_string()), value.first().map(String::as_str).unwrap_or_default());
+                    self.insert("Track", ItemValue::Text(track));
+                }
+            },
+            _ => self.set_raw(field.ape(), value, overwrite)
+        }
+    }
+
+    fn get_field(&self, field: Field) -> Option<Vec<String>> {
+        match field {
+            Field::TrackNumber => self.track().0.map(|n| vec![n]),
+            Field::TrackTotal => self.track().1.map(|t| vec![t]),
+            _ => self.get_raw(field.ape())
+        }
+    }
+
+    fn set_raw(&mut self, tag: &str, value: Vec<String>, overwrite: bool) {
+        if overwrite || self.tag.get(tag).is_none() {
+            if value.is_empty() {
+                self.tag.remove(tag);
+                return;
+            }
+            // APEv2 has native multiple values
+            let separator = self.separator.as_deref().unwrap_or("\0");
+            self.insert(tag, ItemValue::Text(value.join(separator)));
+        }
+    }
+
+    fn get_raw(&self, tag: &str) -> Option<Vec<String>> {
+        let text = self.get_text(tag)?;
+        let mut values = text.split('\0').collect::<Vec<_>>();
+        // Separator override
+        if let (1, Some(separator)) = (values.len(), self.separator.as_ref()) {
+            values = text.split(separator.as_str()).collect();
+        }
+        Some(values.into_iter().map(String::from).collect())
+    }
+
+    fn remove_raw(&mut self, tag: &str) {
+        self.tag.remove(tag);
+    }
+
+    fn set_lyrics(&mut self, lyrics: &Lyrics, synced: bool, overwrite: bool) {
+        if synced {
+            warn!("APE doesn't support synchronized lyrics!");
+            return;
+        }
+        if !overwrite && self.get_raw("Lyrics").is_some() {
+            return;
+        }
+        self.insert("Lyrics", ItemValue::Text(lyrics.text()));
+    }
+
+    fn get_lyrics(&self, synced: bool) -> Option<Lyrics> {
+        Lyrics::parse(self.get_text("Lyrics")?, "eng").filter(|l| !synced || l.synced())
+    }
+
+    fn set_track_number(&mut self, track_number: &str, track_total: Option<u16>, overwrite: bool) {
+        self.set_field(Field::TrackNumber, vec![track_number.to_string()], overwrite);
+        if let Some(total) = track_total {
+            self.set_field(Field::TrackTotal, vec![total.to_string()], overwrite);
+        }
+    }
+
+    fn set_explicit(&mut self, explicit: bool) {
+        let mut comments: Vec<_> = self.get_raw("Comment").unwrap_or(vec![]).into_iter().filter(|i| i != "Explicit").collect();
+        if explicit {
+            comments.push("Explicit".to_string());
+        }
+        self.set_raw("Comment", comments, true);
+    }
+
+    fn get_separator(&self) -> Option<String> {
+        self.separator.clone()
+    }
+}
@@ -0,0 +1,280 @@
+use anyhow::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use id3::{Tag, Version};
+
+/// DSD container
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DSDContainer {
+    /// Sony DSD Stream File, ID3 at the metadata pointer
+    Dsf,
+    /// Philips DSDIFF, unofficial `ID3 ` chunk
+    Dff
+}
+
+impl DSDContainer {
+    /// Detect by magic
+    fn detect(file: &mut File) -> Result<DSDContainer, Error> {
+        let mut magic = [0u8; 4];
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut magic)?;
+        match &magic {
+            b"DSD " => Ok(DSDContainer::Dsf),
+            b"FRM8" => Ok(DSDContainer::Dff),
+            _ => Err(anyhow!("Not a DSF / DFF file"))
+        }
+    }
+}
+
+/// Read ID3 from DSF or DFF, new tag if the file has none
+pub(crate) fn read_dsd(path: impl AsRef<Path>) -> Result<Tag, Error> {
+    let mut file = File::open(path)?;
+    let offset = match DSDContainer::detect(&mut file)? {
+        DSDContainer::Dsf => dsf_metadata_pointer(&mut file)?,
+        DSDContainer::Dff => dff_chunks(&mut file)?.into_iter().find(|c| &c.id == b"ID3 ").map(|c| c.offset + 12),
+    };
+    let Some(offset) = offset else { return Ok(Tag::new()) };
+    let mut data = vec![];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_to_end(&mut data)?;
+    match Tag::read_from2(Cursor::new(data)) {
+        Ok(tag) => Ok(tag),
+        Err(e) => {
+            warn!("Invalid ID3 in DSD file: {e}");
+            Ok(Tag::new())
+        }
+    }
+}
+
+/// Write ID3 into DSF or DFF, replaces the old tag
+pub(crate) fn write_dsd(path: impl AsRef<Path>, tag: &Tag, version: Version) -> Result<(), Error> {
+    let mut data = Cursor::new(vec![]);
+    tag.write_to(&mut data, version)?;
+    let data = data.into_inner();
+
+    let mut file = OpenOptions::new().read(true).write(true).open(&path)?;
+    match DSDContainer::detect(&mut file)? {
+        DSDContainer::Dsf => {
+            // Old tag is always at the end of file, don't truncate if the pointer is invalid
+            let end = match dsf_metadata_pointer(&mut file)? {
+                Some(pointer) if is_id3_at(&mut file, pointer)? => pointer,
+                _ => file.metadata()?.len()
+            };
+            file.set_len(end)?;
+            file.seek(SeekFrom::End(0))?;
+            file.write_all(&data)?;
+            // DSD chunk: total size, metadata pointer
+            file.seek(SeekFrom::Start(12))?;
+            file.write_all(&(end + data.len() as u64).to_le_bytes())?;
+            file.write_all(&end.to_le_bytes())?;
+        },
+        DSDContainer::Dff => {
+            let chunks = dff_chunks(&mut file)?;
+            let len = file.metadata()?.len();
+            // Write into temp file, so the original stays intact on failure
+            let temp_path = dff_temp_path(path.as_ref())?;
+            let result = (|| -> Result<(), Error> {
+                let mut writer = BufWriter::new(File::create(&temp_path)?);
+                let mut header = [0u8; 16];
+                file.seek(SeekFrom::Start(0))?;
+                file.read_exact(&mut header)?;
+                writer.write_all(&header)?;
+                // Copy all chunks except the old tag
+                for (i, chunk) in chunks.iter().enumerate() {
+                    if &chunk.id == b"ID3 " {
+                        continue;
+                    }
+                    let end = chunks.get(i + 1).map(|c| c.offset).unwrap_or(len);
+                    file.seek(SeekFrom::Start(chunk.offset))?;
+                    std::io::copy(&mut (&mut file).take(end - chunk.offset), &mut writer)?;
+                }
+                writer.write_all(b"ID3 ")?;
+                writer.write_all(&(data.len() as u64).to_be_bytes())?;
+                writer.write_all(&data)?;
+                // Chunks are padded to even size
+                if data.len() % 2 == 1 {
+                    writer.write_all(&[0])?;
+                }
+                // FRM8 size
+                let len = writer.stream_position()?;
+                writer.seek(SeekFrom::Start(4))?;
+                writer.write_all(&(len - 12).to_be_bytes())?;
+                writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+                Ok(())
+            })();
+            if let Err(e) = result {
+                std::fs::remove_file(&temp_path).ok();
+                return Err(e);
+            }
+            drop(file);
+            std::fs::rename(&temp_path, &path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Does the data at offset start with ID3 header
+fn is_id3_at(file: &mut File, offset: u64) -> Result<bool, Error> {
+    let mut magic = [0u8; 3];
+    file.seek(SeekFrom::Start(offset))?;
+    Ok(file.read_exact(&mut magic).is_ok() && &magic == b"ID3")
+}
+
+/// Hidden file next to the original, so it can be renamed over it
+fn dff_temp_path(path: &Path) -> Result<std::path::PathBuf, Error> {
+    let filename = path.file_name().ok_or(anyhow!("Missing filename"))?.to_string_lossy();
+    Ok(path.with_file_name(format!(".{filename}.1t-tmp")))
+}
+
+/// Get offset of metadata chunk in DSF
+fn dsf_metadata_pointer(file: &mut File) -> Result<Option<u64>, Error> {
+    let mut buf = [0u8; 8];
+    file.seek(SeekFrom::Start(20))?;
+    file.read_exact(&mut buf)?;
+    let pointer = u64::from_le_bytes(buf);
+    Ok((pointer != 0 && pointer < file.metadata()?.len()).then_some(pointer))
+}
+
+/// Top level DSDIFF chunk
+#[derive(Debug, Clone)]
+struct DFFChunk {
+    id: [u8; 4],
+    /// Offset of chunk header
+    offset: u64,
+}
+
+/// List top level chunks of DFF
+fn dff_chunks(file: &mut File) -> Result<Vec<DFFChunk>, Error> {
+    let len = file.metadata()?.len();
+    let mut chunks = vec![];
+    let mut offset = 16;
+    let mut header = [0u8; 12];
+    while offset + 12 <= len {
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut header)?;
+        let size = u64::from_be_bytes(header[4..12].try_into().unwrap());
+        chunks.push(DFFChunk { id: header[0..4].try_into().unwrap(), offset });
+        offset = offset.saturating_add(12).saturating_add(size).saturating_add(size % 2);
+    }
+    Ok(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use id3::TagLike;
+
+    /// DSD, fmt and data chunk with 8 bytes of audio
+    fn dsf_fixture(tail: &[u8]) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend(b"DSD ");
+        data.extend(28u64.to_le_bytes());
+        data.extend((28 + 52 + 20 + tail.len() as u64).to_le_bytes());
+        // Metadata pointer right after audio if there is a tail
+        let pointer: u64 = if tail.is_empty() { 0 } else { 28 + 52 + 20 };
+        data.extend(pointer.to_le_bytes());
+        data.extend(b"fmt ");
+        data.extend(52u64.to_le_bytes());
+        data.extend([0u8; 16]);
+        data.extend(2822400u32.to_le_bytes());
+        data.extend([0u8; 4]);
+        data.extend(16u64.to_le_bytes());
+        data.extend([0u8; 8]);
+        data.extend(b"data");
+        data.extend(20u64.to_le_bytes());
+        data.extend([0x69; 8]);
+        data.extend(tail);
+        data
+    }
+
+    /// FVER, PROP and DSD chunk, optional chunk after the audio
+    fn dff_fixture(extra: Option<&[u8]>) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend(b"FRM8");
+        data.extend(0u64.to_be_bytes());
+        data.extend(b"DSD ");
+        data.extend(b"FVER");
+        data.extend(4u64.to_be_bytes());
+        data.extend(0x01050000u32.to_be_bytes());
+        data.extend(b"PROP");
+        data.extend(4u64.to_be_bytes());
+        data.extend(b"SND ");
+        data.extend(b"DSD ");
+        data.extend(3u64.to_be_bytes());
+        data.extend([0x69; 3]);
+        data.push(0);
+        if let Some(extra) = extra {
+            data.extend(extra);
+        }
+        let len = data.len() as u64 - 12;
+        data[4..12].copy_from_slice(&len.to_be_bytes());
+        data
+    }
+
+    fn tag(title: &str) -> Tag {
+        let mut tag = Tag::new();
+        tag.set_title(title);
+        tag
+    }
+
+    #[test]
+    fn test_dsf_round_trip() {
+        let path = std::env::temp_dir().join("onetagger_dsd_test.dsf");
+        let original = dsf_fixture(&[]);
+        std::fs::write(&path, &original).unwrap();
+        assert!(read_dsd(&path).unwrap().title().is_none());
+
+        write_dsd(&path, &tag("First"), Version::Id3v24).unwrap();
+        write_dsd(&path, &tag("Second"), Version::Id3v24).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(read_dsd(&path).unwrap().title(), Some("Second"));
+        // Old tag replaced, audio untouched, header updated
+        assert_eq!(&data[..12], &original[..12]);
+        assert_eq!(&data[28..original.len()], &original[28..]);
+        assert_eq!(u64::from_le_bytes(data[12..20].try_into().unwrap()), data.len() as u64);
+        assert_eq!(u64::from_le_bytes(data[20..28].try_into().unwrap()), original.len() as u64);
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_dsf_invalid_pointer() {
+        // Pointer doesn't point at ID3, the data after it must be kept
+        let path = std::env::temp_dir().join("onetagger_dsd_test_pointer.dsf");
+        let original = dsf_fixture(b"JUNKDATA");
+        std::fs::write(&path, &original).unwrap();
+        write_dsd(&path, &tag("Title"), Version::Id3v23).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(&data[28..original.len()], &original[28..]);
+        assert_eq!(u64::from_le_bytes(data[20..28].try_into().unwrap()), original.len() as u64);
+        assert_eq!(read_dsd(&path).unwrap().title(), Some("Title"));
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_dff_round_trip() {
+        let path = std::env::temp_dir().join("onetagger_dsd_test.dff");
+        let mut comment = b"COMT".to_vec();
+        comment.extend(2u64.to_be_bytes());
+        comment.extend([0, 0]);
+        std::fs::write(&path, dff_fixture(Some(&comment))).unwrap();
+        assert!(read_dsd(&path).unwrap().title().is_none());
+
+        write_dsd(&path, &tag("First"), Version::Id3v24).unwrap();
+        // Tag is no longer the last chunk
+        let mut data = std::fs::read(&path).unwrap();
+        data.extend(&comment);
+        let len = data.len() as u64 - 12;
+        data[4..12].copy_from_slice(&len.to_be_bytes());
+        std::fs::write(&path, &data).unwrap();
+
+        write_dsd(&path, &tag("Second"), Version::Id3v24).unwrap();
+        assert_eq!(read_dsd(&path).unwrap().title(), Some("Second"));
+        let ids = dff_chunks(&mut File::open(&path).unwrap()).unwrap().into_iter().map(|c| c.id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![*b"FVER", *b"PROP", *b"DSD ", *b"COMT", *b"COMT", *b"ID3 "]);
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(u64::from_be_bytes(data[4..12].try_into().unwrap()), data.len() as u64 - 12);
+        assert!(!dff_temp_path(&path).unwrap().exists());
+        std::fs::remove_file(&path).ok();
+    }
+}
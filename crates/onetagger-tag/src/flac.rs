@@ -272,6 +272,11 @@ impl TagImpl for FLACTag {
         self.tag.set_vorbis("LYRICS", vec![lyrics.text()]);
     }
 
+    fn get_lyrics(&self, synced: bool) -> Option<Lyrics> {
+        let text = self.tag.get_vorbis("LYRICS").or(self.tag.get_vorbis("UNSYNCEDLYRICS"))?.next()?;
+        Lyrics::parse(text, "eng").filter(|l| !synced || l.synced())
+    }
+
     fn set_explicit(&mut self, explicit: bool) {
         self.tag.remove_vorbis_pair("COMMENT", "Explicit");
         if explicit {
@@ -1,10 +1,13 @@
 use std::collections::HashMap;
 use anyhow::Error;
 use std::path::Path;
+use std::time::Duration;
 use id3::{Version, Tag, Timestamp, Content, TagLike, Encoder, Frame, Encoding};
-use id3::frame::{Picture, PictureType, Comment, Lyrics, Popularimeter, ExtendedText, SynchronisedLyrics, TimestampFormat, SynchronisedLyricsType};
+use id3::frame::{Picture, PictureType, Comment, Lyrics, Popularimeter, ExtendedText, SynchronisedLyrics, TimestampFormat, SynchronisedLyricsType, Chapter, TableOfContents, Unknown};
 use serde::{Serialize, Deserialize};
 use crate::{TagDate, CoverType, Field, TagImpl};
+use crate::wav::WavMeta;
+use crate::ape::APETag;
 
 const COVER_TYPES: [(PictureType, CoverType); 21] = [
     (PictureType::Other, CoverType::Other),
@@ -30,9 +33,53 @@ const COVER_TYPES: [(PictureType, CoverType); 21] = [
     (PictureType::PublisherLogo, CoverType::PublisherLogo),
 ];
 
+/// TXXX descriptions which would otherwise be taken as frame IDs
+const TXXX_4CHAR: [&str; 1] = ["WORK"];
+
+/// Is tag stored in TXXX frame
+fn is_txxx(tag: &str) -> bool {
+    tag.len() != 4 || TXXX_4CHAR.contains(&tag)
+}
+
+/// iTunes movement frames, not supported as text by the ID3 library
+const ITUNES_FRAMES: [&str; 2] = ["MVNM", "MVIN"];
+
+/// Encode text frame content as UTF-16 (valid in both ID3v2.3 and ID3v2.4)
+fn encode_text_frame(value: &str) -> Vec<u8> {
+    let mut data = vec![1, 0xff, 0xfe];
+    data.extend(value.encode_utf16().flat_map(|c| c.to_le_bytes()));
+    data
+}
+
+/// Decode raw text frame content
+fn decode_text_frame(data: &[u8]) -> Option<String> {
+    let (encoding, data) = data.split_first()?;
+    let utf16 = |data: &[u8], le: bool| {
+        let chars = data.chunks_exact(2).map(|c| match le {
+            true => u16::from_le_bytes([c[0], c[1]]),
+            false => u16::from_be_bytes([c[0], c[1]])
+        }).collect::<Vec<_>>();
+        String::from_utf16_lossy(&chars)
+    };
+    let text = match encoding {
+        0 => data.iter().map(|c| *c as char).collect(),
+        1 => match data {
+            [0xfe, 0xff, data @ ..] => utf16(data, false),
+            [0xff, 0xfe, data @ ..] => utf16(data, true),
+            _ => utf16(data, true)
+        },
+        2 => utf16(data, false),
+        3 => String::from_utf8_lossy(data).to_string(),
+        _ => return None
+    };
+    Some(text.trim_end_matches('\0').to_string())
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum ID3AudioFormat {
-    MP3, AIFF, WAV
+    MP3, AIFF, WAV,
+    /// DSF or DFF
+    DSD
 }
 
 pub struct ID3Tag {
@@ -41,6 +88,10 @@ pub struct ID3Tag {
     pub id3_separator: String,
     pub id3v24: bool,
     pub comm_lang: String,
+    /// RIFF INFO and bext of WAV files
+    wav: WavMeta,
+    /// Trailing APEv2 of MP3 files, read only fallback for missing values
+    ape: Option<APETag>,
 }
 
 impl ID3Tag {
@@ -59,7 +110,9 @@ impl ID3Tag {
                     Version::Id3v24 => true,
                     _ => false
                 },
-                comm_lang: "eng".to_string()
+                comm_lang: "eng".to_string(),
+                wav: WavMeta::default(),
+                ape: None
             }.into());
         }
         // AIFF
@@ -74,12 +127,14 @@ impl ID3Tag {
                     Version::Id3v24 => true,
                     _ => false
                 },
-                comm_lang: "eng".to_string()
+                comm_lang: "eng".to_string(),
+                wav: WavMeta::default(),
+                ape: None
             }.into());
         }
         // WAV
         if ext == "wav" {
-            let tag = crate::wav::read_wav(path)?;
+            let (tag, wav) = crate::wav::read_wav(path)?;
             let version = tag.version();
             return Ok(ID3Tag { 
                 tag,
@@ -89,7 +144,26 @@ impl ID3Tag {
                     Version::Id3v24 => true,
                     _ => false
                 },
-                comm_lang: "eng".to_string()
+                comm_lang: "eng".to_string(),
+                wav,
+                ape: None
+            }.into());
+        }
+        // DSF / DFF
+        if ext == "dsf" || ext == "dff" {
+            let tag = crate::dsd::read_dsd(path)?;
+            let version = tag.version();
+            return Ok(ID3Tag {
+                tag,
+                format: ID3AudioFormat::DSD,
+                id3_separator: String::from(", "),
+                id3v24: match version {
+                    Version::Id3v24 => true,
+                    _ => false
+                },
+                comm_lang: "eng".to_string(),
+                wav: WavMeta::default(),
+                ape: None
             }.into());
         }
 
@@ -105,6 +179,8 @@ impl ID3Tag {
             ID3AudioFormat::MP3
         } else if ext == "wav" {
             ID3AudioFormat::WAV
+        } else if ext == "dsf" || ext == "dff" {
+            ID3AudioFormat::DSD
         } else {
             ID3AudioFormat::AIFF
         };
@@ -118,12 +194,19 @@ impl ID3Tag {
                     format,
                     id3_separator: String::from(", "),
                     id3v24: true,
-                    comm_lang: "eng".to_string()
+                    comm_lang: "eng".to_string(),
+                    wav: WavMeta::default(),
+                    ape: None
                 }
             }
         }
     }
 
+    /// Use APEv2 values if the ID3 tag is missing them, they are never written to ID3
+    pub(crate) fn set_ape_fallback(&mut self, ape: APETag) {
+        self.ape = Some(ape);
+    }
+
     // ID3 Settings
     pub fn set_id3v24(&mut self, id3v24: bool) {
         self.id3v24 = id3v24;
@@ -175,6 +258,72 @@ impl ID3Tag {
         self.tag.add_frame(popularimeter);
     }
 
+    // CHAP frames with CTOC
+    pub fn get_chapters(&self) -> Vec<ID3Chapter> {
+        self.tag.chapters().map(|c| c.clone().into()).collect()
+    }
+
+    pub fn set_chapters(&mut self, chapters: &[ID3Chapter]) {
+        self.tag.remove_all_chapters();
+        self.tag.remove_all_tables_of_contents();
+        if chapters.is_empty() {
+            return;
+        }
+        let chapters = chapters.iter().enumerate().map(|(i, c)| {
+            let mut chapter: Chapter = c.clone().into();
+            chapter.element_id = format!("chp{i}");
+            chapter
+        }).collect::<Vec<_>>();
+        self.tag.add_frame(TableOfContents {
+            element_id: "toc".to_string(),
+            top_level: true,
+            ordered: true,
+            elements: chapters.iter().map(|c| c.element_id.clone()).collect(),
+            frames: vec![]
+        });
+        for chapter in chapters {
+            self.tag.add_frame(chapter);
+        }
+    }
+
+    /// Serialize whole tag including non text frames
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let version = match self.tag.version() {
+            Version::Id3v24 => Version::Id3v24,
+            _ => Version::Id3v23
+        };
+        let mut data = vec![];
+        self.tag.write_to(&mut data, version)?;
+        Ok(data)
+    }
+
+    /// Replace whole tag with serialized one (from `to_bytes`)
+    pub fn replace_from_bytes(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.tag = Tag::read_from2(std::io::Cursor::new(data))?;
+        self.id3v24 = self.tag.version() == Version::Id3v24;
+        Ok(())
+    }
+
+    /// Date of the ID3 tag only
+    fn get_id3_date(&self) -> Option<TagDate> {
+        if let Some(date) = self.tag.date_recorded() {
+            return Some(TagDate {
+                year: date.year,
+                month: date.month,
+                day: date.day
+            });
+        }
+        // ID3v2.3
+        if let Some(tag) = self.get_raw("TYER") {
+            return Some(TagDate {
+                year: tag[0].parse().ok()?,
+                month: None,
+                day: None
+            });
+        }
+        None
+    }
+
     // Convert between different cover/picture types
     fn picture_type(&self, cover_type: &CoverType) -> PictureType {
         COVER_TYPES.iter().find(|(_, c)| c == cover_type).unwrap().0
@@ -221,7 +370,8 @@ impl TagImpl for ID3Tag {
                     .write_to_path(&self.tag, path)?;
             },
             ID3AudioFormat::AIFF => self.tag.write_to_path(path, version)?,
-            ID3AudioFormat::WAV => crate::wav::write_wav(path, self.tag.clone(), version)?,
+            ID3AudioFormat::WAV => crate::wav::write_wav(path, self.tag.clone(), version, &self.wav)?,
+            ID3AudioFormat::DSD => crate::dsd::write_dsd(path, &self.tag, version)?,
         }
         
         Ok(())
@@ -243,6 +393,10 @@ impl TagImpl for ID3Tag {
         for extended in self.tag.extended_texts() {
             tags.insert(extended.description.to_string(), extended.value.split(&self.id3_separator).map(String::from).collect());
         }
+        // RIFF INFO and bext
+        for (k, v) in self.wav.all() {
+            tags.entry(k).or_insert(vec![v]);
+        }
         tags
     }
 
@@ -294,7 +448,9 @@ impl TagImpl for ID3Tag {
 
     // Rating
     fn get_rating(&self) -> Option<u8> {
-        let tag = self.tag.get("POPM")?;
+        let Some(tag) = self.tag.get("POPM") else {
+            return self.ape.as_ref()?.get_rating();
+        };
         if let Content::Popularimeter(popm) = tag.content() {
             let rating = (popm.rating as f32 / 51.0).ceil() as u8;
             if rating == 0 {
@@ -340,6 +496,9 @@ impl TagImpl for ID3Tag {
     }
     // Get album art by type
     fn get_art(&self) -> Vec<crate::Picture> {
+        if self.tag.pictures().next().is_none() {
+            return self.ape.as_ref().map(|ape| ape.get_art()).unwrap_or_default();
+        }
         self.tag.pictures().map(
             |p| crate::Picture {
                 kind: self.cover_type(&p.picture_type),
@@ -351,7 +510,7 @@ impl TagImpl for ID3Tag {
     }
     // Check if has album art
     fn has_art(&self) -> bool {
-        self.tag.pictures().next().is_some()
+        self.tag.pictures().next().is_some() || self.ape.as_ref().is_some_and(|ape| ape.has_art())
     }
     fn remove_art(&mut self, kind: CoverType) { 
         // Remove all undefined kinds
@@ -377,23 +536,34 @@ impl TagImpl for ID3Tag {
     }
     fn get_field(&self, field: Field) -> Option<Vec<String>> {
         // Overrides
-        if field == Field::TrackNumber {
-            return self.tag.track().map(|v| vec![v.to_string()]);
-        }
-        if field == Field::TrackTotal {
-            return self.tag.total_tracks().map(|v| vec![v.to_string()]);
-        }
-        if field == Field::DiscNumber {
-            return self.tag.disc().map(|v| vec![v.to_string()]);
-        }
-
-        self.get_raw(field.id3())
+        let value = match field {
+            Field::TrackNumber => self.tag.track().map(|v| vec![v.to_string()]),
+            Field::TrackTotal => self.tag.total_tracks().map(|v| vec![v.to_string()]),
+            Field::DiscNumber => self.tag.disc().map(|v| vec![v.to_string()]),
+            _ => self.get_raw(field.id3())
+        };
+        value.or_else(|| self.ape.as_ref()?.get_field(field))
     }
 
     // Set/Get by tag
     fn set_raw(&mut self, tag: &str, value: Vec<String>, overwrite: bool) {
+        // RIFF INFO / bext
+        if self.format == ID3AudioFormat::WAV && self.wav.is_key(tag) {
+            if overwrite || self.wav.get(tag).is_none() {
+                let value = Some(value.join(&self.id3_separator)).filter(|v| !v.is_empty());
+                self.wav.set(tag, value);
+            }
+            return;
+        }
+        // Removed frame would be restored from INFO
+        if self.format == ID3AudioFormat::WAV && value.is_empty() && (overwrite || self.tag.get(tag).is_none()) {
+            if let Some(id) = crate::wav::mirrored_info_id(tag) {
+                self.wav.set(id, None);
+            }
+        }
+
         // TXXX
-        if tag.len() != 4 {
+        if is_txxx(tag) {
             if overwrite || self.get_raw(tag).is_none() {
                 // Remove if empty
                 if value.is_empty() {
@@ -443,7 +613,18 @@ impl TagImpl for ID3Tag {
             return;
         }
 
-        
+        // iTunes movement frames
+        if ITUNES_FRAMES.contains(&tag) {
+            if overwrite || self.tag.get(tag).is_none() {
+                self.tag.remove(tag);
+                if !value.is_empty() {
+                    let data = encode_text_frame(&value.join(&self.id3_separator));
+                    self.tag.add_frame(Frame::with_content(tag, Content::Unknown(Unknown { data, version: Version::Id3v24 })));
+                }
+            }
+            return;
+        }
+
         // Normal
         if overwrite || self.tag.get(tag).is_none() {
             // Remove if empty
@@ -457,8 +638,13 @@ impl TagImpl for ID3Tag {
 
     // Get raw TEXT field
     fn get_raw(&self, tag: &str) -> Option<Vec<String>> {
+        // RIFF INFO / bext
+        if self.format == ID3AudioFormat::WAV && self.wav.is_key(tag) {
+            return self.wav.get(tag).map(|v| vec![v]);
+        }
+
         // Custom tag (TXXX)
-        if tag.len() != 4 {
+        if is_txxx(tag) {
             if let Some(t) = self.tag.extended_texts().find(|t| t.description == tag) {
                 return Some(vec![t.value.to_string()]);
             }
@@ -481,6 +667,15 @@ impl TagImpl for ID3Tag {
             };
         }
 
+        // iTunes movement frames
+        if ITUNES_FRAMES.contains(&tag) {
+            let value = match self.tag.get(tag)?.content() {
+                Content::Unknown(unknown) => decode_text_frame(&unknown.data)?,
+                content => content.text()?.to_string()
+            };
+            return Some(value.split(&self.id3_separator).map(String::from).collect());
+        }
+
         // Get tag
         if let Some(t) = self.tag.get(tag) {
             if let Some(content) = t.content().text() {
@@ -499,8 +694,19 @@ impl TagImpl for ID3Tag {
     }
 
     fn remove_raw(&mut self, tag: &str) {
+        if self.format == ID3AudioFormat::WAV {
+            if self.wav.is_key(tag) {
+                self.wav.set(tag, None);
+                return;
+            }
+            // Otherwise it would be restored from INFO
+            if let Some(id) = crate::wav::mirrored_info_id(tag) {
+                self.wav.set(id, None);
+            }
+        }
+
         // TXXX
-        if tag.len() != 4 {
+        if is_txxx(tag) {
             self.tag.remove_extended_text(Some(tag), None);
             return;
         }
@@ -509,22 +715,7 @@ impl TagImpl for ID3Tag {
     }
 
     fn get_date(&self) -> Option<TagDate> {
-        if let Some(date) = self.tag.date_recorded() {
-            return Some(TagDate {
-                year: date.year,
-                month: date.month,
-                day: date.day
-            });
-        }
-        // ID3v2.3
-        if let Some(tag) = self.get_raw("TYER") {
-            return Some(TagDate {
-                year: tag[0].parse().ok()?,
-                month: None,
-                day: None
-            });
-        }
-        None
+        self.get_id3_date().or_else(|| self.ape.as_ref()?.get_date())
     }
 
     fn set_track_number(&mut self, track_number: &str, track_total: Option<u16>, overwrite: bool) {
@@ -565,6 +756,27 @@ impl TagImpl for ID3Tag {
         });
     }
 
+    fn get_lyrics(&self, synced: bool) -> Option<crate::Lyrics> {
+        if synced {
+            match self.tag.synchronised_lyrics().next() {
+                Some(sylt) if sylt.timestamp_format == TimestampFormat::Ms => {
+                    let lines = sylt.content.iter().map(|(ts, text)| crate::LyricsLine {
+                        text: text.trim().to_string(),
+                        start: Some(Duration::from_millis(*ts as u64)),
+                        end: None,
+                        parts: vec![]
+                    }).collect();
+                    return crate::Lyrics::from_synced(lines, &sylt.lang);
+                },
+                Some(_) => warn!("SYLT with MPEG frame timestamps is not supported"),
+                None => {}
+            }
+        }
+        // USLT might contain LRC
+        let uslt = self.tag.lyrics().next()?;
+        crate::Lyrics::parse(&uslt.text, &uslt.lang).filter(|l| !synced || l.synced())
+    }
+
     fn set_explicit(&mut self, explicit: bool) {
         self.set_raw("ITUNESADVISORY", vec![
             match explicit { true => "1", false => "2" }.to_string()
@@ -661,4 +873,77 @@ impl From<ID3Comment> for Lyrics {
             text: c.text
         }
     }
-}
\ No newline at end of file
+}
+
+/// Chapter (CHAP frame), times in ms
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ID3Chapter {
+    pub start: u32,
+    pub end: u32,
+    pub title: String,
+    pub artist: Option<String>,
+}
+
+impl From<Chapter> for ID3Chapter {
+    fn from(c: Chapter) -> Self {
+        let text = |id: &str| c.frames.iter().find(|f| f.id() == id).and_then(|f| f.content().text()).map(String::from);
+        Self {
+            start: c.start_time,
+            end: c.end_time,
+            title: text("TIT2").unwrap_or_default(),
+            artist: text("TPE1"),
+        }
+    }
+}
+
+impl From<ID3Chapter> for Chapter {
+    fn from(c: ID3Chapter) -> Self {
+        let mut frames = vec![Frame::text("TIT2", c.title)];
+        if let Some(artist) = c.artist {
+            frames.push(Frame::text("TPE1", artist));
+        }
+        Self {
+            element_id: String::new(),
+            start_time: c.start,
+            end_time: c.end,
+            // Offsets not used
+            start_offset: 0xffffffff,
+            end_offset: 0xffffffff,
+            frames
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fields added for credits, sorting and classical
+    const FIELDS: [Field; 14] = [
+        Field::Composer, Field::Lyricist, Field::Conductor, Field::Producer, Field::Comment, Field::Grouping, Field::Copyright,
+        Field::EncodedBy, Field::Compilation, Field::ArtistSort, Field::TitleSort, Field::AlbumSort, Field::Work, Field::Movement
+    ];
+
+    #[test]
+    fn test_field_round_trip() {
+        for id3v24 in [false, true] {
+            let path = std::env::temp_dir().join(format!("onetagger_id3_test_fields_{}_{id3v24}.mp3", std::process::id()));
+            std::fs::write(&path, []).unwrap();
+            let mut tag = ID3Tag::load_or_new(&path);
+            tag.set_id3v24(id3v24);
+            for (i, field) in FIELDS.iter().enumerate() {
+                tag.set_field(field.clone(), vec![format!("Value {i}")], true);
+            }
+            tag.save_file(&path).unwrap();
+
+            let tag = ID3Tag::load_file(&path).unwrap();
+            std::fs::remove_file(&path).ok();
+            for (i, field) in FIELDS.iter().enumerate() {
+                assert_eq!(tag.get_field(field.clone()), Some(vec![format!("Value {i}")]), "{field:?} (ID3v2.4: {id3v24})");
+            }
+            assert_eq!(tag.get_raw("WORK"), Some(vec!["Value 12".to_string()]));
+            assert!(tag.tag.get("WORK").is_none());
+            assert_eq!(tag.get_raw("MVNM"), Some(vec!["Value 13".to_string()]));
+        }
+    }
+}
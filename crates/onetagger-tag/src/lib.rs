@@ -23,18 +23,23 @@ pub mod mp4;
 #[cfg(feature = "tag")]
 pub mod vorbis;
 #[cfg(feature = "tag")]
+pub mod ape;
+#[cfg(feature = "tag")]
 mod wav;
+#[cfg(feature = "tag")]
+mod dsd;
 
 // Supported extensions
-pub static EXTENSIONS : [&'static str; 11] = ["mp3", "flac", "aif", "aiff", "m4a", 
-    "mp4", "wav", "ogg", "opus", "spx", "oga"];
+pub static EXTENSIONS : [&'static str; 16] = ["mp3", "flac", "aif", "aiff", "m4a", 
+    "mp4", "wav", "ogg", "opus", "spx", "oga", "wv", "ape", "mpc", "dsf", "dff"];
 
 #[cfg(feature = "tag")]
 pub enum Tag {
     FLAC(flac::FLACTag),
     ID3(id3::ID3Tag),
     MP4(mp4::MP4Tag),
-    Vorbis(vorbis::VorbisTag)
+    Vorbis(vorbis::VorbisTag),
+    APE(ape::APETag)
 }
 
 #[cfg(feature = "tag")]
@@ -55,12 +60,23 @@ impl Tag {
             return Ok(Tag::Vorbis(vorbis::VorbisTag::load_file(path)?));
         }
 
+        // APE
+        if ext == "wv" || ext == "ape" || ext == "mpc" {
+            return Ok(Tag::APE(ape::APETag::load_file(path)?));
+        }
+
         // ID3
-        let tag = if allow_new {
-            id3::ID3Tag::load_or_new(path)
+        let mut tag = if allow_new {
+            id3::ID3Tag::load_or_new(&path)
         } else {
-            id3::ID3Tag::load_file(path)?
+            id3::ID3Tag::load_file(&path)?
         };
+        // Older tools write APEv2 into MP3
+        if ext == "mp3" {
+            if let Some(ape) = ape::APETag::load_trailing(&path) {
+                tag.set_ape_fallback(ape);
+            }
+        }
         Ok(Tag::ID3(tag))
     }
 
@@ -71,6 +87,7 @@ impl Tag {
             Tag::ID3(tag) => tag.set_separator(&separators.id3),
             Tag::MP4(tag) => tag.set_separator(&separators.mp4),
             Tag::Vorbis(tag) => tag.set_separator(separators.vorbis.as_ref().unwrap_or(&String::new())),
+            Tag::APE(tag) => tag.set_separator(separators.vorbis.as_ref().unwrap_or(&String::new())),
         }
     }
 
@@ -81,6 +98,7 @@ impl Tag {
             Tag::ID3(tag) => Box::new(tag),
             Tag::MP4(tag) => Box::new(tag),
             Tag::Vorbis(tag) => Box::new(tag),
+            Tag::APE(tag) => Box::new(tag),
         }
     }
     pub fn tag_mut(&mut self) -> Box<&mut dyn TagImpl> {
@@ -88,7 +106,8 @@ impl Tag {
             Tag::FLAC(tag) => Box::new(tag),
             Tag::ID3(tag) => Box::new(tag),
             Tag::MP4(tag) => Box::new(tag),
-            Tag::Vorbis(tag) => Box::new(tag)
+            Tag::Vorbis(tag) => Box::new(tag),
+            Tag::APE(tag) => Box::new(tag)
         }
     }
 
@@ -100,9 +119,11 @@ impl Tag {
             Tag::ID3(id3) => match id3.format {
                 id3::ID3AudioFormat::MP3 => AudioFileFormat::MP3,
                 id3::ID3AudioFormat::AIFF => AudioFileFormat::AIFF,
-                id3::ID3AudioFormat::WAV => AudioFileFormat::WAV
+                id3::ID3AudioFormat::WAV => AudioFileFormat::WAV,
+                id3::ID3AudioFormat::DSD => AudioFileFormat::DSD
             },
-            Tag::Vorbis(_) => AudioFileFormat::OGG
+            Tag::Vorbis(_) => AudioFileFormat::OGG,
+            Tag::APE(_) => AudioFileFormat::APE
         }
     }
 }
@@ -147,6 +168,8 @@ pub trait TagImpl {
 
     /// Set lyrics
     fn set_lyrics(&mut self, lyrics: &Lyrics, synced: bool, overwrite: bool);
+    /// Get embedded lyrics, when `synced` only lyrics with timestamps are returned
+    fn get_lyrics(&self, synced: bool) -> Option<Lyrics>;
 
     /// Set track number (because formats like MP3 and M4A use custom format)
     /// Track number is string because of platforms like discogs
@@ -156,7 +179,7 @@ pub trait TagImpl {
     fn set_explicit(&mut self, explicit: bool);
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TagSeparators {
     pub id3: String,
     pub vorbis: Option<String>,
@@ -176,7 +199,7 @@ impl Default for TagSeparators {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum AudioFileFormat {
-    FLAC, AIFF, MP3, MP4, WAV, OGG
+    FLAC, AIFF, MP3, MP4, WAV, OGG, APE, DSD
 }
 
 impl AudioFileFormat {
@@ -189,6 +212,8 @@ impl AudioFileFormat {
             "m4a" | "mp4" => Some(AudioFileFormat::MP4),
             "wav" => Some(AudioFileFormat::WAV),
             "ogg" | "opus" | "spx" | "oga" => Some(AudioFileFormat::OGG),
+            "wv" | "ape" | "mpc" => Some(AudioFileFormat::APE),
+            "dsf" | "dff" => Some(AudioFileFormat::DSD),
             _ => None
         }
     }
@@ -225,9 +250,15 @@ impl FrameName {
             AudioFileFormat::AIFF => self.id3.to_string(),
             AudioFileFormat::MP3 => self.id3.to_string(),
             AudioFileFormat::WAV => self.id3.to_string(),
+            AudioFileFormat::DSD => self.id3.to_string(),
             AudioFileFormat::FLAC => self.vorbis.to_string(),
             AudioFileFormat::MP4 => self.mp4.to_string(),
-            AudioFileFormat::OGG => self.vorbis.to_string()
+            AudioFileFormat::OGG => self.vorbis.to_string(),
+            // APE keys are case insensitive, only translate known fields
+            AudioFileFormat::APE => Field::all().into_iter()
+                .find(|f| f.vorbis().eq_ignore_ascii_case(&self.vorbis))
+                .map(|f| f.ape().to_string())
+                .unwrap_or(self.vorbis.to_string())
         }
     }
 }
@@ -312,6 +343,20 @@ pub enum Field {
     Mood,
     TrackTotal,
     DiscNumber,
+    Composer,
+    Lyricist,
+    Conductor,
+    Producer,
+    Comment,
+    Grouping,
+    Copyright,
+    EncodedBy,
+    Compilation,
+    ArtistSort,
+    TitleSort,
+    AlbumSort,
+    Work,
+    Movement,
 }
 
 impl Field {
@@ -321,12 +366,24 @@ impl Field {
             AudioFileFormat::FLAC => self.vorbis(),
             AudioFileFormat::AIFF => self.id3(),
             AudioFileFormat::WAV => self.id3(),
+            AudioFileFormat::DSD => self.id3(),
             AudioFileFormat::MP3 => self.id3(),
             AudioFileFormat::MP4 => self.mp4(),
             AudioFileFormat::OGG => self.vorbis(),
+            AudioFileFormat::APE => self.ape(),
         }
     }
 
+    /// All fields
+    pub fn all() -> [Field; 32] {
+        [Field::Title, Field::Artist, Field::Album, Field::AlbumArtist, Field::Key, Field::BPM,
+        Field::Genre, Field::Style, Field::Label, Field::ISRC, Field::CatalogNumber, Field::Version,
+        Field::TrackNumber, Field::Duration, Field::Remixer, Field::Mood, Field::TrackTotal, Field::DiscNumber,
+        Field::Composer, Field::Lyricist, Field::Conductor, Field::Producer, Field::Comment, Field::Grouping,
+        Field::Copyright, Field::EncodedBy, Field::Compilation, Field::ArtistSort, Field::TitleSort,
+        Field::AlbumSort, Field::Work, Field::Movement]
+    }
+
     /// Convert to ID3 frame name
     pub fn id3(&self) -> &'static str {
         match self {
@@ -348,6 +405,20 @@ impl Field {
             Field::Mood => "TMOO",
             Field::TrackTotal => "TRCK",
             Field::DiscNumber => "TPOS",
+            Field::Composer => "TCOM",
+            Field::Lyricist => "TEXT",
+            Field::Conductor => "TPE3",
+            Field::Producer => "PRODUCER",
+            Field::Comment => "COMM",
+            Field::Grouping => "TIT1",
+            Field::Copyright => "TCOP",
+            Field::EncodedBy => "TENC",
+            Field::Compilation => "TCMP",
+            Field::ArtistSort => "TSOP",
+            Field::TitleSort => "TSOT",
+            Field::AlbumSort => "TSOA",
+            Field::Work => "WORK",
+            Field::Movement => "MVNM",
         }
     }
 
@@ -372,6 +443,20 @@ impl Field {
             Field::Mood => "MOOD",
             Field::TrackTotal => "TRACKTOTAL",
             Field::DiscNumber => "DISCNUMBER",
+            Field::Composer => "COMPOSER",
+            Field::Lyricist => "LYRICIST",
+            Field::Conductor => "CONDUCTOR",
+            Field::Producer => "PRODUCER",
+            Field::Comment => "COMMENT",
+            Field::Grouping => "GROUPING",
+            Field::Copyright => "COPYRIGHT",
+            Field::EncodedBy => "ENCODEDBY",
+            Field::Compilation => "COMPILATION",
+            Field::ArtistSort => "ARTISTSORT",
+            Field::TitleSort => "TITLESORT",
+            Field::AlbumSort => "ALBUMSORT",
+            Field::Work => "WORK",
+            Field::Movement => "MOVEMENTNAME",
         }
     }
 
@@ -396,6 +481,58 @@ impl Field {
             Field::Mood => "com.apple.iTunes:MOOD",
             Field::TrackTotal => "trkn",
             Field::DiscNumber => "disk",
+            Field::Composer => "©wrt",
+            Field::Lyricist => "com.apple.iTunes:LYRICIST",
+            Field::Conductor => "com.apple.iTunes:CONDUCTOR",
+            Field::Producer => "com.apple.iTunes:PRODUCER",
+            Field::Comment => "©cmt",
+            Field::Grouping => "©grp",
+            Field::Copyright => "cprt",
+            Field::EncodedBy => "©too",
+            Field::Compilation => "cpil",
+            Field::ArtistSort => "soar",
+            Field::TitleSort => "sonm",
+            Field::AlbumSort => "soal",
+            Field::Work => "©wrk",
+            Field::Movement => "©mvn",
+        }
+    }
+
+    /// Convert to APEv2 item key
+    pub fn ape(&self) -> &'static str {
+        match self {
+            Field::Title => "Title",
+            Field::Artist => "Artist",
+            Field::AlbumArtist => "Album Artist",
+            Field::Album => "Album",
+            Field::Key => "Key",
+            Field::BPM => "BPM",
+            Field::Genre => "Genre",
+            Field::Label => "Label",
+            Field::Style => "Style",
+            Field::ISRC => "ISRC",
+            Field::CatalogNumber => "CatalogNumber",
+            Field::Version => "Subtitle",
+            Field::TrackNumber => "Track",
+            Field::Duration => "Length",
+            Field::Remixer => "MixArtist",
+            Field::Mood => "Mood",
+            Field::TrackTotal => "Track",
+            Field::DiscNumber => "Disc",
+            Field::Composer => "Composer",
+            Field::Lyricist => "Lyricist",
+            Field::Conductor => "Conductor",
+            Field::Producer => "Producer",
+            Field::Comment => "Comment",
+            Field::Grouping => "Grouping",
+            Field::Copyright => "Copyright",
+            Field::EncodedBy => "EncodedBy",
+            Field::Compilation => "Compilation",
+            Field::ArtistSort => "ArtistSort",
+            Field::TitleSort => "TitleSort",
+            Field::AlbumSort => "AlbumSort",
+            Field::Work => "Work",
+            Field::Movement => "Movement",
         }
     }
 }
@@ -531,7 +668,96 @@ impl Lyrics {
         } else {
             parts[0].parse()?
         };
-        return Ok(Duration::from_secs_f32(seconds + minutes as f32 * 60.0))
+        return Ok(Duration::try_from_secs_f32(seconds + minutes as f32 * 60.0)?)
+    }
+
+    /// Parse LRC or plain text lyrics, None if empty
+    pub fn parse(input: &str, language: &str) -> Option<Lyrics> {
+        Self::parse_lrc(input, language).or_else(|| Self::from_text(input, language))
+    }
+
+    /// Plain text, paragraphs are separated by empty line
+    pub fn from_text(input: &str, language: &str) -> Option<Lyrics> {
+        let paragraphs = input.replace("\r\n", "\n").split("\n\n").map(|p| p.lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(|l| LyricsLine { text: l.to_string(), start: None, end: None, parts: vec![] })
+            .collect::<Vec<_>>()
+        ).filter(|p| !p.is_empty()).collect::<Vec<_>>();
+        if paragraphs.is_empty() {
+            return None;
+        }
+        Some(Lyrics { paragraphs, language: language.to_string() })
+    }
+
+    /// Parse LRC (with enhanced word timestamps), None if less than half of the lines are timestamped
+    pub fn parse_lrc(input: &str, language: &str) -> Option<Lyrics> {
+        let mut lines = vec![];
+        let mut untimed = 0;
+        for line in input.lines() {
+            // Line can have multiple timestamps, metadata tags are ignored
+            let mut rest = line.trim();
+            let mut starts = vec![];
+            while let Some(end) = rest.strip_prefix('[').and_then(|r| r.find(']')) {
+                let Ok(start) = Self::parse_lrc_timestamp(&rest[1..end + 1]) else { break };
+                starts.push(start);
+                rest = &rest[end + 2..];
+            }
+            // Text without timestamp, metadata tags don't count
+            if starts.is_empty() {
+                if !rest.is_empty() && !(rest.starts_with('[') && rest.ends_with(']')) {
+                    untimed += 1;
+                }
+                continue;
+            }
+            let (text, parts) = Self::parse_lrc_parts(rest.trim());
+            lines.extend(starts.into_iter().map(|start| LyricsLine {
+                text: text.to_string(), start: Some(start), end: None, parts: parts.clone()
+            }));
+        }
+        // Plain text with a stray timestamp would lose the other lines
+        if lines.is_empty() || untimed > lines.len() {
+            return None;
+        }
+        Self::from_synced(lines, language)
+    }
+
+    /// Enhanced LRC: `<mm:ss.xx> word <mm:ss.xx> word`
+    fn parse_lrc_parts(text: &str) -> (String, Vec<LyricsLinePart>) {
+        let mut parts = text.split('<').filter_map(|segment| {
+            let (start, word) = match segment.split_once('>').map(|(ts, word)| (Self::parse_lrc_timestamp(ts.trim()), word)) {
+                Some((Ok(start), word)) => (Some(start), word),
+                _ => (None, segment)
+            };
+            Some(LyricsLinePart { text: word.trim().to_string(), start, end: None }).filter(|p| !p.text.is_empty())
+        }).collect::<Vec<_>>();
+        if parts.iter().all(|p| p.start.is_none()) {
+            return (text.to_string(), vec![]);
+        }
+        for i in 1..parts.len() {
+            parts[i - 1].end = parts[i].start;
+        }
+        (parts.iter().map(|p| p.text.as_str()).collect::<Vec<_>>().join(" "), parts)
+    }
+
+    /// Sort timestamped lines, line ends where the next starts. Empty lines separate paragraphs
+    pub fn from_synced(mut lines: Vec<LyricsLine>, language: &str) -> Option<Lyrics> {
+        lines.sort_by_key(|l| l.start);
+        let ends = lines.iter().skip(1).map(|l| l.start).chain([None]).collect::<Vec<_>>();
+        let mut paragraphs: Vec<Vec<LyricsLine>> = vec![vec![]];
+        for (mut line, end) in lines.into_iter().zip(ends) {
+            line.end = line.end.or(end);
+            if line.text.trim().is_empty() {
+                paragraphs.push(vec![]);
+                continue;
+            }
+            paragraphs.last_mut().unwrap().push(line);
+        }
+        paragraphs.retain(|p| !p.is_empty());
+        if paragraphs.is_empty() {
+            return None;
+        }
+        Some(Lyrics { paragraphs, language: language.to_string() })
     }
 
     /// Are the lyrics synced?
@@ -570,3 +796,62 @@ pub struct LyricsLinePart {
     pub end: Option<Duration>
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Option<Duration> {
+        Some(Duration::from_millis(ms))
+    }
+
+    #[test]
+    fn test_parse_lrc() {
+        let lrc = "[ar:Artist]\n[ti:Title]\n[00:12.00]First line\n[00:15.50]Second line\n[00:20.00]\n[00:22.00][01:02.00]Chorus";
+        let lyrics = Lyrics::parse(lrc, "eng").unwrap();
+        assert!(lyrics.synced());
+        assert_eq!(lyrics.paragraphs.len(), 2);
+        let lines = lyrics.iter_lines().map(|l| (l.text.as_str(), l.start, l.end)).collect::<Vec<_>>();
+        assert_eq!(lines, vec![
+            ("First line", ms(12000), ms(15500)),
+            ("Second line", ms(15500), ms(20000)),
+            ("Chorus", ms(22000), ms(62000)),
+            ("Chorus", ms(62000), None),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_mostly_untimed() {
+        // Single timestamp in plain text keeps all lines
+        let text = "First line\n[00:12.00]Second line\nThird line";
+        assert!(Lyrics::parse_lrc(text, "eng").is_none());
+        let lyrics = Lyrics::parse(text, "eng").unwrap();
+        assert!(!lyrics.synced());
+        assert_eq!(lyrics.iter_lines().count(), 3);
+        assert_eq!(lyrics.text(), text);
+    }
+
+    #[test]
+    fn test_parse_enhanced_lrc() {
+        let lyrics = Lyrics::parse("[00:01.00]<00:01.00> Hello <00:01.50> world\n[00:03.00]Plain", "eng").unwrap();
+        let line = lyrics.iter_lines().next().unwrap();
+        assert_eq!(line.text, "Hello world");
+        assert_eq!(line.end, ms(3000));
+        assert_eq!(line.parts, vec![
+            LyricsLinePart { text: "Hello".to_string(), start: ms(1000), end: ms(1500) },
+            LyricsLinePart { text: "world".to_string(), start: ms(1500), end: None },
+        ]);
+        assert!(lyrics.iter_lines().nth(1).unwrap().parts.is_empty());
+    }
+
+    #[cfg(feature = "tag")]
+    #[test]
+    fn test_sylt_round_trip() {
+        let lyrics = Lyrics::parse("[00:01.00]First\n[00:02.50]Second\n[00:04.00]Third", "eng").unwrap();
+        let mut tag = crate::id3::ID3Tag::load_or_new(std::env::temp_dir().join("onetagger_lyrics_test_missing.mp3"));
+        tag.set_lyrics(&lyrics, true, true);
+        assert_eq!(tag.get_lyrics(true), Some(lyrics.clone()));
+        // Unsynced copy in USLT
+        assert_eq!(tag.get_lyrics(false).unwrap().text(), lyrics.text());
+    }
+}
@@ -82,6 +82,20 @@ impl MP4Tag {
             Field::Mood => DataIdent::freeform("com.apple.iTunes", "MOOD"),
             Field::TrackTotal => DataIdent::fourcc(*b"trkn"),
             Field::DiscNumber => DataIdent::fourcc(*b"disk"),
+            Field::Composer => DataIdent::fourcc(*b"\xa9wrt"),
+            Field::Comment => DataIdent::fourcc(*b"\xa9cmt"),
+            Field::Grouping => DataIdent::fourcc(*b"\xa9grp"),
+            Field::Copyright => DataIdent::fourcc(*b"cprt"),
+            Field::EncodedBy => DataIdent::fourcc(*b"\xa9too"),
+            Field::Compilation => DataIdent::fourcc(*b"cpil"),
+            Field::ArtistSort => DataIdent::fourcc(*b"soar"),
+            Field::TitleSort => DataIdent::fourcc(*b"sonm"),
+            Field::AlbumSort => DataIdent::fourcc(*b"soal"),
+            Field::Work => DataIdent::fourcc(*b"\xa9wrk"),
+            Field::Movement => DataIdent::fourcc(*b"\xa9mvn"),
+            Field::Lyricist => DataIdent::freeform("com.apple.iTunes", "LYRICIST"),
+            Field::Conductor => DataIdent::freeform("com.apple.iTunes", "CONDUCTOR"),
+            Field::Producer => DataIdent::freeform("com.apple.iTunes", "PRODUCER"),
         }
     }
 
@@ -259,6 +273,13 @@ impl TagImpl for MP4Tag {
                 }
                 return;
             }
+            // Boolean flag
+            if field == Field::Compilation {
+                if value.first().is_some_and(|v| v == "1" || v.eq_ignore_ascii_case("true")) {
+                    self.tag.set_compilation();
+                }
+                return;
+            }
 
             // Add each data separately
             if self.separator.is_empty() {
@@ -285,6 +306,9 @@ impl TagImpl for MP4Tag {
         if field == Field::BPM {
             return self.tag.bpm().map(|b| vec![b.to_string()]);
         }
+        if field == Field::Compilation {
+            return self.tag.compilation().then(|| vec!["1".to_string()]);
+        }
 
         self.raw_by_ident(&MP4Tag::field_to_ident(field))
     }
@@ -356,6 +380,10 @@ impl TagImpl for MP4Tag {
         self.tag.set_lyrics(lyrics.text());
     }
 
+    fn get_lyrics(&self, synced: bool) -> Option<Lyrics> {
+        Lyrics::parse(self.tag.lyrics()?, "eng").filter(|l| !synced || l.synced())
+    }
+
     fn set_explicit(&mut self, explicit: bool) {
         self.tag.set_advisory_rating(match explicit {
             true => AdvisoryRating::Explicit,
@@ -253,6 +253,11 @@ impl TagImpl for VorbisTag {
         self.tag.insert("LYRICS".to_string(), lyrics.text());
     }
 
+    fn get_lyrics(&self, synced: bool) -> Option<Lyrics> {
+        let text = self.tag.get("LYRICS").or(self.tag.get("UNSYNCEDLYRICS"))?;
+        Lyrics::parse(text, "eng").filter(|l| !synced || l.synced())
+    }
+
     fn set_track_number(&mut self, track_number: &str, track_total: Option<u16>, overwrite: bool) {
         self.set_field(Field::TrackNumber, vec![track_number.to_string()], overwrite);
         if let Some(total) = track_total {
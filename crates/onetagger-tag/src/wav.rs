@@ -3,7 +3,8 @@ use std::io::{BufReader, BufWriter, Cursor, Seek, SeekFrom};
 use std::fs::File;
 use std::collections::HashMap;
 use std::path::Path;
-use id3::{Tag, TagLike, Frame, Version};
+use id3::{Tag, TagLike, Frame, Version, Content};
+use id3::frame::Comment;
 use once_cell::sync::Lazy;
 use riff::{Chunk, ChunkId, LIST_ID, RIFF_ID, ChunkContents};
 
@@ -13,6 +14,16 @@ static ID3_ID_2: ChunkId = ChunkId { value: [0x49, 0x44, 0x33, 0x20] };
 /// RIFF WAVE chunk
 static WAVE_ID:  ChunkId = ChunkId { value: [0x57, 0x41, 0x56, 0x45] };
 static INFO_ID:  ChunkId = ChunkId { value: [0x49, 0x4E, 0x46, 0x4F] };
+/// Broadcast Wave extension chunk
+static BEXT_ID:  ChunkId = ChunkId { value: [0x62, 0x65, 0x78, 0x74] };
+static DATA_ID:  ChunkId = ChunkId { value: [0x64, 0x61, 0x74, 0x61] };
+
+/// Standard RIFF INFO chunk IDs (any other `I???` chunk in INFO list is kept as well)
+pub const RIFF_INFO_IDS: [&str; 32] = [
+    "IARL", "IART", "ICMS", "ICMT", "ICOP", "ICRD", "ICRP", "IDIM", "IDPI", "IENG", "IGNR", "IKEY",
+    "ILGT", "IMED", "INAM", "IPLT", "IPRD", "ISBJ", "ISFT", "ISHP", "ISRC", "ISRF", "ITCH", "ITRK",
+    "IPRT", "IWRI", "IMUS", "IPRO", "ILNG", "IDIT", "ISMP", "ISTR"
+];
 
 /// ID3 Frame to RIFF ChunkID
 static ID3_RIFF: Lazy<HashMap<&'static str, ChunkId>> = Lazy::new(|| {
@@ -23,12 +34,222 @@ static ID3_RIFF: Lazy<HashMap<&'static str, ChunkId>> = Lazy::new(|| {
     m.insert("COMM", ChunkId::new("ICMT").unwrap());
     m.insert("TCON", ChunkId::new("IGNR").unwrap());
     m.insert("TSRC", ChunkId::new("ISRC").unwrap());
+    m.insert("TRCK", ChunkId::new("ITRK").unwrap());
+    m.insert("TCOP", ChunkId::new("ICOP").unwrap());
+    m.insert("TSSE", ChunkId::new("ISFT").unwrap());
+    m.insert("TCOM", ChunkId::new("IMUS").unwrap());
+    m.insert("TEXT", ChunkId::new("IWRI").unwrap());
     m
 });
 
+/// INFO chunk ID which mirrors ID3 frame
+pub(crate) fn mirrored_info_id(frame: &str) -> Option<&'static str> {
+    ID3_RIFF.get(frame).map(|id| id.as_str())
+}
+
+/// Is chunk part of the INFO vocabulary (uppercase `I???`)
+fn is_info_id(id: &ChunkId) -> bool {
+    id.value[0] == b'I' && id.value[1..].iter().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+}
+
+/// Text of ID3 frame which gets mirrored to RIFF INFO
+fn frame_text(frame: &Frame) -> Option<String> {
+    match frame.content() {
+        Content::Text(text) => Some(text.replace('\0', ", ")),
+        Content::Comment(comment) => Some(comment.text.to_string()),
+        _ => None
+    }
+}
+
+/// RIFF INFO and BWF `bext` metadata, which can't be stored in ID3
+#[derive(Debug, Clone, Default)]
+pub struct WavMeta {
+    /// INFO chunk ID, value (in original order)
+    pub info: Vec<(String, String)>,
+    pub bext: Option<BextChunk>,
+}
+
+impl WavMeta {
+    /// Is tag name handled by WavMeta instead of ID3
+    pub fn is_key(&self, tag: &str) -> bool {
+        RIFF_INFO_IDS.contains(&tag) || self.info.iter().any(|(k, _)| k == tag) || BextChunk::field(tag).is_some()
+    }
+
+    pub fn get(&self, tag: &str) -> Option<String> {
+        if BextChunk::field(tag).is_some() {
+            return self.bext.as_ref()?.get(tag);
+        }
+        self.info.iter().find(|(k, _)| k == tag).map(|(_, v)| v.to_string())
+    }
+
+    /// Set or remove (None) value
+    pub fn set(&mut self, tag: &str, value: Option<String>) {
+        if BextChunk::field(tag).is_some() {
+            match (self.bext.as_mut(), value) {
+                (Some(bext), value) => bext.set(tag, value.as_deref().unwrap_or_default()),
+                (None, Some(value)) => self.bext.get_or_insert_with(BextChunk::default).set(tag, &value),
+                (None, None) => {}
+            }
+            return;
+        }
+        match (self.info.iter_mut().find(|(k, _)| k == tag), value) {
+            (Some(entry), Some(value)) => entry.1 = value,
+            (None, Some(value)) => self.info.push((tag.to_string(), value)),
+            (_, None) => self.info.retain(|(k, _)| k != tag),
+        }
+    }
+
+    /// All INFO and `bext` values
+    pub fn all(&self) -> Vec<(String, String)> {
+        let mut out = self.info.clone();
+        if let Some(bext) = &self.bext {
+            out.extend(BEXT_FIELDS.iter().filter_map(|(k, _)| Some((k.to_string(), bext.get(k)?))));
+        }
+        out
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BextField {
+    Text(usize, usize),
+    TimeReference,
+    Umid,
+    /// Loudness in 0.01 LU / dB
+    Loudness(usize),
+    CodingHistory,
+}
+
+/// Tag names of `bext` fields
+const BEXT_FIELDS: [(&str, BextField); 13] = [
+    ("BEXT_DESCRIPTION", BextField::Text(0, 256)),
+    ("BEXT_ORIGINATOR", BextField::Text(256, 288)),
+    ("BEXT_ORIGINATOR_REFERENCE", BextField::Text(288, 320)),
+    ("BEXT_ORIGINATION_DATE", BextField::Text(320, 330)),
+    ("BEXT_ORIGINATION_TIME", BextField::Text(330, 338)),
+    ("BEXT_TIME_REFERENCE", BextField::TimeReference),
+    ("BEXT_UMID", BextField::Umid),
+    ("BEXT_LOUDNESS_VALUE", BextField::Loudness(412)),
+    ("BEXT_LOUDNESS_RANGE", BextField::Loudness(414)),
+    ("BEXT_MAX_TRUE_PEAK_LEVEL", BextField::Loudness(416)),
+    ("BEXT_MAX_MOMENTARY_LOUDNESS", BextField::Loudness(418)),
+    ("BEXT_MAX_SHORT_TERM_LOUDNESS", BextField::Loudness(420)),
+    ("BEXT_CODING_HISTORY", BextField::CodingHistory),
+];
+/// Size of `bext` without coding history
+const BEXT_SIZE: usize = 602;
+const BEXT_VERSION: usize = 346;
+/// Unused loudness value
+const LOUDNESS_UNSET: i16 = 0x7FFF;
+
+/// Broadcast Wave `bext` chunk (EBU Tech 3285), edited in place so unknown data is kept
+#[derive(Debug, Clone)]
+pub struct BextChunk {
+    data: Vec<u8>,
+}
+
+impl Default for BextChunk {
+    fn default() -> Self {
+        let mut bext = BextChunk { data: vec![0; BEXT_SIZE] };
+        bext.set_u16(BEXT_VERSION, 2);
+        for offset in (412..422).step_by(2) {
+            bext.set_u16(offset, LOUDNESS_UNSET as u16);
+        }
+        bext
+    }
+}
+
+impl BextChunk {
+    pub fn from_bytes(mut data: Vec<u8>) -> BextChunk {
+        if data.len() < BEXT_SIZE {
+            data.resize(BEXT_SIZE, 0);
+        }
+        BextChunk { data }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    fn field(tag: &str) -> Option<BextField> {
+        BEXT_FIELDS.iter().find(|(k, _)| *k == tag).map(|(_, f)| *f)
+    }
+
+    fn u16(&self, offset: usize) -> u16 {
+        u16::from_le_bytes([self.data[offset], self.data[offset + 1]])
+    }
+
+    fn set_u16(&mut self, offset: usize, value: u16) {
+        self.data[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// Get field value by tag name
+    pub fn get(&self, tag: &str) -> Option<String> {
+        let value = match Self::field(tag)? {
+            BextField::Text(start, end) => {
+                let text = &self.data[start..end];
+                String::from_utf8_lossy(text.split(|c| *c == 0).next().unwrap_or_default()).trim().to_string()
+            },
+            BextField::TimeReference => u64::from_le_bytes(self.data[338..346].try_into().unwrap()).to_string(),
+            BextField::Umid => match self.data[348..412].iter().all(|b| *b == 0) {
+                true => String::new(),
+                false => self.data[348..412].iter().map(|b| format!("{b:02X}")).collect()
+            },
+            BextField::Loudness(offset) => match self.u16(offset) as i16 {
+                v if self.u16(BEXT_VERSION) < 2 || v == LOUDNESS_UNSET => String::new(),
+                v => format!("{:.2}", v as f32 / 100.0)
+            },
+            BextField::CodingHistory => String::from_utf8_lossy(&self.data[BEXT_SIZE..]).trim_end_matches('\0').trim().to_string()
+        };
+        match value.is_empty() {
+            true => None,
+            false => Some(value)
+        }
+    }
+
+    /// Set field value by tag name, empty value clears it
+    pub fn set(&mut self, tag: &str, value: &str) {
+        let value = value.trim();
+        match Self::field(tag) {
+            Some(BextField::Text(start, end)) => {
+                self.data[start..end].fill(0);
+                let len = value.len().min(end - start);
+                self.data[start..start + len].copy_from_slice(&value.as_bytes()[..len]);
+            },
+            Some(BextField::TimeReference) => {
+                let value: u64 = value.parse().unwrap_or(0);
+                self.data[338..346].copy_from_slice(&value.to_le_bytes());
+            },
+            Some(BextField::Umid) => {
+                self.data[348..412].fill(0);
+                let bytes = (0..value.len() / 2).filter_map(|i| u8::from_str_radix(value.get(i * 2..i * 2 + 2)?, 16).ok());
+                for (i, b) in bytes.take(64).enumerate() {
+                    self.data[348 + i] = b;
+                }
+            },
+            Some(BextField::Loudness(offset)) => {
+                // Loudness fields were added in version 2
+                if self.u16(BEXT_VERSION) < 2 {
+                    self.set_u16(BEXT_VERSION, 2);
+                    for o in (412..422).step_by(2) {
+                        self.set_u16(o, LOUDNESS_UNSET as u16);
+                    }
+                }
+                let v = value.parse::<f32>().map(|v| (v * 100.0).round() as i16).unwrap_or(LOUDNESS_UNSET);
+                self.set_u16(offset, v as u16);
+            },
+            Some(BextField::CodingHistory) => {
+                self.data.truncate(BEXT_SIZE);
+                self.data.extend_from_slice(value.as_bytes());
+            },
+            None => {}
+        }
+    }
+}
+
+
 /// Write wav to path
 /// Will copy ID3 meta into RIFF INFO chunk
-pub(crate) fn write_wav(path: impl AsRef<Path>, tag: Tag, version: Version) -> Result<(), Error> {
+pub(crate) fn write_wav(path: impl AsRef<Path>, tag: Tag, version: Version, meta: &WavMeta) -> Result<(), Error> {
     let mut file = BufReader::new(File::open(&path)?);
     let mut offset = 0;
     // Read all the chunks
@@ -87,35 +308,69 @@ pub(crate) fn write_wav(path: impl AsRef<Path>, tag: Tag, version: Version) -> R
 
     // Generate the RIFF chunk
     let mut riff_data = vec![];
+    let mut bext = meta.bext.as_ref();
     for chunk in riff_chunks {
         // skip old ID3 chunk
         if chunk.id() == ID3_ID_1 || chunk.id() == ID3_ID_2 {
             continue;
         }
+        // Updated bext in place
+        if chunk.id() == BEXT_ID {
+            if let Some(bext) = bext.take() {
+                riff_data.push(ChunkContents::Data(BEXT_ID.clone(), bext.bytes().to_vec()));
+            }
+            continue;
+        }
+        // New bext goes before audio data
+        if chunk.id() == DATA_ID {
+            if let Some(bext) = bext.take() {
+                riff_data.push(ChunkContents::Data(BEXT_ID.clone(), bext.bytes().to_vec()));
+            }
+        }
         // Passthru
         let data = ChunkContents::Data(chunk.id(), chunk.read_contents(&mut file)?);
         riff_data.push(data);
     }
+    if let Some(bext) = bext {
+        riff_data.push(ChunkContents::Data(BEXT_ID.clone(), bext.bytes().to_vec()));
+    }
     // Add ID3 chunk
     let mut out = vec![];
     tag.write_to(Cursor::new(&mut out), version)?;
     riff_data.push(ChunkContents::Data(ID3_ID_1.clone(), out));
     let riff_chunk = ChunkContents::Children(RIFF_ID.clone(), WAVE_ID.clone(), riff_data);
 
-    // Generate LIST chunk
-    let mut list_data = vec![];
-    for frame in tag.frames() {
-        if let Some(chunk_id) = ID3_RIFF.get(frame.id()) {
-            if let Some(text) = frame.content().text() {
-                let data = ChunkContents::Data(chunk_id.clone(), format!("{text}").as_bytes().to_owned());
-                list_data.push(data);
-            }
+    // Generate LIST chunk, ID3 values take priority
+    let mut info = meta.info.iter()
+        .filter_map(|(k, v)| Some((ChunkId::new(k).ok()?, v.to_string())))
+        .collect::<Vec<_>>();
+    let mut mirrored = tag.frames()
+        .filter_map(|frame| Some((ID3_RIFF.get(frame.id())?.clone(), frame_text(frame)?)))
+        .collect::<Vec<_>>();
+    if let Some(date) = tag.date_recorded() {
+        mirrored.push((ChunkId::new("ICRD").unwrap(), date.to_string()));
+    } else if let Some(year) = tag.year() {
+        mirrored.push((ChunkId::new("ICRD").unwrap(), year.to_string()));
+    }
+    for (id, mut value) in mirrored {
+        if id.as_str() == "ITRK" {
+            value = value.split('/').next().unwrap_or_default().to_string();
+        }
+        match info.iter_mut().find(|(i, _)| i == &id) {
+            Some(entry) => entry.1 = value,
+            None => info.push((id, value)),
         }
     }
-    
-    // Add original LIST chunks
+    let mut list_data = info.into_iter().map(|(id, value)| {
+        // NULL terminated
+        let mut data = value.into_bytes();
+        data.push(0);
+        ChunkContents::Data(id, data)
+    }).collect::<Vec<_>>();
+
+    // Add original LIST chunks (INFO values are already in meta)
     for chunk in list_chunks {
-        if list_data.iter().any(|c| matches!(c, ChunkContents::Data(id, _) if id == &chunk.id())) {
+        if is_info_id(&chunk.id()) || list_data.iter().any(|c| matches!(c, ChunkContents::Data(id, _) if id == &chunk.id())) {
             continue;
         }
         let data = ChunkContents::Data(chunk.id(), chunk.read_contents(&mut file)?);
@@ -132,7 +387,7 @@ pub(crate) fn write_wav(path: impl AsRef<Path>, tag: Tag, version: Version) -> R
 }
 
 /// Read WAV from file, will copy missing tags from RIFF to ID3
-pub(crate) fn read_wav(path: impl AsRef<Path>) -> Result<Tag, Error> {
+pub(crate) fn read_wav(path: impl AsRef<Path>) -> Result<(Tag, WavMeta), Error> {
     let mut file = BufReader::new(File::open(path)?);
     let mut offset = 0;
     // Read all the chunks
@@ -167,21 +422,143 @@ pub(crate) fn read_wav(path: impl AsRef<Path>) -> Result<Tag, Error> {
     }
     let chunks = new_chunks;
 
+    // RIFF INFO and bext
+    let mut meta = WavMeta::default();
+    for chunk in &chunks {
+        if is_info_id(&chunk.id()) {
+            let data = chunk.read_contents(&mut file)?;
+            let value = String::from_utf8_lossy(&data).trim_end_matches('\0').trim().to_string();
+            meta.info.push((chunk.id().as_str().to_string(), value));
+        }
+        if chunk.id() == BEXT_ID {
+            meta.bext = Some(BextChunk::from_bytes(chunk.read_contents(&mut file)?));
+        }
+    }
+
     // Read ID3 using the new ID3 reader
     file.seek(SeekFrom::Start(0))?;
-    let mut id3 = Tag::read_from2(&mut file).unwrap_or(Tag::new());
+    let mut id3 = match Tag::read_from2(&mut file) {
+        Ok(tag) => tag,
+        Err(e) => {
+            debug!("No ID3 in WAV: {e}");
+            Tag::new()
+        }
+    };
 
     // Copy tags from RIFF to ID3 if missing
     for (frame_name, chunk_id) in ID3_RIFF.iter() {
-        if id3.get(frame_name).is_none() {
-            if let Some(chunk) = chunks.iter().find(|c| &c.id() == chunk_id) {
-                let data = chunk.read_contents(&mut file)?;
-                if let Ok(data) = String::from_utf8(data) {
-                    id3.add_frame(Frame::text(frame_name, data.replace("\0", "")));
-                }
-            }
+        if id3.get(frame_name).is_some() {
+            continue;
+        }
+        let Some(value) = meta.get(chunk_id.as_str()).filter(|v| !v.is_empty()) else { continue };
+        match *frame_name {
+            "COMM" => { id3.add_frame(Comment { lang: "eng".to_string(), description: String::new(), text: value }); },
+            _ => { id3.add_frame(Frame::text(frame_name, value)); }
         }
     }
 
-    Ok(id3)
-}
\ No newline at end of file
+    Ok((id3, meta))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TagImpl;
+    use crate::id3::ID3Tag;
+    use std::path::PathBuf;
+
+    /// PCM WAV with 2 samples and no metadata
+    const WAV: &[u8] = b"RIFF\x28\0\0\0WAVEfmt \x10\0\0\0\x01\0\x01\0\x44\xac\0\0\x88\x58\x01\0\x02\0\x10\0data\x04\0\0\0\x01\x02\x03\x04";
+
+    /// Create WAV in temp dir with given INFO / bext
+    fn create_wav(name: &str, meta: &WavMeta) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("onetagger_wav_test_{}_{name}.wav", std::process::id()));
+        std::fs::write(&path, WAV).unwrap();
+        write_wav(&path, Tag::new(), Version::Id3v24, meta).unwrap();
+        path
+    }
+
+    fn info(values: &[(&str, &str)]) -> WavMeta {
+        WavMeta { info: values.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(), bext: None }
+    }
+
+    fn info_ids(meta: &WavMeta) -> Vec<&str> {
+        meta.info.iter().map(|(k, _)| k.as_str()).collect()
+    }
+
+    #[test]
+    fn test_info_round_trip() {
+        let path = create_wav("info", &info(&[("IENG", "Eng"), ("INAM", "Old"), ("IXYZ", "Custom1")]));
+        let (mut tag, meta) = read_wav(&path).unwrap();
+        assert_eq!(info_ids(&meta), vec!["IENG", "INAM", "IXYZ"]);
+        // Missing ID3 values are copied from INFO
+        assert_eq!(tag.title(), Some("Old"));
+
+        tag.set_title("New");
+        tag.set_artist("Artist");
+        write_wav(&path, tag, Version::Id3v24, &meta).unwrap();
+        let (tag, meta) = read_wav(&path).unwrap();
+        assert_eq!(tag.title(), Some("New"));
+        // Order kept, mirrored values updated in place, new ones appended
+        assert_eq!(info_ids(&meta), vec!["IENG", "INAM", "IXYZ", "IART"]);
+        assert_eq!(meta.get("INAM").as_deref(), Some("New"));
+        assert_eq!(meta.get("IENG").as_deref(), Some("Eng"));
+        assert_eq!(meta.get("IXYZ").as_deref(), Some("Custom1"));
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_remove_mirrored() {
+        let path = create_wav("remove", &info(&[("INAM", "Old"), ("IART", "Artist")]));
+        let mut tag = ID3Tag::load_file(&path).unwrap();
+        tag.set_raw("TIT2", vec![], true);
+        tag.remove_raw("TPE1");
+        tag.save_file(&path).unwrap();
+        let (tag, meta) = read_wav(&path).unwrap();
+        assert!(tag.title().is_none());
+        assert!(tag.artist().is_none());
+        assert!(meta.info.is_empty());
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_bext_loudness_upgrade() {
+        // Version 0 bext
+        let path = create_wav("bext", &WavMeta { info: vec![], bext: Some(BextChunk::from_bytes(b"Desc".to_vec())) });
+        let (tag, mut meta) = read_wav(&path).unwrap();
+        // Loudness isn't present in version 0
+        assert_eq!(meta.get("BEXT_DESCRIPTION").as_deref(), Some("Desc"));
+        assert!(meta.get("BEXT_LOUDNESS_VALUE").is_none());
+
+        meta.set("BEXT_LOUDNESS_VALUE", Some("-14.5".to_string()));
+        write_wav(&path, tag, Version::Id3v24, &meta).unwrap();
+        let (_, meta) = read_wav(&path).unwrap();
+        let bext = meta.bext.as_ref().unwrap();
+        assert_eq!(bext.u16(BEXT_VERSION), 2);
+        assert_eq!(meta.get("BEXT_LOUDNESS_VALUE").as_deref(), Some("-14.50"));
+        assert!(meta.get("BEXT_LOUDNESS_RANGE").is_none());
+        assert_eq!(meta.get("BEXT_DESCRIPTION").as_deref(), Some("Desc"));
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_bext_coding_history() {
+        let path = create_wav("history", &WavMeta::default());
+        let (tag, mut meta) = read_wav(&path).unwrap();
+        assert!(meta.bext.is_none());
+
+        // New bext is created
+        meta.set("BEXT_CODING_HISTORY", Some("A=PCM,F=44100,W=16".to_string()));
+        write_wav(&path, tag, Version::Id3v24, &meta).unwrap();
+        let (tag, mut meta) = read_wav(&path).unwrap();
+        assert_eq!(meta.get("BEXT_CODING_HISTORY").as_deref(), Some("A=PCM,F=44100,W=16"));
+
+        // Shorter history replaces the old one
+        meta.set("BEXT_CODING_HISTORY", Some("A=ANALOG".to_string()));
+        write_wav(&path, tag, Version::Id3v24, &meta).unwrap();
+        let (_, meta) = read_wav(&path).unwrap();
+        assert_eq!(meta.get("BEXT_CODING_HISTORY").as_deref(), Some("A=ANALOG"));
+        assert_eq!(meta.bext.as_ref().unwrap().bytes().len(), BEXT_SIZE + 8);
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -4,7 +4,7 @@ use log::{Record, Level, RecordBuilder};
 use crate::TrackMatch;
 
 /// Version of supported custom platform
-pub const CUSTOM_PLATFORM_COMPATIBILITY: i32 = 45;
+pub const CUSTOM_PLATFORM_COMPATIBILITY: i32 = 46;
 
 /// Logging from plugins
 #[no_mangle]
@@ -24,6 +24,12 @@ pub extern "C" fn write_log(record: *mut FFIRecord) {
     }
 }
 
+/// Rate limit waits from plugins, added to the calling tagging thread
+#[no_mangle]
+pub extern "C" fn report_throttled(ms: u64) {
+    crate::ratelimit::report_throttled(std::time::Duration::from_millis(ms));
+}
+
 /// Macro for creating custom platform plugins
 #[macro_export]
 macro_rules! create_plugin {
@@ -66,6 +72,12 @@ macro_rules! create_plugin {
             log::set_max_level(log::LevelFilter::Trace);
         }
 
+        /// Pass the rate limit callback to the plugin, so throttling shows up in 1T
+        #[no_mangle]
+        pub extern "C" fn _1t_register_throttle_callback(f: extern "C" fn(u64)) {
+            onetagger_tagger::ratelimit::set_host_callback(f);
+        }
+
         /// Create new instance of the AutotaggerSourceBuilder and convert it into a raw pointer
         #[no_mangle]
         pub extern "C" fn _1t_create_builder() -> *mut std::ffi::c_void {
@@ -0,0 +1,301 @@
+use anyhow::Error;
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
+use serde::de::Error as _;
+use std::fmt::Display;
+use std::str::FromStr;
+
+const SHARPS: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+const FLATS: [&str; 12] = ["C", "Db", "D", "Eb", "E", "F", "Gb", "G", "Ab", "A", "Bb", "B"];
+/// Most common spelling of each key
+const MAJOR: [&str; 12] = ["C", "Db", "D", "Eb", "E", "F", "F#", "G", "Ab", "A", "Bb", "B"];
+const MINOR: [&str; 12] = ["C", "C#", "D", "Eb", "E", "F", "F#", "G", "G#", "A", "Bb", "B"];
+const MINOR_KEYS: [&str; 12] = ["Cm", "C#m", "Dm", "Ebm", "Em", "Fm", "F#m", "Gm", "G#m", "Am", "Bbm", "Bm"];
+/// By wheel position
+const CAMELOT_MINOR: [&str; 12] = ["1A", "2A", "3A", "4A", "5A", "6A", "7A", "8A", "9A", "10A", "11A", "12A"];
+const CAMELOT_MAJOR: [&str; 12] = ["1B", "2B", "3B", "4B", "5B", "6B", "7B", "8B", "9B", "10B", "11B", "12B"];
+
+/// How should keys be written
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum KeyNotation {
+    /// Keep the value from platform
+    #[default]
+    Original,
+    /// `Am`, `C#m`, `Eb`
+    Musical,
+    /// `Am`, `C#m`, `D#`
+    Sharps,
+    /// `Am`, `Dbm`, `Eb`
+    Flats,
+    /// `8A`, `12A`, `5B`
+    Camelot,
+    /// `1m`, `6m`, `10d`
+    OpenKey,
+    /// `A min`, `C# min`, `Eb maj` as used by Traktor and Beatport
+    Traktor,
+}
+
+impl FromStr for KeyNotation {
+    type Err = Error;
+
+    /// Case insensitive, ignores separators (`open-key`, `OpenKey`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let notation = match s.replace(['-', '_', ' '], "").to_lowercase().as_str() {
+            "original" => KeyNotation::Original,
+            "musical" => KeyNotation::Musical,
+            "sharps" => KeyNotation::Sharps,
+            "flats" => KeyNotation::Flats,
+            "camelot" => KeyNotation::Camelot,
+            "openkey" => KeyNotation::OpenKey,
+            "traktor" | "beatport" => KeyNotation::Traktor,
+            _ => bail!("Invalid key notation: {s}")
+        };
+        Ok(notation)
+    }
+}
+
+/// Also accepts the old `camelot: bool` option
+pub(crate) fn deserialize_key_notation<'de, D: Deserializer<'de>>(deserializer: D) -> Result<KeyNotation, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Notation(KeyNotation),
+        Camelot(bool),
+    }
+    Ok(match Value::deserialize(deserializer)? {
+        Value::Notation(notation) => notation,
+        Value::Camelot(true) => KeyNotation::Camelot,
+        Value::Camelot(false) => KeyNotation::Original,
+    })
+}
+
+/// Musical key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    /// Pitch class of tonic, C = 0
+    pub tonic: u8,
+    pub minor: bool,
+}
+
+impl Key {
+    pub fn new(tonic: u8, minor: bool) -> Key {
+        Key { tonic: tonic % 12, minor }
+    }
+
+    /// Parse musical (`Am`, `A min`, `G♯m`, `Bb Major`), Camelot (`8A`) or Open Key (`1m`)
+    pub fn parse(key: &str) -> Option<Key> {
+        let key = key.trim()
+            .replace('♯', "#")
+            .replace('♭', "b")
+            .replace([' ', '-', '_'], "")
+            .to_lowercase();
+
+        // Camelot / Open Key
+        let digits = key.chars().take_while(|c| c.is_ascii_digit()).count();
+        if digits > 0 {
+            let number = key[..digits].parse::<i32>().ok()?;
+            if !(1..=12).contains(&number) {
+                return None;
+            }
+            // Open Key 1 = Camelot 8
+            let (camelot, minor) = match &key[digits..] {
+                "a" => (number, true),
+                "b" => (number, false),
+                "m" => ((number + 6) % 12 + 1, true),
+                "d" => ((number + 6) % 12 + 1, false),
+                _ => return None
+            };
+            let tonic = match minor {
+                true => 7 * (camelot - 5),
+                false => 7 * (camelot - 8)
+            };
+            return Some(Key::new(tonic.rem_euclid(12) as u8, minor));
+        }
+
+        // Musical
+        let mut tonic: i32 = match key.chars().next()? {
+            'c' => 0, 'd' => 2, 'e' => 4, 'f' => 5, 'g' => 7, 'a' => 9, 'b' => 11,
+            _ => return None
+        };
+        let mut rest = &key[1..];
+        for (accidental, shift) in [("sharp", 1), ("flat", -1), ("#", 1), ("b", -1)] {
+            if let Some(r) = rest.strip_prefix(accidental) {
+                tonic += shift;
+                rest = r;
+                break;
+            }
+        }
+        let minor = match rest {
+            "" | "maj" | "major" => false,
+            "m" | "min" | "minor" => true,
+            _ => return None
+        };
+        Some(Key::new(tonic.rem_euclid(12) as u8, minor))
+    }
+
+    /// Parse only Camelot notation (`8A`, `12B`)
+    pub fn parse_camelot(key: &str) -> Option<Key> {
+        let key = key.trim();
+        if !key.starts_with(|c: char| c.is_ascii_digit()) || !key.ends_with(['A', 'a', 'B', 'b']) {
+            return None;
+        }
+        Key::parse(key)
+    }
+
+    /// Musical notation (`Am`, `Eb`)
+    pub fn musical_str(&self) -> &'static str {
+        match self.minor {
+            true => MINOR_KEYS[self.tonic as usize],
+            false => MAJOR[self.tonic as usize]
+        }
+    }
+
+    /// Camelot notation (`8A`, `5B`)
+    pub fn camelot_str(&self) -> &'static str {
+        let index = self.camelot_number() as usize - 1;
+        match self.minor {
+            true => CAMELOT_MINOR[index],
+            false => CAMELOT_MAJOR[index]
+        }
+    }
+
+    /// Camelot wheel position
+    fn camelot_number(&self) -> i32 {
+        let n = match self.minor {
+            true => (7 * self.tonic as i32 + 5) % 12,
+            false => (7 * self.tonic as i32 + 8) % 12
+        };
+        if n == 0 { 12 } else { n }
+    }
+
+    /// Render in given notation, `Original` is same as `Musical`
+    pub fn render(&self, notation: KeyNotation) -> String {
+        let minor = if self.minor { "m" } else { "" };
+        match notation {
+            KeyNotation::Original | KeyNotation::Musical => self.to_string(),
+            KeyNotation::Sharps => format!("{}{minor}", SHARPS[self.tonic as usize]),
+            KeyNotation::Flats => format!("{}{minor}", FLATS[self.tonic as usize]),
+            KeyNotation::Camelot => self.camelot_str().to_string(),
+            KeyNotation::OpenKey => format!("{}{}", (self.camelot_number() + 4) % 12 + 1, if self.minor { "m" } else { "d" }),
+            KeyNotation::Traktor => match self.minor {
+                true => format!("{} min", MINOR[self.tonic as usize]),
+                false => format!("{} maj", MAJOR[self.tonic as usize]),
+            }
+        }
+    }
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.musical_str())
+    }
+}
+
+impl FromStr for Key {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Key::parse(s).ok_or(anyhow!("Invalid key: {s}"))
+    }
+}
+
+impl Serialize for Key {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Key::parse(&s).ok_or(D::Error::custom(format!("Invalid key: {s}")))
+    }
+}
+
+/// Convert key into notation, unknown values are returned as they are
+pub fn convert_key(key: &str, notation: KeyNotation) -> String {
+    if notation == KeyNotation::Original {
+        return key.to_string();
+    }
+    match Key::parse(key) {
+        Some(k) => k.render(notation),
+        None => key.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key() {
+        let a_minor = Key::new(9, true);
+        for k in ["Am", "A min", "Amin", "a minor", "8A", "08A", "1m", "A-Minor"] {
+            assert_eq!(Key::parse(k), Some(a_minor), "{k}");
+        }
+        let g_sharp_minor = Key::new(8, true);
+        for k in ["G♯m", "G#m", "Abm", "A♭ Minor", "1A", "6m", "G sharp minor"] {
+            assert_eq!(Key::parse(k), Some(g_sharp_minor), "{k}");
+        }
+        assert_eq!(Key::parse("Bb Major"), Some(Key::new(10, false)));
+        assert_eq!(Key::parse("Bm"), Some(Key::new(11, true)));
+        assert_eq!(Key::parse("Cb"), Some(Key::new(11, false)));
+        assert_eq!(Key::parse("12B"), Some(Key::new(4, false)));
+        for k in ["Dd", "13A", "0B", "H", "", "Am7"] {
+            assert_eq!(Key::parse(k), None, "{k}");
+        }
+    }
+
+    #[test]
+    fn test_render_key() {
+        // Every key survives a round trip through every notation
+        for tonic in 0..12 {
+            for minor in [false, true] {
+                let key = Key::new(tonic, minor);
+                for notation in [KeyNotation::Musical, KeyNotation::Sharps, KeyNotation::Flats, KeyNotation::Camelot, KeyNotation::OpenKey, KeyNotation::Traktor] {
+                    assert_eq!(Key::parse(&key.render(notation)), Some(key));
+                }
+            }
+        }
+        let key = Key::new(1, true);
+        assert_eq!(key.render(KeyNotation::Musical), "C#m");
+        assert_eq!(key.render(KeyNotation::Flats), "Dbm");
+        assert_eq!(key.render(KeyNotation::Camelot), "12A");
+        assert_eq!(key.render(KeyNotation::OpenKey), "5m");
+        assert_eq!(key.render(KeyNotation::Traktor), "C# min");
+        assert_eq!(Key::new(0, false).render(KeyNotation::OpenKey), "1d");
+        assert_eq!(convert_key("F#", KeyNotation::Camelot), "2B");
+        assert_eq!(convert_key("A min", KeyNotation::Original), "A min");
+        assert_eq!(convert_key("unknown", KeyNotation::Camelot), "unknown");
+        assert_eq!(Key::parse_camelot("8A"), Some(Key::new(9, true)));
+        assert_eq!(Key::parse_camelot("Am"), None);
+        assert_eq!(Key::parse_camelot("1m"), None);
+        assert_eq!("open-key".parse::<KeyNotation>().unwrap(), KeyNotation::OpenKey);
+        assert!("hex".parse::<KeyNotation>().is_err());
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_deprecated_camelot() {
+        assert_eq!(crate::to_camelot("F#"), "2B");
+        assert_eq!(crate::to_camelot("unknown"), "unknown");
+        assert_eq!(crate::from_camelot("8A"), "Am");
+        assert_eq!(crate::from_camelot(" 5B "), "Eb");
+        assert_eq!(crate::from_camelot("Am"), "Am");
+    }
+
+    #[test]
+    fn test_camelot_alias() {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Config {
+            #[serde(default, alias = "camelot", deserialize_with = "deserialize_key_notation")]
+            key_notation: KeyNotation,
+        }
+        let parse = |json: &str| serde_json::from_str::<Config>(json).unwrap().key_notation;
+        assert_eq!(parse(r#"{"camelot": true}"#), KeyNotation::Camelot);
+        assert_eq!(parse(r#"{"camelot": false}"#), KeyNotation::Original);
+        assert_eq!(parse(r#"{"keyNotation": "openKey"}"#), KeyNotation::OpenKey);
+        assert_eq!(parse("{}"), KeyNotation::Original);
+    }
+}
@@ -16,13 +16,16 @@ use serde_json::Value;
 use strsim::normalized_levenshtein;
 use unidecode::unidecode;
 
+pub mod key;
 pub mod custom;
+pub mod ratelimit;
 
 const ATTRIBUTES_TO_REMOVE: [&'static str; 23] = ["(intro)", "(clean)", "(intro clean)", "(dirty)", "(intro dirty)", "(clean extended)",
     "(intro outro)", "(extended)", "(instrumental)", "(quick hit)", "(club version)", "(radio version)", "(club)", "(radio)", "(main)", 
     "(radio edit)", "(ck cut)", "(super cut)", "(mega cutz)", "(snip hitz)", "(jd live cut)", "(djcity intro)", "(vdj jd edit)"];
 
 // Re-export
+pub use key::{Key, KeyNotation, convert_key};
 pub use onetagger_tag::{TagSeparators, FrameName, AudioFileFormat, Field, Lyrics, LyricsLine, LyricsLinePart};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -48,7 +51,9 @@ pub struct TaggerConfig {
     pub strictness: f64,
     pub merge_genres: bool,
     pub album_art_file: bool,
-    pub camelot: bool,
+    /// Notation of written keys, old configs have `camelot: bool`
+    #[serde(default, alias = "camelot", deserialize_with = "key::deserialize_key_notation")]
+    pub key_notation: KeyNotation,
     pub parse_filename: bool,
     pub filename_template: Option<String>,
     pub short_title: bool,
@@ -64,6 +69,18 @@ pub struct TaggerConfig {
     pub track_number_leading_zeroes: usize,
     pub enable_shazam: bool,
     pub force_shazam: bool,
+    /// Service used by `enable_shazam` and `force_shazam`
+    #[serde(default)]
+    pub identify_backend: IdentifyBackend,
+    /// AcoustID application API key
+    #[serde(default)]
+    pub acoustid_key: Option<String>,
+    /// Shazam segments and caching
+    #[serde(default)]
+    pub shazam: ShazamConfig,
+    /// Cache of platform responses
+    #[serde(default)]
+    pub http_cache: HttpCacheConfig,
     pub skip_tagged: bool,
     pub include_subfolders: bool,
     pub only_year: bool,
@@ -87,6 +104,18 @@ pub struct TaggerConfig {
     pub album_tagging_ratio: f32,
     /// Renamer template
     pub cover_filename: Option<String>,
+    /// Don't write anything, only generate a report of changes
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Detect BPM from audio for files which didn't get it from any platform
+    #[serde(default)]
+    pub local_bpm: LocalBpmConfig,
+    /// Detect key from audio for files which don't have it
+    #[serde(default)]
+    pub local_key: LocalKeyConfig,
+    /// Scan loudness and write ReplayGain tags after tagging
+    #[serde(default)]
+    pub replay_gain: ReplayGainConfig,
 
     /// Platform specific. Format: `{ platform: { custom_option: value }}`
     pub custom: PlatformTaggerConfig,
@@ -137,7 +166,7 @@ impl Default for TaggerConfig {
             overwrite_tags: vec![],
             merge_genres: false, 
             album_art_file: false, 
-            camelot: false, 
+            key_notation: KeyNotation::Original, 
             styles_options: StylesOptions::Default,
             parse_filename: false, 
             filename_template: Some("%artists% - %title%".to_string()), 
@@ -156,6 +185,8 @@ impl Default for TaggerConfig {
             track_number_leading_zeroes: 0, 
             enable_shazam: false, 
             force_shazam: false, 
+            identify_backend: IdentifyBackend::Shazam,
+            acoustid_key: None,
             skip_tagged: false, 
             move_success: false, 
             move_success_path: None, 
@@ -169,11 +200,69 @@ impl Default for TaggerConfig {
             fetch_all_results: false,
             album_tagging: false,
             album_tagging_ratio: 0.5,
-            cover_filename: None
+            cover_filename: None,
+            dry_run: false,
+            local_bpm: LocalBpmConfig::default(),
+            local_key: LocalKeyConfig::default(),
+            replay_gain: ReplayGainConfig::default(),
+            shazam: ShazamConfig::default(),
+            http_cache: HttpCacheConfig::default(),
         }
     }
 }
 
+/// Offline BPM detection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalBpmConfig {
+    pub enabled: bool,
+    /// Allowed BPM range, results outside of it get halved / doubled
+    pub min: f32,
+    pub max: f32,
+    /// Results with lower confidence (0.0 - 1.0) are not written
+    pub min_confidence: f32,
+}
+
+impl Default for LocalBpmConfig {
+    fn default() -> Self {
+        Self { enabled: false, min: 70.0, max: 180.0, min_confidence: 0.3 }
+    }
+}
+
+/// Offline key detection, output notation follows `key_notation`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalKeyConfig {
+    pub enabled: bool,
+    /// Results with lower confidence (0.0 - 1.0) are not written
+    pub min_confidence: f32,
+}
+
+impl Default for LocalKeyConfig {
+    fn default() -> Self {
+        Self { enabled: false, min_confidence: 0.5 }
+    }
+}
+
+/// EBU R128 loudness scan, writes ReplayGain (or R128 for Opus) tags
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayGainConfig {
+    pub enabled: bool,
+    /// Write album gain, files in the same folder are treated as album
+    pub album: bool,
+    /// Target loudness in LUFS, ReplayGain 2.0 uses -18
+    pub reference: f32,
+    /// Rescan files which already have gain tags
+    pub overwrite: bool,
+}
+
+impl Default for ReplayGainConfig {
+    fn default() -> Self {
+        Self { enabled: false, album: true, reference: -18.0, overwrite: false }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[repr(transparent)]
 pub struct PlatformTaggerConfig(pub HashMap<String, Value>);
@@ -206,6 +295,68 @@ pub struct SpotifyConfig {
     pub client_secret: String
 }
 
+/// Shazam recognition of multiple segments
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShazamConfig {
+    /// Positions of the 12s segments, as fraction of track duration
+    pub positions: Vec<f32>,
+    /// Minimum ratio (0.0 - 1.0) of segments agreeing on the result
+    pub min_agreement: f32,
+    /// Cache signatures and responses, so re-runs don't query Shazam again
+    pub cache: bool,
+}
+
+impl Default for ShazamConfig {
+    fn default() -> Self {
+        Self { positions: vec![0.3, 0.5, 0.7], min_agreement: 0.0, cache: true }
+    }
+}
+
+/// Persistent on-disk cache of platform responses
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpCacheConfig {
+    pub enabled: bool,
+    /// Don't make any requests, use only cached responses
+    pub offline: bool,
+    /// How long are responses valid, in hours
+    pub default_ttl: u64,
+    /// Override of TTL by platform ID
+    pub platform_ttl: HashMap<String, u64>,
+    /// In MB, oldest responses are removed first
+    pub max_size: u64,
+}
+
+impl HttpCacheConfig {
+    /// Get TTL in hours for platform
+    pub fn ttl(&self, platform: &str) -> u64 {
+        self.platform_ttl.get(platform).copied().unwrap_or(self.default_ttl)
+    }
+}
+
+impl Default for HttpCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            offline: false,
+            default_ttl: 24 * 7,
+            // Release data rarely changes
+            platform_ttl: HashMap::from([("discogs".to_string(), 24 * 30), ("musicbrainz".to_string(), 24 * 30)]),
+            max_size: 512
+        }
+    }
+}
+
+/// Audio identification service
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum IdentifyBackend {
+    #[default]
+    Shazam,
+    /// Chromaprint fingerprint lookup, returns MusicBrainz recording IDs
+    AcoustId,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum MultipleMatchesSort {
@@ -263,6 +414,21 @@ pub struct Track {
     pub mood: Option<String>,
     /// None = unknown
     pub explicit: Option<bool>,
+    pub composers: Vec<String>,
+    pub lyricists: Vec<String>,
+    pub conductors: Vec<String>,
+    pub producers: Vec<String>,
+    pub comment: Option<String>,
+    pub grouping: Option<String>,
+    pub copyright: Option<String>,
+    /// Various artists release
+    pub compilation: Option<bool>,
+    pub artist_sort: Option<String>,
+    pub title_sort: Option<String>,
+    pub album_sort: Option<String>,
+    /// Classical work and movement
+    pub work: Option<String>,
+    pub movement: Option<String>,
 
     pub lyrics: Option<Lyrics>,
     
@@ -316,7 +482,8 @@ impl Track {
         }
         gen_track_merge_option!(version, album, key, bpm, art, label, catalog_number, track_number, 
                 track_total, disc_number, isrc, mood, explicit, lyrics, release_date, release_year, 
-                publish_date, publish_year, thumbnail);
+                publish_date, publish_year, thumbnail, comment, grouping, copyright, compilation,
+                artist_sort, title_sort, album_sort, work, movement);
 
         // Generate merge_array(&mut self.value, other.value)
         macro_rules! gen_track_merge_array {
@@ -324,7 +491,8 @@ impl Track {
                 $( merge_array(&mut self.$a, other.$a); )*
             }
         }
-        gen_track_merge_array!(artists, album_artists, genres, styles, other, remixers);
+        gen_track_merge_array!(artists, album_artists, genres, styles, other, remixers, composers, lyricists,
+                conductors, producers);
 
         self
     }
@@ -446,6 +614,8 @@ impl From<String> for StylesOptions {
     }
 }
 
+/// Musical key to Camelot pairs
+#[deprecated(note = "use `Key` and `convert_key` instead")]
 pub const CAMELOT_NOTES: [(&str, &str); 35] = [
     ("Abm", "1A"),
     ("G#m", "1A"),
@@ -485,22 +655,17 @@ pub const CAMELOT_NOTES: [(&str, &str); 35] = [
 ];
 
 /// Convert to camelot or return original
+#[deprecated(note = "use `convert_key` with `KeyNotation::Camelot` instead")]
 pub fn to_camelot(key: &str) -> &str {
-    if let Some((_, v)) = CAMELOT_NOTES.iter().find(|(k, _)| *k == key.trim()) {
-        return *v;
-    }
-    key
+    Key::parse(key).map(|k| k.camelot_str()).unwrap_or(key)
 }
 
 /// Convert from camelot to normal key
+#[deprecated(note = "use `Key::parse_camelot` instead")]
 pub fn from_camelot(key: &str) -> &str {
-    if let Some((v, _)) = CAMELOT_NOTES.iter().find(|(_, k)| *k == key.trim()) {
-        return *v;
-    }
-    key
+    Key::parse_camelot(key).map(|k| k.musical_str()).unwrap_or(key)
 }
 
-
 pub trait LyricsExt {
     /// Generate LRC data
     /// If meta is present, will be written
@@ -736,6 +901,8 @@ pub enum SupportedTag {
     AlbumArt, OtherTags, CatalogNumber, TrackId, ReleaseId, Version,
     Duration, AlbumArtist, Remixer, TrackNumber, TrackTotal, DiscNumber,
     Mood, SyncedLyrics, UnsyncedLyrics, Label, Explicit, MetaTags,
+    Composer, Lyricist, Conductor, Producer, Comment, Grouping, Copyright,
+    Compilation, ArtistSort, TitleSort, AlbumSort, Work, Movement,
     #[serde(rename = "bpm")]
     BPM,
     #[serde(rename = "url")]
@@ -0,0 +1,202 @@
+use anyhow::Error;
+use rand::Rng;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
+
+/// How many times are 429 / 5xx responses retried
+pub const MAX_RETRIES: u32 = 4;
+/// First backoff delay, doubled on every retry
+const BASE_DELAY: Duration = Duration::from_secs(1);
+/// Don't retry if the server wants us to wait longer
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(600);
+
+/// Limits and pauses per platform, shared by all tagging threads
+static PLATFORMS: Mutex<BTreeMap<String, PlatformLimit>> = Mutex::new(BTreeMap::new());
+/// Set in custom platforms, so throttling is reported to 1T
+static HOST_CALLBACK: OnceLock<extern "C" fn(u64)> = OnceLock::new();
+
+thread_local! {
+    /// Time the current thread spent waiting because of rate limits
+    static THROTTLED: Cell<Duration> = const { Cell::new(Duration::ZERO) };
+}
+
+#[derive(Debug, Default)]
+struct PlatformLimit {
+    bucket: Option<TokenBucket>,
+    /// Don't send anything until (after 429 / 5xx)
+    paused_until: Option<Instant>,
+}
+
+/// Token bucket refilled at a constant rate, burst of up to 1 second of requests
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    requests_per_minute: u32,
+    capacity: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    fn new(requests_per_minute: u32) -> TokenBucket {
+        let capacity = (requests_per_minute as f64 / 60.0).ceil().max(1.0);
+        TokenBucket { requests_per_minute, capacity, tokens: capacity, last: Instant::now() }
+    }
+
+    /// Take a token, returns how long to wait until it is valid.
+    /// Tokens can go negative, so concurrent callers queue up without holding the lock.
+    fn reserve(&mut self, now: Instant) -> Duration {
+        let per_second = self.requests_per_minute as f64 / 60.0;
+        self.tokens = (self.tokens + now.duration_since(self.last).as_secs_f64() * per_second).min(self.capacity);
+        self.last = now;
+        self.tokens -= 1.0;
+        match self.tokens < 0.0 {
+            true => Duration::from_secs_f64(-self.tokens / per_second),
+            false => Duration::ZERO
+        }
+    }
+}
+
+/// Set requests per minute for platform, None for no limit
+pub fn set_limit(platform: &str, requests_per_minute: Option<u32>) {
+    let mut platforms = PLATFORMS.lock().unwrap();
+    let limit = platforms.entry(platform.to_string()).or_default();
+    match requests_per_minute.filter(|r| *r > 0) {
+        Some(rpm) if limit.bucket.as_ref().map(|b| b.requests_per_minute) != Some(rpm) => limit.bucket = Some(TokenBucket::new(rpm)),
+        Some(_) => {},
+        None => limit.bucket = None
+    }
+}
+
+/// Block all requests of platform for the duration
+pub fn pause(platform: &str, duration: Duration) {
+    let mut platforms = PLATFORMS.lock().unwrap();
+    let limit = platforms.entry(platform.to_string()).or_default();
+    let until = Instant::now() + duration;
+    limit.paused_until = Some(limit.paused_until.map_or(until, |p| p.max(until)));
+}
+
+/// Wait until platform request can be made, returns the waited time
+pub fn acquire(platform: &str) -> Duration {
+    let wait = {
+        let mut platforms = PLATFORMS.lock().unwrap();
+        let Some(limit) = platforms.get_mut(platform) else { return Duration::ZERO };
+        let now = Instant::now();
+        let paused = limit.paused_until.map(|p| p.saturating_duration_since(now)).unwrap_or_default();
+        paused + limit.bucket.as_mut().map(|b| b.reserve(now + paused)).unwrap_or_default()
+    };
+    if !wait.is_zero() {
+        debug!("{platform} rate limit, waiting {:.2}s", wait.as_secs_f32());
+        std::thread::sleep(wait);
+        report_throttled(wait);
+    }
+    wait
+}
+
+/// Should the response status be retried
+pub fn is_retryable(status: u16) -> bool {
+    status == 429 || (500..600).contains(&status)
+}
+
+/// Parse `Retry-After` header, either seconds or HTTP date
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<f64>() {
+        return Duration::try_from_secs_f64(seconds).ok();
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((date.with_timezone(&Utc) - Utc::now()).to_std().unwrap_or_default())
+}
+
+/// Delay before retry (attempt starting at 0). `Retry-After` is honoured, otherwise jittered exponential backoff.
+/// None if the server asks for too long wait.
+pub fn retry_delay(attempt: u32, retry_after: Option<&str>) -> Option<Duration> {
+    let mut rng = rand::thread_rng();
+    match retry_after.and_then(parse_retry_after) {
+        Some(delay) if delay > MAX_RETRY_AFTER => None,
+        // Spread threads which got the same header
+        Some(delay) => Some(delay + Duration::from_millis(rng.gen_range(0..500))),
+        None => Some(BASE_DELAY * 2u32.pow(attempt.min(6)) * rng.gen_range(50..=100) / 100)
+    }
+}
+
+/// Send request with platform rate limit, retry 429 and 5xx responses.
+/// `status` returns the HTTP status code and `Retry-After` header of response.
+pub fn execute<T>(platform: &str, mut send: impl FnMut() -> Result<T, Error>, status: impl Fn(&T) -> (u16, Option<String>)) -> Result<T, Error> {
+    let mut attempt = 0;
+    loop {
+        acquire(platform);
+        let response = send()?;
+        let (code, retry_after) = status(&response);
+        if !is_retryable(code) || attempt >= MAX_RETRIES {
+            return Ok(response);
+        }
+        let Some(delay) = retry_delay(attempt, retry_after.as_deref()) else {
+            warn!("{platform} returned {code}, Retry-After too long: {retry_after:?}");
+            return Ok(response);
+        };
+        warn!("{platform} returned {code}, retrying in {:.1}s...", delay.as_secs_f32());
+        pause(platform, delay);
+        attempt += 1;
+    }
+}
+
+/// Add time spent waiting to the current thread's total
+pub fn report_throttled(duration: Duration) {
+    match HOST_CALLBACK.get() {
+        Some(f) => f(duration.as_millis() as u64),
+        None => THROTTLED.with(|t| t.set(t.get() + duration)),
+    }
+}
+
+/// Get and reset time the current thread spent throttled
+pub fn take_throttled() -> Duration {
+    THROTTLED.with(|t| t.replace(Duration::ZERO))
+}
+
+/// Used by custom platforms to send throttling info to 1T
+pub fn set_host_callback(f: extern "C" fn(u64)) {
+    HOST_CALLBACK.set(f).ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(120);
+        // Burst of 2, then 500ms per request
+        assert_eq!(bucket.reserve(start), Duration::ZERO);
+        assert_eq!(bucket.reserve(start), Duration::ZERO);
+        assert_eq!(bucket.reserve(start), Duration::from_millis(500));
+        assert_eq!(bucket.reserve(start), Duration::from_millis(1000));
+        // Refilled, but not over capacity
+        let later = start + Duration::from_secs(10);
+        assert_eq!(bucket.reserve(later), Duration::ZERO);
+        assert_eq!(bucket.reserve(later), Duration::ZERO);
+        assert!(bucket.reserve(later) > Duration::ZERO);
+    }
+
+    #[test]
+    fn test_retry() {
+        assert_eq!(parse_retry_after("3"), Some(Duration::from_secs(3)));
+        assert!(parse_retry_after("invalid").is_none());
+        let delay = retry_delay(0, Some("2")).unwrap();
+        assert!(delay >= Duration::from_secs(2) && delay < Duration::from_millis(2500));
+        let delay = retry_delay(2, None).unwrap();
+        assert!(delay >= Duration::from_secs(2) && delay <= Duration::from_secs(4));
+        assert!(retry_delay(0, Some("3600")).is_none());
+
+        // 503, 429, then 200
+        let mut responses = vec![200, 429, 503];
+        let start = Instant::now();
+        let r = execute("test", || Ok(responses.pop().unwrap()), |r| (*r, Some("0.1".to_string()))).unwrap();
+        assert_eq!(r, 200);
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert!(take_throttled() >= Duration::from_millis(200));
+    }
+}
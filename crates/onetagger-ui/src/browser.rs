@@ -6,6 +6,7 @@ use walkdir::WalkDir;
 use dunce::canonicalize;
 
 use onetagger_tag::EXTENSIONS;
+use onetagger_library::{Library, LibraryQuery, TagFilter};
 use onetagger_playlist::{PLAYLIST_EXTENSIONS, get_files_from_playlist_file};
 
 pub struct FileBrowser {
@@ -33,6 +34,22 @@ impl FileBrowser {
         Ok((path.clone(), Self::list_dir(path, playlists, files, recursive)?))
     }
 
+    /// Files in folder matching all the tag filters, using the library index
+    pub fn query_library(path: impl AsRef<Path>, recursive: bool, filters: Vec<TagFilter>, limit: Option<usize>) -> Result<Vec<PathBuf>, Error> {
+        let library = Library::open_default()?;
+        if !library.is_indexed(&path)? {
+            return Err(anyhow!("Folder is not in the library, add it to filter by tags!"));
+        }
+        let query = LibraryQuery {
+            path: Some(path.as_ref().to_owned()),
+            recursive,
+            filters,
+            limit,
+            ..Default::default()
+        };
+        Ok(library.query(&query)?.into_iter().map(|f| f.path).filter(|p| p.exists()).collect())
+    }
+
     /// List files in dir
     pub fn list_dir(path: impl AsRef<Path>, playlists: bool, files: bool, recursive: bool) -> Result<Vec<FolderEntry>, Error> {
         let browser = FileBrowser {
@@ -9,6 +9,7 @@ use image::{ImageFormat, ImageReader};
 use serde::{Deserialize, Serialize};
 use onetagger_tag::{AudioFileFormat, Field, Tag, EXTENSIONS, TagSeparators};
 use onetagger_playlist::{UIPlaylist, get_files_from_playlist_file};
+use onetagger_library::{Library, LibraryFile};
 
 pub struct QuickTag {}
 
@@ -61,11 +62,23 @@ impl QuickTag {
 
     /// Check extension and load file
     pub fn load_files(files: Vec<PathBuf>, separators: &TagSeparators) -> Result<QuickTagData, Error> {
+        // Unchanged files are loaded from index, which uses the default separators
+        let library = match Library::open_default() {
+            Ok(library) => Some(library).filter(|_| separators == &TagSeparators::default()),
+            Err(e) => {
+                warn!("Failed opening library: {e}");
+                None
+            }
+        };
         let mut out = vec![];
         let mut failed = vec![];
         for path in files {
             if EXTENSIONS.iter().any(|e| path.extension().unwrap_or_default().to_ascii_lowercase() == *e) {
-                match QuickTagFile::from_path(&path, separators) {
+                let file = match library.as_ref().and_then(|l| l.fresh(&path).ok().flatten()) {
+                    Some(file) => QuickTagFile::from_library(file),
+                    None => QuickTagFile::from_path(&path, separators)
+                };
+                match file {
                     Ok(t) => out.push(t),
                     Err(e) => {
                         failed.push(QuickTagFailed::new(&path, e.to_string()));
@@ -156,6 +169,25 @@ impl QuickTagFile {
         })
     }
 
+    /// Load from library index
+    pub fn from_library(file: LibraryFile) -> Result<QuickTagFile, Error> {
+        let format = AudioFileFormat::from_extension(&file.path.extension().unwrap_or_default().to_string_lossy())
+            .ok_or(anyhow!("Unsupported format"))?;
+        let field = |field: Field| file.tags.get(field.by_format(&format)).cloned();
+        Ok(QuickTagFile {
+            title: field(Field::Title).ok_or(anyhow!("Missing title tag"))?.first().ok_or(anyhow!("Missing title"))?.to_string(),
+            artists: field(Field::Artist).ok_or(anyhow!("Missing artist tag"))?,
+            genres: field(Field::Genre).unwrap_or_default(),
+            rating: file.rating.unwrap_or(0),
+            bpm: field(Field::BPM).and_then(|b| b.first()?.parse().ok()),
+            year: file.year,
+            key: field(Field::Key).and_then(|k| k.first().map(String::from)),
+            tags: file.tags.iter().map(|(k, v)| (k.to_string(), v.iter().map(|v| v.replace("\0", "")).collect())).collect(),
+            path: file.path,
+            format,
+        })
+    }
+
     /// Load album art from tag and downscale
     pub fn get_art(path: impl AsRef<Path>) -> Result<Vec<u8>, Error> {
         // Load
@@ -14,15 +14,21 @@ use onetagger_tag::{TagChanges, TagSeparators, Tag, Field};
 use onetagger_tagger::{TaggerConfig, AudioFileInfo, TrackMatch};
 use onetagger_autotag::{Tagger, AudioFileInfoImpl, TaggerConfigExt, AUTOTAGGER_PLATFORMS};
 use onetagger_autotag::audiofeatures::{AudioFeaturesConfig, AudioFeatures};
+use onetagger_autotag::dryrun::DryRunReport;
+use onetagger_autotag::journal::UndoJournal;
+use onetagger_autotag::resume::RunLog;
+use onetagger_autotag::loudness::LoudnessScanner;
+use onetagger_autotag::duplicates::{DuplicateFinder, DuplicateReport, DuplicatesConfig};
 use onetagger_platforms::spotify::Spotify;
+use onetagger_songdownloader::{SongDownloader, SongInfo, DownloadState};
 use onetagger_player::{AudioSources, AudioPlayer};
 use onetagger_shared::{Settings, COMMIT};
 use onetagger_playlist::{UIPlaylist, PLAYLIST_EXTENSIONS, get_files_from_playlist_file};
-use std::process::Command;
+use onetagger_library::{Library, LibraryQuery, TagFilter};
 use std::thread;
 use crossbeam_channel::unbounded;
-use std::env;
 use std::fs;
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
 
 use crate::StartContext;
 use crate::quicktag::{QuickTag, QuickTagFile, QuickTagData};
@@ -35,7 +41,41 @@ struct FoundSong {
     title: String,
     artist: String,
     video_url: String,
+    /// Position in the video in seconds
     timestamp: Option<u64>,
+    /// Used as name of the download folder
+    #[serde(default)]
+    video_title: String,
+}
+
+impl FoundSong {
+    /// Convert from song downloader
+    pub fn from_song_info(song: SongInfo) -> FoundSong {
+        let timestamp = song.timestamp.as_ref()
+            .map(|t| t.split(':').map(|p| p.parse::<u64>().ok()).collect::<Option<Vec<_>>>())
+            .flatten()
+            .map(|parts| parts.into_iter().fold(0, |acc, p| acc * 60 + p));
+        FoundSong {
+            title: song.song_title,
+            artist: song.artist,
+            video_url: song.video_url,
+            timestamp,
+            video_title: song.video_title,
+        }
+    }
+
+    /// Convert to song downloader
+    pub fn into_song_info(self) -> SongInfo {
+        SongInfo {
+            video_title: self.video_title,
+            video_url: self.video_url,
+            song_title: self.title,
+            artist: self.artist,
+            timestamp: self.timestamp.map(|t| format!("{}:{:02}:{:02}", t / 3600, (t % 3600) / 60, t % 60)),
+            downloaded: false,
+            match_confidence: 1.0,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -58,20 +98,33 @@ enum Action {
     LoadPlatforms,
     StartTagging { config: TaggerConfigs, playlist: Option<UIPlaylist> },
     StopTagging,
+    ListResumableRuns,
+    ResumeTagging { id: String },
+    DryRunReport { path: PathBuf },
+    ListRuns,
+    RevertRun { id: String },
+    LibraryRoots,
+    LibraryAddRoot { path: PathBuf },
+    LibraryRemoveRoot { path: PathBuf },
+    LibraryScan,
+    LibraryQuery { query: LibraryQuery },
+    FindDuplicates { config: DuplicatesConfig, output: Option<PathBuf> },
+    QuarantineDuplicates { report: DuplicateReport, folder: PathBuf },
     ConfigCallback { config: Value, platform: String, id: String },
     RepoManifest,
     #[serde(rename_all = "camelCase")]
     InstallPlatform { id: String, version: String, is_native: bool },
 
     Waveform { path: PathBuf },
-    PlayerLoad { path: PathBuf },
+    #[serde(rename_all = "camelCase")]
+    PlayerLoad { path: PathBuf, #[serde(default)] replay_gain: bool },
     PlayerPlay, 
     PlayerPause,
     PlayerSeek { pos: u64 },
     PlayerVolume { volume: f32 },
     PlayerStop,
 
-    QuickTagLoad { path: Option<String>, playlist: Option<UIPlaylist>, recursive: Option<bool>, separators: TagSeparators, limit: Option<bool> },
+    QuickTagLoad { path: Option<String>, playlist: Option<UIPlaylist>, recursive: Option<bool>, separators: TagSeparators, limit: Option<bool>, filters: Option<Vec<TagFilter>> },
     QuickTagSave { changes: TagChanges },
     QuickTagFolder { path: Option<String>, subdir: Option<String> },
 
@@ -79,7 +132,7 @@ enum Action {
     SpotifyAuthorize { client_id: String, client_secret: String },
     SpotifyAuthorized,
 
-    TagEditorFolder { path: Option<String>, subdir: Option<String>, recursive: Option<bool>  },
+    TagEditorFolder { path: Option<String>, subdir: Option<String>, recursive: Option<bool>, filters: Option<Vec<TagFilter>> },
     TagEditorLoad { path: PathBuf },
     TagEditorSave { changes: TagChanges },
 
@@ -138,17 +191,36 @@ impl TaggerConfigs {
 struct SocketContext {
     player: AudioPlayer,
     spotify: Option<Spotify>,
-    start_context: StartContext
+    start_context: StartContext,
+    /// Undo journals of QuickTag / Tag Editor, one per session
+    journals: HashMap<&'static str, UndoJournal>,
+    /// Messages from background tasks, sent by the connection loop
+    sender: UnboundedSender<Value>,
 } 
 
 impl SocketContext {
-    pub fn new(start_context: StartContext) -> SocketContext {
+    pub fn new(start_context: StartContext, sender: UnboundedSender<Value>) -> SocketContext {
         SocketContext {
             player: AudioPlayer::new(),
             spotify: None,
-            start_context
+            start_context,
+            journals: HashMap::new(),
+            sender
         }
     }
+
+    /// Get the session journal of given kind, created on first use
+    pub fn journal(&mut self, kind: &'static str) -> Result<&UndoJournal, Error> {
+        if !self.journals.contains_key(kind) {
+            self.journals.insert(kind, UndoJournal::new(kind)?);
+        }
+        Ok(&self.journals[kind])
+    }
+
+    /// Save state of file into journal of given kind before writing
+    pub fn snapshot(&mut self, kind: &'static str, path: impl AsRef<Path>) -> Result<(), Error> {
+        self.journal(kind)?.snapshot(path)
+    }
 }
 
 
@@ -187,9 +259,21 @@ impl InitData {
 }
 
 pub(crate) async fn handle_ws_connection(mut websocket: WebSocket, context: StartContext) -> Result<(), Error> {
-    let mut context = SocketContext::new(context);
+    let (sender, mut receiver) = unbounded_channel();
+    let mut context = SocketContext::new(context, sender);
     
-    while let Some(message) = websocket.recv().await {
+    loop {
+        let message = tokio::select! {
+            message = websocket.recv() => match message {
+                Some(message) => message,
+                None => break
+            },
+            // Results of background tasks
+            Some(json) = receiver.recv() => {
+                send_socket(&mut websocket, json).await.ok();
+                continue;
+            }
+        };
         match message {
             Ok(msg) => {
                 match msg.to_text() {
@@ -238,102 +322,16 @@ async fn send_socket_inner<D: Serialize>(ws: &mut WebSocket, json: D) -> Result<
     Ok(())
 }
 
-/// Analyze a YouTube URL to find songs
-fn analyze_songs(url: &str, confidence: f32) -> Result<Vec<FoundSong>, Error> {
-    // Get the path to the Python script
-    let script_path = env::current_dir()?
-        .join("YoutubeToSpotify")
-        .join("analyzer.py");
-    
-    // Check if the script exists
-    if !script_path.exists() {
-        return Err(anyhow::anyhow!("Song analyzer script not found at {:?}", script_path));
-    }
-    
-    // Prepare the command
-    let output = Command::new("python")
-        .arg(&script_path)
-        .arg("--url").arg(url)
-        .arg("--confidence").arg(confidence.to_string())
-        .arg("--mode").arg("analyze")
-        .output()?;
-    
-    if !output.status.success() {
-        let error_message = String::from_utf8_lossy(&output.stderr);
-        return Err(anyhow::anyhow!("Failed to analyze songs: {}", error_message));
-    }
-    
-    // Parse the output JSON
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let songs: Vec<FoundSong> = serde_json::from_str(&stdout)?;
-    
-    Ok(songs)
-}
-
-/// Download songs from a YouTube URL
-fn download_songs(
-    url: &str,
-    output_path: &str,
-    confidence: f32,
-    enable_auto_tag: bool,
-    auto_tag_config: Option<String>,
-    enable_audio_features: bool,
-    songs: &[FoundSong]
-) -> Result<(), Error> {
-    // Create the output directory if it doesn't exist
-    fs::create_dir_all(output_path)?;
-    
-    // Get the path to the Python script
-    let script_path = env::current_dir()?
-        .join("YoutubeToSpotify")
-        .join("downloader.py");
-    
-    // Check if the script exists
-    if !script_path.exists() {
-        return Err(anyhow::anyhow!("Song downloader script not found at {:?}", script_path));
-    }
-    
-    // Create a temporary JSON file with song information
-    let songs_json = serde_json::to_string(songs)?;
-    let temp_songs_file = env::temp_dir().join("onetagger_songs.json");
-    fs::write(&temp_songs_file, &songs_json)?;
-    
-    // Build the command
-    let mut cmd = Command::new("python");
-    cmd.arg(&script_path)
-        .arg("--url").arg(url)
-        .arg("--output").arg(output_path)
-        .arg("--confidence").arg(confidence.to_string())
-        .arg("--songs-file").arg(&temp_songs_file);
-    
-    // Add optional flags
-    if enable_auto_tag {
-        cmd.arg("--enable-auto-tag");
-        if let Some(config) = &auto_tag_config {
-            let config_path = env::temp_dir().join("onetagger_autotagconfig.json");
-            fs::write(&config_path, config)?;
-            cmd.arg("--auto-tag-config").arg(&config_path);
-        }
-    }
-    
-    if enable_audio_features {
-        cmd.arg("--enable-audio-features");
-    }
-    
-    // Run the command
-    let output = cmd.output()?;
-    
-    // Clean up temporary files
-    if temp_songs_file.exists() {
-        fs::remove_file(temp_songs_file).ok();
-    }
-    
-    if !output.status.success() {
-        let error_message = String::from_utf8_lossy(&output.stderr);
-        return Err(anyhow::anyhow!("Failed to download songs: {}", error_message));
+/// Load AutoTagger config for song downloader, either JSON or path to JSON file
+fn load_song_downloader_at_config(config: Option<String>) -> Result<TaggerConfig, Error> {
+    let config = match config.map(|c| c.trim().to_string()).filter(|c| !c.is_empty()) {
+        Some(config) => config,
+        None => return Ok(TaggerConfig::custom_default()),
+    };
+    if config.starts_with('{') {
+        return Ok(serde_json::from_str(&config)?);
     }
-    
-    Ok(())
+    Ok(serde_json::from_reader(std::fs::File::open(&config)?)?)
 }
 
 async fn handle_message(text: &str, websocket: &mut WebSocket, context: &mut SocketContext) -> Result<(), Error> {
@@ -499,6 +497,129 @@ async fn handle_message(text: &str, websocket: &mut WebSocket, context: &mut Soc
         Action::StopTagging => {
             onetagger_autotag::STOP_TAGGING.store(true, Ordering::SeqCst);
         },
+        // Stopped or crashed AutoTagger runs
+        Action::ListResumableRuns => {
+            send_socket(websocket, json!({
+                "action": "resumableRuns",
+                "runs": RunLog::list()?
+            })).await.ok();
+        },
+        Action::ResumeTagging { id } => {
+            let state = RunLog::load(&id)?;
+            let tagger_finished = Arc::new(Mutex::new(None));
+            let rx = Tagger::resume(&id, tagger_finished.clone())?;
+            let start = timestamp!();
+            send_socket(websocket, json!({
+                "action": "startTagging",
+                "files": state.remaining(),
+                "type": "autoTagger"
+            })).await.ok();
+            for status in rx {
+                send_socket(websocket, json!({
+                    "action": "taggingProgress",
+                    "status": status
+                })).await.ok();
+            }
+            info!("Tagging finished, took: {} seconds.", (timestamp!() - start) / 1000);
+            send_socket(websocket, json!({
+                "action": "taggingDone",
+                "path": state.config.path,
+                "data": *tagger_finished.lock().unwrap()
+            })).await.ok();
+        },
+        // Load the full diff report of a dry run
+        Action::DryRunReport { path } => {
+            send_socket(websocket, json!({
+                "action": "dryRunReport",
+                "report": DryRunReport::load(&path)?
+            })).await.ok();
+        },
+        // Undo journals
+        Action::ListRuns => {
+            send_socket(websocket, json!({
+                "action": "listRuns",
+                "runs": UndoJournal::list()?
+            })).await.ok();
+        },
+        Action::RevertRun { id } => {
+            let results = UndoJournal::revert(&id)?;
+            let failed = results.iter().filter_map(|(path, r)| r.as_ref().err().map(|e| json!({
+                "path": path,
+                "error": e.to_string()
+            }))).collect::<Vec<_>>();
+            send_socket(websocket, json!({
+                "action": "revertRun",
+                "id": id,
+                "reverted": results.len() - failed.len(),
+                "failed": failed
+            })).await.ok();
+        },
+        Action::LibraryRoots => {
+            send_socket(websocket, json!({
+                "action": "libraryRoots",
+                "roots": Library::open_default()?.roots()?
+            })).await.ok();
+        },
+        Action::LibraryAddRoot { path } => {
+            let library = Library::open_default()?;
+            library.add_root(canonicalize(path)?)?;
+            send_socket(websocket, json!({
+                "action": "libraryRoots",
+                "roots": library.roots()?
+            })).await.ok();
+        },
+        Action::LibraryRemoveRoot { path } => {
+            let library = Library::open_default()?;
+            library.remove_root(path)?;
+            send_socket(websocket, json!({
+                "action": "libraryRoots",
+                "roots": library.roots()?
+            })).await.ok();
+        },
+        Action::LibraryScan => {
+            let stats = tokio::task::spawn_blocking(|| {
+                Library::open_default()?.scan_all()
+            }).await??;
+            send_socket(websocket, json!({
+                "action": "libraryScan",
+                "stats": stats
+            })).await.ok();
+        },
+        Action::LibraryQuery { query } => {
+            send_socket(websocket, json!({
+                "action": "libraryQuery",
+                "files": Library::open_default()?.query(&query)?
+            })).await.ok();
+        },
+        // Fingerprinting is slow, keep it off the async runtime
+        // Scan in background, so the socket can handle other messages meanwhile
+        Action::FindDuplicates { config, output } => {
+            let sender = context.sender.clone();
+            tokio::task::spawn_blocking(move || {
+                let report = DuplicateFinder::find(&config);
+                if let Some(output) = output {
+                    if let Err(e) = report.save(output) {
+                        error!("Failed saving duplicates report: {e}");
+                        sender.send(json!({
+                            "action": "error",
+                            "message": format!("Failed saving duplicates report: {e}")
+                        })).ok();
+                    }
+                }
+                sender.send(json!({
+                    "action": "findDuplicates",
+                    "report": report
+                })).ok();
+            });
+        },
+        // Move inferior copies, saved as `duplicates` undo run
+        Action::QuarantineDuplicates { report, folder } => {
+            let moved = report.quarantine(&folder)?;
+            send_socket(websocket, json!({
+                "action": "quarantineDuplicates",
+                "moved": moved.len()
+            })).await.ok();
+        },
         Action::Waveform { path } => {
             let source = AudioSources::from_path(&path)?;
             let (waveform_rx, cancel_tx) = source.generate_waveform(180)?;
@@ -519,7 +640,7 @@ async fn handle_message(text: &str, websocket: &mut WebSocket, context: &mut Soc
             })).await.ok();
         },
         // Load player file
-        Action::PlayerLoad { path } => {
+        Action::PlayerLoad { path, replay_gain } => {
             let source = AudioSources::from_path(&path)?;
             // Meta
             let tag = Tag::load_file(&path, false)?;
@@ -534,6 +655,11 @@ async fn handle_message(text: &str, websocket: &mut WebSocket, context: &mut Soc
             })).await.ok();
             // Load
             context.player.load_file(source);
+            let gain = match replay_gain {
+                true => LoudnessScanner::playback_gain(&path).unwrap_or(1.0),
+                false => 1.0
+            };
+            context.player.gain(gain);
         },
         //  Controls
         Action::PlayerPlay => context.player.play(),
@@ -547,7 +673,7 @@ async fn handle_message(text: &str, websocket: &mut WebSocket, context: &mut Soc
         Action::PlayerVolume { volume } => context.player.volume(volume),
         Action::PlayerStop => context.player.stop(),
         // Load quicktag files or playlist
-        Action::QuickTagLoad { path, playlist, recursive, separators, limit } => {
+        Action::QuickTagLoad { path, playlist, recursive, separators, limit, filters } => {
             let mut data = QuickTagData::default();
             // Playlist
             if let Some(playlist) = playlist {
@@ -557,13 +683,17 @@ async fn handle_message(text: &str, websocket: &mut WebSocket, context: &mut Soc
             if let Some(path) = path {
                 if PLAYLIST_EXTENSIONS.iter().any(|e| path.to_lowercase().ends_with(e)) {
                     data = QuickTag::load_files(get_files_from_playlist_file(&path)?, &separators)?;
+                } else if let Some(filters) = filters.filter(|f| !f.is_empty()) {
+                    // Filter using library index
+                    let files = FileBrowser::query_library(&path, recursive.unwrap_or(false), filters, limit.and_then(|l| l.then_some(500)))?;
+                    data = QuickTag::load_files(files, &separators)?;
                 } else {
                     data = QuickTag::load_files_path(
                         &path, 
                         recursive.unwrap_or(false), 
                         &separators, 
                         0, 
-                        limit.map(|l| l.then_some(500)).flatten().unwrap_or(usize::MAX)
+                        limit.and_then(|l| l.then_some(500)).unwrap_or(usize::MAX)
                     )?;
                 }
             }
@@ -574,6 +704,7 @@ async fn handle_message(text: &str, websocket: &mut WebSocket, context: &mut Soc
         },
         // Save quicktag changes
         Action::QuickTagSave { changes } => {
+            context.snapshot("quicktag", &changes.path)?;
             let tag = changes.commit()?;
             send_socket(websocket, json!({
                 "action": "quickTagSaved",
@@ -616,9 +747,14 @@ async fn handle_message(text: &str, websocket: &mut WebSocket, context: &mut Soc
                 "value": context.spotify.is_some()
             })).await.ok();
         },
-        Action::TagEditorFolder { path, subdir, recursive } => {
+        Action::TagEditorFolder { path, subdir, recursive, filters } => {
             let recursive = recursive.unwrap_or(false);
-            let (new_path, files) = FileBrowser::list_dir_or_default(path.clone().map(|p| PathBuf::from(p)), subdir, true, true, recursive)?;
+            let (new_path, mut files) = FileBrowser::list_dir_or_default(path.clone().map(|p| PathBuf::from(p)), subdir, true, true, recursive)?;
+            // Only files matching tags, folders are kept for navigation
+            if let Some(filters) = filters.filter(|f| !f.is_empty()) {
+                let matching = FileBrowser::query_library(&new_path, recursive, filters, None)?;
+                files.retain(|f| f.dir || f.playlist || matching.contains(&f.path));
+            }
             send_socket(websocket, json!({
                 "action": "tagEditorFolder",
                 "files": files,
@@ -637,6 +773,7 @@ async fn handle_message(text: &str, websocket: &mut WebSocket, context: &mut Soc
         },
         // Save changes
         Action::TagEditorSave { changes } => {
+            context.snapshot("tageditor", &changes.path)?;
             let _tag = changes.commit()?;
             send_socket(websocket, json!({
                 "action": "tagEditorSave"
@@ -745,7 +882,7 @@ async fn handle_message(text: &str, websocket: &mut WebSocket, context: &mut Soc
         },
         // Apply the tags from manual tagger
         Action::ManualTagApply { matches, path, config } => {
-            match onetagger_autotag::manual_tagger_apply(matches, path, &config) {
+            match onetagger_autotag::manual_tagger_apply(matches, path, &config, context.journal("manualtag")?) {
                 Ok(_) => {
                     send_socket(websocket, json!({
                         "action": "manualTagApplied",
@@ -764,85 +901,99 @@ async fn handle_message(text: &str, websocket: &mut WebSocket, context: &mut Soc
         },
 
         Action::AnalyzeSongs { url, confidence } => {
-            info!("Analyzing YouTube URL: {}", url);
-            
-            // Process in a background thread
-            let (tx, rx) = unbounded();
-            
-            // Clone values for thread
-            let url_clone = url.clone();
-            
-            thread::spawn(move || {
-                match analyze_songs(&url_clone, confidence) {
-                    Ok(songs) => tx.send(Ok(songs)).ok(),
-                    Err(e) => tx.send(Err(e)).ok(),
-                }
-            });
-            
-            // Wait for result from background thread
-            if let Ok(result) = rx.recv() {
-                match result {
-                    Ok(songs) => {
-                        send_socket(websocket, json!({
-                            "action": "analyzeSongs",
-                            "songs": songs
-                        })).await.ok();
-                    },
-                    Err(e) => {
-                        error!("Failed analyzing songs: {}", e);
-                        send_socket(websocket, json!({
-                            "action": "analyzeSongs",
-                            "error": e.to_string()
-                        })).await.ok();
-                    }
+            info!("Analyzing URL: {}", url);
+            let result = tokio::task::spawn_blocking(move || {
+                SongDownloader::new()
+                    .with_url(&url)
+                    .with_confidence(confidence)
+                    .query_songs()
+            }).await?;
+            match result {
+                Ok((url_info, songs)) => {
+                    send_socket(websocket, json!({
+                        "action": "analyzeSongs",
+                        "title": url_info.title,
+                        "songs": songs.into_iter().map(FoundSong::from_song_info).collect::<Vec<_>>()
+                    })).await.ok();
+                },
+                Err(e) => {
+                    error!("Failed analyzing songs: {}", e);
+                    send_socket(websocket, json!({
+                        "action": "analyzeSongs",
+                        "error": e.to_string()
+                    })).await.ok();
                 }
             }
         },
         
         Action::DownloadSongs { url, output_path, confidence, enable_auto_tag, auto_tag_config, enable_audio_features, songs } => {
             info!("Starting song download process from URL: {}", url);
+            fs::create_dir_all(&output_path)?;
             
-            // Start a background thread to handle the download
-            let (tx, rx) = unbounded();
-            
-            // Clone values for thread
-            let url_clone = url.clone();
-            let output_path_clone = output_path.clone();
-            let songs_clone = songs.clone();
-            let auto_tag_config_clone = auto_tag_config.clone();
-            
-            thread::spawn(move || {
-                let result = download_songs(
-                    &url_clone, 
-                    &output_path_clone, 
-                    confidence, 
-                    enable_auto_tag, 
-                    auto_tag_config_clone,
-                    enable_audio_features, 
-                    &songs_clone
-                );
-                tx.send(result).ok();
-            });
-            
-            // Wait for result
-            if let Ok(result) = rx.recv() {
-                match result {
-                    Ok(_) => {
-                        send_socket(websocket, json!({
-                            "action": "downloadSongs",
-                            "success": true
-                        })).await.ok();
+            // Download in background, stream progress
+            let songs = songs.into_iter().map(FoundSong::into_song_info).collect::<Vec<_>>();
+            let finished = Arc::new(Mutex::new(None));
+            let rx = match SongDownloader::new()
+                .with_url(&url)
+                .with_directory(Path::new(&output_path))
+                .with_confidence(confidence)
+                .with_library(&Library::default_path()?)
+                .download_song_list(songs, finished.clone())
+            {
+                Ok(rx) => rx,
+                Err(e) => {
+                    error!("Failed downloading songs: {}", e);
+                    send_socket(websocket, json!({
+                        "action": "downloadSongs",
+                        "success": false,
+                        "error": e.to_string()
+                    })).await.ok();
+                    return Ok(());
+                }
+            };
+            for progress in rx {
+                send_socket(websocket, json!({
+                    "action": "downloadSongsProgress",
+                    "progress": progress
+                })).await.ok();
+            }
+            let results = finished.lock().unwrap().take().unwrap_or_default();
+            let files = results.iter().flat_map(|r| r.files.clone()).collect::<Vec<_>>();
+            let failed = results.iter().filter(|r| r.state == DownloadState::Failed).collect::<Vec<_>>();
+            info!("Downloaded {} files", files.len());
+
+            // Tag the downloaded files
+            if enable_auto_tag && !files.is_empty() {
+                let config = load_song_downloader_at_config(auto_tag_config)?;
+                let rx = Tagger::tag_files(&config, files.clone(), Arc::new(Mutex::new(None)));
+                for status in rx {
+                    send_socket(websocket, json!({
+                        "action": "downloadSongsTagging",
+                        "status": status
+                    })).await.ok();
+                }
+            }
+            if enable_audio_features && !files.is_empty() {
+                match context.spotify.as_ref() {
+                    Some(spotify) => {
+                        let rx = AudioFeatures::start_tagging(AudioFeaturesConfig::default(), spotify.to_owned(), files.clone());
+                        for status in rx {
+                            send_socket(websocket, json!({
+                                "action": "downloadSongsTagging",
+                                "status": status
+                            })).await.ok();
+                        }
                     },
-                    Err(e) => {
-                        error!("Failed downloading songs: {}", e);
-                        send_socket(websocket, json!({
-                            "action": "downloadSongs",
-                            "success": false,
-                            "error": e.to_string()
-                        })).await.ok();
-                    }
+                    None => warn!("Spotify unauthorized, skipping Audio Features!"),
                 }
             }
+
+            send_socket(websocket, json!({
+                "action": "downloadSongs",
+                "success": true,
+                "files": files,
+                "failed": failed
+            })).await.ok();
         },
 
         Action::RepoManifest => {